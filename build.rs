@@ -0,0 +1,41 @@
+use tonic_build::manual::{Builder, Method, Service};
+
+/// Generate the server side of the `Bookings` gRPC service (proto/bookings.proto)
+/// without protoc: the messages are written by hand in `src/grpc.rs`.
+fn main() {
+    let method = |name: &str, route_name: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route_name)
+            .input_type(format!("crate::grpc::{}", input))
+            .output_type(format!("crate::grpc::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+            .build()
+    };
+
+    let bookings = Service::builder()
+        .name("Bookings")
+        .package("bookings.v1")
+        .method(method(
+            "create_booking",
+            "CreateBooking",
+            "CreateBookingRequest",
+            "Booking",
+        ))
+        .method(method(
+            "cancel_booking",
+            "CancelBooking",
+            "CancelBookingRequest",
+            "Booking",
+        ))
+        .method(method(
+            "list_upcoming_bookings",
+            "ListUpcomingBookings",
+            "ListUpcomingBookingsRequest",
+            "ListUpcomingBookingsResponse",
+        ))
+        .build();
+
+    Builder::new().build_client(false).compile(&[bookings]);
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,1544 @@
+use std::{env, fmt, fs, path::Path, str::FromStr, time::Duration};
+
+use clap::Parser;
+use cron::Schedule;
+use lettre::message::Mailbox;
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+
+use crate::{errors::ErrorFormat, models::vaccination_model::RabiesPolicy};
+
+/// File read when `CONFIG_FILE` is not set; it is optional.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Command line arguments, they take precedence over every other source.
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "Dog walking bookings API")]
+pub struct Cli {
+    /// Port to listen on
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Address to bind, e.g. 0.0.0.0 to listen on every interface
+    #[arg(long)]
+    pub bind: Option<String>,
+    /// MongoDB connection string
+    #[arg(long)]
+    pub mongo_uri: Option<String>,
+    /// off, error, warn, info, debug or trace
+    #[arg(long)]
+    pub log_level: Option<String>,
+    /// Number of HTTP worker threads (defaults to the number of CPUs)
+    #[arg(long)]
+    pub workers: Option<usize>,
+    /// Keep owners, dogs and bookings in memory instead of MongoDB, for demos.
+    /// Walkers, logins, API keys and the admin endpoints are unavailable
+    #[arg(long)]
+    pub in_memory: bool,
+    /// Install the $jsonSchema validators of the owner, dog and booking collections
+    /// at startup (needs the collMod privilege on the database)
+    #[arg(long)]
+    pub init_schema: bool,
+}
+
+/// Startup configuration.
+/// Values come from the defaults, then `config.toml` (or the file named by
+/// `CONFIG_FILE`) when it exists, then the environment variables, then the
+/// command line arguments, which win.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub mongo: MongoConfig,
+    pub rate_limit: RateLimitConfig,
+    pub telemetry: TelemetryConfig,
+    pub recurrence: RecurrenceConfig,
+    pub bookings: BookingsConfig,
+    pub jobs: JobsConfig,
+    pub email: EmailConfig,
+    pub sms: SmsConfig,
+    pub push: PushConfig,
+    pub webhooks: WebhookConfig,
+    pub payments: PaymentsConfig,
+    pub auth: AuthConfig,
+    /// `error`, `warn`, `info`, `debug`, `trace` or `off`.
+    pub log_level: String,
+    /// `LOG_FORMAT`
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human readable lines, for development.
+    Text,
+    /// One JSON object per line, for log collectors in production.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `BIND_ADDRESS`
+    pub bind_address: String,
+    /// `PORT`
+    pub port: u16,
+    /// `WORKERS`, actix-web starts one per CPU when unset.
+    pub workers: Option<usize>,
+    /// `SHUTDOWN_TIMEOUT_SECS`, time given to in-flight requests after SIGTERM/SIGINT.
+    pub shutdown_timeout_secs: u64,
+    /// `TLS_CERT_FILE`, PEM certificate chain; with `tls_key_file` the server speaks HTTPS.
+    pub tls_cert_file: Option<String>,
+    /// `TLS_KEY_FILE`, PEM private key of the certificate.
+    pub tls_key_file: Option<String>,
+    /// `GRPC_PORT`, serves the `Bookings` gRPC service on `bind_address` when set.
+    pub grpc_port: Option<u16>,
+    /// `PUBLIC_BASE_URL`, where clients reach this API, prefixed to the links sent by email.
+    pub public_base_url: String,
+    /// `ERROR_FORMAT`, shape of the error bodies.
+    pub error_format: ErrorFormat,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MongoConfig {
+    /// `MONGO_URI`
+    pub uri: String,
+    /// `MONGO_DATABASE`
+    pub database: String,
+    /// `MONGO_MIN_POOL_SIZE`
+    pub min_pool_size: Option<u32>,
+    /// `MONGO_MAX_POOL_SIZE`
+    pub max_pool_size: Option<u32>,
+    /// `MONGO_CONNECT_TIMEOUT_MS`, to open a connection (driver default 10 s).
+    pub connect_timeout_ms: Option<u64>,
+    /// `MONGO_SERVER_SELECTION_TIMEOUT_MS`, to find a server for an operation
+    /// (driver default 30 s).
+    pub server_selection_timeout_ms: Option<u64>,
+    /// `MONGO_MAX_TIME_MS`, `maxTimeMS` of each query, count, aggregation and
+    /// find-and-modify, unlimited when unset. The driver has no socket timeout, plain
+    /// inserts, updates and deletes are bounded by `write_concern_timeout_ms`.
+    pub max_time_ms: Option<u64>,
+    /// `OWNER_CACHE_TTL_SECS`, how long an owner read stays in the owner cache,
+    /// 0 disables the cache.
+    pub owner_cache_ttl_secs: u64,
+    /// `MONGO_RETRY_ATTEMPTS`, tries of an operation failing on a network or
+    /// server selection error before the request is answered with 503.
+    pub retry_attempts: u32,
+    /// `MONGO_RETRY_MAX_DELAY_MS`, ceiling of the jittered exponential backoff
+    /// between two tries, also the `Retry-After` of the 503 (rounded up to seconds).
+    pub retry_max_delay_ms: u64,
+    /// `MONGO_CONNECT_ATTEMPTS`, tries to reach MongoDB at startup before giving up.
+    pub connect_attempts: u32,
+    /// `MONGO_CONNECT_MAX_DELAY_SECS`, ceiling of the backoff between two tries at startup.
+    pub connect_max_delay_secs: u64,
+    /// `MONGO_START_DEGRADED`, serve anyway once the startup tries are used up, with
+    /// `GET /ready` answering 503 until MongoDB comes up, instead of exiting.
+    pub start_degraded: bool,
+    /// `MONGO_BREAKER_FAILURES`, consecutive calls failing on MongoDB being out of
+    /// reach that open the circuit breaker.
+    pub breaker_failures: u32,
+    /// `MONGO_BREAKER_OPEN_SECS`, how long the open breaker answers 503 at once
+    /// before letting a probe call through.
+    pub breaker_open_secs: u64,
+    /// `MONGO_READ_PREFERENCE`, members of the replica set the booking listing
+    /// aggregation (`get_bookings`) reads from; every other read, and this one when
+    /// unset, follows the `readPreference` of the URI (the primary by default).
+    pub read_preference: Option<ReadPreferenceMode>,
+    /// `MONGO_WRITE_CONCERN`, `majority`, a number of members or a custom write
+    /// concern of the replica set, the server default when unset.
+    pub write_concern: Option<String>,
+    /// `MONGO_WRITE_CONCERN_TIMEOUT_MS`, how long a write waits for `write_concern`.
+    pub write_concern_timeout_ms: Option<u64>,
+    /// `MONGO_RETRY_WRITES`, the driver retries a write once after a failover
+    /// (driver default `true`).
+    pub retry_writes: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadPreferenceMode {
+    Primary,
+    PrimaryPreferred,
+    Secondary,
+    /// A secondary, the primary when none is available. Lags behind the
+    /// primary, a booking just written may not be listed yet.
+    SecondaryPreferred,
+    /// The member with the least network latency.
+    Nearest,
+}
+
+impl FromStr for ReadPreferenceMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "primary" => Ok(ReadPreferenceMode::Primary),
+            "primary_preferred" => Ok(ReadPreferenceMode::PrimaryPreferred),
+            "secondary" => Ok(ReadPreferenceMode::Secondary),
+            "secondary_preferred" => Ok(ReadPreferenceMode::SecondaryPreferred),
+            "nearest" => Ok(ReadPreferenceMode::Nearest),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Per client token bucket applied to every route.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// `RATE_LIMIT_PER_SECOND`, sustained requests per second, 0 disables the limit.
+    pub per_second: f64,
+    /// `RATE_LIMIT_BURST`, requests accepted at once before the sustained rate applies.
+    pub burst: u32,
+    /// `RATE_LIMIT_BACKEND`, where the buckets live.
+    pub backend: RateLimitBackend,
+    /// `REDIS_URL`, used by the `redis` backend.
+    pub redis_url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitBackend {
+    /// Buckets kept in this process, each replica enforces its own limit.
+    Memory,
+    /// Buckets kept in Redis, the limit holds across every replica.
+    Redis,
+}
+
+impl FromStr for RateLimitBackend {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "memory" => Ok(RateLimitBackend::Memory),
+            "redis" => Ok(RateLimitBackend::Redis),
+            _ => Err(()),
+        }
+    }
+}
+
+/// OpenTelemetry trace export, off unless an endpoint is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`, full OTLP/HTTP URL, e.g. `http://collector:4318/v1/traces`.
+    pub otlp_endpoint: Option<String>,
+    /// `OTEL_SERVICE_NAME`
+    pub service_name: String,
+}
+
+/// Booking of the walks of recurring series ahead of time.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecurrenceConfig {
+    /// `RECURRENCE_HORIZON_WEEKS`, walks starting within this many weeks are booked.
+    pub horizon_weeks: u32,
+    /// `RECURRENCE_INTERVAL_SECS`, how often the series are extended to the horizon.
+    pub interval_secs: u64,
+}
+
+impl Default for RecurrenceConfig {
+    fn default() -> Self {
+        RecurrenceConfig {
+            horizon_weeks: 4,
+            interval_secs: 3600,
+        }
+    }
+}
+
+/// Rules applied when a walk is booked.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BookingsConfig {
+    /// `RABIES_VACCINATION_POLICY`, what the rabies vaccination of the dogs must be
+    /// on the day of the walk.
+    pub rabies_vaccination_policy: RabiesPolicy,
+    /// `PENDING_BOOKING_EXPIRY_MINUTES`, how long a new booking waits for its
+    /// confirmation (and the payment authorized with it) before it expires.
+    pub pending_expiry_minutes: u64,
+    /// `MAX_CONCURRENT_BOOKINGS`, active bookings of any owner that may overlap,
+    /// unlimited when unset.
+    pub max_concurrent_bookings: Option<usize>,
+    /// `BOOKINGS_MAX_RESULTS`, most bookings a listing returns, whatever its `limit`.
+    pub max_results: u64,
+}
+
+impl BookingsConfig {
+    pub fn pending_expiry(&self) -> Duration {
+        Duration::from_secs(self.pending_expiry_minutes * 60)
+    }
+}
+
+impl Default for BookingsConfig {
+    fn default() -> Self {
+        BookingsConfig {
+            rabies_vaccination_policy: RabiesPolicy::default(),
+            pending_expiry_minutes: 60,
+            max_concurrent_bookings: None,
+            max_results: 1000,
+        }
+    }
+}
+
+/// When the background jobs run, as cron expressions with seconds:
+/// `sec min hour day-of-month month day-of-week`, in UTC.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    /// `JOB_REMINDERS_SCHEDULE`, reminding the owners whose walk starts in about `sms.reminder_hours`.
+    pub reminders: String,
+    /// `JOB_EXPIRE_PENDING_SCHEDULE`, expiring the pending bookings past their `expires_at`
+    /// (`bookings.pending_expiry_minutes` after booking, at the latest when the walk starts).
+    pub expire_pending: String,
+    /// `JOB_PROMOTE_WAITLIST_SCHEDULE`, booking the waitlisted owners once a walker frees up.
+    pub promote_waitlist: String,
+    /// `JOB_ARCHIVE_SCHEDULE`, moving the finished bookings older than `archive_after_days`
+    /// to the `booking_archive` collection, listed by `GET /admin/bookings/archive`.
+    pub archive: String,
+    /// `JOB_ARCHIVE_AFTER_DAYS`
+    pub archive_after_days: u32,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            reminders: "0 */5 * * * *".to_string(),
+            expire_pending: "0 */15 * * * *".to_string(),
+            promote_waitlist: "0 * * * * *".to_string(),
+            archive: "0 30 3 * * *".to_string(),
+            archive_after_days: 365,
+        }
+    }
+}
+
+/// Outgoing email and the booking events the owners are emailed about.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    /// `EMAIL_TRANSPORT`
+    pub transport: EmailTransport,
+    /// `EMAIL_FROM`, sender of every email, e.g. `Dog Walks <no-reply@example.com>`.
+    pub from: String,
+    /// `SMTP_HOST`, used by the `smtp` transport.
+    pub smtp_host: String,
+    /// `SMTP_PORT`
+    pub smtp_port: u16,
+    /// `SMTP_TLS`
+    pub smtp_tls: SmtpTls,
+    /// `SMTP_USERNAME`, the server is used without authentication when missing.
+    pub smtp_username: Option<String>,
+    /// `SMTP_PASSWORD`
+    pub smtp_password: Option<String>,
+    pub events: BookingEmailEvents,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTransport {
+    /// Emails are written to the logs, for development.
+    Log,
+    /// Emails are sent through `smtp_host`.
+    Smtp,
+}
+
+impl FromStr for EmailTransport {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "log" => Ok(EmailTransport::Log),
+            "smtp" => Ok(EmailTransport::Smtp),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS, usually on port 587.
+    Starttls,
+    /// TLS from the start, usually on port 465.
+    Implicit,
+    /// No encryption, only for a local mail catcher.
+    None,
+}
+
+impl FromStr for SmtpTls {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "starttls" => Ok(SmtpTls::Starttls),
+            "implicit" => Ok(SmtpTls::Implicit),
+            "none" => Ok(SmtpTls::None),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Booking events emailed to the owner, each can be turned off.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BookingEmailEvents {
+    /// Also the walks booked from the waitlist.
+    pub created: bool,
+    pub confirmed: bool,
+    pub cancelled: bool,
+    pub rescheduled: bool,
+    pub reminder: bool,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            transport: EmailTransport::Log,
+            from: "Dog Walks <no-reply@localhost>".to_string(),
+            smtp_host: "localhost".to_string(),
+            smtp_port: 587,
+            smtp_tls: SmtpTls::Starttls,
+            smtp_username: None,
+            smtp_password: None,
+            events: BookingEmailEvents::default(),
+        }
+    }
+}
+
+/// Text messages to the owners: reminders of their walks and cancellation notices,
+/// unless they opted out with `sms_opt_out`. Also when the walks are reminded.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmsConfig {
+    /// `SMS_PROVIDER`
+    pub provider: SmsProviderKind,
+    /// `SMS_API_URL`, base URL of the Twilio compatible API.
+    pub api_url: String,
+    /// `SMS_ACCOUNT_SID`
+    pub account_sid: Option<String>,
+    /// `SMS_AUTH_TOKEN`
+    pub auth_token: Option<String>,
+    /// `SMS_FROM`, number or sender id the messages come from.
+    pub from: String,
+    /// `SMS_REMINDER_HOURS`, the owner is reminded, on every channel, about this
+    /// many hours before the walk.
+    pub reminder_hours: u32,
+    /// `SMS_REMINDER_WINDOW_MINUTES`, walks starting up to this many minutes before
+    /// or after `reminder_hours` from now are reminded. Wider than half the period
+    /// of `jobs.reminders`, so no walk falls between two runs.
+    pub reminder_window_minutes: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmsProviderKind {
+    /// Messages are written to the logs, for development.
+    Log,
+    /// Messages are sent through the Twilio Messages API at `api_url`.
+    Twilio,
+}
+
+impl FromStr for SmsProviderKind {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "log" => Ok(SmsProviderKind::Log),
+            "twilio" => Ok(SmsProviderKind::Twilio),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Push notifications to the owners' phones registered with `POST /owner/{id}/devices`.
+/// A platform without credentials has its notifications logged instead.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PushConfig {
+    /// `FCM_PROJECT_ID`, Firebase project of the Android app.
+    pub fcm_project_id: Option<String>,
+    /// `FCM_CREDENTIALS_FILE`, JSON key of a service account allowed to send messages.
+    pub fcm_credentials_file: Option<String>,
+    /// `APNS_KEY_FILE`, `.p8` signing key of the iOS app.
+    pub apns_key_file: Option<String>,
+    /// `APNS_KEY_ID`
+    pub apns_key_id: Option<String>,
+    /// `APNS_TEAM_ID`
+    pub apns_team_id: Option<String>,
+    /// `APNS_TOPIC`, bundle id of the iOS app.
+    pub apns_topic: Option<String>,
+    /// `APNS_SANDBOX`, push to the development environment of APNs.
+    pub apns_sandbox: bool,
+}
+
+/// Delivery of the booking events to the webhooks of `POST /admin/webhooks`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// `WEBHOOK_INTERVAL_SECS`, how often the due deliveries are sent.
+    pub interval_secs: u64,
+    /// `WEBHOOK_MAX_ATTEMPTS`, a delivery is marked failed after this many attempts.
+    pub max_attempts: u32,
+    /// `WEBHOOK_TIMEOUT_SECS`, an attempt without answer by then failed.
+    pub timeout_secs: u64,
+}
+
+/// Sign-in tokens and signed cancel links. The secrets are required unless running
+/// `--in-memory`, where random ones are used and every session and link ends with
+/// the process.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// `JWT_SECRET`, HS256 key of the access tokens issued by `POST /auth/login`.
+    pub jwt_secret: Option<String>,
+    /// `JWT_TTL_SECS`, validity of an access token.
+    pub jwt_ttl_secs: u64,
+    /// `PASSWORD_RESET_TTL_SECS`, validity of an emailed password reset link.
+    pub password_reset_ttl_secs: u64,
+    /// `TOKEN_SECRET`, HMAC-SHA256 key of the links of `POST /booking/{id}/cancel-link`.
+    pub token_secret: Option<String>,
+    /// `CANCEL_LINK_TTL_SECS`, validity of a cancel link.
+    pub cancel_link_ttl_secs: u64,
+    /// `ADMIN_API_KEY`, `X-Admin-Key` of `POST /admin/accounts`, which creates the
+    /// first admin login; that endpoint is closed when unset.
+    pub admin_api_key: Option<String>,
+}
+
+/// Card payments of the bookings, mocked unless both Stripe secrets are set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaymentsConfig {
+    /// `STRIPE_SECRET_KEY`, secret (`sk_`) or restricted (`rk_`) API key.
+    pub stripe_secret_key: Option<String>,
+    /// `STRIPE_WEBHOOK_SECRET`, signing secret (`whsec_`) of the endpoint
+    /// `POST /webhooks/stripe`.
+    pub stripe_webhook_secret: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: None,
+            jwt_ttl_secs: 60 * 60,
+            password_reset_ttl_secs: 60 * 60,
+            token_secret: None,
+            cancel_link_ttl_secs: 48 * 60 * 60,
+            admin_api_key: None,
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            interval_secs: 10,
+            max_attempts: 8,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl Default for SmsConfig {
+    fn default() -> Self {
+        SmsConfig {
+            provider: SmsProviderKind::Log,
+            api_url: "https://api.twilio.com".to_string(),
+            account_sid: None,
+            auth_token: None,
+            from: String::new(),
+            reminder_hours: 24,
+            reminder_window_minutes: 30,
+        }
+    }
+}
+
+impl Default for BookingEmailEvents {
+    fn default() -> Self {
+        BookingEmailEvents {
+            created: true,
+            confirmed: true,
+            cancelled: true,
+            rescheduled: true,
+            reminder: true,
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            otlp_endpoint: None,
+            service_name: "dog-walking-api".to_string(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            server: ServerConfig::default(),
+            mongo: MongoConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            recurrence: RecurrenceConfig::default(),
+            bookings: BookingsConfig::default(),
+            jobs: JobsConfig::default(),
+            email: EmailConfig::default(),
+            sms: SmsConfig::default(),
+            push: PushConfig::default(),
+            webhooks: WebhookConfig::default(),
+            payments: PaymentsConfig::default(),
+            auth: AuthConfig::default(),
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "127.0.0.1".to_string(),
+            port: 5001,
+            workers: None,
+            shutdown_timeout_secs: 30,
+            tls_cert_file: None,
+            tls_key_file: None,
+            grpc_port: None,
+            public_base_url: "http://127.0.0.1:5001".to_string(),
+            error_format: ErrorFormat::Envelope,
+        }
+    }
+}
+
+impl Default for MongoConfig {
+    fn default() -> Self {
+        MongoConfig {
+            uri: "mongodb://localhost:27017/?directConnection=true".to_string(),
+            database: "dog_walking".to_string(),
+            min_pool_size: None,
+            max_pool_size: None,
+            connect_timeout_ms: None,
+            server_selection_timeout_ms: None,
+            max_time_ms: None,
+            owner_cache_ttl_secs: 60,
+            retry_attempts: 3,
+            retry_max_delay_ms: 2000,
+            connect_attempts: 10,
+            connect_max_delay_secs: 30,
+            start_degraded: false,
+            breaker_failures: 5,
+            breaker_open_secs: 30,
+            read_preference: None,
+            write_concern: None,
+            write_concern_timeout_ms: None,
+            retry_writes: None,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_second: 10.0,
+            burst: 20,
+            backend: RateLimitBackend::Memory,
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+        }
+    }
+}
+
+/// Every problem found while loading the configuration, reported together.
+#[derive(Debug)]
+pub struct ConfigError(Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn load(cli: &Cli) -> Result<Config, ConfigError> {
+        let path = env::var("CONFIG_FILE").ok();
+        let config = match read_file(path.as_deref()) {
+            Ok(config) => config,
+            Err(err) => return Err(ConfigError(vec![err])),
+        };
+        Config::resolve(config, cli, |name| env::var(name).ok())
+    }
+
+    /// Apply the environment variables found by `var`, then the command line
+    /// arguments, and validate the result.
+    fn resolve(
+        mut config: Config,
+        cli: &Cli,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut errors = Vec::new();
+
+        override_from_env(
+            &var,
+            &mut config.server.bind_address,
+            "BIND_ADDRESS",
+            &mut errors,
+        );
+        override_from_env(&var, &mut config.server.port, "PORT", &mut errors);
+        override_optional_from_env(
+            &var,
+            &mut config.server.tls_cert_file,
+            "TLS_CERT_FILE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.server.tls_key_file,
+            "TLS_KEY_FILE",
+            &mut errors,
+        );
+        override_optional_from_env(&var, &mut config.server.grpc_port, "GRPC_PORT", &mut errors);
+        override_from_env(
+            &var,
+            &mut config.server.public_base_url,
+            "PUBLIC_BASE_URL",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.server.error_format,
+            "ERROR_FORMAT",
+            &mut errors,
+        );
+        override_from_env(&var, &mut config.mongo.uri, "MONGO_URI", &mut errors);
+        override_from_env(
+            &var,
+            &mut config.mongo.database,
+            "MONGO_DATABASE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.min_pool_size,
+            "MONGO_MIN_POOL_SIZE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.max_pool_size,
+            "MONGO_MAX_POOL_SIZE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.connect_timeout_ms,
+            "MONGO_CONNECT_TIMEOUT_MS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.server_selection_timeout_ms,
+            "MONGO_SERVER_SELECTION_TIMEOUT_MS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.max_time_ms,
+            "MONGO_MAX_TIME_MS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.owner_cache_ttl_secs,
+            "OWNER_CACHE_TTL_SECS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.retry_attempts,
+            "MONGO_RETRY_ATTEMPTS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.retry_max_delay_ms,
+            "MONGO_RETRY_MAX_DELAY_MS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.connect_attempts,
+            "MONGO_CONNECT_ATTEMPTS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.connect_max_delay_secs,
+            "MONGO_CONNECT_MAX_DELAY_SECS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.start_degraded,
+            "MONGO_START_DEGRADED",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.breaker_failures,
+            "MONGO_BREAKER_FAILURES",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.mongo.breaker_open_secs,
+            "MONGO_BREAKER_OPEN_SECS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.read_preference,
+            "MONGO_READ_PREFERENCE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.write_concern,
+            "MONGO_WRITE_CONCERN",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.write_concern_timeout_ms,
+            "MONGO_WRITE_CONCERN_TIMEOUT_MS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.mongo.retry_writes,
+            "MONGO_RETRY_WRITES",
+            &mut errors,
+        );
+        override_optional_from_env(&var, &mut config.server.workers, "WORKERS", &mut errors);
+        override_from_env(
+            &var,
+            &mut config.server.shutdown_timeout_secs,
+            "SHUTDOWN_TIMEOUT_SECS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.rate_limit.per_second,
+            "RATE_LIMIT_PER_SECOND",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.rate_limit.burst,
+            "RATE_LIMIT_BURST",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.rate_limit.backend,
+            "RATE_LIMIT_BACKEND",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.rate_limit.redis_url,
+            "REDIS_URL",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.telemetry.otlp_endpoint,
+            "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.telemetry.service_name,
+            "OTEL_SERVICE_NAME",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.recurrence.horizon_weeks,
+            "RECURRENCE_HORIZON_WEEKS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.recurrence.interval_secs,
+            "RECURRENCE_INTERVAL_SECS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.bookings.rabies_vaccination_policy,
+            "RABIES_VACCINATION_POLICY",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.bookings.pending_expiry_minutes,
+            "PENDING_BOOKING_EXPIRY_MINUTES",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.bookings.max_concurrent_bookings,
+            "MAX_CONCURRENT_BOOKINGS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.bookings.max_results,
+            "BOOKINGS_MAX_RESULTS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.jobs.reminders,
+            "JOB_REMINDERS_SCHEDULE",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.jobs.expire_pending,
+            "JOB_EXPIRE_PENDING_SCHEDULE",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.jobs.promote_waitlist,
+            "JOB_PROMOTE_WAITLIST_SCHEDULE",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.jobs.archive,
+            "JOB_ARCHIVE_SCHEDULE",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.jobs.archive_after_days,
+            "JOB_ARCHIVE_AFTER_DAYS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.email.transport,
+            "EMAIL_TRANSPORT",
+            &mut errors,
+        );
+        override_from_env(&var, &mut config.email.from, "EMAIL_FROM", &mut errors);
+        override_from_env(&var, &mut config.email.smtp_host, "SMTP_HOST", &mut errors);
+        override_from_env(&var, &mut config.email.smtp_port, "SMTP_PORT", &mut errors);
+        override_from_env(&var, &mut config.email.smtp_tls, "SMTP_TLS", &mut errors);
+        override_optional_from_env(
+            &var,
+            &mut config.email.smtp_username,
+            "SMTP_USERNAME",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.email.smtp_password,
+            "SMTP_PASSWORD",
+            &mut errors,
+        );
+        override_from_env(&var, &mut config.sms.provider, "SMS_PROVIDER", &mut errors);
+        override_from_env(&var, &mut config.sms.api_url, "SMS_API_URL", &mut errors);
+        override_optional_from_env(
+            &var,
+            &mut config.sms.account_sid,
+            "SMS_ACCOUNT_SID",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.sms.auth_token,
+            "SMS_AUTH_TOKEN",
+            &mut errors,
+        );
+        override_from_env(&var, &mut config.sms.from, "SMS_FROM", &mut errors);
+        override_from_env(
+            &var,
+            &mut config.sms.reminder_hours,
+            "SMS_REMINDER_HOURS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.sms.reminder_window_minutes,
+            "SMS_REMINDER_WINDOW_MINUTES",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.push.fcm_project_id,
+            "FCM_PROJECT_ID",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.push.fcm_credentials_file,
+            "FCM_CREDENTIALS_FILE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.push.apns_key_file,
+            "APNS_KEY_FILE",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.push.apns_key_id,
+            "APNS_KEY_ID",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.push.apns_team_id,
+            "APNS_TEAM_ID",
+            &mut errors,
+        );
+        override_optional_from_env(&var, &mut config.push.apns_topic, "APNS_TOPIC", &mut errors);
+        override_from_env(
+            &var,
+            &mut config.push.apns_sandbox,
+            "APNS_SANDBOX",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.webhooks.interval_secs,
+            "WEBHOOK_INTERVAL_SECS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.webhooks.max_attempts,
+            "WEBHOOK_MAX_ATTEMPTS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.webhooks.timeout_secs,
+            "WEBHOOK_TIMEOUT_SECS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.payments.stripe_secret_key,
+            "STRIPE_SECRET_KEY",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.payments.stripe_webhook_secret,
+            "STRIPE_WEBHOOK_SECRET",
+            &mut errors,
+        );
+        override_optional_from_env(&var, &mut config.auth.jwt_secret, "JWT_SECRET", &mut errors);
+        override_from_env(
+            &var,
+            &mut config.auth.jwt_ttl_secs,
+            "JWT_TTL_SECS",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.auth.password_reset_ttl_secs,
+            "PASSWORD_RESET_TTL_SECS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.auth.token_secret,
+            "TOKEN_SECRET",
+            &mut errors,
+        );
+        override_from_env(
+            &var,
+            &mut config.auth.cancel_link_ttl_secs,
+            "CANCEL_LINK_TTL_SECS",
+            &mut errors,
+        );
+        override_optional_from_env(
+            &var,
+            &mut config.auth.admin_api_key,
+            "ADMIN_API_KEY",
+            &mut errors,
+        );
+        override_from_env(&var, &mut config.log_level, "LOG_LEVEL", &mut errors);
+        override_from_env(&var, &mut config.log_format, "LOG_FORMAT", &mut errors);
+
+        if let Some(bind) = &cli.bind {
+            config.server.bind_address = bind.clone();
+        }
+        if let Some(port) = cli.port {
+            config.server.port = port;
+        }
+        if let Some(workers) = cli.workers {
+            config.server.workers = Some(workers);
+        }
+        if let Some(uri) = &cli.mongo_uri {
+            config.mongo.uri = uri.clone();
+        }
+        if let Some(log_level) = &cli.log_level {
+            config.log_level = log_level.clone();
+        }
+
+        // A random secret is fine for a demo, a deployment would log everyone out
+        // and break the emailed links on each restart, and disagree between replicas.
+        if !cli.in_memory {
+            if config.auth.jwt_secret.is_none() {
+                errors.push(
+                    "auth.jwt_secret (JWT_SECRET) must be set unless running with --in-memory"
+                        .to_string(),
+                );
+            }
+            if config.auth.token_secret.is_none() {
+                errors.push(
+                    "auth.token_secret (TOKEN_SECRET) must be set unless running with --in-memory"
+                        .to_string(),
+                );
+            }
+        }
+        config.validate(&mut errors);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(errors))
+        }
+    }
+
+    /// `log_level` as a tracing filter, checked by `validate`.
+    pub fn log_filter(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log_level).unwrap_or(LevelFilter::INFO)
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.server.bind_address.trim().is_empty() {
+            errors.push("server.bind_address must not be empty".to_string());
+        }
+        if self.server.port == 0 {
+            errors.push("server.port must be between 1 and 65535".to_string());
+        }
+        if !self.server.public_base_url.starts_with("https://")
+            && !self.server.public_base_url.starts_with("http://")
+        {
+            errors.push("server.public_base_url must be an http(s) URL".to_string());
+        }
+        if self.server.tls_cert_file.is_some() != self.server.tls_key_file.is_some() {
+            errors.push(
+                "server.tls_cert_file and server.tls_key_file must be set together".to_string(),
+            );
+        }
+        match self.server.grpc_port {
+            Some(0) => errors.push("server.grpc_port must be between 1 and 65535".to_string()),
+            Some(port) if port == self.server.port => {
+                errors.push("server.grpc_port must differ from server.port".to_string())
+            }
+            _ => {}
+        }
+        if self.server.workers == Some(0) {
+            errors.push("server.workers must be at least 1".to_string());
+        }
+        if !self.mongo.uri.starts_with("mongodb://")
+            && !self.mongo.uri.starts_with("mongodb+srv://")
+        {
+            errors.push("mongo.uri must start with mongodb:// or mongodb+srv://".to_string());
+        }
+        if self.mongo.database.trim().is_empty() {
+            errors.push("mongo.database must not be empty".to_string());
+        }
+        if self.mongo.max_pool_size == Some(0) {
+            errors.push("mongo.max_pool_size must be at least 1".to_string());
+        }
+        if let (Some(min), Some(max)) = (self.mongo.min_pool_size, self.mongo.max_pool_size)
+            && min > max
+        {
+            errors.push(format!(
+                "mongo.min_pool_size ({}) must not exceed mongo.max_pool_size ({})",
+                min, max
+            ));
+        }
+        for (name, timeout) in [
+            ("connect_timeout_ms", self.mongo.connect_timeout_ms),
+            (
+                "server_selection_timeout_ms",
+                self.mongo.server_selection_timeout_ms,
+            ),
+            ("max_time_ms", self.mongo.max_time_ms),
+            (
+                "write_concern_timeout_ms",
+                self.mongo.write_concern_timeout_ms,
+            ),
+        ] {
+            if timeout == Some(0) {
+                errors.push(format!("mongo.{} must be at least 1", name));
+            }
+        }
+        if let Some(write_concern) = &self.mongo.write_concern
+            && (write_concern.trim().is_empty() || write_concern == "0")
+        {
+            errors.push(
+                "mongo.write_concern must be majority, a number of members (at least 1) or a custom write concern"
+                    .to_string(),
+            );
+        }
+        if !(1..=10).contains(&self.mongo.retry_attempts) {
+            errors.push("mongo.retry_attempts must be between 1 and 10".to_string());
+        }
+        if !(100..=60_000).contains(&self.mongo.retry_max_delay_ms) {
+            errors.push("mongo.retry_max_delay_ms must be between 100 and 60000".to_string());
+        }
+        if !(1..=100).contains(&self.mongo.connect_attempts) {
+            errors.push("mongo.connect_attempts must be between 1 and 100".to_string());
+        }
+        if !(1..=600).contains(&self.mongo.connect_max_delay_secs) {
+            errors.push("mongo.connect_max_delay_secs must be between 1 and 600".to_string());
+        }
+        if !(1..=1000).contains(&self.mongo.breaker_failures) {
+            errors.push("mongo.breaker_failures must be between 1 and 1000".to_string());
+        }
+        if !(1..=3600).contains(&self.mongo.breaker_open_secs) {
+            errors.push("mongo.breaker_open_secs must be between 1 and 3600".to_string());
+        }
+        if !self.rate_limit.per_second.is_finite() || self.rate_limit.per_second < 0.0 {
+            errors.push("rate_limit.per_second must be a positive number or 0".to_string());
+        }
+        if self.rate_limit.burst == 0 {
+            errors.push("rate_limit.burst must be at least 1".to_string());
+        }
+        if self.rate_limit.backend == RateLimitBackend::Redis
+            && !self.rate_limit.redis_url.starts_with("redis://")
+            && !self.rate_limit.redis_url.starts_with("rediss://")
+        {
+            errors.push("rate_limit.redis_url must start with redis:// or rediss://".to_string());
+        }
+        if let Some(endpoint) = &self.telemetry.otlp_endpoint
+            && !endpoint.starts_with("http://")
+            && !endpoint.starts_with("https://")
+        {
+            errors.push("telemetry.otlp_endpoint must be an http(s) URL".to_string());
+        }
+        if !(1..=52).contains(&self.recurrence.horizon_weeks) {
+            errors.push("recurrence.horizon_weeks must be between 1 and 52".to_string());
+        }
+        if self.recurrence.interval_secs < 60 {
+            errors.push("recurrence.interval_secs must be at least 60".to_string());
+        }
+        if !(1..=10_080).contains(&self.bookings.pending_expiry_minutes) {
+            errors.push("bookings.pending_expiry_minutes must be between 1 and 10080".to_string());
+        }
+        if self.bookings.max_concurrent_bookings == Some(0) {
+            errors.push("bookings.max_concurrent_bookings must be at least 1".to_string());
+        }
+        if self.bookings.max_results == 0 {
+            errors.push("bookings.max_results must be at least 1".to_string());
+        }
+        for (name, schedule) in [
+            ("reminders", &self.jobs.reminders),
+            ("expire_pending", &self.jobs.expire_pending),
+            ("promote_waitlist", &self.jobs.promote_waitlist),
+            ("archive", &self.jobs.archive),
+        ] {
+            if let Err(err) = Schedule::from_str(schedule) {
+                errors.push(format!(
+                    "jobs.{} `{}` is not a valid cron expression: {}",
+                    name, schedule, err
+                ));
+            }
+        }
+        if !(30..=3650).contains(&self.jobs.archive_after_days) {
+            errors.push("jobs.archive_after_days must be between 30 and 3650".to_string());
+        }
+        if self.email.from.parse::<Mailbox>().is_err() {
+            errors.push(format!(
+                "email.from `{}` is not a valid address",
+                self.email.from
+            ));
+        }
+        if self.email.smtp_username.is_some() != self.email.smtp_password.is_some() {
+            errors.push("email.smtp_username and email.smtp_password go together".to_string());
+        }
+        if self.sms.provider == SmsProviderKind::Twilio {
+            if self.sms.account_sid.is_none() || self.sms.auth_token.is_none() {
+                errors.push(
+                    "sms.account_sid and sms.auth_token are required by the twilio provider"
+                        .to_string(),
+                );
+            }
+            if self.sms.from.is_empty() {
+                errors.push("sms.from is required by the twilio provider".to_string());
+            }
+            if !self.sms.api_url.starts_with("https://") && !self.sms.api_url.starts_with("http://")
+            {
+                errors.push("sms.api_url must be an http(s) URL".to_string());
+            }
+        }
+        if !(1..=72).contains(&self.sms.reminder_hours) {
+            errors.push("sms.reminder_hours must be between 1 and 72".to_string());
+        }
+        if self.sms.reminder_window_minutes == 0
+            || self.sms.reminder_window_minutes >= self.sms.reminder_hours * 60
+        {
+            errors.push(
+                "sms.reminder_window_minutes must be at least 1 and less than sms.reminder_hours"
+                    .to_string(),
+            );
+        }
+        if self.push.fcm_project_id.is_some() != self.push.fcm_credentials_file.is_some() {
+            errors
+                .push("push.fcm_project_id and push.fcm_credentials_file go together".to_string());
+        }
+        let apns = [
+            &self.push.apns_key_file,
+            &self.push.apns_key_id,
+            &self.push.apns_team_id,
+            &self.push.apns_topic,
+        ];
+        if apns.iter().any(|value| value.is_some()) && !apns.iter().all(|value| value.is_some()) {
+            errors.push(
+                "push.apns_key_file, push.apns_key_id, push.apns_team_id and push.apns_topic go together"
+                    .to_string(),
+            );
+        }
+        if self.webhooks.interval_secs == 0 {
+            errors.push("webhooks.interval_secs must be at least 1".to_string());
+        }
+        if !(1..=20).contains(&self.webhooks.max_attempts) {
+            errors.push("webhooks.max_attempts must be between 1 and 20".to_string());
+        }
+        if !(1..=60).contains(&self.webhooks.timeout_secs) {
+            errors.push("webhooks.timeout_secs must be between 1 and 60".to_string());
+        }
+        match (
+            &self.payments.stripe_secret_key,
+            &self.payments.stripe_webhook_secret,
+        ) {
+            (Some(secret_key), Some(webhook_secret)) => {
+                if !secret_key.starts_with("sk_") && !secret_key.starts_with("rk_") {
+                    errors.push(
+                        "payments.stripe_secret_key must be a Stripe secret (sk_) or restricted (rk_) key"
+                            .to_string(),
+                    );
+                }
+                if !webhook_secret.starts_with("whsec_") {
+                    errors.push(
+                        "payments.stripe_webhook_secret must be a Stripe signing secret (whsec_)"
+                            .to_string(),
+                    );
+                }
+            }
+            (None, None) => {}
+            _ => errors.push(
+                "payments.stripe_secret_key and payments.stripe_webhook_secret go together"
+                    .to_string(),
+            ),
+        }
+        if self.auth.jwt_secret.as_deref() == Some("") {
+            errors.push("auth.jwt_secret must not be empty".to_string());
+        }
+        if self.auth.jwt_ttl_secs == 0 {
+            errors.push("auth.jwt_ttl_secs must be at least 1".to_string());
+        }
+        if self.auth.password_reset_ttl_secs == 0 {
+            errors.push("auth.password_reset_ttl_secs must be at least 1".to_string());
+        }
+        if self.auth.token_secret.as_deref() == Some("") {
+            errors.push("auth.token_secret must not be empty".to_string());
+        }
+        if self.auth.cancel_link_ttl_secs == 0 {
+            errors.push("auth.cancel_link_ttl_secs must be at least 1".to_string());
+        }
+        if self.auth.admin_api_key.as_deref() == Some("") {
+            errors.push("auth.admin_api_key must not be empty".to_string());
+        }
+        if LevelFilter::from_str(&self.log_level).is_err() {
+            errors.push(format!(
+                "log_level `{}` must be one of off, error, warn, info, debug, trace",
+                self.log_level
+            ));
+        }
+    }
+}
+
+/// Parse the configuration file. A missing default file means "defaults only",
+/// a missing file explicitly named by `CONFIG_FILE` is an error.
+fn read_file(path: Option<&str>) -> Result<Config, String> {
+    let (path, explicit) = match path {
+        Some(path) => (path, true),
+        None => (DEFAULT_CONFIG_FILE, false),
+    };
+    if !explicit && !Path::new(path).exists() {
+        return Ok(Config::default());
+    }
+
+    let content = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    toml::from_str(&content).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn override_from_env<T: FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    target: &mut T,
+    name: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(value) = var(name) {
+        match value.parse() {
+            Ok(value) => *target = value,
+            Err(_) => errors.push(format!("{} has an invalid value `{}`", name, value)),
+        }
+    }
+}
+
+fn override_optional_from_env<T: FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    target: &mut Option<T>,
+    name: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(value) = var(name) {
+        match value.parse() {
+            Ok(value) => *target = Some(value),
+            Err(_) => errors.push(format!("{} has an invalid value `{}`", name, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn resolve_with(cli: &Cli, vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        Config::resolve(Config::default(), cli, |name| {
+            vars.get(name).map(|value| value.to_string())
+        })
+    }
+
+    /// Resolve like `--in-memory`, which needs no secret.
+    fn resolve(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let cli = Cli {
+            in_memory: true,
+            ..Cli::default()
+        };
+        resolve_with(&cli, vars)
+    }
+
+    #[test]
+    fn rate_limit_backend_and_redis_url_come_from_the_environment() {
+        let config = resolve(&[
+            ("RATE_LIMIT_BACKEND", "redis"),
+            ("REDIS_URL", "redis://cache.internal:6380"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.rate_limit.backend, RateLimitBackend::Redis);
+        assert_eq!(config.rate_limit.redis_url, "redis://cache.internal:6380");
+    }
+
+    #[test]
+    fn unknown_rate_limit_backend_is_reported() {
+        let err = resolve(&[("RATE_LIMIT_BACKEND", "memcached")]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "RATE_LIMIT_BACKEND has an invalid value `memcached`"
+        );
+    }
+
+    #[test]
+    fn jwt_secret_and_lifetimes_come_from_the_environment() {
+        let config = resolve(&[
+            ("JWT_SECRET", "0123456789abcdef0123456789abcdef"),
+            ("JWT_TTL_SECS", "900"),
+            ("PASSWORD_RESET_TTL_SECS", "1800"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.auth.jwt_secret.as_deref(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+        assert_eq!(config.auth.jwt_ttl_secs, 900);
+        assert_eq!(config.auth.password_reset_ttl_secs, 1800);
+    }
+
+    #[test]
+    fn cancel_link_secret_and_lifetime_come_from_the_environment() {
+        let config = resolve(&[
+            ("TOKEN_SECRET", "fedcba9876543210fedcba9876543210"),
+            ("CANCEL_LINK_TTL_SECS", "3600"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.auth.token_secret.as_deref(),
+            Some("fedcba9876543210fedcba9876543210")
+        );
+        assert_eq!(config.auth.cancel_link_ttl_secs, 3600);
+    }
+
+    #[test]
+    fn secrets_are_required_outside_in_memory() {
+        let err = resolve_with(&Cli::default(), &[]).unwrap_err();
+        assert!(err.to_string().contains("JWT_SECRET"), "{}", err);
+        assert!(err.to_string().contains("TOKEN_SECRET"), "{}", err);
+
+        resolve_with(
+            &Cli::default(),
+            &[
+                ("JWT_SECRET", "0123456789abcdef0123456789abcdef"),
+                ("TOKEN_SECRET", "fedcba9876543210fedcba9876543210"),
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn server_and_cache_settings_come_from_the_environment() {
+        let config = resolve(&[
+            ("PUBLIC_BASE_URL", "https://walks.example.com"),
+            ("ERROR_FORMAT", "problem"),
+            ("OWNER_CACHE_TTL_SECS", "0"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.server.public_base_url, "https://walks.example.com");
+        assert_eq!(config.server.error_format, ErrorFormat::Problem);
+        assert_eq!(config.mongo.owner_cache_ttl_secs, 0);
+    }
+
+    #[test]
+    fn public_base_url_must_be_http() {
+        let err = resolve(&[("PUBLIC_BASE_URL", "walks.example.com")]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "server.public_base_url must be an http(s) URL"
+        );
+    }
+}
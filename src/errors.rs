@@ -0,0 +1,351 @@
+use actix_web::{
+    HttpResponse, ResponseError,
+    http::{StatusCode, header},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt, str::FromStr};
+use tracing::error;
+use utoipa::ToSchema;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+/// Return type of every route handler.
+pub type ApiResponse<T = HttpResponse> = Result<T, AppError>;
+
+/// Body of every error response, so clients can branch on `code`
+/// and quote `request_id` when reporting a problem.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiErrorBody {
+    /// Stable, machine readable identifier, see `AppError::code`.
+    pub code: &'static str,
+    /// Human readable explanation, never contains internal details.
+    pub message: String,
+    /// Extra data depending on `code`, e.g. the clashing booking or the invalid fields.
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+    /// `X-Request-Id` of the failed request, also sent back as a response header.
+    pub request_id: Option<String>,
+}
+
+/// RFC 7807 `application/problem+json` body, sent instead of `ApiErrorBody`
+/// when `server.error_format` is `problem`. `code`, `details` and `request_id` are kept
+/// as extension members.
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub detail: String,
+    pub instance: Option<String>,
+    pub code: &'static str,
+    pub details: Option<Value>,
+    pub request_id: Option<String>,
+}
+
+/// Shape of error bodies, `server.error_format`. Registered as app data for
+/// `middleware::error_context`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorFormat {
+    /// `ApiErrorBody` (default).
+    Envelope,
+    /// `ProblemDetails`, for gateways expecting RFC 7807.
+    Problem,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "envelope" => Ok(ErrorFormat::Envelope),
+            "problem" => Ok(ErrorFormat::Problem),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Crate-wide error returned by `Database` methods and route handlers.
+/// Implements `ResponseError`, so handlers can simply use `?` and clients
+/// get an `ApiErrorBody` with a meaningful status.
+#[derive(Debug)]
+pub enum AppError {
+    /// 404, the requested resource does not exist.
+    NotFound(String),
+    /// 400, the request is malformed.
+    Validation(String),
+    /// 422, the request is well formed but some fields break the DTO rules.
+    InvalidFields(ValidationErrors),
+    /// 500, MongoDB failed; the driver error is logged, not sent to the client.
+    Database(mongodb::error::Error),
+    /// 409, the request conflicts with the current state of the data.
+    Conflict {
+        code: &'static str,
+        message: String,
+        details: Option<Value>,
+    },
+    /// 401, missing or invalid credentials.
+    Unauthorized(String),
+    /// 403, authenticated but not allowed to touch this resource.
+    Forbidden(String),
+    /// 410, the resource (or link) is no longer available.
+    Gone(String),
+    /// 413, the uploaded body is over the size limit.
+    PayloadTooLarge(String),
+    /// 415, the uploaded file is not of an accepted type.
+    UnsupportedMediaType(String),
+    /// 412, the `If-Match` version is not the current one, someone else updated the resource.
+    PreconditionFailed(String),
+    /// 428, the update must say which version of the resource it applies to.
+    PreconditionRequired(String),
+    /// 429, the client exhausted its rate limit; sent with `Retry-After`.
+    RateLimited { retry_after_secs: u64 },
+    /// 503, MongoDB stayed unreachable through every retry, or the circuit breaker
+    /// is open and refused the call (`error` is `None`); sent with `Retry-After`,
+    /// the driver error is only logged.
+    DatabaseUnavailable {
+        error: Option<mongodb::error::Error>,
+        retry_after_secs: u64,
+    },
+    /// 500, any other server side failure; like `Database` the cause is only logged.
+    Internal(String),
+}
+
+impl AppError {
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict {
+            code: "conflict",
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Envelope sent to the client, internal causes are replaced by a generic message.
+    pub fn body(&self, request_id: Option<String>) -> ApiErrorBody {
+        let message = match self {
+            AppError::Database(_) => "Internal database error".to_string(),
+            AppError::DatabaseUnavailable {
+                retry_after_secs, ..
+            } => format!(
+                "The database is unavailable, retry in {} seconds",
+                retry_after_secs
+            ),
+            AppError::Internal(_) => "Internal server error".to_string(),
+            other => other.to_string(),
+        };
+        let details = match self {
+            AppError::Conflict { details, .. } => details.clone(),
+            AppError::InvalidFields(errors) => Some(field_messages(errors)),
+            _ => None,
+        };
+
+        ApiErrorBody {
+            code: self.code(),
+            message,
+            details,
+            request_id,
+        }
+    }
+
+    /// RFC 7807 view of `body`, `instance` is the path of the failed request.
+    pub fn problem(&self, request_id: Option<String>, instance: Option<String>) -> ProblemDetails {
+        let status = self.status_code();
+        let body = self.body(request_id);
+
+        ProblemDetails {
+            type_uri: format!("urn:problem-type:dog-walking:{}", body.code),
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: body.message,
+            instance,
+            code: body.code,
+            details: body.details,
+            request_id: body.request_id,
+        }
+    }
+
+    /// Error response in `format`, without logging anything.
+    pub fn to_response(
+        &self,
+        format: ErrorFormat,
+        request_id: Option<String>,
+        instance: Option<String>,
+    ) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let AppError::RateLimited { retry_after_secs }
+        | AppError::DatabaseUnavailable {
+            retry_after_secs, ..
+        } = self
+        {
+            response.insert_header((header::RETRY_AFTER, retry_after_secs.to_string()));
+        }
+        match format {
+            ErrorFormat::Envelope => response.json(self.body(request_id)),
+            ErrorFormat::Problem => response.content_type("application/problem+json").body(
+                serde_json::to_string(&self.problem(request_id, instance)).unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// Stable, machine readable identifier of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation(_) => "validation_error",
+            AppError::InvalidFields(_) => "invalid_fields",
+            AppError::Database(_) => "database_error",
+            AppError::Conflict { code, .. } => code,
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::Gone(_) => "gone",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::PreconditionFailed(_) => "version_mismatch",
+            AppError::PreconditionRequired(_) => "version_required",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::DatabaseUnavailable { .. } => "database_unavailable",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Gone(message)
+            | AppError::PayloadTooLarge(message)
+            | AppError::UnsupportedMediaType(message)
+            | AppError::PreconditionFailed(message)
+            | AppError::PreconditionRequired(message)
+            | AppError::Conflict { message, .. } => write!(f, "{}", message),
+            AppError::InvalidFields(_) => write!(f, "Some fields are invalid"),
+            AppError::RateLimited { retry_after_secs } => write!(
+                f,
+                "Too many requests, retry in {} seconds",
+                retry_after_secs
+            ),
+            AppError::Database(err) => write!(f, "Database error: {}", err),
+            AppError::DatabaseUnavailable {
+                error: Some(error), ..
+            } => write!(f, "Database unavailable: {}", error),
+            AppError::DatabaseUnavailable { error: None, .. } => {
+                write!(f, "Database unavailable: circuit breaker open")
+            }
+            AppError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(err: mongodb::error::Error) -> Self {
+        AppError::Database(err)
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for AppError {
+    fn from(err: mongodb::bson::ser::Error) -> Self {
+        AppError::Database(err.into())
+    }
+}
+
+impl From<mongodb::bson::de::Error> for AppError {
+    fn from(err: mongodb::bson::de::Error) -> Self {
+        AppError::Database(err.into())
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        AppError::InvalidFields(errors)
+    }
+}
+
+impl From<actix_web::error::BlockingError> for AppError {
+    fn from(err: actix_web::error::BlockingError) -> Self {
+        AppError::Internal(err.to_string())
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::DatabaseUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            AppError::Database(err) => error!(error = %err, "Database error"),
+            // Refused by the open breaker: logged once when it opened.
+            AppError::DatabaseUnavailable {
+                error: Some(error), ..
+            } => error!(error = %error, "Database unavailable"),
+            AppError::Internal(cause) => error!(error = %cause, "Internal error"),
+            _ => {}
+        }
+
+        // `middleware::error_context` rebuilds it in the configured format.
+        self.to_response(ErrorFormat::Envelope, None, None)
+    }
+}
+
+/// `{"field": ["message", ...]}` from the derive-based validation errors,
+/// nested fields are named like `owner.email` or `dogs[1].name`.
+fn field_messages(errors: &ValidationErrors) -> Value {
+    let mut fields = serde_json::Map::new();
+    collect_field_messages(errors, "", &mut fields);
+    Value::Object(fields)
+}
+
+fn collect_field_messages(
+    errors: &ValidationErrors,
+    prefix: &str,
+    fields: &mut serde_json::Map<String, Value>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                let messages = errors
+                    .iter()
+                    .map(|error| match &error.message {
+                        Some(message) => Value::from(message.as_ref()),
+                        None => Value::from(error.code.as_ref()),
+                    })
+                    .collect();
+                fields.insert(path, Value::Array(messages));
+            }
+            ValidationErrorsKind::Struct(errors) => collect_field_messages(errors, &path, fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_messages(errors, &format!("{}[{}]", path, index), fields);
+                }
+            }
+        }
+    }
+}
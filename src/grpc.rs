@@ -0,0 +1,329 @@
+use std::{future::Future, net::SocketAddr};
+
+use actix_web::web::Data;
+use mongodb::bson::doc;
+use tonic::{Request, Response, Status, transport::Server};
+use tracing::error;
+use validator::Validate;
+
+use crate::{
+    errors::AppError,
+    models::{
+        api_key_model::ApiKeyScope,
+        audit_model::{AuditAction, AuditActor, EntityKind, EntityRef, snapshot},
+        auth_model::Role,
+        booking_model::{self, BookingQuery, BookingRequest, FullBooking, ListedBooking},
+        owner_model::OwnerWithDogs,
+        serde_helpers::WithId,
+        vaccination_model::RabiesPolicy,
+    },
+    routes::{audit, booking_routes::ensure_vaccinated, extractors::parse_object_id},
+    services::{
+        auth::hash_one_time_token,
+        db::Database,
+        repository::{BookingRepository, OwnerRepository},
+    },
+};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/bookings.v1.Bookings.rs"));
+}
+
+use generated::bookings_server::{Bookings, BookingsServer};
+
+/// Metadata entry carrying the API key, the gRPC counterpart of `X-Api-Key`.
+const API_KEY_METADATA: &str = "x-api-key";
+
+// Messages of proto/bookings.proto, the service itself is generated by build.rs.
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CreateBookingRequest {
+    #[prost(string, tag = "1")]
+    pub owner_id: String,
+    #[prost(string, tag = "2")]
+    pub start_time: String,
+    #[prost(uint32, tag = "3")]
+    pub duration_in_minutes: u32,
+    #[prost(string, optional, tag = "4")]
+    pub coupon_code: Option<String>,
+    #[prost(bool, tag = "5")]
+    pub redeem_points: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CancelBookingRequest {
+    #[prost(string, tag = "1")]
+    pub booking_id: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListUpcomingBookingsRequest {
+    #[prost(string, optional, tag = "1")]
+    pub owner_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListUpcomingBookingsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub bookings: Vec<Booking>,
+    #[prost(uint32, tag = "2")]
+    pub skipped: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Booking {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub owner_id: String,
+    #[prost(string, tag = "3")]
+    pub start_time: String,
+    #[prost(uint32, tag = "4")]
+    pub duration_in_minutes: u32,
+    #[prost(string, tag = "5")]
+    pub status: String,
+    #[prost(string, optional, tag = "6")]
+    pub walker_id: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    pub cancelled_at: Option<String>,
+    #[prost(string, repeated, tag = "8")]
+    pub dog_ids: Vec<String>,
+    #[prost(int64, optional, tag = "9")]
+    pub price_cents: Option<i64>,
+    #[prost(string, optional, tag = "10")]
+    pub currency: Option<String>,
+    #[prost(int64, optional, tag = "11")]
+    pub cancellation_fee_cents: Option<i64>,
+}
+
+impl From<booking_model::Booking> for Booking {
+    fn from(booking: booking_model::Booking) -> Self {
+        Booking {
+            id: booking._id.to_hex(),
+            owner_id: booking.owner.to_hex(),
+            start_time: booking
+                .start_time
+                .try_to_rfc3339_string()
+                .unwrap_or_default(),
+            duration_in_minutes: booking.duration_in_minutes.into(),
+            status: booking.status.as_str().to_string(),
+            walker_id: booking.walker.map(|walker| walker.to_hex()),
+            cancelled_at: booking
+                .cancelled_at
+                .and_then(|at| at.try_to_rfc3339_string().ok()),
+            dog_ids: Vec::new(),
+            price_cents: booking.price_cents,
+            currency: booking.currency,
+            cancellation_fee_cents: booking.cancellation_fee_cents,
+        }
+    }
+}
+
+impl From<WithId<FullBooking>> for Booking {
+    fn from(booking: WithId<FullBooking>) -> Self {
+        let WithId(booking) = booking;
+        Booking {
+            id: booking._id.to_hex(),
+            owner_id: booking.owner._id.to_hex(),
+            start_time: booking
+                .start_time
+                .try_to_rfc3339_string()
+                .unwrap_or_default(),
+            duration_in_minutes: booking.duration_in_minutes.into(),
+            status: booking.status.as_str().to_string(),
+            walker_id: booking.walker.map(|walker| walker.to_hex()),
+            cancelled_at: booking
+                .cancelled_at
+                .and_then(|at| at.try_to_rfc3339_string().ok()),
+            dog_ids: booking.dogs.iter().map(|dog| dog._id.to_hex()).collect(),
+            price_cents: booking.price_cents,
+            currency: booking.currency,
+            cancellation_fee_cents: booking.cancellation_fee_cents,
+        }
+    }
+}
+
+/// gRPC codes for the errors the handlers would answer over HTTP.
+impl From<AppError> for Status {
+    fn from(err: AppError) -> Self {
+        let message = err.body(None).message;
+        match err {
+            AppError::NotFound(_) => Status::not_found(message),
+            AppError::Validation(_)
+            | AppError::InvalidFields(_)
+            | AppError::PayloadTooLarge(_)
+            | AppError::UnsupportedMediaType(_) => Status::invalid_argument(message),
+            AppError::Conflict { .. }
+            | AppError::Gone(_)
+            | AppError::PreconditionFailed(_)
+            | AppError::PreconditionRequired(_) => Status::failed_precondition(message),
+            AppError::Unauthorized(_) => Status::unauthenticated(message),
+            AppError::Forbidden(_) => Status::permission_denied(message),
+            AppError::RateLimited { .. } => Status::resource_exhausted(message),
+            AppError::Database(err) => {
+                error!(error = %err, "Database error");
+                Status::internal(message)
+            }
+            AppError::DatabaseUnavailable { error, .. } => {
+                if let Some(error) = error {
+                    error!(error = %error, "Database unavailable");
+                }
+                Status::unavailable(message)
+            }
+            AppError::Internal(cause) => {
+                error!(error = %cause, "Internal error");
+                Status::internal(message)
+            }
+        }
+    }
+}
+
+/// Booking operations for internal consumers, sharing the `Database` of the HTTP server.
+/// Callers authenticate with an API key like backend integrations of the REST API.
+pub struct BookingService {
+    db: Data<Database>,
+    rabies_policy: RabiesPolicy,
+}
+
+impl BookingService {
+    /// Refuse the call unless its `x-api-key` is active and carries `scope`.
+    /// The key is the author of the changes, like over HTTP where it acts as an admin.
+    async fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        scope: ApiKeyScope,
+    ) -> Result<AuditActor, Status> {
+        let key = request
+            .metadata()
+            .get(API_KEY_METADATA)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("Missing API key"))?;
+
+        let api_key = self
+            .db
+            .find_active_api_key(&hash_one_time_token(key))
+            .await?
+            .ok_or_else(|| Status::unauthenticated("Invalid or revoked API key"))?;
+        if !api_key.scopes.contains(&scope) {
+            return Err(Status::permission_denied(format!(
+                "This API key lacks the `{}` scope",
+                scope.as_str()
+            )));
+        }
+        Ok(AuditActor {
+            user_id: Some(api_key._id),
+            role: Some(Role::Admin),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Bookings for BookingService {
+    async fn create_booking(
+        &self,
+        request: Request<CreateBookingRequest>,
+    ) -> Result<Response<Booking>, Status> {
+        let actor = self.authorize(&request, ApiKeyScope::BookingsWrite).await?;
+
+        let request = request.into_inner();
+        let request = BookingRequest {
+            owner: parse_object_id(&request.owner_id)?,
+            start_time: request.start_time,
+            duration_in_minutes: u8::try_from(request.duration_in_minutes).unwrap_or(u8::MAX),
+            coupon_code: request.coupon_code,
+            redeem_points: request.redeem_points,
+            recurrence: None,
+        };
+        request.validate().map_err(AppError::from)?;
+
+        let coupon_code = request.coupon_code.clone();
+        let redeem_points = request.redeem_points;
+        let booking = booking_model::Booking::try_from(request)
+            .map_err(|err| AppError::Validation(err.to_string()))?;
+        let OwnerWithDogs { owner, dogs } = self.db.get_owner_full(&booking.owner, false).await?;
+        if !owner.0.email_verified {
+            return Err(Status::failed_precondition(
+                "The owner must verify its email before booking",
+            ));
+        }
+        ensure_vaccinated(self.rabies_policy, &dogs, booking.start_time)?;
+
+        let booking_id = booking._id;
+        self.db
+            .create_booking(booking, coupon_code.as_deref(), redeem_points)
+            .await?;
+        let booking = self.db.get_booking(&booking_id).await?;
+        audit(
+            self.db.get_ref(),
+            actor,
+            AuditAction::Create,
+            EntityRef::new(EntityKind::Booking, booking_id),
+            None,
+            snapshot(&booking),
+        )
+        .await;
+        Ok(Response::new(booking.into()))
+    }
+
+    async fn cancel_booking(
+        &self,
+        request: Request<CancelBookingRequest>,
+    ) -> Result<Response<Booking>, Status> {
+        let actor = self.authorize(&request, ApiKeyScope::BookingsWrite).await?;
+
+        let booking_id = parse_object_id(&request.into_inner().booking_id)?;
+        let before = self.db.get_booking(&booking_id).await?;
+        let booking = self.db.cancel_booking(&booking_id).await?;
+        audit(
+            self.db.get_ref(),
+            actor,
+            AuditAction::Cancel,
+            EntityRef::new(EntityKind::Booking, booking_id),
+            snapshot(&before),
+            snapshot(&booking),
+        )
+        .await;
+        Ok(Response::new(booking.into()))
+    }
+
+    async fn list_upcoming_bookings(
+        &self,
+        request: Request<ListUpcomingBookingsRequest>,
+    ) -> Result<Response<ListUpcomingBookingsResponse>, Status> {
+        self.authorize(&request, ApiKeyScope::BookingsRead).await?;
+
+        let filter = match request.into_inner().owner_id {
+            Some(owner_id) => doc! {"owner": parse_object_id(&owner_id)?},
+            None => doc! {},
+        };
+        let list = self
+            .db
+            .get_bookings(&BookingQuery::upcoming(filter), false)
+            .await?;
+        Ok(Response::new(ListUpcomingBookingsResponse {
+            // `upcoming` asks for no `fields`, every booking is complete.
+            bookings: list
+                .bookings
+                .into_iter()
+                .filter_map(|booking| match booking {
+                    ListedBooking::Full(booking) => Some(Booking::from(*booking)),
+                    ListedBooking::Partial(_) => None,
+                })
+                .collect(),
+            skipped: u32::try_from(list.skipped).unwrap_or(u32::MAX),
+        }))
+    }
+}
+
+/// Serve the `Bookings` service on `address` until `shutdown` resolves.
+pub async fn serve(
+    address: SocketAddr,
+    db: Data<Database>,
+    rabies_policy: RabiesPolicy,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(BookingsServer::new(BookingService { db, rabies_policy }))
+        .serve_with_shutdown(address, shutdown)
+        .await
+}
@@ -0,0 +1,10 @@
+//! Dog walking booking API: the modules behind the `api_server_mongodb_actix_web`
+//! binary, exposed so the integration tests in `tests/` can build the app.
+pub mod config;
+pub mod errors;
+pub mod grpc;
+pub mod models;
+pub mod routes;
+pub mod services;
+pub mod telemetry;
+pub mod tls;
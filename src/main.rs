@@ -1,38 +1,318 @@
-use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, get, web::Data};
-use std::io::Result;
+use actix_web::{
+    App, HttpResponse, HttpServer, Responder, get,
+    middleware::from_fn,
+    web::{self, Data, JsonConfig, PathConfig, QueryConfig},
+};
+use clap::Parser;
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::ToSocketAddrs,
+    sync::Arc,
+};
+use tracing::{error, info, warn};
+use tracing_actix_web::TracingLogger;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
-use crate::{
+use api_server_mongodb_actix_web::{
+    config::{Cli, Config},
+    errors::AppError,
+    grpc, routes,
     routes::{
-        booking_routes::{cancel_booking, create_booking, get_bookings},
-        dog_routes::create_dog,
-        owner_routes::create_owner,
+        PublicUrl,
+        extractors::AdminApiKey,
+        health_routes::{health, ready},
+        middleware::{
+            RequestSpan, api_key_auth, error_context, etag, rate_limit, request_id, route_not_found,
+        },
+        openapi::ApiDoc,
+    },
+    services::{
+        auth::Authenticator,
+        change_streams,
+        db::Database,
+        jobs::{JobContext, Scheduler},
+        mailer::{self, Mailer},
+        memory::InMemoryDatabase,
+        notifications::Notifier,
+        payments::{self, PaymentProvider},
+        push::PushProviders,
+        rate_limit::RateLimiter,
+        repository::{
+            AuditRepository, BookingRepository, DogRepository, IdempotencyRepository,
+            OwnerRepository, SearchRepository,
+        },
+        retry::RetryPolicy,
+        series, sms,
+        tokens::TokenSigner,
+        webhooks,
     },
-    services::db::Database,
+    telemetry, tls,
 };
-mod models;
-mod routes;
-mod services;
 #[get("/")]
 async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello Rusty")
 }
 #[actix_web::main]
 async fn main() -> Result<()> {
-    let db = Database::init().await;
-    let db_data = Data::new(db);
+    let cli = Cli::parse();
+    let config = Config::load(&cli).unwrap_or_else(|err| {
+        eprintln!("Invalid configuration: {}", err);
+        std::process::exit(1);
+    });
+    let tracer_provider = telemetry::init(&config).unwrap_or_else(|err| {
+        eprintln!("Invalid telemetry configuration: {}", err);
+        std::process::exit(1);
+    });
+
+    let tls_config = tls::load(&config.server).unwrap_or_else(|err| {
+        eprintln!("Invalid TLS configuration: {}", err);
+        std::process::exit(1);
+    });
+
+    // Handlers of owners, dogs, bookings and search only see these traits, implemented by
+    // `Database` and, with `--in-memory`, by `InMemoryDatabase`. Every other handler
+    // needs `Database` and answers 500 when it isn't registered.
+    let (db_data, owners_data, dogs_data, bookings_data, audit_data, idempotency_data, search_data) =
+        if cli.in_memory {
+            warn!(
+                "Running with --in-memory, data is lost on exit and only owners, dogs, bookings and search are served"
+            );
+            let memory = Arc::new(InMemoryDatabase::new(&config.bookings));
+            (
+                None,
+                Data::from(memory.clone() as Arc<dyn OwnerRepository>),
+                Data::from(memory.clone() as Arc<dyn DogRepository>),
+                Data::from(memory.clone() as Arc<dyn BookingRepository>),
+                Data::from(memory.clone() as Arc<dyn AuditRepository>),
+                Data::from(memory.clone() as Arc<dyn IdempotencyRepository>),
+                Data::from(memory as Arc<dyn SearchRepository>),
+            )
+        } else {
+            let db = Arc::new(
+                Database::connect(&config.mongo, &config.bookings)
+                    .await
+                    .unwrap_or_else(|err| {
+                        eprintln!("Invalid MongoDB configuration: {}", err);
+                        std::process::exit(1);
+                    }),
+            );
+            let startup = RetryPolicy::startup(&config.mongo);
+            match startup.run(|| db.prepare(cli.init_schema)).await {
+                Ok(()) => {}
+                Err(err @ AppError::DatabaseUnavailable { .. }) if config.mongo.start_degraded => {
+                    warn!(error = %err, "MongoDB is out of reach, starting degraded until it comes up");
+                    Database::spawn_prepare(db.clone(), startup, cli.init_schema);
+                }
+                Err(err) => {
+                    error!(error = %err, "Failed to prepare MongoDB, exiting");
+                    std::process::exit(1);
+                }
+            }
+            (
+                Some(Data::from(db.clone())),
+                Data::from(db.clone() as Arc<dyn OwnerRepository>),
+                Data::from(db.clone() as Arc<dyn DogRepository>),
+                Data::from(db.clone() as Arc<dyn BookingRepository>),
+                Data::from(db.clone() as Arc<dyn AuditRepository>),
+                Data::from(db.clone() as Arc<dyn IdempotencyRepository>),
+                Data::from(db as Arc<dyn SearchRepository>),
+            )
+        };
+    let mailer = mailer::from_config(&config.email).unwrap_or_else(|err| {
+        eprintln!("Invalid email configuration: {}", err);
+        std::process::exit(1);
+    });
+    let mailer_data: Data<dyn Mailer> = Data::from(mailer);
+    let sms = sms::from_config(&config.sms).unwrap_or_else(|err| {
+        eprintln!("Invalid SMS configuration: {}", err);
+        std::process::exit(1);
+    });
+    let push = PushProviders::from_config(&config.push).unwrap_or_else(|err| {
+        eprintln!("Invalid push configuration: {}", err);
+        std::process::exit(1);
+    });
+    let notifier_data = Data::new(Notifier::new(
+        owners_data.clone(),
+        mailer_data.clone(),
+        Data::from(sms),
+        push,
+        config.email.events.clone(),
+    ));
+    series::spawn_materializer(
+        owners_data.clone(),
+        bookings_data.clone(),
+        config.recurrence.clone(),
+        config.bookings.rabies_vaccination_policy,
+    );
+    let scheduler = Scheduler::new(&config.jobs).unwrap_or_else(|err| {
+        eprintln!("Invalid jobs configuration: {}", err);
+        std::process::exit(1);
+    });
+    let scheduler_data = Data::new(scheduler);
+    scheduler_data.start(JobContext {
+        owners: owners_data.clone(),
+        bookings: bookings_data.clone(),
+        notifier: notifier_data.clone(),
+        reminder_hours: config.sms.reminder_hours,
+        reminder_window_minutes: config.sms.reminder_window_minutes,
+        archive_after_days: config.jobs.archive_after_days,
+        rabies_policy: config.bookings.rabies_vaccination_policy,
+    });
+    if let Some(db_data) = &db_data {
+        webhooks::spawn_deliveries(db_data.clone(), config.webhooks.clone());
+        change_streams::spawn_booking_watcher(db_data.clone());
+    }
+    let db_handle = db_data.clone();
+    let signer_data = Data::new(TokenSigner::from_config(&config.auth));
+    let auth_data = Data::new(Authenticator::from_config(&config.auth));
+    let payments_data: Data<dyn PaymentProvider> =
+        Data::from(payments::from_config(&config.payments));
+    let limiter = RateLimiter::new(&config.rate_limit)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to connect to the rate limiter backend: {}", err);
+            std::process::exit(1);
+        });
+    let limiter_data = Data::new(limiter);
+    let rabies_policy_data = Data::new(config.bookings.rabies_vaccination_policy);
+    let public_url_data = Data::new(PublicUrl::new(&config.server.public_base_url));
+    let error_format_data = Data::new(config.server.error_format);
+    let admin_api_key_data = Data::new(AdminApiKey(config.auth.admin_api_key.clone()));
 
-    println!("API running at http://127.0.0.1:5001");
-    HttpServer::new(move || {
+    let (bind_address, port) = (config.server.bind_address.clone(), config.server.port);
+    let scheme = if tls_config.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    info!("API running at {}://{}:{}", scheme, bind_address, port);
+    let grpc_server = match (config.server.grpc_port, &db_data) {
+        (Some(_), None) => {
+            warn!("The gRPC service needs MongoDB for its API keys, not started with --in-memory");
+            None
+        }
+        (Some(grpc_port), Some(db_data)) => {
+            let address = (bind_address.as_str(), grpc_port)
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| Error::new(ErrorKind::AddrNotAvailable, "No gRPC address"))?;
+            info!("gRPC bookings service running at {}", address);
+            let grpc_db = db_data.clone();
+            let rabies_policy = config.bookings.rabies_vaccination_policy;
+            Some(actix_web::rt::spawn(async move {
+                if let Err(err) =
+                    grpc::serve(address, grpc_db, rabies_policy, shutdown_signal()).await
+                {
+                    error!(error = %err, "gRPC server failed");
+                }
+            }))
+        }
+        (None, _) => None,
+    };
+    let openapi = ApiDoc::openapi();
+    let mut server = HttpServer::new(move || {
         App::new()
-            .app_data(db_data.clone())
+            .configure(|cfg| {
+                if let Some(db_data) = &db_data {
+                    cfg.app_data(db_data.clone());
+                }
+            })
+            .app_data(owners_data.clone())
+            .app_data(dogs_data.clone())
+            .app_data(bookings_data.clone())
+            .app_data(audit_data.clone())
+            .app_data(idempotency_data.clone())
+            .app_data(search_data.clone())
+            .app_data(signer_data.clone())
+            .app_data(auth_data.clone())
+            .app_data(mailer_data.clone())
+            .app_data(notifier_data.clone())
+            .app_data(scheduler_data.clone())
+            .app_data(payments_data.clone())
+            .app_data(limiter_data.clone())
+            .app_data(rabies_policy_data.clone())
+            .app_data(public_url_data.clone())
+            .app_data(error_format_data.clone())
+            .app_data(admin_api_key_data.clone())
+            // Malformed bodies and query strings get the same JSON error as the rest of the API.
+            .app_data(
+                JsonConfig::default()
+                    .error_handler(|err, _| AppError::Validation(err.to_string()).into()),
+            )
+            .app_data(
+                QueryConfig::default()
+                    .error_handler(|err, _| AppError::Validation(err.to_string()).into()),
+            )
+            .app_data(
+                PathConfig::default()
+                    .error_handler(|err, _| AppError::Validation(err.to_string()).into()),
+            )
+            .wrap(from_fn(etag))
+            .wrap(from_fn(rate_limit))
+            .wrap(from_fn(api_key_auth))
+            .wrap(from_fn(error_context))
+            .wrap(TracingLogger::<RequestSpan>::new())
+            .wrap(from_fn(request_id))
+            .default_service(web::to(route_not_found))
             .service(hello)
-            .service(create_owner)
-            .service(create_dog)
-            .service(create_booking)
-            .service(get_bookings)
-            .service(cancel_booking)
+            .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", openapi.clone()))
+            .service(health)
+            .service(ready)
+            .service(web::scope(routes::API_V1).configure(routes::v1))
     })
-    .bind(("127.0.0.1", 5001))?
-    .run()
-    .await
+    .shutdown_signal(shutdown_signal())
+    .shutdown_timeout(config.server.shutdown_timeout_secs);
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+
+    server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23((bind_address, port), tls_config)?,
+        None => server.bind((bind_address, port))?,
+    };
+    let result = server.run().await;
+    if let Some(grpc_server) = grpc_server {
+        let _ = grpc_server.await;
+    }
+
+    // Every worker is stopped, the in-flight requests are done (or timed out).
+    if let Some(db_handle) = db_handle {
+        db_handle.shutdown().await;
+        info!("MongoDB client closed, bye");
+    }
+    if let Some(tracer_provider) = tracer_provider
+        && let Err(err) = tracer_provider.shutdown()
+    {
+        eprintln!("Failed to flush the last traces: {}", err);
+    }
+    result
+}
+
+/// Resolve on SIGTERM (sent by Kubernetes on redeploy) or SIGINT (Ctrl+C).
+/// The server then stops accepting connections and drains the requests in flight
+/// for up to `server.shutdown_timeout_secs`; actix would otherwise stop at once on SIGINT.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use actix_web::rt::{
+            pin,
+            signal::unix::{SignalKind, signal},
+        };
+        use futures_util::future::{Either, select};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        let terminate = terminate.recv();
+        let interrupt = actix_web::rt::signal::ctrl_c();
+        pin!(terminate, interrupt);
+        match select(terminate, interrupt).await {
+            Either::Left(_) => info!("SIGTERM received, draining in-flight requests"),
+            Either::Right(_) => info!("SIGINT received, draining in-flight requests"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = actix_web::rt::signal::ctrl_c().await;
+        info!("Ctrl+C received, draining in-flight requests");
+    }
 }
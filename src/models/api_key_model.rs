@@ -0,0 +1,132 @@
+use actix_web::http::Method;
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// What an API key may touch, as `{resource}:{read|write}`.
+/// `read` covers `GET` requests, `write` every other method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+pub enum ApiKeyScope {
+    #[serde(rename = "bookings:read")]
+    BookingsRead,
+    #[serde(rename = "bookings:write")]
+    BookingsWrite,
+    #[serde(rename = "owners:read")]
+    OwnersRead,
+    #[serde(rename = "owners:write")]
+    OwnersWrite,
+    #[serde(rename = "dogs:read")]
+    DogsRead,
+    #[serde(rename = "dogs:write")]
+    DogsWrite,
+    #[serde(rename = "walkers:read")]
+    WalkersRead,
+    #[serde(rename = "walkers:write")]
+    WalkersWrite,
+    #[serde(rename = "invoices:read")]
+    InvoicesRead,
+    #[serde(rename = "invoices:write")]
+    InvoicesWrite,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiKeyScope::BookingsRead => "bookings:read",
+            ApiKeyScope::BookingsWrite => "bookings:write",
+            ApiKeyScope::OwnersRead => "owners:read",
+            ApiKeyScope::OwnersWrite => "owners:write",
+            ApiKeyScope::DogsRead => "dogs:read",
+            ApiKeyScope::DogsWrite => "dogs:write",
+            ApiKeyScope::WalkersRead => "walkers:read",
+            ApiKeyScope::WalkersWrite => "walkers:write",
+            ApiKeyScope::InvoicesRead => "invoices:read",
+            ApiKeyScope::InvoicesWrite => "invoices:write",
+        }
+    }
+
+    /// Scope needed to call `method path`, `None` for routes API keys can't use
+    /// (sign up, login, admin endpoints...). The `/api/{version}` prefix is skipped,
+    /// a scope covers the resource in every API version.
+    pub fn required_for(method: &Method, path: &str) -> Option<ApiKeyScope> {
+        let read = method == Method::GET || method == Method::HEAD;
+        let mut segments = path.trim_start_matches('/').split('/');
+        let mut resource = segments.next()?;
+        if resource == "api" {
+            resource = segments.nth(1)?;
+        }
+
+        Some(match (resource, read) {
+            ("booking" | "bookings", true) => ApiKeyScope::BookingsRead,
+            ("booking" | "bookings", false) => ApiKeyScope::BookingsWrite,
+            ("owner" | "owners", true) => ApiKeyScope::OwnersRead,
+            ("owner" | "owners", false) => ApiKeyScope::OwnersWrite,
+            ("dog" | "dogs", true) => ApiKeyScope::DogsRead,
+            ("dog" | "dogs", false) => ApiKeyScope::DogsWrite,
+            ("walker" | "walkers", true) => ApiKeyScope::WalkersRead,
+            ("walker" | "walkers", false) => ApiKeyScope::WalkersWrite,
+            ("invoice" | "invoices", true) => ApiKeyScope::InvoicesRead,
+            ("invoice" | "invoices", false) => ApiKeyScope::InvoicesWrite,
+            _ => return None,
+        })
+    }
+}
+
+/// Credential of a backend integration, sent as `X-Api-Key` instead of a bearer token.
+/// Only the SHA-256 of the key is stored, the key itself is shown once at creation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub _id: ObjectId,
+    pub name: String,
+    pub key_hash: String,
+    /// First characters of the key, to recognize it in listings.
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime,
+    /// Set by `DELETE /admin/api-keys/{id}`, a revoked key is refused.
+    pub revoked_at: Option<DateTime>,
+}
+
+/// Body of `POST /admin/api-keys`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ApiKeyRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "must contain at least one scope"))]
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// HTTP view of an `ApiKey`, without its hash.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyView {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: String,
+    pub revoked_at: Option<String>,
+}
+
+impl From<ApiKey> for ApiKeyView {
+    fn from(key: ApiKey) -> Self {
+        ApiKeyView {
+            id: key._id.to_hex(),
+            name: key.name,
+            prefix: key.prefix,
+            scopes: key.scopes,
+            created_at: key.created_at.try_to_rfc3339_string().unwrap_or_default(),
+            revoked_at: key
+                .revoked_at
+                .and_then(|at| at.try_to_rfc3339_string().ok()),
+        }
+    }
+}
+
+/// Response of `POST /admin/api-keys`, the only time `key` is returned.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKeyView,
+    pub key: String,
+}
@@ -0,0 +1,140 @@
+use mongodb::bson::{Bson, DateTime, Document, oid::ObjectId, to_document};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{
+    auth_model::Role,
+    serde_helpers::{DateTimeJson, ObjectIdJson},
+};
+
+/// One mutation, stored in the "audit_log" collection by `record_audit`.
+/// `before` and `after` are the stored documents around the change,
+/// missing when there was nothing before (create) or after (delete).
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = DateTimeJson)]
+    pub at: DateTime,
+    pub actor: AuditActor,
+    pub action: AuditAction,
+    pub entity: EntityRef,
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Document>,
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Document>,
+}
+
+/// Who made the change. Both fields are empty for unauthenticated calls,
+/// `user_id` alone is empty for calls made with the admin key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct AuditActor {
+    /// Owner, walker or admin `_id`, or the id of the API key used.
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub user_id: Option<ObjectId>,
+    pub role: Option<Role>,
+}
+
+impl AuditActor {
+    pub fn anonymous() -> Self {
+        AuditActor::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    VerifyEmail,
+    Cancel,
+    Confirm,
+    Start,
+    Complete,
+    AssignWalker,
+    Report,
+    Refund,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Owner,
+    Dog,
+    Walker,
+    Booking,
+    Review,
+    Incident,
+    Invoice,
+    Payout,
+    Series,
+    Waitlist,
+    GroupWalk,
+}
+
+impl EntityKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntityKind::Owner => "owner",
+            EntityKind::Dog => "dog",
+            EntityKind::Walker => "walker",
+            EntityKind::Booking => "booking",
+            EntityKind::Review => "review",
+            EntityKind::Incident => "incident",
+            EntityKind::Invoice => "invoice",
+            EntityKind::Payout => "payout",
+            EntityKind::Series => "series",
+            EntityKind::Waitlist => "waitlist",
+            EntityKind::GroupWalk => "group_walk",
+        }
+    }
+}
+
+impl From<EntityKind> for Bson {
+    fn from(kind: EntityKind) -> Self {
+        Bson::String(kind.as_str().to_string())
+    }
+}
+
+/// The document a change applies to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct EntityRef {
+    pub kind: EntityKind,
+    #[schema(value_type = ObjectIdJson)]
+    pub id: ObjectId,
+}
+
+impl EntityRef {
+    pub fn new(kind: EntityKind, id: ObjectId) -> Self {
+        EntityRef { kind, id }
+    }
+}
+
+/// The stored shape of `value`, for `before` and `after`.
+pub fn snapshot<T: Serialize>(value: &T) -> Option<Document> {
+    to_document(value).ok()
+}
+
+/// Query string of `GET /admin/audit`, every filter is optional.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// ObjectId of the owner, dog, walker or booking.
+    pub entity_id: Option<String>,
+    /// RFC 3339, inclusive.
+    pub from: Option<String>,
+    /// RFC 3339, exclusive.
+    pub to: Option<String>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+/// `AuditQuery` once parsed.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub entity_id: Option<ObjectId>,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+}
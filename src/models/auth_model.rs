@@ -0,0 +1,122 @@
+use mongodb::bson::{Bson, DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::serde_helpers::deserialize_optional_object_id;
+
+/// What an authenticated user is allowed to do, carried in the access token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Manages its own dogs and bookings.
+    Owner,
+    /// Sees and walks the bookings assigned to it.
+    Walker,
+    /// Full access.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Walker => "walker",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl From<Role> for Bson {
+    fn from(role: Role) -> Self {
+        Bson::String(role.as_str().to_string())
+    }
+}
+
+/// Login of a user, stored apart from the `owner` and `walker` documents so the
+/// password hash never ends up in a response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Credentials {
+    pub _id: ObjectId,
+    /// Owner or walker `_id` depending on `role`, a fresh id for admins.
+    pub user_id: ObjectId,
+    pub email: String,
+    /// Argon2id hash in PHC string format.
+    pub password_hash: String,
+    pub role: Role,
+}
+
+/// Body of `POST /auth/register`: the owner profile plus a password.
+/// Profile fields follow the `OwnerRequest` rules.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct RegisterRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
+    pub name: String,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(min = 6, max = 20, message = "must be 6 to 20 characters long"))]
+    pub phone: String,
+    #[validate(length(min = 1, max = 200, message = "must be 1 to 200 characters long"))]
+    pub address: String,
+    #[validate(length(min = 8, max = 128, message = "must be 8 to 128 characters long"))]
+    pub password: String,
+}
+
+/// Body of `POST /admin/accounts`, creates the login of a walker or an admin.
+/// `user_id` is required for walkers and must reference an existing walker.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct AccountRequest {
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(min = 8, max = 128, message = "must be 8 to 128 characters long"))]
+    pub password: String,
+    pub role: Role,
+    #[serde(default, deserialize_with = "deserialize_optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub user_id: Option<ObjectId>,
+}
+
+/// Body of `POST /auth/login`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct LoginRequest {
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(min = 1, message = "is required"))]
+    pub password: String,
+}
+
+/// Pending password reset. Only the SHA-256 of the emailed token is stored,
+/// and a TTL index on `expires_at` removes the document once it expires.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordReset {
+    pub _id: ObjectId,
+    pub credentials: ObjectId,
+    pub token_hash: String,
+    pub expires_at: DateTime,
+}
+
+/// Body of `POST /auth/forgot-password`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ForgotPasswordRequest {
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+}
+
+/// Body of `POST /auth/reset-password`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1, message = "is required"))]
+    pub token: String,
+    #[validate(length(min = 8, max = 128, message = "must be 8 to 128 characters long"))]
+    pub password: String,
+}
+
+/// Response of register and login.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub role: Role,
+    pub token_type: &'static str,
+    /// Validity of the token in seconds.
+    pub expires_in: u64,
+}
@@ -0,0 +1,305 @@
+use chrono::{Datelike, NaiveDate, Timelike};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+use super::{
+    booking_model::{parse_rfc3339, validate_rfc3339},
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson},
+    series_model::DayOfWeek,
+};
+
+const MINUTE_MILLIS: i64 = 60 * 1000;
+const DAY_MINUTES: i64 = 24 * 60;
+
+/// When a walker works, set with `POST /walker/{id}/availability` and stored under
+/// the walker's id. `POST /booking/{id}/assign/{walker_id}` only assigns a walk that
+/// fits in one weekly slot and misses every block. A walker without one can be
+/// assigned any time.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WalkerAvailability {
+    /// ObjectId of the walker.
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    /// Hours worked every week, in UTC, sorted.
+    pub weekly: Vec<WeeklySlot>,
+    /// One-off time off, oldest first.
+    pub blocks: Vec<AvailabilityBlock>,
+    #[schema(value_type = DateTimeJson)]
+    pub updated_at: DateTime,
+}
+
+impl WalkerAvailability {
+    /// Availability of `walker` from a request whose fields are validated.
+    pub fn new(walker: ObjectId, request: AvailabilityRequest) -> Result<Self, String> {
+        let mut weekly = request.weekly;
+        for slot in &weekly {
+            let (from, to) = slot.minutes()?;
+            if from >= to {
+                return Err(format!(
+                    "The {} slot must end after it starts",
+                    slot.weekday.as_str()
+                ));
+            }
+        }
+        weekly.sort_by_key(|slot| (slot.weekday.number(), slot.from.clone()));
+        for pair in weekly.windows(2) {
+            if pair[0].weekday == pair[1].weekday && pair[1].from < pair[0].to {
+                return Err(format!("The {} slots overlap", pair[0].weekday.as_str()));
+            }
+        }
+
+        let mut blocks = request
+            .blocks
+            .into_iter()
+            .map(AvailabilityBlock::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        blocks.sort_by_key(|block| block.from);
+        Ok(WalkerAvailability {
+            _id: walker,
+            weekly,
+            blocks,
+            updated_at: DateTime::now(),
+        })
+    }
+
+    /// Whether a walk over `[start, end)` lies in one weekly slot and misses every block.
+    pub fn allows(&self, start: DateTime, end: DateTime) -> bool {
+        let Some(day_start) = day_start(start) else {
+            return false;
+        };
+        let from = (start.timestamp_millis() - day_start.timestamp_millis()) / MINUTE_MILLIS;
+        let to = (end.timestamp_millis() - day_start.timestamp_millis() + MINUTE_MILLIS - 1)
+            / MINUTE_MILLIS;
+        let weekday = DayOfWeek::of(start);
+        let in_slot = self.weekly.iter().any(|slot| {
+            slot.weekday == weekday
+                && slot
+                    .minutes()
+                    .is_ok_and(|(slot_from, slot_to)| slot_from <= from && to <= slot_to)
+        });
+        in_slot
+            && !self
+                .blocks
+                .iter()
+                .any(|block| block.from < end && start < block.to)
+    }
+
+    /// Working hours of the 7 days from `monday` (UTC midnight), blocks taken out.
+    pub fn hours(&self, monday: DateTime) -> Vec<TimeRange> {
+        let mut hours = Vec::new();
+        for day in 0..7 {
+            let day_start = monday.timestamp_millis() + day * DAY_MINUTES * MINUTE_MILLIS;
+            let weekday = DayOfWeek::of(DateTime::from_millis(day_start));
+            for slot in self.weekly.iter().filter(|slot| slot.weekday == weekday) {
+                let Ok((from, to)) = slot.minutes() else {
+                    continue;
+                };
+                let mut open = vec![TimeRange {
+                    from: DateTime::from_millis(day_start + from * MINUTE_MILLIS),
+                    to: DateTime::from_millis(day_start + to * MINUTE_MILLIS),
+                }];
+                for block in &self.blocks {
+                    open = open
+                        .into_iter()
+                        .flat_map(|range| range.without(block.from, block.to))
+                        .collect();
+                }
+                hours.extend(open);
+            }
+        }
+        hours
+    }
+}
+
+impl HasObjectId for WalkerAvailability {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// Hours worked on one day of every week, `HH:MM` in UTC, `to` up to `24:00`.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct WeeklySlot {
+    pub weekday: DayOfWeek,
+    #[validate(custom(function = "validate_time_of_day"))]
+    #[schema(example = "08:00")]
+    pub from: String,
+    #[validate(custom(function = "validate_time_of_day"))]
+    #[schema(example = "12:30")]
+    pub to: String,
+}
+
+impl WeeklySlot {
+    /// `from` and `to` in minutes since midnight.
+    fn minutes(&self) -> Result<(i64, i64), String> {
+        Ok((parse_time_of_day(&self.from)?, parse_time_of_day(&self.to)?))
+    }
+}
+
+/// Time off of a walker, e.g. holidays.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AvailabilityBlock {
+    #[schema(value_type = DateTimeJson)]
+    pub from: DateTime,
+    #[schema(value_type = DateTimeJson)]
+    pub to: DateTime,
+    pub reason: Option<String>,
+}
+
+impl TryFrom<BlockRequest> for AvailabilityBlock {
+    type Error = String;
+
+    fn try_from(item: BlockRequest) -> Result<Self, Self::Error> {
+        let from = parse_rfc3339(&item.from)?;
+        let to = parse_rfc3339(&item.to)?;
+        if to <= from {
+            return Err("A block must end after it starts".to_string());
+        }
+        Ok(AvailabilityBlock {
+            from,
+            to,
+            reason: item.reason,
+        })
+    }
+}
+
+/// Body of `POST /walker/{id}/availability`, replacing the whole availability.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct AvailabilityRequest {
+    #[validate(length(max = 50, message = "must have at most 50 slots"), nested)]
+    pub weekly: Vec<WeeklySlot>,
+    #[serde(default)]
+    #[validate(length(max = 100, message = "must have at most 100 blocks"), nested)]
+    pub blocks: Vec<BlockRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct BlockRequest {
+    #[validate(custom(function = "validate_rfc3339"))]
+    #[schema(example = "2025-08-04T00:00:00Z")]
+    pub from: String,
+    #[validate(custom(function = "validate_rfc3339"))]
+    #[schema(example = "2025-08-18T00:00:00Z")]
+    pub to: String,
+    #[validate(length(max = 200, message = "must be at most 200 characters long"))]
+    pub reason: Option<String>,
+}
+
+/// Query string of `GET /walker/{id}/availability`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WeekQuery {
+    /// ISO week, e.g. `2025-W37`. The current week by default.
+    pub week: Option<String>,
+}
+
+/// Answer of `GET /walker/{id}/availability`, for the scheduling UI.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeekAvailability {
+    #[schema(value_type = ObjectIdJson)]
+    pub walker: ObjectId,
+    /// ISO week, e.g. `2025-W37`.
+    pub week: String,
+    /// Working hours of the week less the blocks, every hour when the walker
+    /// has no availability.
+    pub hours: Vec<TimeRange>,
+    /// Blocks overlapping the week.
+    pub blocks: Vec<AvailabilityBlock>,
+    /// Active bookings of the walker in the week.
+    pub booked: Vec<BookedRange>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimeRange {
+    #[schema(value_type = DateTimeJson)]
+    pub from: DateTime,
+    #[schema(value_type = DateTimeJson)]
+    pub to: DateTime,
+}
+
+impl TimeRange {
+    /// What is left of the range once `[from, to)` is taken out.
+    fn without(self, from: DateTime, to: DateTime) -> Vec<TimeRange> {
+        if to <= self.from || self.to <= from {
+            return vec![self];
+        }
+        let mut left = Vec::new();
+        if self.from < from {
+            left.push(TimeRange {
+                from: self.from,
+                to: from,
+            });
+        }
+        if to < self.to {
+            left.push(TimeRange {
+                from: to,
+                to: self.to,
+            });
+        }
+        left
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BookedRange {
+    #[schema(value_type = ObjectIdJson)]
+    pub booking: ObjectId,
+    #[schema(value_type = DateTimeJson)]
+    pub from: DateTime,
+    #[schema(value_type = DateTimeJson)]
+    pub to: DateTime,
+}
+
+/// Monday of an ISO week such as `2025-W37`, the current week when `None`.
+pub fn parse_week(week: Option<&str>) -> Result<NaiveDate, String> {
+    match week {
+        Some(week) => NaiveDate::parse_from_str(&format!("{}-1", week), "%G-W%V-%u")
+            .map_err(|_| format!("`{}` is not an ISO week such as 2025-W37", week)),
+        None => {
+            let today = chrono::Utc::now().date_naive();
+            Ok(today - chrono::Days::new(u64::from(today.weekday().num_days_from_monday())))
+        }
+    }
+}
+
+/// Name of the ISO week of `monday`, e.g. `2025-W37`.
+pub fn week_name(monday: NaiveDate) -> String {
+    let week = monday.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+/// UTC midnight of the day of `at`.
+fn day_start(at: DateTime) -> Option<DateTime> {
+    let at = chrono::DateTime::from_timestamp_millis(at.timestamp_millis())?;
+    let midnight = at
+        .with_hour(0)?
+        .with_minute(0)?
+        .with_second(0)?
+        .with_nanosecond(0)?;
+    Some(DateTime::from_millis(midnight.timestamp_millis()))
+}
+
+/// Minutes since midnight of `HH:MM`, `24:00` is the end of the day.
+fn parse_time_of_day(value: &str) -> Result<i64, String> {
+    let invalid = || format!("`{}` is not a time such as 08:30", value);
+    let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+    let digits = |part: &str| part.len() == 2 && part.chars().all(|c| c.is_ascii_digit());
+    if !digits(hours) || !digits(minutes) {
+        return Err(invalid());
+    }
+    let hours: i64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+    let minute = hours * 60 + minutes;
+    match minutes < 60 && minute <= DAY_MINUTES {
+        true => Ok(minute),
+        false => Err(invalid()),
+    }
+}
+
+fn validate_time_of_day(value: &str) -> Result<(), ValidationError> {
+    parse_time_of_day(value).map(|_| ()).map_err(|_| {
+        ValidationError::new("time_of_day").with_message("must be a time such as 08:30".into())
+    })
+}
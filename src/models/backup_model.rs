@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{booking_model::Booking, dog_model::Dog, owner_model::Owner};
+
+/// Full dataset, the shape streamed by `GET /admin/export` and accepted by `POST /admin/import`.
+/// Every document goes through the model types, so an import can't store anything
+/// the API would later fail to deserialize.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct Backup {
+    pub owners: Vec<Owner>,
+    pub dogs: Vec<Dog>,
+    pub bookings: Vec<Booking>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Only allowed when the owner, dog and booking collections are empty.
+    Replace,
+    /// Insert next to existing data, skipping `_id`s that already exist.
+    Merge,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    pub mode: Option<ImportMode>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct CollectionImport {
+    pub inserted: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub owners: CollectionImport,
+    pub dogs: CollectionImport,
+    pub bookings: CollectionImport,
+}
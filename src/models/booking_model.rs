@@ -1,34 +1,679 @@
 use std::{convert::TryFrom, time::SystemTime};
 
-use super::{dog_model::Dog, owner_model::Owner};
+use super::{
+    coupon_model::BookingCoupon,
+    dog_model::Dog,
+    owner_model::Owner,
+    page_model::PageQuery,
+    payment_model::BookingPayment,
+    pricing_model::Quote,
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson, WithId, deserialize_object_id},
+    series_model::RecurrenceRequest,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use chrono::Utc;
-use mongodb::bson::{oid::ObjectId, DateTime};
+use mongodb::bson::{Bson, DateTime, Document, doc, oid::ObjectId};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct Booking {
+    #[schema(value_type = ObjectIdJson)]
     pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
     pub owner: ObjectId,
+    #[schema(value_type = DateTimeJson)]
     pub start_time: DateTime,
     pub duration_in_minutes: u8,
-    pub cancelled: bool,
+    pub status: BookingStatus,
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub cancelled_at: Option<DateTime>,
+    pub cancellation_reason: Option<String>,
+    /// Owed under the cancellation policy, set when the owner cancels.
+    #[serde(default)]
+    pub cancellation_fee_cents: Option<i64>,
+    pub report: Option<WalkReport>,
+    /// Walker assigned with `POST /booking/{id}/assign/{walker_id}`, missing on older bookings.
+    #[serde(default)]
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub walker: Option<ObjectId>,
+    /// Between 0 and 1, set when the walker was picked by the matching on creation,
+    /// missing when an admin assigned it.
+    #[serde(default)]
+    pub match_score: Option<f64>,
+    /// When the owner was reminded of the walk, set before the reminder is sent.
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub reminder_sent_at: Option<DateTime>,
+    /// A pending booking not confirmed by then expires, freeing its slot. Set by the
+    /// storage on creation, cleared once confirmed.
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub expires_at: Option<DateTime>,
+    /// Incremented by every update, `PUT /booking/{id}` must send the current one.
+    #[serde(default)]
+    pub version: i64,
+    /// Set by `DELETE /booking/{id}`, cleared by `POST /booking/{id}/restore`.
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub deleted_at: Option<DateTime>,
+    /// Priced when booked, again when rescheduled or given a walker.
+    /// Missing on bookings made before pricing.
+    #[serde(default)]
+    pub price_cents: Option<i64>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Authorized when the booking is confirmed, missing until then.
+    #[serde(default)]
+    pub payment: Option<BookingPayment>,
+    /// Coupon redeemed when booking, its discount is a line of every quote.
+    #[serde(default)]
+    pub coupon: Option<BookingCoupon>,
+    /// Loyalty points spent on a free walk when booking, a line of every quote.
+    #[serde(default)]
+    pub points_redeemed: i64,
+    /// Loyalty points the owner earned, set once when the booking completes.
+    #[serde(default)]
+    pub points_earned: Option<i64>,
+    /// Recurring series the booking is a walk of, see `GET /series/{id}`.
+    #[serde(default)]
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub series: Option<ObjectId>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Booking {
+    pub fn with_price(self, quote: Quote) -> Self {
+        Booking {
+            price_cents: Some(quote.price_cents),
+            currency: Some(quote.currency),
+            ..self
+        }
+    }
+}
+
+/// Lifecycle of a booking, stored as a snake_case string.
+///
+/// ```text
+/// Pending -> Confirmed -> InProgress -> Completed
+///    |  |        |
+///    |  |        +-> NoShow
+///    |  +--------+-> Cancelled
+///    +-> Expired, not confirmed by its `expires_at`
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BookingStatus {
+    Pending,
+    Confirmed,
+    InProgress,
+    Completed,
+    Cancelled,
+    NoShow,
+    Expired,
+}
+
+impl BookingStatus {
+    /// Statuses a booking must currently have to move to `self`.
+    pub fn allowed_from(self) -> &'static [BookingStatus] {
+        use BookingStatus::*;
+        match self {
+            Pending => &[],
+            Confirmed => &[Pending],
+            InProgress => &[Confirmed],
+            Completed => &[InProgress],
+            Cancelled => &[Pending, Confirmed],
+            NoShow => &[Confirmed],
+            Expired => &[Pending],
+        }
+    }
+
+    /// Update sent to `GET /ws/bookings` when a booking moves to `self`, if any.
+    pub fn update_kind(self) -> Option<BookingUpdateKind> {
+        match self {
+            BookingStatus::Cancelled => Some(BookingUpdateKind::Cancelled),
+            BookingStatus::Completed => Some(BookingUpdateKind::Completed),
+            _ => None,
+        }
+    }
+
+    /// Statuses of bookings still holding their time slot.
+    pub fn active() -> &'static [BookingStatus] {
+        &[
+            BookingStatus::Pending,
+            BookingStatus::Confirmed,
+            BookingStatus::InProgress,
+        ]
+    }
+
+    /// Inverse of `as_str`.
+    pub fn parse(value: &str) -> Option<BookingStatus> {
+        use BookingStatus::*;
+        [
+            Pending, Confirmed, InProgress, Completed, Cancelled, NoShow, Expired,
+        ]
+        .into_iter()
+        .find(|status| status.as_str() == value)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BookingStatus::Pending => "pending",
+            BookingStatus::Confirmed => "confirmed",
+            BookingStatus::InProgress => "in_progress",
+            BookingStatus::Completed => "completed",
+            BookingStatus::Cancelled => "cancelled",
+            BookingStatus::NoShow => "no_show",
+            BookingStatus::Expired => "expired",
+        }
+    }
+}
+
+impl From<BookingStatus> for Bson {
+    fn from(status: BookingStatus) -> Self {
+        Bson::String(status.as_str().to_string())
+    }
+}
+
+/// Convert a list of statuses for `$in` filters.
+pub fn status_list(statuses: &[BookingStatus]) -> Vec<Bson> {
+    statuses.iter().map(|status| Bson::from(*status)).collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct BookingRequest {
-    pub owner: String,
+    #[serde(deserialize_with = "deserialize_object_id")]
+    #[schema(value_type = String, example = "66f1c0de2a9b4c0012345678")]
+    pub owner: ObjectId,
+    #[validate(custom(function = "validate_rfc3339"))]
+    #[schema(example = "2025-09-06T18:30:00+02:00")]
     pub start_time: String,
+    #[validate(range(min = 15, max = 240, message = "must be between 15 and 240 minutes"))]
     pub duration_in_minutes: u8,
+    /// Redeemed when the booking is created, only checked by `POST /booking/quote`.
+    #[validate(length(min = 1, max = 32, message = "must be 1 to 32 characters long"))]
+    #[schema(example = "SPRING25")]
+    pub coupon_code: Option<String>,
+    /// Spend the owner's loyalty points on a free walk, see `GET /owner/{id}/points`.
+    #[serde(default)]
+    pub redeem_points: bool,
+    /// Book the same walk every week, the first one at `start_time`.
+    /// Can't be combined with `coupon_code` or `redeem_points`.
+    #[validate(nested)]
+    pub recurrence: Option<RecurrenceRequest>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Body of `PUT /booking/{id}`: new RFC 3339 start_time and/or duration.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BookingUpdateRequest {
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub start_time: Option<String>,
+    #[validate(range(min = 15, max = 240, message = "must be between 15 and 240 minutes"))]
+    pub duration_in_minutes: Option<u8>,
+    /// Current `version` of the booking, for clients that can't send `If-Match`.
+    pub expected_version: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct FullBooking {
+    #[schema(value_type = ObjectIdJson)]
     pub _id: ObjectId,
-    pub owner: Owner,
-    pub dogs: Vec<Dog>,
+    pub owner: WithId<Owner>,
+    /// Live dogs of the owner, with the `profile` the walker must read before the walk.
+    pub dogs: Vec<WithId<Dog>>,
+    #[schema(value_type = DateTimeJson)]
     pub start_time: DateTime,
     pub duration_in_minutes: u8,
-    pub cancelled: bool,
+    pub status: BookingStatus,
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub cancelled_at: Option<DateTime>,
+    pub cancellation_reason: Option<String>,
+    #[serde(default)]
+    pub cancellation_fee_cents: Option<i64>,
+    pub report: Option<WalkReport>,
+    /// Walker assigned with `POST /booking/{id}/assign/{walker_id}`, missing on older bookings.
+    #[serde(default)]
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub walker: Option<ObjectId>,
+    #[serde(default)]
+    pub match_score: Option<f64>,
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub reminder_sent_at: Option<DateTime>,
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub expires_at: Option<DateTime>,
+    #[serde(default)]
+    pub version: i64,
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub deleted_at: Option<DateTime>,
+    #[serde(default)]
+    pub price_cents: Option<i64>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub payment: Option<BookingPayment>,
+    #[serde(default)]
+    pub coupon: Option<BookingCoupon>,
+    #[serde(default)]
+    pub points_redeemed: i64,
+    #[serde(default)]
+    pub points_earned: Option<i64>,
+    #[serde(default)]
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub series: Option<ObjectId>,
+}
+
+/// Response of `GET /bookings`, one page of the matching bookings.
+/// `skipped` counts stored documents of the page that could not be deserialized.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BookingList {
+    pub bookings: Vec<ListedBooking>,
+    pub skipped: usize,
+    /// Missing when the page was asked with `after`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    pub limit: u64,
+    /// Bookings matching the query, over every page. Not counted when the page
+    /// was asked with `after`, to keep walking large result sets cheap.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// `after` of the next page, missing on the last one.
+    pub next_cursor: Option<String>,
+}
+
+/// A booking of `GET /bookings`: complete, or reduced to the paths asked with `?fields=`.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum ListedBooking {
+    Full(Box<WithId<FullBooking>>),
+    /// `_id`, its `id` alias and the asked paths.
+    #[schema(value_type = Object)]
+    Partial(Document),
+}
+
+impl ListedBooking {
+    /// Wrap a projected booking. The projection also keeps `start_time` for
+    /// `next_cursor` (see `projection`), dropped here unless it was asked for.
+    pub fn partial(mut booking: Document, fields: &[String]) -> Self {
+        if !fields.iter().any(|field| field == "start_time") {
+            booking.remove("start_time");
+        }
+        if let Ok(id) = booking.get_object_id("_id") {
+            booking.insert("id", id.to_hex());
+        }
+        ListedBooking::Partial(booking)
+    }
+}
+
+/// `$project` document of `?fields=`, plus the `_id` and `start_time` the pagination needs.
+pub fn projection(fields: &[String]) -> Document {
+    let mut projection = doc! {"_id": 1, "start_time": 1};
+    for field in fields {
+        projection.insert(field.as_str(), 1);
+    }
+    projection
+}
+
+/// Top level fields of a listed booking, the first segment of every `?fields=` path.
+const LISTED_FIELDS: &[&str] = &[
+    "_id",
+    "owner",
+    "dogs",
+    "start_time",
+    "duration_in_minutes",
+    "status",
+    "cancelled_at",
+    "cancellation_reason",
+    "cancellation_fee_cents",
+    "report",
+    "walker",
+    "match_score",
+    "reminder_sent_at",
+    "expires_at",
+    "version",
+    "deleted_at",
+    "series",
+];
+
+/// Most paths accepted in one `?fields=`.
+const MAX_FIELDS: usize = 20;
+
+/// Parse `?fields=`, comma separated dotted paths such as `owner.name`.
+/// `id` stands for `_id`. A path under another asked path is dropped:
+/// MongoDB refuses to project both `owner` and `owner.name`.
+pub fn parse_fields(raw: &str) -> Result<Vec<String>, String> {
+    let mut fields: Vec<String> = Vec::new();
+    for field in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+    {
+        let field = if field == "id" { "_id" } else { field };
+        let valid = field.split('.').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+        });
+        let top = field.split('.').next().unwrap_or_default();
+        if !valid || !LISTED_FIELDS.contains(&top) {
+            return Err(format!("`{}` is not a field of a booking", field));
+        }
+        fields.push(field.to_string());
+    }
+    if fields.is_empty() {
+        return Err("`fields` must name at least one field".to_string());
+    }
+    if fields.len() > MAX_FIELDS {
+        return Err(format!("`fields` accepts at most {} paths", MAX_FIELDS));
+    }
+
+    let covered = |field: &String, by: &String| field.starts_with(&format!("{}.", by));
+    let kept = fields
+        .iter()
+        .filter(|field| !fields.iter().any(|other| covered(field, other)))
+        .cloned()
+        .fold(Vec::new(), |mut kept: Vec<String>, field| {
+            if !kept.contains(&field) {
+                kept.push(field);
+            }
+            kept
+        });
+    Ok(kept)
+}
+
+/// Position of a booking in the `(start_time, _id)` order of `GET /bookings`,
+/// the next page starts right after it. Clients see it as an opaque base64url string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookingCursor {
+    pub start_time: DateTime,
+    pub id: ObjectId,
+}
+
+impl BookingCursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.start_time.timestamp_millis(),
+            self.id.to_hex()
+        ))
+    }
+
+    pub fn decode(raw: &str) -> Result<Self, String> {
+        let invalid = || "`after` is not a cursor returned by this API".to_string();
+        let decoded = URL_SAFE_NO_PAD.decode(raw).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (millis, id) = decoded.split_once(':').ok_or_else(invalid)?;
+        Ok(BookingCursor {
+            start_time: DateTime::from_millis(millis.parse().map_err(|_| invalid())?),
+            id: ObjectId::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Sort order of `GET /bookings`, ties are broken by `_id`.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum BookingSort {
+    #[default]
+    #[serde(rename = "start_time:asc")]
+    StartTimeAsc,
+    #[serde(rename = "start_time:desc")]
+    StartTimeDesc,
+}
+
+/// Query string of `GET /bookings`. Without any, the first page of the
+/// upcoming active bookings, soonest first.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BookingListQuery {
+    /// RFC 3339, bookings starting at or after it. Defaults to now.
+    pub from: Option<String>,
+    /// RFC 3339, bookings starting before it.
+    pub to: Option<String>,
+    /// ObjectId of the owner, owners may only ask for their own bookings.
+    pub owner: Option<String>,
+    /// Comma separated statuses, defaults to `pending,confirmed,in_progress`.
+    pub status: Option<String>,
+    /// Offset pagination, pages start at 1.
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    /// `next_cursor` of the previous page, keyset pagination for large
+    /// result sets. Can't be combined with `page`.
+    pub after: Option<String>,
+    #[serde(default)]
+    pub sort: BookingSort,
+    /// Comma separated paths to return instead of the whole booking,
+    /// e.g. `start_time,owner.name,dogs.name`.
+    pub fields: Option<String>,
+}
+
+impl BookingListQuery {
+    /// Parse the dates, statuses and pagination. `filter` holds the equality
+    /// conditions decided by the caller (`{"owner": id}`, `{"walker": id}`).
+    pub fn resolve(&self, filter: Document) -> Result<BookingQuery, String> {
+        let (page, limit) = PageQuery {
+            page: self.page,
+            limit: self.limit,
+        }
+        .resolve()?;
+        let after = self
+            .after
+            .as_deref()
+            .map(BookingCursor::decode)
+            .transpose()?;
+        if after.is_some() && self.page.is_some() {
+            return Err("`page` and `after` can't be combined".to_string());
+        }
+
+        let from = match &self.from {
+            Some(from) => parse_rfc3339(from)?,
+            None => DateTime::now(),
+        };
+        let to = self.to.as_deref().map(parse_rfc3339).transpose()?;
+        if to.is_some_and(|to| to <= from) {
+            return Err("`to` must be after `from`".to_string());
+        }
+
+        let statuses = match &self.status {
+            Some(statuses) => statuses
+                .split(',')
+                .map(|status| {
+                    BookingStatus::parse(status.trim())
+                        .ok_or_else(|| format!("`{}` is not a booking status", status.trim()))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => BookingStatus::active().to_vec(),
+        };
+
+        Ok(BookingQuery {
+            filter,
+            from,
+            to,
+            statuses,
+            sort: self.sort,
+            page,
+            limit,
+            after,
+            fields: self.fields.as_deref().map(parse_fields).transpose()?,
+        })
+    }
+}
+
+/// Bookings to list, see `BookingRepository::get_bookings`.
+#[derive(Debug)]
+pub struct BookingQuery {
+    /// Equality conditions, e.g. `{"owner": id}`.
+    pub filter: Document,
+    pub from: DateTime,
+    pub to: Option<DateTime>,
+    pub statuses: Vec<BookingStatus>,
+    pub sort: BookingSort,
+    /// Ignored when `after` is set.
+    pub page: u64,
+    pub limit: u64,
+    pub after: Option<BookingCursor>,
+    /// Paths to project, see `parse_fields`.
+    pub fields: Option<Vec<String>>,
+}
+
+impl BookingQuery {
+    /// Every upcoming active booking matching `filter`, soonest first, up to
+    /// the `bookings.max_results` guard of the implementation.
+    pub fn upcoming(filter: Document) -> Self {
+        BookingQuery {
+            filter,
+            from: DateTime::now(),
+            to: None,
+            statuses: BookingStatus::active().to_vec(),
+            sort: BookingSort::StartTimeAsc,
+            page: 1,
+            limit: u64::MAX,
+            after: None,
+            fields: None,
+        }
+    }
+}
+
+/// Query string of `GET /admin/bookings/archive`, every filter is optional.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArchiveQuery {
+    /// ObjectId of the owner.
+    pub owner: Option<String>,
+    /// RFC 3339, walks starting from then, inclusive.
+    pub from: Option<String>,
+    /// RFC 3339, walks starting before then, exclusive.
+    pub to: Option<String>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+/// `ArchiveQuery` once parsed.
+#[derive(Debug, Default)]
+pub struct ArchiveFilter {
+    pub owner: Option<ObjectId>,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+}
+
+/// Report filled in by the walker once the walk is over, embedded in the booking.
+#[derive(Debug, Clone, Deserialize, Serialize, Validate, ToSchema)]
+pub struct WalkReport {
+    #[validate(length(min = 1, max = 2000, message = "must be 1 to 2000 characters long"))]
+    pub notes: String,
+    #[validate(range(max = 50_000, message = "must be at most 50 km"))]
+    pub distance_meters: u32,
+    #[validate(length(max = 2000, message = "must be at most 2000 characters long"))]
+    pub incidents: Option<String>,
+    #[serde(default)]
+    pub pee: bool,
+    #[serde(default)]
+    pub poo: bool,
+    /// The dog was given water.
+    #[serde(default)]
+    pub water: bool,
+    /// References (URLs) of the photos taken during the walk.
+    #[serde(default)]
+    #[validate(
+        length(max = 10, message = "must hold at most 10 photos"),
+        custom(function = "validate_photo_refs")
+    )]
+    pub photos: Vec<String>,
+}
+
+pub fn validate_photo_refs(photos: &[String]) -> Result<(), ValidationError> {
+    if photos
+        .iter()
+        .any(|photo| photo.trim().is_empty() || photo.len() > 500)
+    {
+        return Err(ValidationError::new("photo")
+            .with_message("every photo must be 1 to 500 characters long".into()));
+    }
+    Ok(())
+}
+
+/// Query string of `POST /booking/{id}/report`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportQuery {
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+impl HasObjectId for Booking {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+impl HasObjectId for FullBooking {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// Body of `POST /bookings/cancel`: cancel every booking starting in `[from, to)`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct BulkCancelRequest {
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub from: String,
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub to: String,
+    #[validate(length(min = 1, max = 500, message = "must be 1 to 500 characters long"))]
+    pub reason: String,
+}
+
+/// Result of a bulk cancellation, the ids let us notify the affected owners.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkCancelResult {
+    pub modified_count: u64,
+    #[schema(value_type = Vec<ObjectIdJson>)]
+    pub booking_ids: Vec<ObjectId>,
+}
+
+/// Change of a booking sent to the dashboards listening on `GET /ws/bookings`,
+/// one JSON text message each. Fetch `GET /booking/{id}` for the booking itself.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BookingUpdate {
+    pub event: BookingUpdateKind,
+    #[schema(example = "66f1c0de2a9b4c0012345678")]
+    pub booking_id: String,
+    /// RFC 3339 time of the change.
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BookingUpdateKind {
+    Created,
+    /// A walker was assigned, by an admin or by the matching.
+    Assigned,
+    Cancelled,
+    Completed,
+}
+
+/// Parse an RFC 3339 string ("2025-09-06T18:30:00+02:00") into a UTC bson::DateTime.
+pub fn parse_rfc3339(value: &str) -> Result<DateTime, String> {
+    //RFC 3339 C’est un format standard pour représenter une date et une heure. "2025-09-06T18:30:00+02:00"
+    //DateTime<FixedOffset> => contient une date + heure + fuseau horaire fixe (+02:00).
+    //parse_from_rfc3339 → "2025-09-06T18:30:00+02:00" → DateTime<FixedOffset>.
+    //with_timezone(&Utc) => Convertit ton DateTime<FixedOffset> en DateTime<Utc>. 2025-09-06T18:30:00+02:00 =>2025-09-06T16:30:00Z (UTC).
+    //into() Convertit le DateTime<Utc> en SystemTime
+    let chrono_datetime: SystemTime = chrono::DateTime::parse_from_rfc3339(value)
+        .map_err(|err| format!("Failed to parse date: {}", err))?
+        .with_timezone(&Utc)
+        .into();
+
+    Ok(DateTime::from(chrono_datetime))
+}
+
+/// Field validator for RFC 3339 date strings.
+pub fn validate_rfc3339(value: &str) -> Result<(), ValidationError> {
+    parse_rfc3339(value).map(|_| ()).map_err(|_| {
+        ValidationError::new("rfc3339").with_message("must be an RFC 3339 date".into())
+    })
 }
 
 impl TryFrom<BookingRequest> for Booking {
@@ -36,22 +681,32 @@ impl TryFrom<BookingRequest> for Booking {
     //transforme le DTO (BookingRequest) en Booking
     //Par ex. conversion du start_time (string RFC3339) en bson::DateTime
     fn try_from(item: BookingRequest) -> Result<Self, Self::Error> {
-        //RFC 3339 C’est un format standard pour représenter une date et une heure. "2025-09-06T18:30:00+02:00"
-        //DateTime<FixedOffset> => contient une date + heure + fuseau horaire fixe (+02:00).
-        //parse_from_rfc3339 → "2025-09-06T18:30:00+02:00" → DateTime<FixedOffset>.
-        //with_timezone(&Utc) => Convertit ton DateTime<FixedOffset> en DateTime<Utc>. 2025-09-06T18:30:00+02:00 =>2025-09-06T16:30:00Z (UTC).
-        //into() Convertit le DateTime<Utc> en SystemTime
-        let chrono_datetime: SystemTime = chrono::DateTime::parse_from_rfc3339(&item.start_time)
-            .map_err(|err| format!("Failed to parse start_time: {}", err))?
-            .with_timezone(&Utc)
-            .into();
+        let start_time = parse_rfc3339(&item.start_time)
+            .map_err(|err| format!("Failed to parse start_time: {}", err))?;
 
         Ok(Self {
             _id: ObjectId::new(),
-            owner: ObjectId::parse_str(&item.owner).expect("Failed to parse owner"),
-            start_time: DateTime::from(chrono_datetime),
+            owner: item.owner,
+            start_time,
             duration_in_minutes: item.duration_in_minutes,
-            cancelled: false,
+            status: BookingStatus::Pending,
+            cancelled_at: None,
+            cancellation_reason: None,
+            cancellation_fee_cents: None,
+            report: None,
+            walker: None,
+            match_score: None,
+            reminder_sent_at: None,
+            expires_at: None,
+            version: 0,
+            deleted_at: None,
+            price_cents: None,
+            currency: None,
+            payment: None,
+            coupon: None,
+            points_redeemed: 0,
+            points_earned: None,
+            series: None,
         })
     }
 }
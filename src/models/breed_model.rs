@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Breed accepted for any dog missing from the catalog.
+pub const OTHER_BREED: &str = "Other";
+
+/// Catalog written to an empty `breeds` collection at startup.
+pub const SEED_BREEDS: &[&str] = &[
+    "Akita",
+    "Australian Shepherd",
+    "Basset Hound",
+    "Beagle",
+    "Bernese Mountain Dog",
+    "Bichon Frise",
+    "Border Collie",
+    "Boston Terrier",
+    "Boxer",
+    "Bulldog",
+    "Cane Corso",
+    "Cavalier King Charles Spaniel",
+    "Chihuahua",
+    "Cocker Spaniel",
+    "Dachshund",
+    "Dalmatian",
+    "Doberman Pinscher",
+    "French Bulldog",
+    "German Shepherd",
+    "Golden Retriever",
+    "Great Dane",
+    "Havanese",
+    "Jack Russell Terrier",
+    "Labrador Retriever",
+    "Maltese",
+    "Miniature Schnauzer",
+    "Mixed Breed",
+    "Pembroke Welsh Corgi",
+    "Pomeranian",
+    "Poodle",
+    "Pug",
+    "Rottweiler",
+    "Saint Bernard",
+    "Samoyed",
+    "Shetland Sheepdog",
+    "Shiba Inu",
+    "Shih Tzu",
+    "Siberian Husky",
+    "Staffordshire Bull Terrier",
+    "Weimaraner",
+    "West Highland White Terrier",
+    "Whippet",
+    "Yorkshire Terrier",
+    OTHER_BREED,
+];
+
+/// Entry of the `breeds` collection, keyed by its lowercased name so a breed
+/// is found whatever the case it was typed in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Breed {
+    #[serde(rename = "_id")]
+    pub key: String,
+    pub name: String,
+}
+
+impl Breed {
+    pub fn new(name: &str) -> Self {
+        Breed {
+            key: breed_key(name),
+            name: name.to_string(),
+        }
+    }
+}
+
+/// Lowercased name with single spaces, the `_id` of a breed.
+pub fn breed_key(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Whether one of the words of `name` starts with `prefix` (already a `breed_key`),
+/// e.g. `retr` finds `Golden Retriever`.
+pub fn matches_prefix(name: &str, prefix: &str) -> bool {
+    let key = breed_key(name);
+    key.starts_with(prefix) || key.split(' ').any(|word| word.starts_with(prefix))
+}
+
+/// Default and largest number of breeds of `GET /breeds`.
+pub const DEFAULT_BREED_LIMIT: u64 = 10;
+pub const MAX_BREED_LIMIT: u64 = 50;
+
+/// Query string of `GET /breeds`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BreedQuery {
+    /// Start of the name, or of any word of it. Every breed when missing.
+    pub q: Option<String>,
+    /// Defaults to 10, at most 50.
+    pub limit: Option<u64>,
+}
+
+impl BreedQuery {
+    /// The normalized prefix and the number of breeds to return.
+    pub fn resolve(&self) -> Result<(Option<String>, u64), String> {
+        let prefix = self.q.as_deref().map(breed_key).filter(|q| !q.is_empty());
+        if prefix.as_ref().is_some_and(|q| q.len() > 50) {
+            return Err("`q` must be at most 50 characters long".to_string());
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_BREED_LIMIT);
+        if limit == 0 || limit > MAX_BREED_LIMIT {
+            return Err(format!("`limit` must be between 1 and {}", MAX_BREED_LIMIT));
+        }
+        Ok((prefix, limit))
+    }
+}
+
+/// Answer of `GET /breeds`, names in alphabetical order.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BreedList {
+    #[schema(example = json!(["Golden Retriever", "Labrador Retriever"]))]
+    pub breeds: Vec<String>,
+}
@@ -0,0 +1,151 @@
+use std::convert::TryFrom;
+
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use super::{
+    booking_model::{parse_rfc3339, validate_rfc3339},
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson},
+};
+
+/// Discount code handed out by the admins, redeemed by `POST /booking` with `coupon_code`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Coupon {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    /// Unique, stored uppercase: codes are matched case-insensitively.
+    #[schema(example = "SPRING25")]
+    pub code: String,
+    pub discount: Discount,
+    /// Usable from this date, right away when missing.
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub valid_from: Option<DateTime>,
+    /// Usable until this date excluded, forever when missing.
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub valid_until: Option<DateTime>,
+    pub max_uses: i64,
+    /// Decremented by every booking made with the code. A cancelled booking keeps its use.
+    pub remaining_uses: i64,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+}
+
+impl Coupon {
+    /// Why the coupon can't be redeemed at `now`, `None` when it can.
+    pub fn unavailable_reason(&self, now: DateTime) -> Option<&'static str> {
+        if self.valid_from.is_some_and(|from| now < from) {
+            Some("is not valid yet")
+        } else if self.valid_until.is_some_and(|until| now >= until) {
+            Some("has expired")
+        } else if self.remaining_uses <= 0 {
+            Some("is used up")
+        } else {
+            None
+        }
+    }
+
+    /// What a booking made with the coupon keeps of it.
+    pub fn applied(&self) -> BookingCoupon {
+        BookingCoupon {
+            code: self.code.clone(),
+            discount: self.discount,
+        }
+    }
+}
+
+impl HasObjectId for Coupon {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// Taken off the price of the booking, after every surcharge. A fixed amount is
+/// in cents of the pricing currency and never brings the price below zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Discount {
+    Percent { percent: i64 },
+    Fixed { amount_cents: i64 },
+}
+
+/// Coupon redeemed by a booking, stored on it as `coupon` so the discount
+/// survives the repricing of a reschedule or a walker assignment.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BookingCoupon {
+    pub code: String,
+    pub discount: Discount,
+}
+
+/// Key of a coupon code: trimmed and uppercase.
+pub fn coupon_key(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Body of `POST /admin/coupons`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CouponRequest {
+    #[validate(custom(function = "validate_coupon_code"))]
+    #[schema(example = "SPRING25")]
+    pub code: String,
+    #[validate(custom(function = "validate_discount"))]
+    pub discount: Discount,
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub valid_from: Option<String>,
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub valid_until: Option<String>,
+    #[validate(range(min = 1, max = 1_000_000, message = "must be between 1 and 1000000"))]
+    pub max_uses: i64,
+}
+
+fn validate_coupon_code(code: &str) -> Result<(), ValidationError> {
+    let code = code.trim();
+    let valid = (3..=32).contains(&code.len())
+        && code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("coupon_code")
+            .with_message("must be 3 to 32 letters, digits, `-` or `_`".into())),
+    }
+}
+
+fn validate_discount(discount: &Discount) -> Result<(), ValidationError> {
+    match *discount {
+        Discount::Percent { percent } if !(1..=100).contains(&percent) => {
+            Err(ValidationError::new("range")
+                .with_message("percent must be between 1 and 100".into()))
+        }
+        Discount::Fixed { amount_cents } if amount_cents <= 0 => {
+            Err(ValidationError::new("range").with_message("amount_cents must be positive".into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl TryFrom<CouponRequest> for Coupon {
+    type Error = String;
+
+    fn try_from(item: CouponRequest) -> Result<Self, Self::Error> {
+        let valid_from = item.valid_from.as_deref().map(parse_rfc3339).transpose()?;
+        let valid_until = item.valid_until.as_deref().map(parse_rfc3339).transpose()?;
+        if let (Some(from), Some(until)) = (valid_from, valid_until)
+            && until <= from
+        {
+            return Err("valid_until must be after valid_from".to_string());
+        }
+
+        Ok(Coupon {
+            _id: ObjectId::new(),
+            code: coupon_key(&item.code),
+            discount: item.discount,
+            valid_from,
+            valid_until,
+            max_uses: item.max_uses,
+            remaining_uses: item.max_uses,
+            created_at: DateTime::now(),
+        })
+    }
+}
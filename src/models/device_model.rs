@@ -0,0 +1,69 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson};
+
+/// Phone of an owner receiving push notifications, registered with
+/// `POST /owner/{id}/devices`. Removed once the push service reports its token invalid.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Device {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    pub platform: DevicePlatform,
+    /// Registration token of the app, unique across owners.
+    pub token: String,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+    /// Last time the app registered the token.
+    #[schema(value_type = DateTimeJson)]
+    pub registered_at: DateTime,
+}
+
+impl Device {
+    pub fn new(owner: ObjectId, request: DeviceRequest) -> Self {
+        Device {
+            _id: ObjectId::new(),
+            owner,
+            platform: request.platform,
+            token: request.token,
+            created_at: DateTime::now(),
+            registered_at: DateTime::now(),
+        }
+    }
+}
+
+impl HasObjectId for Device {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DevicePlatform {
+    /// Firebase Cloud Messaging, Android apps.
+    Fcm,
+    /// Apple Push Notification service, iOS apps.
+    Apns,
+}
+
+impl DevicePlatform {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DevicePlatform::Fcm => "fcm",
+            DevicePlatform::Apns => "apns",
+        }
+    }
+}
+
+/// Body of `POST /owner/{id}/devices`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeviceRequest {
+    pub platform: DevicePlatform,
+    #[validate(length(min = 32, max = 4096, message = "must be 32 to 4096 characters long"))]
+    pub token: String,
+}
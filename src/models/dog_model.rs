@@ -1,33 +1,319 @@
-use mongodb::bson::oid::ObjectId;
+use chrono::{Datelike, NaiveDate, NaiveTime, Utc};
+use mongodb::bson::{Bson, DateTime, Document, oid::ObjectId};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Deserialize, Serialize)]
+use super::{
+    booking_model::parse_rfc3339,
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson, deserialize_object_id},
+    vaccination_model::Vaccination,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct Dog {
+    #[schema(value_type = ObjectIdJson)]
     pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
     pub owner: ObjectId,
     pub name: Option<String>,
+    /// Age typed in by hand before `birthdate` existed, only read from legacy documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub age: Option<u8>,
+    /// Responses add `age_years`, computed from it or from the legacy `age`.
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub birthdate: Option<DateTime>,
     pub breed: Option<String>,
+    /// GridFS file of the photo uploaded with `POST /dog/{id}/photo`.
+    #[serde(default)]
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub photo_id: Option<ObjectId>,
+    /// Managed under `/dog/{id}/vaccinations`.
+    #[serde(default)]
+    pub vaccinations: Vec<Vaccination>,
+    /// Medical and behavior notes for the walkers, set with `PATCH /dog/{id}/profile`.
+    #[serde(default)]
+    pub profile: DogProfile,
+    /// Set by `DELETE /dog/{id}` or with its owner, cleared by `POST /dog/{id}/restore`.
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub deleted_at: Option<DateTime>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct DogRequest {
-    pub owner: String,
+    #[serde(deserialize_with = "deserialize_object_id")]
+    #[schema(value_type = String, example = "66f1c0de2a9b4c0012345678")]
+    pub owner: ObjectId,
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters long"))]
     pub name: Option<String>,
-    pub age: Option<u8>,
+    /// RFC 3339 date (`2021-04-12`) or date-time, not in the future.
+    #[validate(custom(function = "validate_birthdate"))]
+    pub birthdate: Option<String>,
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters long"))]
+    pub breed: Option<String>,
+}
+
+/// Dog of `POST /owner/with-dogs`, owned by the owner created with it.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct NewOwnerDog {
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters long"))]
+    pub name: Option<String>,
+    /// RFC 3339 date (`2021-04-12`) or date-time, not in the future.
+    #[validate(custom(function = "validate_birthdate"))]
+    pub birthdate: Option<String>,
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters long"))]
     pub breed: Option<String>,
 }
 
+impl NewOwnerDog {
+    pub fn into_dog(self, owner: ObjectId) -> Result<Dog, String> {
+        Ok(Dog {
+            _id: ObjectId::new(),
+            owner,
+            name: self.name,
+            age: None,
+            birthdate: self.birthdate.as_deref().map(parse_birthdate).transpose()?,
+            breed: self.breed,
+            photo_id: None,
+            vaccinations: Vec::new(),
+            profile: DogProfile::default(),
+            deleted_at: None,
+        })
+    }
+}
+
+/// What a walker should know before taking the dog out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DogProfile {
+    /// Gives the `size` added to responses, see `DogSize`.
+    pub weight_kg: Option<f64>,
+    #[serde(default)]
+    pub allergies: Vec<String>,
+    /// e.g. `"Apoquel 16mg in the morning"`.
+    #[serde(default)]
+    pub medications: Vec<String>,
+    #[serde(default)]
+    pub temperament: Vec<TemperamentFlag>,
+}
+
+/// Size class of a dog, from its weight: small under 10 kg, medium under 25 kg,
+/// large under 45 kg, giant from 45 kg. Walkers may only take some of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DogSize {
+    Small,
+    Medium,
+    Large,
+    Giant,
+}
+
+impl DogSize {
+    pub fn of_weight(weight_kg: f64) -> Self {
+        match weight_kg {
+            w if w < 10.0 => DogSize::Small,
+            w if w < 25.0 => DogSize::Medium,
+            w if w < 45.0 => DogSize::Large,
+            _ => DogSize::Giant,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DogSize::Small => "small",
+            DogSize::Medium => "medium",
+            DogSize::Large => "large",
+            DogSize::Giant => "giant",
+        }
+    }
+}
+
+/// Behavior a walker must be warned about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperamentFlag {
+    /// Lunges or barks when on the leash.
+    LeashReactive,
+    DogReactive,
+    /// Afraid of strangers, may bite when cornered.
+    Fearful,
+    Bites,
+    /// Pulls out of collars or jumps fences.
+    EscapeArtist,
+    /// Eats anything found on the ground.
+    Scavenger,
+}
+
+/// Body of `PATCH /dog/{id}/profile`, only the provided fields are replaced.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DogProfileRequest {
+    #[validate(range(min = 0.5, max = 120.0, message = "must be between 0.5 and 120"))]
+    pub weight_kg: Option<f64>,
+    #[validate(
+        length(max = 20, message = "must hold at most 20 entries"),
+        custom(function = "validate_notes")
+    )]
+    pub allergies: Option<Vec<String>>,
+    #[validate(
+        length(max = 20, message = "must hold at most 20 entries"),
+        custom(function = "validate_notes")
+    )]
+    pub medications: Option<Vec<String>>,
+    pub temperament: Option<Vec<TemperamentFlag>>,
+}
+
+fn validate_notes(notes: &[String]) -> Result<(), ValidationError> {
+    if notes
+        .iter()
+        .any(|note| note.trim().is_empty() || note.len() > 200)
+    {
+        return Err(ValidationError::new("note")
+            .with_message("every entry must be 1 to 200 characters long".into()));
+    }
+    Ok(())
+}
+
+impl DogProfileRequest {
+    pub fn is_empty(&self) -> bool {
+        self.weight_kg.is_none()
+            && self.allergies.is_none()
+            && self.medications.is_none()
+            && self.temperament.is_none()
+    }
+
+    /// `profile` with the provided fields replaced, temperament flags deduplicated.
+    pub fn apply_to(&self, mut profile: DogProfile) -> DogProfile {
+        if let Some(weight_kg) = self.weight_kg {
+            profile.weight_kg = Some(weight_kg);
+        }
+        if let Some(allergies) = &self.allergies {
+            profile.allergies = allergies.clone();
+        }
+        if let Some(medications) = &self.medications {
+            profile.medications = medications.clone();
+        }
+        if let Some(temperament) = &self.temperament {
+            profile.temperament = Vec::new();
+            for flag in temperament {
+                if !profile.temperament.contains(flag) {
+                    profile.temperament.push(*flag);
+                }
+            }
+        }
+        profile
+    }
+}
+
+/// Body of `PUT /dog/{id}`, only the provided fields are updated.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DogUpdateRequest {
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters long"))]
+    pub name: Option<String>,
+    /// RFC 3339 date (`2021-04-12`) or date-time, not in the future.
+    #[validate(custom(function = "validate_birthdate"))]
+    pub birthdate: Option<String>,
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters long"))]
+    pub breed: Option<String>,
+}
+
+impl DogUpdateRequest {
+    /// Build the `$set` document from the provided fields only.
+    pub fn to_set_document(&self) -> Document {
+        let mut set = Document::new();
+        if let Some(name) = &self.name {
+            set.insert("name", name);
+        }
+        if let Some(birthdate) = &self.birthdate {
+            // Checked by `validate`, the legacy `age` is dropped in favor of it.
+            if let Ok(birthdate) = parse_birthdate(birthdate) {
+                set.insert("birthdate", birthdate);
+                set.insert("age", Bson::Null);
+            }
+        }
+        if let Some(breed) = &self.breed {
+            set.insert("breed", breed);
+        }
+        set
+    }
+}
+
 impl TryFrom<DogRequest> for Dog {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(item: DogRequest) -> Result<Self, Self::Error> {
         Ok(Self {
             _id: ObjectId::new(),
-            owner: ObjectId::parse_str(&item.owner).expect("Failed to parse owner"),
+            owner: item.owner,
             name: item.name,
-            age: item.age,
+            age: None,
+            birthdate: item.birthdate.as_deref().map(parse_birthdate).transpose()?,
             breed: item.breed,
+            photo_id: None,
+            vaccinations: Vec::new(),
+            profile: DogProfile::default(),
+            deleted_at: None,
         })
     }
 }
+
+impl Dog {
+    /// Size class of the dog from the `weight_kg` of its profile, unknown without one.
+    pub fn size(&self) -> Option<DogSize> {
+        self.profile.weight_kg.map(DogSize::of_weight)
+    }
+
+    /// Whole years since `birthdate`, the legacy `age` for dogs created without one.
+    pub fn age_years(&self) -> Option<u32> {
+        match self.birthdate {
+            Some(birthdate) => Some(years_between(birthdate, DateTime::now())),
+            None => self.age.map(u32::from),
+        }
+    }
+}
+
+impl HasObjectId for Dog {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+
+    fn computed_fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        fields.insert("age_years".to_string(), json!(self.age_years()));
+        fields.insert("size".to_string(), json!(self.size()));
+        fields
+    }
+}
+
+/// `2021-04-12`, taken as midnight UTC, or a full RFC 3339 date-time.
+pub fn parse_birthdate(value: &str) -> Result<DateTime, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(DateTime::from_millis(
+            date.and_time(NaiveTime::MIN).and_utc().timestamp_millis(),
+        ));
+    }
+    parse_rfc3339(value)
+}
+
+fn validate_birthdate(value: &str) -> Result<(), ValidationError> {
+    let birthdate = parse_birthdate(value).map_err(|_| {
+        ValidationError::new("birthdate")
+            .with_message("must be an RFC 3339 date like 2021-04-12".into())
+    })?;
+    if birthdate > DateTime::now() {
+        return Err(ValidationError::new("future").with_message("must not be in the future".into()));
+    }
+    Ok(())
+}
+
+/// Birthdays passed between `from` and `to`, in UTC.
+fn years_between(from: DateTime, to: DateTime) -> u32 {
+    let from = chrono::DateTime::<Utc>::from(from.to_system_time());
+    let to = chrono::DateTime::<Utc>::from(to.to_system_time());
+    let mut years = to.year() - from.year();
+    if (to.month(), to.day()) < (from.month(), from.day()) {
+        years -= 1;
+    }
+    years.max(0) as u32
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::ValidationError;
+
+use super::{serde_helpers::WithId, walker_model::Walker};
+
+/// GeoJSON point as stored by MongoDB and covered by the `2dsphere` indexes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct GeoPoint {
+    #[serde(rename = "type")]
+    pub kind: GeoPointType,
+    /// `[longitude, latitude]`, in this order.
+    #[schema(example = json!([2.3522, 48.8566]))]
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum GeoPointType {
+    Point,
+}
+
+impl GeoPoint {
+    pub fn new(longitude: f64, latitude: f64) -> Result<Self, String> {
+        let point = GeoPoint {
+            kind: GeoPointType::Point,
+            coordinates: [longitude, latitude],
+        };
+        point.check()?;
+        Ok(point)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let [longitude, latitude] = self.coordinates;
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err("longitude must be between -180 and 180".to_string());
+        }
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err("latitude must be between -90 and 90".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// GeoJSON line through the `[longitude, latitude]` positions, in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct GeoLineString {
+    #[serde(rename = "type")]
+    pub kind: GeoLineStringType,
+    #[schema(example = json!([[2.3522, 48.8566], [2.3530, 48.8570]]))]
+    pub coordinates: Vec<[f64; 2]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum GeoLineStringType {
+    LineString,
+}
+
+impl FromIterator<GeoPoint> for GeoLineString {
+    fn from_iter<I: IntoIterator<Item = GeoPoint>>(points: I) -> Self {
+        GeoLineString {
+            kind: GeoLineStringType::LineString,
+            coordinates: points.into_iter().map(|point| point.coordinates).collect(),
+        }
+    }
+}
+
+/// `location` of a request must be on the globe, MongoDB refuses to index it otherwise.
+pub fn validate_geo_point(point: &GeoPoint) -> Result<(), ValidationError> {
+    point
+        .check()
+        .map_err(|message| ValidationError::new("geo_point").with_message(message.into()))
+}
+
+/// Default and largest radius of `GET /walkers/near`, in kilometers.
+pub const DEFAULT_NEAR_KM: f64 = 5.0;
+pub const MAX_NEAR_KM: f64 = 100.0;
+
+/// Query string of `GET /walkers/near`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NearQuery {
+    /// Longitude of the address, -180 to 180.
+    pub lng: f64,
+    /// Latitude of the address, -90 to 90.
+    pub lat: f64,
+    /// Search radius, defaults to 5 and at most 100.
+    pub max_km: Option<f64>,
+    /// Defaults to 20, at most 100.
+    pub limit: Option<u64>,
+}
+
+impl NearQuery {
+    /// The center, the radius in meters and the number of walkers to return.
+    pub fn resolve(&self) -> Result<(GeoPoint, f64, u64), String> {
+        let center = GeoPoint::new(self.lng, self.lat)?;
+        let max_km = self.max_km.unwrap_or(DEFAULT_NEAR_KM);
+        if !(max_km > 0.0 && max_km <= MAX_NEAR_KM) {
+            return Err(format!(
+                "`max_km` must be above 0 and at most {}",
+                MAX_NEAR_KM
+            ));
+        }
+        let limit = self.limit.unwrap_or(20);
+        if limit == 0 || limit > 100 {
+            return Err("`limit` must be between 1 and 100".to_string());
+        }
+        Ok((center, max_km * 1000.0, limit))
+    }
+}
+
+/// Walker found by `GET /walkers/near`, closest first.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NearbyWalker {
+    /// Distance from the asked point, in kilometers.
+    pub distance_km: f64,
+    pub walker: WithId<Walker>,
+}
@@ -0,0 +1,153 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    booking_model::{parse_rfc3339, validate_rfc3339},
+    dog_model::Dog,
+    owner_model::Owner,
+    serde_helpers::{
+        DateTimeJson, HasObjectId, ObjectIdJson, WithId, deserialize_object_id,
+        deserialize_object_ids,
+    },
+    walker_model::Walker,
+};
+
+/// Walk where one walker takes the dogs of several owners in the same time slot,
+/// opened with `POST /group-walk` by a walker whose profile has a `group_capacity`.
+/// The price of walking every dog together is split between the owners by their
+/// number of dogs, so each share moves as owners join and leave.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GroupWalk {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub walker: ObjectId,
+    #[schema(value_type = DateTimeJson)]
+    pub start_time: DateTime,
+    pub duration_in_minutes: u8,
+    /// Most dogs the walk takes, the `group_capacity` of the walker when it was opened.
+    pub capacity: u32,
+    /// Dogs of all the participants.
+    pub dog_count: u32,
+    /// In the order they joined.
+    pub participants: Vec<GroupParticipant>,
+    /// Price of walking every dog, the sum of the shares.
+    pub price_cents: i64,
+    pub currency: String,
+    /// Incremented by every join and leave, the shares are written for one version.
+    pub version: i64,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+}
+
+impl GroupWalk {
+    /// Empty walk of `request`, taking at most `capacity` dogs.
+    pub fn new(request: &GroupWalkRequest, capacity: u32) -> Result<Self, String> {
+        let start_time = parse_rfc3339(&request.start_time)
+            .map_err(|err| format!("Failed to parse start_time: {}", err))?;
+        if start_time <= DateTime::now() {
+            return Err("start_time must be in the future".to_string());
+        }
+        Ok(GroupWalk {
+            _id: ObjectId::new(),
+            walker: request.walker,
+            start_time,
+            duration_in_minutes: request.duration_in_minutes,
+            capacity,
+            dog_count: 0,
+            participants: Vec::new(),
+            price_cents: 0,
+            currency: String::new(),
+            version: 0,
+            created_at: DateTime::now(),
+        })
+    }
+
+    pub fn end_time(&self) -> DateTime {
+        DateTime::from_millis(
+            self.start_time.timestamp_millis() + i64::from(self.duration_in_minutes) * 60_000,
+        )
+    }
+}
+
+impl HasObjectId for GroupWalk {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// An owner taking part in a group walk with some of its dogs.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GroupParticipant {
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    #[schema(value_type = Vec<ObjectIdJson>)]
+    pub dogs: Vec<ObjectId>,
+    /// Share of the price of the walk owed by the owner.
+    pub price_cents: i64,
+    #[schema(value_type = DateTimeJson)]
+    pub joined_at: DateTime,
+}
+
+/// Body of `POST /group-walk`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct GroupWalkRequest {
+    #[serde(deserialize_with = "deserialize_object_id")]
+    #[schema(value_type = String, example = "66f1c0de2a9b4c0012345678")]
+    pub walker: ObjectId,
+    #[validate(custom(function = "validate_rfc3339"))]
+    #[schema(example = "2025-09-06T18:30:00+02:00")]
+    pub start_time: String,
+    #[validate(range(min = 15, max = 240, message = "must be between 15 and 240 minutes"))]
+    pub duration_in_minutes: u8,
+}
+
+/// Body of `POST /group-walk/{id}/join`: the dogs of the owner taking part.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct JoinGroupWalkRequest {
+    #[serde(deserialize_with = "deserialize_object_id")]
+    #[schema(value_type = String, example = "66f1c0de2a9b4c0012345678")]
+    pub owner: ObjectId,
+    #[serde(deserialize_with = "deserialize_object_ids")]
+    #[validate(length(min = 1, max = 20, message = "must name 1 to 20 dogs"))]
+    #[schema(value_type = Vec<String>, example = json!(["66f1c0de2a9b4c0012345679"]))]
+    pub dogs: Vec<ObjectId>,
+}
+
+/// A group walk with its walker, and every owner with the dogs it brings,
+/// answered by `GET /group-walk/{id}`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FullGroupWalk {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    pub walker: WithId<Walker>,
+    #[schema(value_type = DateTimeJson)]
+    pub start_time: DateTime,
+    pub duration_in_minutes: u8,
+    pub capacity: u32,
+    pub dog_count: u32,
+    pub participants: Vec<FullGroupParticipant>,
+    pub price_cents: i64,
+    pub currency: String,
+    pub version: i64,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+}
+
+impl HasObjectId for FullGroupWalk {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FullGroupParticipant {
+    pub owner: WithId<Owner>,
+    /// The live dogs among the ones the owner brings.
+    pub dogs: Vec<WithId<Dog>>,
+    pub price_cents: i64,
+    #[schema(value_type = DateTimeJson)]
+    pub joined_at: DateTime,
+}
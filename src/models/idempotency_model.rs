@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How long the response of a request sent with an `Idempotency-Key` is replayed.
+pub const IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A key still without a response after this long belongs to a request that died
+/// midway (crash, restart), the next request with the key runs again.
+pub const IDEMPOTENCY_CLAIM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `Idempotency-Key` of a request and, once it succeeded, the response to replay.
+/// A TTL index on `expires_at` removes the document after `IDEMPOTENCY_TTL`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    /// `<caller id>:<key>`, so a key only replays for the caller who sent it.
+    pub _id: String,
+    /// SHA-256 of the request body, the key can't be reused for another request.
+    pub request_hash: String,
+    /// Missing while the first request is running.
+    pub response: Option<StoredResponse>,
+    pub claimed_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+impl IdempotencyRecord {
+    /// Record claiming `key` for a request about to run.
+    pub fn claim(scope: &str, key: &str, request_hash: String) -> Self {
+        let now = DateTime::now();
+        IdempotencyRecord {
+            _id: format!("{}:{}", scope, key),
+            request_hash,
+            response: None,
+            claimed_at: now,
+            expires_at: DateTime::from_millis(
+                now.timestamp_millis() + IDEMPOTENCY_TTL.as_millis() as i64,
+            ),
+        }
+    }
+
+    /// True once the record should be ignored: past its TTL (MongoDB removes
+    /// expired documents with a delay) or abandoned before getting a response.
+    pub fn is_stale(&self, now: DateTime) -> bool {
+        self.expires_at <= now || (self.response.is_none() && self.claimed_at <= claim_cutoff(now))
+    }
+}
+
+/// Claims made before this instant without a response are considered abandoned.
+pub fn claim_cutoff(now: DateTime) -> DateTime {
+    DateTime::from_millis(now.timestamp_millis() - IDEMPOTENCY_CLAIM_TIMEOUT.as_millis() as i64)
+}
+
+/// Successful response of the first request, sent back as is to the replays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    /// JSON body.
+    pub body: String,
+}
+
+/// SHA-256 of the JSON form of a request body, hex encoded.
+pub fn request_hash<T: Serialize>(request: &T) -> String {
+    let json = serde_json::to_vec(request).unwrap_or_default();
+    Sha256::digest(&json)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
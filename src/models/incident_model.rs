@@ -0,0 +1,171 @@
+use mongodb::bson::{Bson, DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use super::{
+    booking_model::{Booking, validate_photo_refs},
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson},
+};
+
+/// Something that went wrong during a walk, filed by its walker and triaged by the admins.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Incident {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub booking: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub walker: ObjectId,
+    pub kind: IncidentKind,
+    pub severity: IncidentSeverity,
+    pub description: String,
+    /// References (URLs) of the photos taken on the spot.
+    #[serde(default)]
+    pub photos: Vec<String>,
+    pub status: IncidentStatus,
+    /// What the admins found or did, set when triaging.
+    pub resolution: Option<String>,
+    #[schema(value_type = DateTimeJson)]
+    pub reported_at: DateTime,
+    #[schema(value_type = DateTimeJson)]
+    pub updated_at: DateTime,
+}
+
+impl HasObjectId for Incident {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentKind {
+    DogFight,
+    Injury,
+    LostDog,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentSeverity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// Triage of an incident, stored as a snake_case string.
+///
+/// ```text
+/// Open -> Investigating -> Resolved
+///   |          |
+///   +----------+-> Dismissed
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentStatus {
+    Open,
+    Investigating,
+    Resolved,
+    Dismissed,
+}
+
+impl IncidentStatus {
+    /// Statuses an incident must currently have to move to `self`.
+    pub fn allowed_from(self) -> &'static [IncidentStatus] {
+        use IncidentStatus::*;
+        match self {
+            Open => &[],
+            Investigating => &[Open],
+            Resolved | Dismissed => &[Open, Investigating],
+        }
+    }
+
+    /// Statuses still waiting for an admin, listed by default.
+    pub fn unresolved() -> &'static [IncidentStatus] {
+        &[IncidentStatus::Open, IncidentStatus::Investigating]
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IncidentStatus::Open => "open",
+            IncidentStatus::Investigating => "investigating",
+            IncidentStatus::Resolved => "resolved",
+            IncidentStatus::Dismissed => "dismissed",
+        }
+    }
+}
+
+impl From<IncidentStatus> for Bson {
+    fn from(status: IncidentStatus) -> Self {
+        Bson::String(status.as_str().to_string())
+    }
+}
+
+/// Body of `POST /booking/{id}/incident`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct IncidentRequest {
+    pub kind: IncidentKind,
+    pub severity: IncidentSeverity,
+    #[validate(length(min = 1, max = 4000, message = "must be 1 to 4000 characters long"))]
+    pub description: String,
+    #[serde(default)]
+    #[validate(
+        length(max = 10, message = "must hold at most 10 photos"),
+        custom(function = "validate_photo_refs")
+    )]
+    pub photos: Vec<String>,
+}
+
+impl IncidentRequest {
+    /// Open incident on `booking`, reported by its `walker`.
+    pub fn into_incident(self, booking: &Booking, walker: ObjectId) -> Incident {
+        let now = DateTime::now();
+        Incident {
+            _id: ObjectId::new(),
+            booking: booking._id,
+            owner: booking.owner,
+            walker,
+            kind: self.kind,
+            severity: self.severity,
+            description: self.description,
+            photos: self.photos,
+            status: IncidentStatus::Open,
+            resolution: None,
+            reported_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Body of `PATCH /incident/{id}`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct IncidentTriageRequest {
+    pub status: IncidentStatus,
+    #[validate(length(min = 1, max = 4000, message = "must be 1 to 4000 characters long"))]
+    pub resolution: Option<String>,
+}
+
+/// Query string of `GET /incidents`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IncidentQuery {
+    /// Only this status, `open` and `investigating` ones when missing.
+    pub status: Option<IncidentStatus>,
+    pub severity: Option<IncidentSeverity>,
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+impl IncidentQuery {
+    pub fn statuses(&self) -> Vec<IncidentStatus> {
+        match self.status {
+            Some(status) => vec![status],
+            None => IncidentStatus::unresolved().to_vec(),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use mongodb::bson::{Bson, DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    booking_model::Booking,
+    pricing_model::{PriceLine, Quote},
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson},
+};
+
+/// Bill of a booking, issued once when it completes, or when a cancelled one is refunded.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Invoice {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub booking: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub walker: Option<ObjectId>,
+    /// Priced by the pricing engine at completion.
+    pub lines: Vec<PriceLine>,
+    pub total_cents: i64,
+    pub currency: String,
+    pub payment_status: PaymentStatus,
+    /// Id of the payment intent at the payment provider, from the booking's `payment`.
+    pub payment_reference: Option<String>,
+    #[schema(value_type = DateTimeJson)]
+    pub issued_at: DateTime,
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub paid_at: Option<DateTime>,
+    #[schema(value_type = DateTimeJson)]
+    pub updated_at: DateTime,
+    /// Set by `POST /booking/{id}/refund` once the cancellation fee is kept,
+    /// also when it refunds nothing.
+    #[serde(default)]
+    pub refund: Option<InvoiceRefund>,
+}
+
+impl Invoice {
+    /// Unpaid invoice of `booking` for `quote`.
+    pub fn new(booking: &Booking, quote: Quote) -> Self {
+        let now = DateTime::now();
+        Invoice {
+            _id: ObjectId::new(),
+            booking: booking._id,
+            owner: booking.owner,
+            walker: booking.walker,
+            lines: quote.lines,
+            total_cents: quote.price_cents,
+            currency: quote.currency,
+            payment_status: PaymentStatus::Pending,
+            payment_reference: booking
+                .payment
+                .as_ref()
+                .map(|payment| payment.intent_id.clone()),
+            issued_at: now,
+            paid_at: None,
+            updated_at: now,
+            refund: None,
+        }
+    }
+}
+
+impl HasObjectId for Invoice {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// Payment of an invoice, reported by the payment integration.
+///
+/// ```text
+/// Pending -> Paid -> Refunded
+///    |        ^
+///    +-> Failed
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Pending,
+    Paid,
+    Failed,
+    Refunded,
+}
+
+impl PaymentStatus {
+    /// Statuses an invoice must currently have to move to `self`.
+    pub fn allowed_from(self) -> &'static [PaymentStatus] {
+        use PaymentStatus::*;
+        match self {
+            Pending => &[],
+            Paid => &[Pending, Failed],
+            Failed => &[Pending],
+            Refunded => &[Paid],
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "pending",
+            PaymentStatus::Paid => "paid",
+            PaymentStatus::Failed => "failed",
+            PaymentStatus::Refunded => "refunded",
+        }
+    }
+}
+
+impl From<PaymentStatus> for Bson {
+    fn from(status: PaymentStatus) -> Self {
+        Bson::String(status.as_str().to_string())
+    }
+}
+
+/// Share of a cancelled booking given back, what its cancellation fee leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundPolicy {
+    Full,
+    Partial,
+    None,
+}
+
+/// Refund of a cancelled booking, recorded on its invoice.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InvoiceRefund {
+    pub policy: RefundPolicy,
+    /// Minutes between the cancellation and the planned start, 0 when cancelled late.
+    pub notice_minutes: i64,
+    pub amount_cents: i64,
+    #[schema(value_type = DateTimeJson)]
+    pub refunded_at: DateTime,
+}
+
+/// Body of `PATCH /invoice/{id}/payment`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct PaymentUpdateRequest {
+    pub status: PaymentStatus,
+    #[validate(length(min = 1, max = 200, message = "must be 1 to 200 characters long"))]
+    pub reference: Option<String>,
+}
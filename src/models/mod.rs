@@ -1,4 +1,33 @@
 //mod = déclare un module
+pub mod api_key_model;
+pub mod audit_model;
+pub mod auth_model;
+pub mod availability_model;
+pub mod backup_model;
 pub mod booking_model;
+pub mod breed_model;
+pub mod coupon_model;
+pub mod device_model;
 pub mod dog_model;
+pub mod geo_model;
+pub mod group_walk_model;
+pub mod idempotency_model;
+pub mod incident_model;
+pub mod invoice_model;
+pub mod owner_event_model;
 pub mod owner_model;
+pub mod page_model;
+pub mod payment_model;
+pub mod payout_model;
+pub mod photo_model;
+pub mod pricing_model;
+pub mod result_model;
+pub mod review_model;
+pub mod search_model;
+pub mod serde_helpers;
+pub mod series_model;
+pub mod track_model;
+pub mod vaccination_model;
+pub mod waitlist_model;
+pub mod walker_model;
+pub mod webhook_model;
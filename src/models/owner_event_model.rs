@@ -0,0 +1,35 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{booking_model::BookingStatus, geo_model::GeoPoint};
+
+/// Data of an event of `GET /owner/{id}/events`, `type` is also the SSE `event` name.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OwnerEventData {
+    /// A booking of the owner was created or moved to `status`.
+    BookingStatus {
+        #[schema(example = "66f1c0de2a9b4c0012345678")]
+        booking_id: String,
+        status: BookingStatus,
+        /// RFC 3339 time of the change.
+        at: String,
+    },
+    /// Position sent by the walker during a walk of the owner's dogs.
+    WalkerLocation {
+        #[schema(example = "66f1c0de2a9b4c0012345678")]
+        booking_id: String,
+        location: GeoPoint,
+        /// RFC 3339 time the position was taken.
+        at: String,
+    },
+}
+
+impl OwnerEventData {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OwnerEventData::BookingStatus { .. } => "booking_status",
+            OwnerEventData::WalkerLocation { .. } => "walker_location",
+        }
+    }
+}
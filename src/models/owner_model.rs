@@ -1,24 +1,309 @@
-use std::time::SystemTime;
-
-use chrono::Utc;
-use mongodb::bson::{oid::ObjectId, DateTime};
+use mongodb::bson::{DateTime, Document, oid::ObjectId, to_bson};
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Serialize, Deserialize)]
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use super::{
+    dog_model::{Dog, NewOwnerDog},
+    geo_model::{GeoPoint, validate_geo_point},
+    pricing_model::PricingRules,
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson, WithId},
+};
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Owner {
+    #[schema(value_type = ObjectIdJson)]
     pub _id: ObjectId,
     pub name: String,
     pub email: String,
     pub phone: String,
     pub address: String,
+    /// Optional position of `address`.
+    #[serde(default)]
+    pub location: Option<GeoPoint>,
+    /// Set by `GET /owner/verify/{token}`, bookings are refused until then.
+    #[serde(default)]
+    pub email_verified: bool,
+    /// Incremented by every update, `PUT /owner/{id}` must send the current one.
+    #[serde(default)]
+    pub version: i64,
+    /// Set by `DELETE /owner/{id}`, cleared by `POST /owner/{id}/restore`.
+    #[serde(default)]
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub deleted_at: Option<DateTime>,
+    /// Loyalty points, earned by completed walks and spent with `redeem_points`
+    /// when booking. Not editable through `PUT /owner/{id}`.
+    #[serde(default)]
+    pub points_balance: i64,
+    /// Set with `PUT /owner/{id}` to stop the SMS reminders and cancellation notices.
+    #[serde(default)]
+    pub sms_opt_out: bool,
+    /// Booking events the owner hears about on each channel, set with
+    /// `PATCH /owner/{id}/preferences`.
+    #[serde(default)]
+    pub notification_preferences: NotificationPreferences,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Booking events sent to the owner per channel, everything is on until turned off.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct NotificationPreferences {
+    pub email: EventPreferences,
+    pub sms: EventPreferences,
+    pub push: EventPreferences,
+}
+
+/// One toggle per booking event. A channel only carries some of the events
+/// (e.g. no email when the walker is on the way), the toggles of the others have no effect.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct EventPreferences {
+    pub created: bool,
+    pub confirmed: bool,
+    pub cancelled: bool,
+    pub rescheduled: bool,
+    pub reminder: bool,
+    pub walker_on_the_way: bool,
+    pub completed: bool,
+    /// A walk waited for with `POST /waitlist` got booked.
+    pub waitlist_booked: bool,
+}
+
+impl Default for EventPreferences {
+    fn default() -> Self {
+        EventPreferences {
+            created: true,
+            confirmed: true,
+            cancelled: true,
+            rescheduled: true,
+            reminder: true,
+            walker_on_the_way: true,
+            completed: true,
+            waitlist_booked: true,
+        }
+    }
+}
+
+/// Body of `PATCH /owner/{id}/preferences`, only the provided toggles change.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationPreferencesUpdate {
+    pub email: Option<EventPreferencesUpdate>,
+    pub sms: Option<EventPreferencesUpdate>,
+    pub push: Option<EventPreferencesUpdate>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EventPreferencesUpdate {
+    pub created: Option<bool>,
+    pub confirmed: Option<bool>,
+    pub cancelled: Option<bool>,
+    pub rescheduled: Option<bool>,
+    pub reminder: Option<bool>,
+    pub walker_on_the_way: Option<bool>,
+    pub completed: Option<bool>,
+    pub waitlist_booked: Option<bool>,
+}
 
+impl NotificationPreferencesUpdate {
+    pub fn is_empty(&self) -> bool {
+        [&self.email, &self.sms, &self.push]
+            .iter()
+            .all(|update| update.as_ref().is_none_or(EventPreferencesUpdate::is_empty))
+    }
+
+    /// `preferences` with the provided toggles replaced.
+    pub fn apply_to(&self, mut preferences: NotificationPreferences) -> NotificationPreferences {
+        if let Some(email) = &self.email {
+            preferences.email = email.apply_to(preferences.email);
+        }
+        if let Some(sms) = &self.sms {
+            preferences.sms = sms.apply_to(preferences.sms);
+        }
+        if let Some(push) = &self.push {
+            preferences.push = push.apply_to(preferences.push);
+        }
+        preferences
+    }
+}
+
+impl EventPreferencesUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_none()
+            && self.confirmed.is_none()
+            && self.cancelled.is_none()
+            && self.rescheduled.is_none()
+            && self.reminder.is_none()
+            && self.walker_on_the_way.is_none()
+            && self.completed.is_none()
+            && self.waitlist_booked.is_none()
+    }
+
+    fn apply_to(&self, preferences: EventPreferences) -> EventPreferences {
+        EventPreferences {
+            created: self.created.unwrap_or(preferences.created),
+            confirmed: self.confirmed.unwrap_or(preferences.confirmed),
+            cancelled: self.cancelled.unwrap_or(preferences.cancelled),
+            rescheduled: self.rescheduled.unwrap_or(preferences.rescheduled),
+            reminder: self.reminder.unwrap_or(preferences.reminder),
+            walker_on_the_way: self
+                .walker_on_the_way
+                .unwrap_or(preferences.walker_on_the_way),
+            completed: self.completed.unwrap_or(preferences.completed),
+            waitlist_booked: self.waitlist_booked.unwrap_or(preferences.waitlist_booked),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct OwnerRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
     pub name: String,
+    #[validate(email(message = "must be a valid email address"))]
     pub email: String,
+    #[validate(length(min = 6, max = 20, message = "must be 6 to 20 characters long"))]
     pub phone: String,
+    #[validate(length(min = 1, max = 200, message = "must be 1 to 200 characters long"))]
     pub address: String,
+    #[serde(default)]
+    #[validate(custom(function = "validate_geo_point"))]
+    pub location: Option<GeoPoint>,
+}
+
+/// Body of `PUT /owner/{id}`, only the provided fields are updated.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct OwnerUpdateRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
+    pub name: Option<String>,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: Option<String>,
+    #[validate(length(min = 6, max = 20, message = "must be 6 to 20 characters long"))]
+    pub phone: Option<String>,
+    #[validate(length(min = 1, max = 200, message = "must be 1 to 200 characters long"))]
+    pub address: Option<String>,
+    #[validate(custom(function = "validate_geo_point"))]
+    pub location: Option<GeoPoint>,
+    pub sms_opt_out: Option<bool>,
+    /// Current `version` of the owner, for clients that can't send `If-Match`.
+    pub expected_version: Option<i64>,
+}
+
+impl OwnerUpdateRequest {
+    /// Build the `$set` document from the provided fields only.
+    pub fn to_set_document(&self) -> Document {
+        let mut set = Document::new();
+        if let Some(name) = &self.name {
+            set.insert("name", name);
+        }
+        if let Some(email) = &self.email {
+            set.insert("email", email.to_lowercase());
+        }
+        if let Some(phone) = &self.phone {
+            set.insert("phone", phone);
+        }
+        if let Some(address) = &self.address {
+            set.insert("address", address);
+        }
+        if let Some(location) = &self.location
+            && let Ok(location) = to_bson(location)
+        {
+            set.insert("location", location);
+        }
+        if let Some(sms_opt_out) = self.sms_opt_out {
+            set.insert("sms_opt_out", sms_opt_out);
+        }
+        set
+    }
+}
+
+/// Body of `POST /owner/with-dogs`: the owner and its dogs are created together or not at all.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct OwnerWithDogsRequest {
+    #[validate(nested)]
+    pub owner: OwnerRequest,
+    #[validate(length(max = 20, message = "must hold at most 20 dogs"), nested)]
+    pub dogs: Vec<NewOwnerDog>,
+}
+
+/// Answer of `POST /owner/with-dogs`, the dog ids are in the order of the request.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedOwnerWithDogs {
+    #[schema(value_type = ObjectIdJson)]
+    pub owner_id: ObjectId,
+    #[schema(value_type = Vec<ObjectIdJson>)]
+    pub dog_ids: Vec<ObjectId>,
+}
+
+/// Pending email verification of a new owner, only the SHA-256 of the
+/// emailed token is stored and a TTL index removes it once expired.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerification {
+    pub _id: ObjectId,
+    pub owner: ObjectId,
+    pub token_hash: String,
+    pub expires_at: DateTime,
+}
+
+/// Summary of `DELETE /owner/{id}`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OwnerDeletion {
+    pub deleted_dogs: u64,
+    pub cancelled_bookings: u64,
+}
+
+/// Owner with all of its dogs, returned by `GET /owner/{id}`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OwnerWithDogs {
+    pub owner: WithId<Owner>,
+    pub dogs: Vec<WithId<Dog>>,
+}
+
+/// Answer of `GET /owner/{id}/points`, with the rules to spend them.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OwnerPoints {
+    pub points_balance: i64,
+    /// Earned by every completed walk.
+    pub points_per_booking: i64,
+    /// Spent by a booking made with `redeem_points`.
+    pub points_per_free_walk: i64,
+    /// Minutes of walk a redemption pays for.
+    pub free_walk_minutes: i64,
+    /// Redemptions the balance covers.
+    pub free_walks: i64,
+}
+
+impl OwnerPoints {
+    pub fn new(points_balance: i64, rules: &PricingRules) -> Self {
+        OwnerPoints {
+            points_balance,
+            points_per_booking: rules.points_per_booking,
+            points_per_free_walk: rules.points_per_free_walk,
+            free_walk_minutes: rules.free_walk_minutes,
+            free_walks: match rules.points_per_free_walk {
+                cost if cost > 0 => points_balance / cost,
+                _ => 0,
+            },
+        }
+    }
+}
+
+/// Sort order of `GET /owners`.
+#[derive(Debug, Default, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnerSort {
+    #[default]
+    Name,
+    /// ObjectIds embed their creation time, so this sorts on `_id`.
+    CreatedAt,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OwnerListQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub sort: OwnerSort,
 }
 
 impl TryFrom<OwnerRequest> for Owner {
@@ -27,9 +312,23 @@ impl TryFrom<OwnerRequest> for Owner {
         Ok(Self {
             _id: ObjectId::new(),
             name: item.name,
-            email: item.email,
+            // Lowercased so the unique index on `email` also catches case variants.
+            email: item.email.to_lowercase(),
             phone: item.phone,
             address: item.address,
+            location: item.location,
+            email_verified: false,
+            version: 0,
+            deleted_at: None,
+            points_balance: 0,
+            sms_opt_out: false,
+            notification_preferences: NotificationPreferences::default(),
         })
     }
 }
+
+impl HasObjectId for Owner {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
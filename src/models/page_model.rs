@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Default page size when `?limit=` is not given.
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+/// Largest page size a client can ask for.
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+/// Offset pagination query string (`?page=&limit=`), pages start at 1.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    pub page: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+impl PageQuery {
+    /// Resolve the page and limit, falling back to the defaults and clamping the limit.
+    pub fn resolve(&self) -> Result<(u64, u64), String> {
+        let page = self.page.unwrap_or(1);
+        if page == 0 {
+            return Err("`page` starts at 1".to_string());
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit == 0 {
+            return Err("`limit` must be at least 1".to_string());
+        }
+
+        Ok((page, limit.min(MAX_PAGE_SIZE)))
+    }
+}
+
+/// Envelope of paginated listings.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u64,
+    pub limit: u64,
+    pub total: u64,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Card payment authorized when the booking is confirmed and captured when
+/// it completes, stored on the booking as `payment`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BookingPayment {
+    /// `stripe` or `mock`.
+    pub provider: String,
+    /// Id of the payment intent at the provider, the `payment_reference` of the invoice.
+    pub intent_id: String,
+    /// Authorized amount, at most this much is captured.
+    pub amount_cents: i64,
+    pub currency: String,
+    /// Handed to the provider's client library by the owner's app to enter the card.
+    pub client_secret: Option<String>,
+}
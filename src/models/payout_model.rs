@@ -0,0 +1,181 @@
+use mongodb::bson::{Bson, DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use super::{
+    booking_model::validate_rfc3339,
+    invoice_model::Invoice,
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson},
+};
+
+/// What a walker earned for one completed booking, an entry of the `payouts`
+/// ledger: the walk when its invoice is issued, then the owner's tip if any.
+/// One of each kind per booking.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Payout {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub walker: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub booking: ObjectId,
+    #[serde(default)]
+    pub kind: PayoutKind,
+    /// For the walk, `walker_share_percent` of the price before the coupon and
+    /// the loyalty points. A tip goes to the walker whole.
+    pub amount_cents: i64,
+    pub currency: String,
+    #[schema(value_type = DateTimeJson)]
+    pub earned_at: DateTime,
+    /// Batch of `POST /admin/payouts/settle` that paid the entry out, `None` until then.
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub batch: Option<ObjectId>,
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub settled_at: Option<DateTime>,
+    /// Id of the payment intent that charged a tip.
+    #[serde(default)]
+    pub payment_reference: Option<String>,
+}
+
+impl Payout {
+    /// Unsettled entry of `amount_cents` for the walk of `invoice`, `None` when nobody walked it.
+    pub fn new(invoice: &Invoice, amount_cents: i64) -> Option<Self> {
+        Some(Payout {
+            _id: ObjectId::new(),
+            walker: invoice.walker?,
+            booking: invoice.booking,
+            kind: PayoutKind::Walk,
+            amount_cents,
+            currency: invoice.currency.clone(),
+            earned_at: invoice.issued_at,
+            batch: None,
+            settled_at: None,
+            payment_reference: None,
+        })
+    }
+
+    /// Unsettled tip of `amount_cents` for the walk of `invoice`, charged by `payment_reference`.
+    pub fn tip(invoice: &Invoice, amount_cents: i64, payment_reference: String) -> Option<Self> {
+        Some(Payout {
+            kind: PayoutKind::Tip,
+            amount_cents,
+            earned_at: DateTime::now(),
+            payment_reference: Some(payment_reference),
+            ..Payout::new(invoice, amount_cents)?
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutKind {
+    #[default]
+    Walk,
+    Tip,
+}
+
+impl From<PayoutKind> for Bson {
+    fn from(kind: PayoutKind) -> Self {
+        Bson::String(
+            match kind {
+                PayoutKind::Walk => "walk",
+                PayoutKind::Tip => "tip",
+            }
+            .to_string(),
+        )
+    }
+}
+
+/// Body of `POST /booking/{id}/tip`, charged in the currency of the invoice.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TipRequest {
+    #[validate(range(min = 50, max = 100_000, message = "must be between 50 and 100000"))]
+    #[schema(example = 500)]
+    pub amount_cents: i64,
+}
+
+impl HasObjectId for Payout {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// Width of the buckets of `GET /walker/{id}/earnings`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EarningsPeriod {
+    #[default]
+    Daily,
+    /// Weeks start on Monday.
+    Weekly,
+    Monthly,
+}
+
+impl EarningsPeriod {
+    /// Unit of `$dateTrunc`.
+    pub fn unit(self) -> &'static str {
+        match self {
+            EarningsPeriod::Daily => "day",
+            EarningsPeriod::Weekly => "week",
+            EarningsPeriod::Monthly => "month",
+        }
+    }
+}
+
+/// Query string of `GET /walker/{id}/earnings`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EarningsQuery {
+    /// `daily` by default.
+    #[serde(default)]
+    pub period: EarningsPeriod,
+    /// RFC 3339, inclusive.
+    pub from: Option<String>,
+    /// RFC 3339, exclusive.
+    pub to: Option<String>,
+}
+
+/// Earnings of one period, in UTC.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EarningsBucket {
+    #[schema(value_type = DateTimeJson)]
+    pub start: DateTime,
+    pub currency: String,
+    pub walks: u64,
+    pub earned_cents: i64,
+    /// Part of `earned_cents` given as tips.
+    pub tips_cents: i64,
+    /// Part of `earned_cents` already paid out.
+    pub settled_cents: i64,
+}
+
+/// Answer of `GET /walker/{id}/earnings`, oldest bucket first.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WalkerEarnings {
+    #[schema(value_type = ObjectIdJson)]
+    pub walker: ObjectId,
+    pub period: EarningsPeriod,
+    pub buckets: Vec<EarningsBucket>,
+}
+
+/// Body of `POST /admin/payouts/settle`: pay out every entry earned before `until`,
+/// for one walker or all of them.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SettleRequest {
+    /// ObjectId of the walker, every walker when missing.
+    pub walker: Option<String>,
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub until: String,
+}
+
+/// Entries of the ledger paid out together.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PayoutBatch {
+    #[schema(value_type = ObjectIdJson)]
+    pub batch: ObjectId,
+    #[schema(value_type = DateTimeJson)]
+    pub settled_at: DateTime,
+    pub payouts: u64,
+    pub amount_cents: i64,
+}
@@ -0,0 +1,41 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+
+/// Largest photo accepted by `POST /dog/{id}/photo`.
+pub const MAX_PHOTO_BYTES: usize = 5 * 1024 * 1024;
+
+/// GridFS bucket holding the dog photos, `dog_photos.files` and `dog_photos.chunks`.
+pub const DOG_PHOTO_BUCKET: &str = "dog_photos";
+
+/// Content type of an image from its first bytes, `None` for anything but
+/// JPEG, PNG and WebP. The type declared by the client is not trusted.
+pub fn image_content_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// GridFS file of a dog photo, without its content.
+#[derive(Debug, Clone)]
+pub struct StoredPhoto {
+    pub id: ObjectId,
+    pub content_type: String,
+    pub length: u64,
+    pub uploaded_at: DateTime,
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// `_id` of the rules document of the `pricing_rules` collection.
+pub const PRICING_RULES_ID: &str = "default";
+
+/// How bookings are priced, one document of the `pricing_rules` collection edited
+/// by hand. Missing fields take their default, percentages apply to the base
+/// price plus the extra dogs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PricingRules {
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// ISO 4217 code, prices are in its cents.
+    pub currency: String,
+    pub base_cents_per_hour: i64,
+    /// Added for every dog after the first one.
+    pub extra_dog_percent: i64,
+    /// Walks starting on a Saturday or a Sunday.
+    pub weekend_percent: i64,
+    /// Walks starting at or after `evening_from_hour`, or before `evening_until_hour`.
+    pub evening_percent: i64,
+    pub evening_from_hour: u32,
+    pub evening_until_hour: u32,
+    /// Local time of the weekend and evening surcharges, in minutes east of UTC.
+    pub utc_offset_minutes: i32,
+    pub senior_walker_percent: i64,
+    pub expert_walker_percent: i64,
+    /// Loyalty points an owner earns per completed walk.
+    pub points_per_booking: i64,
+    /// Points a booking made with `redeem_points` spends, 0 turns redemptions off.
+    pub points_per_free_walk: i64,
+    /// Minutes of walk at the hourly rate taken off the price of such a booking.
+    pub free_walk_minutes: i64,
+    /// Cancelled at least this long before the start: free. With less notice the
+    /// owner owes `late_cancel_fee_percent` of the price; once started it can't be cancelled.
+    pub free_cancel_notice_hours: i64,
+    pub late_cancel_fee_percent: i64,
+    /// Part of the price of a walk paid out to its walker.
+    pub walker_share_percent: i64,
+    /// Days after its invoice during which the owner may tip the walker.
+    pub tip_window_days: i64,
+}
+
+impl Default for PricingRules {
+    fn default() -> Self {
+        PricingRules {
+            id: PRICING_RULES_ID.to_string(),
+            currency: "EUR".to_string(),
+            base_cents_per_hour: 2000,
+            extra_dog_percent: 50,
+            weekend_percent: 20,
+            evening_percent: 15,
+            evening_from_hour: 19,
+            evening_until_hour: 7,
+            utc_offset_minutes: 0,
+            senior_walker_percent: 10,
+            expert_walker_percent: 25,
+            points_per_booking: 10,
+            points_per_free_walk: 100,
+            free_walk_minutes: 30,
+            free_cancel_notice_hours: 24,
+            late_cancel_fee_percent: 50,
+            walker_share_percent: 80,
+            tip_window_days: 7,
+        }
+    }
+}
+
+/// What a line of a quote charges for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceLineKind {
+    /// The duration at the hourly rate.
+    Base,
+    ExtraDogs,
+    Weekend,
+    Evening,
+    WalkerTier,
+    /// Free walk paid with loyalty points, negative.
+    Points,
+    /// Discount of the booking's coupon, negative.
+    Coupon,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PriceLine {
+    pub kind: PriceLineKind,
+    pub amount_cents: i64,
+}
+
+/// Price of a booking, stored on it as `price_cents` and `currency` and
+/// previewed by `POST /booking/quote`. Lines that add nothing are left out.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Quote {
+    #[schema(example = 2400)]
+    pub price_cents: i64,
+    #[schema(example = "EUR")]
+    pub currency: String,
+    pub lines: Vec<PriceLine>,
+}
@@ -0,0 +1,62 @@
+use mongodb::{bson::oid::ObjectId, results::UpdateResult};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::serde_helpers::{ObjectIdJson, serialize_object_id_as_hex};
+
+/// Answer of the create endpoints: the hex `id` of the resources, next to the
+/// `insertedId` of the driver's `InsertOneResult` kept for the older clients.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertedId {
+    #[serde(serialize_with = "serialize_object_id_as_hex")]
+    #[schema(value_type = String, example = "66f1c0de2a9b4c0012345678")]
+    pub id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub inserted_id: ObjectId,
+}
+
+impl From<ObjectId> for InsertedId {
+    fn from(inserted_id: ObjectId) -> Self {
+        InsertedId {
+            id: inserted_id,
+            inserted_id,
+        }
+    }
+}
+
+/// Answer of `POST /booking/{id}/report`, same shape as the driver's `UpdateResult`.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatedCount {
+    pub matched_count: u64,
+    pub modified_count: u64,
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub upserted_id: Option<ObjectId>,
+}
+
+impl From<UpdateResult> for UpdatedCount {
+    fn from(result: UpdateResult) -> Self {
+        UpdatedCount {
+            matched_count: result.matched_count,
+            modified_count: result.modified_count,
+            upserted_id: result.upserted_id.and_then(|id| id.as_object_id()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn inserted_id_has_the_hex_id_and_the_extended_json_one() {
+        let id = ObjectId::new();
+        assert_eq!(
+            serde_json::to_value(InsertedId::from(id)).unwrap(),
+            json!({"id": id.to_hex(), "insertedId": {"$oid": id.to_hex()}})
+        );
+    }
+}
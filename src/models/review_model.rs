@@ -0,0 +1,73 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    booking_model::Booking,
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson, WithId},
+    walker_model::Walker,
+};
+
+/// Owner's review of the walker of a completed booking, one per booking.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Review {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub booking: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub walker: ObjectId,
+    /// 1 to 5.
+    pub stars: u8,
+    pub comment: Option<String>,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+}
+
+impl HasObjectId for Review {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+/// Body of `POST /booking/{id}/review`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ReviewRequest {
+    #[validate(range(min = 1, max = 5, message = "must be between 1 and 5"))]
+    pub stars: u8,
+    #[validate(length(min = 1, max = 1000, message = "must be 1 to 1000 characters long"))]
+    pub comment: Option<String>,
+}
+
+impl ReviewRequest {
+    /// Review of the walker assigned to `booking`.
+    pub fn into_review(self, booking: &Booking, walker: ObjectId) -> Review {
+        Review {
+            _id: ObjectId::new(),
+            booking: booking._id,
+            owner: booking.owner,
+            walker,
+            stars: self.stars,
+            comment: self.comment,
+            created_at: DateTime::now(),
+        }
+    }
+}
+
+/// Average of the reviews of a walker, `average` is missing until the first one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct WalkerRating {
+    pub average: Option<f64>,
+    pub count: u64,
+}
+
+/// Answer of `GET /walker/{id}`, the walker with its rating.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RatedWalker {
+    #[serde(flatten)]
+    pub walker: WithId<Walker>,
+    pub rating: WalkerRating,
+}
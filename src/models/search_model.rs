@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{dog_model::Dog, owner_model::Owner, serde_helpers::WithId};
+
+/// Default and largest number of results of `GET /search`.
+pub const DEFAULT_SEARCH_LIMIT: u64 = 20;
+pub const MAX_SEARCH_LIMIT: u64 = 100;
+
+/// Query string of `GET /search`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Words to look for in owner names and emails, dog names and breeds.
+    pub q: String,
+    /// Defaults to 20, at most 100.
+    pub limit: Option<u64>,
+}
+
+impl SearchQuery {
+    /// The trimmed search text and the number of results to return.
+    pub fn resolve(&self) -> Result<(&str, u64), String> {
+        let text = self.q.trim();
+        if text.is_empty() {
+            return Err("`q` must not be empty".to_string());
+        }
+        if text.len() > 200 {
+            return Err("`q` must be at most 200 characters long".to_string());
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+        if limit == 0 || limit > MAX_SEARCH_LIMIT {
+            return Err(format!(
+                "`limit` must be between 1 and {}",
+                MAX_SEARCH_LIMIT
+            ));
+        }
+        Ok((text, limit))
+    }
+}
+
+/// One match of `GET /search`, `type` tells which collection it comes from.
+/// `score` is the text relevance, higher is better.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SearchHit {
+    Owner { score: f64, owner: WithId<Owner> },
+    Dog { score: f64, dog: WithId<Dog> },
+}
+
+impl SearchHit {
+    pub fn score(&self) -> f64 {
+        match self {
+            SearchHit::Owner { score, .. } | SearchHit::Dog { score, .. } => *score,
+        }
+    }
+}
+
+/// Answer of `GET /search`, best matches first.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResults {
+    pub results: Vec<SearchHit>,
+}
+
+/// Merge the matches of every collection, best first, keeping `limit` of them.
+pub fn rank(mut hits: Vec<SearchHit>, limit: u64) -> Vec<SearchHit> {
+    hits.sort_by(|a, b| b.score().total_cmp(&a.score()));
+    hits.truncate(limit as usize);
+    hits
+}
@@ -0,0 +1,272 @@
+use std::{borrow::Cow, ops::Deref};
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serde_json::{Map, Value};
+use utoipa::{
+    ToSchema,
+    openapi::{AllOfBuilder, ObjectBuilder, Ref, RefOr, Schema, Type},
+};
+
+/// Implemented by every stored resource so `WithId` can expose its ObjectId.
+pub trait HasObjectId {
+    fn object_id(&self) -> ObjectId;
+
+    /// Fields of the HTTP view computed from the document, never stored.
+    fn computed_fields(&self) -> Map<String, Value> {
+        Map::new()
+    }
+}
+
+/// So a borrowed resource can be viewed without cloning it.
+impl<T: HasObjectId> HasObjectId for &T {
+    fn object_id(&self) -> ObjectId {
+        (*self).object_id()
+    }
+
+    fn computed_fields(&self) -> Map<String, Value> {
+        (*self).computed_fields()
+    }
+}
+
+/// HTTP view of a stored resource.
+/// Serializes `id` as a plain hex string next to every field of the inner
+/// document, including the legacy `_id` (extended JSON `{"$oid": ...}`) which
+/// is kept for one deprecation cycle so existing consumers don't break.
+/// Deserialization is transparent, so it can be used inside aggregation results.
+#[derive(Debug, Clone)]
+pub struct WithId<T>(pub T);
+
+impl<T> Deref for WithId<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Serialize + HasObjectId> Serialize for WithId<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Aliased<'a, T> {
+            #[serde(serialize_with = "serialize_object_id_as_hex")]
+            id: ObjectId,
+            #[serde(flatten)]
+            inner: &'a T,
+            #[serde(flatten)]
+            computed: Map<String, Value>,
+        }
+
+        Aliased {
+            id: self.0.object_id(),
+            inner: &self.0,
+            computed: self.0.computed_fields(),
+        }
+        .serialize(serializer)
+    }
+}
+
+// Implementing the derive's `ComposeSchema` gives `PartialSchema` through utoipa's blanket
+// impl, so `WithId<T>` fields work in derived schemas like `FullBooking`.
+impl<T: ToSchema> utoipa::__dev::ComposeSchema for WithId<T> {
+    fn compose(_: Vec<RefOr<Schema>>) -> RefOr<Schema> {
+        AllOfBuilder::new()
+            .item(
+                ObjectBuilder::new()
+                    .property("id", ObjectBuilder::new().schema_type(Type::String))
+                    .required("id"),
+            )
+            .item(Ref::from_schema_name(T::name()))
+            .into()
+    }
+}
+
+impl<T: ToSchema> ToSchema for WithId<T> {
+    // utoipa appends the generic argument, e.g. `WithId_Dog`.
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("WithId")
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((T::name().into_owned(), T::schema()));
+        T::schemas(schemas);
+    }
+}
+
+/// OpenAPI view of an ObjectId in a response, the extended JSON `{"$oid": "<hex>"}`.
+#[derive(Serialize, ToSchema)]
+#[schema(as = ObjectId)]
+pub struct ObjectIdJson {
+    #[serde(rename = "$oid")]
+    #[schema(example = "66f1c0de2a9b4c0012345678")]
+    pub oid: String,
+}
+
+/// OpenAPI view of a bson date in a response, `{"$date": {"$numberLong": "<millis>"}}`.
+#[derive(Serialize, ToSchema)]
+#[schema(as = DateTime)]
+pub struct DateTimeJson {
+    #[serde(rename = "$date")]
+    pub date: NumberLongJson,
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = NumberLong)]
+pub struct NumberLongJson {
+    #[serde(rename = "$numberLong")]
+    #[schema(example = "1757176200000")]
+    pub number_long: String,
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for WithId<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(WithId)
+    }
+}
+
+/// Serialize an ObjectId as its 24 characters hex string.
+pub fn serialize_object_id_as_hex<S: Serializer>(
+    id: &ObjectId,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&id.to_hex())
+}
+
+/// Inbound ObjectId references are accepted either as a plain hex string
+/// (`"owner": "<hex>"`) or in the legacy extended JSON form (`"owner": {"$oid": "<hex>"}`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ObjectIdRepr {
+    Hex(String),
+    Extended {
+        #[serde(rename = "$oid")]
+        oid: String,
+    },
+}
+
+/// Deserialize an ObjectId from either of the `ObjectIdRepr` forms.
+pub fn deserialize_object_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ObjectId, D::Error> {
+    let hex = match ObjectIdRepr::deserialize(deserializer)? {
+        ObjectIdRepr::Hex(hex) => hex,
+        ObjectIdRepr::Extended { oid } => oid,
+    };
+
+    ObjectId::parse_str(&hex).map_err(de::Error::custom)
+}
+
+/// Same as `deserialize_object_id` for an optional reference, `null` gives `None`.
+pub fn deserialize_optional_object_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ObjectId>, D::Error> {
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "deserialize_object_id")] ObjectId);
+
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(id)| id))
+}
+
+/// Same as `deserialize_object_id` for a list of references.
+pub fn deserialize_object_ids<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<ObjectId>, D::Error> {
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "deserialize_object_id")] ObjectId);
+
+    Ok(Vec::<Wrapper>::deserialize(deserializer)?
+        .into_iter()
+        .map(|Wrapper(id)| id)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Resource {
+        _id: ObjectId,
+        name: String,
+    }
+
+    impl HasObjectId for Resource {
+        fn object_id(&self) -> ObjectId {
+            self._id
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Reference {
+        #[serde(
+            serialize_with = "serialize_object_id_as_hex",
+            deserialize_with = "deserialize_object_id"
+        )]
+        owner: ObjectId,
+        #[serde(default, deserialize_with = "deserialize_optional_object_id")]
+        walker: Option<ObjectId>,
+        #[serde(default, deserialize_with = "deserialize_object_ids")]
+        dogs: Vec<ObjectId>,
+    }
+
+    #[test]
+    fn with_id_adds_the_hex_id_next_to_the_extended_json_one() {
+        let id = ObjectId::new();
+        let value = serde_json::to_value(WithId(Resource {
+            _id: id,
+            name: "Rex".to_string(),
+        }))
+        .unwrap();
+
+        assert_eq!(
+            value,
+            json!({"id": id.to_hex(), "_id": {"$oid": id.to_hex()}, "name": "Rex"})
+        );
+        let WithId(resource) = serde_json::from_value::<WithId<Resource>>(value).unwrap();
+        assert_eq!(resource._id, id);
+        assert_eq!(resource.name, "Rex");
+    }
+
+    #[test]
+    fn references_deserialize_from_hex_and_extended_json() {
+        let (owner, walker, dog) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let reference: Reference = serde_json::from_value(json!({
+            "owner": owner.to_hex(),
+            "walker": {"$oid": walker.to_hex()},
+            "dogs": [dog.to_hex(), {"$oid": dog.to_hex()}],
+        }))
+        .unwrap();
+        assert_eq!(reference.owner, owner);
+        assert_eq!(reference.walker, Some(walker));
+        assert_eq!(reference.dogs, vec![dog, dog]);
+
+        let reference: Reference =
+            serde_json::from_value(json!({"owner": {"$oid": owner.to_hex()}, "walker": null}))
+                .unwrap();
+        assert_eq!(reference.owner, owner);
+        assert_eq!(reference.walker, None);
+    }
+
+    #[test]
+    fn references_serialize_as_hex_and_read_back() {
+        let owner = ObjectId::new();
+        let value = serde_json::to_value(Reference {
+            owner,
+            walker: None,
+            dogs: Vec::new(),
+        })
+        .unwrap();
+        assert_eq!(value["owner"], json!(owner.to_hex()));
+
+        let reference: Reference = serde_json::from_value(value).unwrap();
+        assert_eq!(reference.owner, owner);
+    }
+
+    #[test]
+    fn invalid_references_are_rejected() {
+        for owner in [json!("not-an-id"), json!({"$oid": "1234"}), json!(42)] {
+            assert!(serde_json::from_value::<Reference>(json!({"owner": owner})).is_err());
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    booking_model::{Booking, BookingStatus, parse_rfc3339, validate_rfc3339},
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson, WithId},
+};
+
+/// Longest a series may run, from its first walk to `until`.
+pub const MAX_SERIES_DAYS: i64 = 366;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// Weekly schedule booked by `POST /booking` with a `recurrence`. Its walks
+/// (occurrences) are created as bookings a few weeks ahead by the materializer.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BookingSeries {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    /// Start of the first walk, the others start at the same UTC time of day.
+    #[schema(value_type = DateTimeJson)]
+    pub first_start: DateTime,
+    pub duration_in_minutes: u8,
+    pub weekdays: Vec<DayOfWeek>,
+    /// No walk starts after it.
+    #[schema(value_type = DateTimeJson)]
+    pub until: DateTime,
+    /// Every walk starting up to it is booked.
+    #[schema(value_type = DateTimeJson)]
+    pub materialized_until: DateTime,
+    /// Start of the walks cancelled before they were booked, never booked.
+    #[serde(default)]
+    #[schema(value_type = Vec<DateTimeJson>)]
+    pub skipped: Vec<DateTime>,
+    /// Set by `PUT /series/{id}/cancel`, no walk is booked after it.
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub cancelled_at: Option<DateTime>,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+}
+
+impl BookingSeries {
+    /// Series of `recurrence` whose first walk is `first`, which is booked already.
+    pub fn new(first: &Booking, recurrence: &RecurrenceRequest) -> Result<Self, String> {
+        let until = parse_rfc3339(&recurrence.until)
+            .map_err(|err| format!("Failed to parse recurrence.until: {}", err))?;
+        if until <= first.start_time {
+            return Err("recurrence.until must be after start_time".to_string());
+        }
+        if until.timestamp_millis() - first.start_time.timestamp_millis()
+            > MAX_SERIES_DAYS * DAY_MILLIS
+        {
+            return Err(format!(
+                "recurrence.until must be at most {} days after start_time",
+                MAX_SERIES_DAYS
+            ));
+        }
+        let weekday = DayOfWeek::of(first.start_time);
+        if !recurrence.weekdays.contains(&weekday) {
+            return Err(format!(
+                "start_time is a {}, which must be one of recurrence.weekdays",
+                weekday.as_str()
+            ));
+        }
+
+        let mut weekdays = recurrence.weekdays.clone();
+        weekdays.sort_by_key(|day| day.number());
+        weekdays.dedup();
+        Ok(BookingSeries {
+            _id: ObjectId::new(),
+            owner: first.owner,
+            first_start: first.start_time,
+            duration_in_minutes: first.duration_in_minutes,
+            weekdays,
+            until,
+            materialized_until: first.start_time,
+            skipped: Vec::new(),
+            cancelled_at: None,
+            created_at: DateTime::now(),
+        })
+    }
+
+    /// Start of every walk in `(after, up_to]`, oldest first, skipped ones left out.
+    pub fn occurrences(&self, after: DateTime, up_to: DateTime) -> Vec<DateTime> {
+        let first = self.first_start.timestamp_millis();
+        let up_to = up_to.min(self.until).timestamp_millis();
+        // Whole days from the first walk, so every candidate keeps its time of day.
+        let mut day = (after.timestamp_millis() - first).max(0) / DAY_MILLIS;
+        let mut starts = Vec::new();
+        loop {
+            let start = DateTime::from_millis(first + day * DAY_MILLIS);
+            if start.timestamp_millis() > up_to {
+                break starts;
+            }
+            if start > after
+                && self.weekdays.contains(&DayOfWeek::of(start))
+                && !self.skipped.contains(&start)
+            {
+                starts.push(start);
+            }
+            day += 1;
+        }
+    }
+
+    /// Start of the walk of the series on `date` (UTC), booked or not.
+    pub fn occurrence_on(&self, date: NaiveDate) -> Option<DateTime> {
+        let first = chrono::DateTime::from_timestamp_millis(self.first_start.timestamp_millis())?;
+        let days = (date - first.date_naive()).num_days();
+        let start = DateTime::from_millis(first.timestamp_millis() + days * DAY_MILLIS);
+        (days >= 0 && start <= self.until && self.weekdays.contains(&DayOfWeek::of(start)))
+            .then_some(start)
+    }
+
+    /// The pending booking of the walk starting at `start`.
+    pub fn occurrence(&self, start: DateTime) -> Booking {
+        Booking {
+            _id: ObjectId::new(),
+            owner: self.owner,
+            start_time: start,
+            duration_in_minutes: self.duration_in_minutes,
+            status: BookingStatus::Pending,
+            cancelled_at: None,
+            cancellation_reason: None,
+            cancellation_fee_cents: None,
+            report: None,
+            walker: None,
+            match_score: None,
+            reminder_sent_at: None,
+            expires_at: None,
+            version: 0,
+            deleted_at: None,
+            price_cents: None,
+            currency: None,
+            payment: None,
+            coupon: None,
+            points_redeemed: 0,
+            points_earned: None,
+            series: Some(self._id),
+        }
+    }
+}
+
+impl HasObjectId for BookingSeries {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DayOfWeek {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl DayOfWeek {
+    /// Day of the week of `at`, in UTC.
+    pub fn of(at: DateTime) -> Self {
+        let weekday = chrono::DateTime::from_timestamp_millis(at.timestamp_millis())
+            .map(|at| at.weekday())
+            .unwrap_or(Weekday::Mon);
+        match weekday {
+            Weekday::Mon => DayOfWeek::Monday,
+            Weekday::Tue => DayOfWeek::Tuesday,
+            Weekday::Wed => DayOfWeek::Wednesday,
+            Weekday::Thu => DayOfWeek::Thursday,
+            Weekday::Fri => DayOfWeek::Friday,
+            Weekday::Sat => DayOfWeek::Saturday,
+            Weekday::Sun => DayOfWeek::Sunday,
+        }
+    }
+
+    /// 0 for Monday to 6 for Sunday.
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DayOfWeek::Monday => "monday",
+            DayOfWeek::Tuesday => "tuesday",
+            DayOfWeek::Wednesday => "wednesday",
+            DayOfWeek::Thursday => "thursday",
+            DayOfWeek::Friday => "friday",
+            DayOfWeek::Saturday => "saturday",
+            DayOfWeek::Sunday => "sunday",
+        }
+    }
+}
+
+/// `recurrence` of `POST /booking`: repeat the walk every week on `weekdays`,
+/// at the UTC time of day of `start_time`, until `until`. `start_time` must fall
+/// on one of the days.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RecurrenceRequest {
+    #[validate(length(min = 1, max = 7, message = "must name 1 to 7 days"))]
+    #[schema(example = json!(["monday", "wednesday", "friday"]))]
+    pub weekdays: Vec<DayOfWeek>,
+    #[validate(custom(function = "validate_rfc3339"))]
+    #[schema(example = "2025-12-19T23:59:59+01:00")]
+    pub until: String,
+}
+
+/// Answer of `PUT /series/{id}/cancel`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SeriesCancellation {
+    pub series: WithId<BookingSeries>,
+    /// Upcoming walks cancelled with the series, each with its cancellation fee.
+    pub cancelled: Vec<WithId<Booking>>,
+}
+
+/// Answer of `PUT /series/{id}/occurrences/{date}/cancel`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OccurrenceCancellation {
+    pub series: WithId<BookingSeries>,
+    /// The cancelled booking of the walk, missing when it wasn't booked yet
+    /// and the walk was skipped instead.
+    pub booking: Option<WithId<Booking>>,
+}
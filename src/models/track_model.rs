@@ -0,0 +1,70 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use super::{
+    booking_model::parse_rfc3339,
+    geo_model::{GeoPoint, validate_geo_point},
+};
+
+/// Position of the walker during a walk, stored in the "walk_track" collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackPing {
+    pub _id: ObjectId,
+    pub booking: ObjectId,
+    /// When the position was taken, the route is drawn in this order.
+    pub at: DateTime,
+    pub location: GeoPoint,
+}
+
+/// Body of `POST /booking/{id}/track`, pings buffered by the walker's phone
+/// are sent together.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TrackRequest {
+    #[validate(
+        length(min = 1, max = 100, message = "must hold 1 to 100 pings"),
+        nested
+    )]
+    pub pings: Vec<PingRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct PingRequest {
+    #[validate(custom(function = "validate_geo_point"))]
+    pub location: GeoPoint,
+    /// RFC 3339, when the position was taken. Defaults to the time it is received.
+    #[validate(custom(function = "validate_ping_time"))]
+    pub at: Option<String>,
+}
+
+/// Pings can be sent late but not from the future, a minute of clock skew aside.
+fn validate_ping_time(value: &str) -> Result<(), ValidationError> {
+    let at = parse_rfc3339(value).map_err(|_| {
+        ValidationError::new("rfc3339").with_message("must be an RFC 3339 date".into())
+    })?;
+    if at.timestamp_millis() > DateTime::now().timestamp_millis() + 60_000 {
+        return Err(ValidationError::new("future").with_message("must not be in the future".into()));
+    }
+    Ok(())
+}
+
+impl TrackRequest {
+    /// The pings to store for `booking`.
+    pub fn into_pings(self, booking: ObjectId) -> Vec<TrackPing> {
+        let now = DateTime::now();
+        self.pings
+            .into_iter()
+            .map(|ping| TrackPing {
+                _id: ObjectId::new(),
+                booking,
+                at: ping
+                    .at
+                    .as_deref()
+                    .and_then(|at| parse_rfc3339(at).ok())
+                    .unwrap_or(now),
+                location: ping.location,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use super::{
+    booking_model::parse_rfc3339,
+    dog_model::Dog,
+    serde_helpers::{DateTimeJson, ObjectIdJson},
+};
+
+/// Vaccine given to a dog, embedded in its `vaccinations` array.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Vaccination {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    /// e.g. `rabies`, `DHPP`.
+    pub vaccine: String,
+    #[schema(value_type = DateTimeJson)]
+    pub administered_on: DateTime,
+    /// Missing for vaccines that don't need a booster.
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub expires_on: Option<DateTime>,
+    /// Reference (URL) of the scanned certificate.
+    pub document: Option<String>,
+}
+
+impl Vaccination {
+    /// True for the rabies vaccine, whatever the spelling of the name around it.
+    pub fn is_rabies(&self) -> bool {
+        self.vaccine.to_lowercase().contains("rabies")
+    }
+
+    pub fn is_valid_at(&self, at: DateTime) -> bool {
+        self.expires_on.is_none_or(|expires_on| expires_on > at)
+    }
+}
+
+/// Body of `POST /dog/{id}/vaccinations` and `PUT /dog/{id}/vaccinations/{vaccination_id}`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct VaccinationRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
+    pub vaccine: String,
+    /// RFC 3339.
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub administered_on: String,
+    /// RFC 3339, after `administered_on`.
+    #[validate(custom(function = "validate_rfc3339"))]
+    pub expires_on: Option<String>,
+    #[validate(length(min = 1, max = 500, message = "must be 1 to 500 characters long"))]
+    pub document: Option<String>,
+}
+
+fn validate_rfc3339(value: &str) -> Result<(), ValidationError> {
+    parse_rfc3339(value).map(|_| ()).map_err(|_| {
+        ValidationError::new("rfc3339").with_message("must be an RFC 3339 date".into())
+    })
+}
+
+impl VaccinationRequest {
+    /// The record to store under `id`, a new one or the one being replaced.
+    pub fn into_vaccination(self, id: ObjectId) -> Result<Vaccination, String> {
+        let administered_on = parse_rfc3339(&self.administered_on)?;
+        let expires_on = self.expires_on.as_deref().map(parse_rfc3339).transpose()?;
+        if expires_on.is_some_and(|expires_on| expires_on <= administered_on) {
+            return Err("expires_on must be after administered_on".to_string());
+        }
+        Ok(Vaccination {
+            _id: id,
+            vaccine: self.vaccine,
+            administered_on,
+            expires_on,
+            document: self.document,
+        })
+    }
+}
+
+/// What booking creation requires of the rabies vaccination of the owner's dogs,
+/// `bookings.rabies_vaccination_policy` of the configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RabiesPolicy {
+    /// `off`, no check.
+    Off,
+    /// `expired` (default), refuse dogs whose rabies vaccinations have all expired
+    /// by the start of the walk. Dogs without any rabies record may still book.
+    #[default]
+    Expired,
+    /// `required`, every dog needs a rabies vaccination valid at the start of the walk.
+    Required,
+}
+
+impl FromStr for RabiesPolicy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(RabiesPolicy::Off),
+            "expired" => Ok(RabiesPolicy::Expired),
+            "required" => Ok(RabiesPolicy::Required),
+            _ => Err(()),
+        }
+    }
+}
+
+impl RabiesPolicy {
+    /// Whether `dog` may be walked at `at`.
+    pub fn allows(self, dog: &Dog, at: DateTime) -> bool {
+        let mut rabies = dog.vaccinations.iter().filter(|v| v.is_rabies()).peekable();
+        match self {
+            RabiesPolicy::Off => true,
+            RabiesPolicy::Expired if rabies.peek().is_none() => true,
+            RabiesPolicy::Expired | RabiesPolicy::Required => {
+                rabies.any(|vaccination| vaccination.is_valid_at(at))
+            }
+        }
+    }
+}
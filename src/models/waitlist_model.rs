@@ -0,0 +1,138 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    booking_model::{Booking, BookingStatus, parse_rfc3339, validate_rfc3339},
+    serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson, WithId, deserialize_object_id},
+};
+
+/// Place of an owner in the queue of a fully booked time slot, taken with
+/// `POST /waitlist` after `POST /booking` answered `capacity_reached`.
+/// A background job books the walk for the oldest entry once a walker frees up.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WaitlistEntry {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub owner: ObjectId,
+    #[schema(value_type = DateTimeJson)]
+    pub start_time: DateTime,
+    pub duration_in_minutes: u8,
+    pub status: WaitlistStatus,
+    /// Booking created when the entry was promoted.
+    #[schema(value_type = Option<ObjectIdJson>)]
+    pub booking: Option<ObjectId>,
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub promoted_at: Option<DateTime>,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+}
+
+impl WaitlistEntry {
+    pub fn end_time(&self) -> DateTime {
+        DateTime::from_millis(
+            self.start_time.timestamp_millis() + i64::from(self.duration_in_minutes) * 60_000,
+        )
+    }
+
+    /// The pending booking of the walk, created when the entry is promoted.
+    pub fn booking(&self) -> Booking {
+        Booking {
+            _id: ObjectId::new(),
+            owner: self.owner,
+            start_time: self.start_time,
+            duration_in_minutes: self.duration_in_minutes,
+            status: BookingStatus::Pending,
+            cancelled_at: None,
+            cancellation_reason: None,
+            cancellation_fee_cents: None,
+            report: None,
+            walker: None,
+            match_score: None,
+            reminder_sent_at: None,
+            expires_at: None,
+            version: 0,
+            deleted_at: None,
+            price_cents: None,
+            currency: None,
+            payment: None,
+            coupon: None,
+            points_redeemed: 0,
+            points_earned: None,
+            series: None,
+        }
+    }
+}
+
+impl HasObjectId for WaitlistEntry {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+impl TryFrom<WaitlistRequest> for WaitlistEntry {
+    type Error = String;
+
+    fn try_from(item: WaitlistRequest) -> Result<Self, Self::Error> {
+        let start_time = parse_rfc3339(&item.start_time)
+            .map_err(|err| format!("Failed to parse start_time: {}", err))?;
+        if start_time <= DateTime::now() {
+            return Err("start_time must be in the future".to_string());
+        }
+        Ok(WaitlistEntry {
+            _id: ObjectId::new(),
+            owner: item.owner,
+            start_time,
+            duration_in_minutes: item.duration_in_minutes,
+            status: WaitlistStatus::Waiting,
+            booking: None,
+            promoted_at: None,
+            created_at: DateTime::now(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitlistStatus {
+    /// In the queue of the slot.
+    Waiting,
+    /// The walk is booked, see `booking`.
+    Promoted,
+    /// The walk started before a walker freed up.
+    Expired,
+}
+
+impl WaitlistStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WaitlistStatus::Waiting => "waiting",
+            WaitlistStatus::Promoted => "promoted",
+            WaitlistStatus::Expired => "expired",
+        }
+    }
+}
+
+/// Body of `POST /waitlist`, the walk `POST /booking` refused.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct WaitlistRequest {
+    #[serde(deserialize_with = "deserialize_object_id")]
+    #[schema(value_type = String, example = "66f1c0de2a9b4c0012345678")]
+    pub owner: ObjectId,
+    #[validate(custom(function = "validate_rfc3339"))]
+    #[schema(example = "2025-09-06T18:30:00+02:00")]
+    pub start_time: String,
+    #[validate(range(min = 15, max = 240, message = "must be between 15 and 240 minutes"))]
+    pub duration_in_minutes: u8,
+}
+
+/// An entry with its place in the queue.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WaitlistPlace {
+    pub entry: WithId<WaitlistEntry>,
+    /// 1 for the next owner booked when a walker frees up, missing once the
+    /// entry is no longer waiting.
+    pub position: Option<u64>,
+}
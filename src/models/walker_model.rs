@@ -0,0 +1,195 @@
+use mongodb::bson::{Document, oid::ObjectId, to_bson};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    dog_model::{Dog, DogSize},
+    geo_model::{GeoPoint, validate_geo_point},
+    serde_helpers::{HasObjectId, ObjectIdJson},
+};
+
+/// Person walking the dogs, assigned to bookings with `POST /booking/{id}/assign/{walker_id}`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Walker {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    /// Where the walker works from, found by `GET /walkers/near`.
+    #[serde(default)]
+    pub location: Option<GeoPoint>,
+    /// Experience level, senior and expert walkers cost more (see `PricingRules`).
+    #[serde(default)]
+    pub tier: WalkerTier,
+    /// Most dogs the walker takes on a group walk, no group walks when missing.
+    #[serde(default)]
+    pub group_capacity: Option<u32>,
+    /// Most dogs the walker takes on a booking, any number when missing.
+    #[serde(default)]
+    pub max_dogs: Option<u32>,
+    /// Sizes of the dogs the walker takes, every size when empty.
+    #[serde(default)]
+    pub accepted_sizes: Vec<DogSize>,
+}
+
+impl Walker {
+    /// Why the walker can't take the `dogs` of a booking: more than `max_dogs`, or
+    /// one of them of a size it doesn't take. `None` when it can.
+    pub fn refusal(&self, dogs: &[Dog]) -> Option<String> {
+        if let Some(max_dogs) = self.max_dogs
+            && dogs.len() > max_dogs as usize
+        {
+            return Some(format!(
+                "The walk has {} dogs, the walker takes at most {}",
+                dogs.len(),
+                max_dogs
+            ));
+        }
+        self.size_refusal(dogs)
+    }
+
+    /// Why the walker can't take one of `dogs` for its size, `None` when it can.
+    /// With `accepted_sizes` set, a dog without a weight in its profile is refused.
+    pub fn size_refusal(&self, dogs: &[Dog]) -> Option<String> {
+        if self.accepted_sizes.is_empty() {
+            return None;
+        }
+        let accepted = self
+            .accepted_sizes
+            .iter()
+            .map(|size| size.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        dogs.iter().find_map(|dog| {
+            let name = dog.name.clone().unwrap_or_else(|| dog._id.to_hex());
+            match dog.size() {
+                Some(size) if self.accepted_sizes.contains(&size) => None,
+                Some(size) => Some(format!(
+                    "{} is a {} dog, the walker only takes {} dogs",
+                    name,
+                    size.as_str(),
+                    accepted
+                )),
+                None => Some(format!(
+                    "{} has no weight_kg in its profile, the walker only takes {} dogs",
+                    name, accepted
+                )),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WalkerTier {
+    #[default]
+    Standard,
+    Senior,
+    Expert,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct WalkerRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
+    pub name: String,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: String,
+    #[validate(length(min = 6, max = 20, message = "must be 6 to 20 characters long"))]
+    pub phone: String,
+    #[validate(custom(function = "validate_geo_point"))]
+    pub location: Option<GeoPoint>,
+    /// Defaults to `standard`.
+    #[serde(default)]
+    pub tier: WalkerTier,
+    #[validate(range(min = 2, max = 12, message = "must be between 2 and 12 dogs"))]
+    pub group_capacity: Option<u32>,
+    #[validate(range(min = 1, max = 12, message = "must be between 1 and 12 dogs"))]
+    pub max_dogs: Option<u32>,
+    /// Defaults to every size.
+    #[serde(default)]
+    pub accepted_sizes: Vec<DogSize>,
+}
+
+/// Body of `PUT /walker/{id}`, only the provided fields are updated.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct WalkerUpdateRequest {
+    #[validate(length(min = 1, max = 100, message = "must be 1 to 100 characters long"))]
+    pub name: Option<String>,
+    #[validate(email(message = "must be a valid email address"))]
+    pub email: Option<String>,
+    #[validate(length(min = 6, max = 20, message = "must be 6 to 20 characters long"))]
+    pub phone: Option<String>,
+    #[validate(custom(function = "validate_geo_point"))]
+    pub location: Option<GeoPoint>,
+    pub tier: Option<WalkerTier>,
+    #[validate(range(min = 2, max = 12, message = "must be between 2 and 12 dogs"))]
+    pub group_capacity: Option<u32>,
+    #[validate(range(min = 1, max = 12, message = "must be between 1 and 12 dogs"))]
+    pub max_dogs: Option<u32>,
+    /// Replaces the accepted sizes, empty for every size.
+    pub accepted_sizes: Option<Vec<DogSize>>,
+}
+
+impl WalkerUpdateRequest {
+    /// Build the `$set` document from the provided fields only.
+    pub fn to_set_document(&self) -> Document {
+        let mut set = Document::new();
+        if let Some(name) = &self.name {
+            set.insert("name", name);
+        }
+        if let Some(email) = &self.email {
+            set.insert("email", email);
+        }
+        if let Some(phone) = &self.phone {
+            set.insert("phone", phone);
+        }
+        if let Some(location) = &self.location
+            && let Ok(location) = to_bson(location)
+        {
+            set.insert("location", location);
+        }
+        if let Some(tier) = &self.tier
+            && let Ok(tier) = to_bson(tier)
+        {
+            set.insert("tier", tier);
+        }
+        if let Some(group_capacity) = self.group_capacity {
+            set.insert("group_capacity", group_capacity);
+        }
+        if let Some(max_dogs) = self.max_dogs {
+            set.insert("max_dogs", max_dogs);
+        }
+        if let Some(accepted_sizes) = &self.accepted_sizes
+            && let Ok(accepted_sizes) = to_bson(accepted_sizes)
+        {
+            set.insert("accepted_sizes", accepted_sizes);
+        }
+        set
+    }
+}
+
+impl TryFrom<WalkerRequest> for Walker {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(item: WalkerRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            _id: ObjectId::new(),
+            name: item.name,
+            email: item.email,
+            phone: item.phone,
+            location: item.location,
+            tier: item.tier,
+            group_capacity: item.group_capacity,
+            max_dogs: item.max_dogs,
+            accepted_sizes: item.accepted_sizes,
+        })
+    }
+}
+
+impl HasObjectId for Walker {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
@@ -0,0 +1,133 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::serde_helpers::{DateTimeJson, HasObjectId, ObjectIdJson};
+
+/// Booking change an integrator can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEvent {
+    #[serde(rename = "booking.created")]
+    Created,
+    #[serde(rename = "booking.cancelled")]
+    Cancelled,
+    #[serde(rename = "booking.completed")]
+    Completed,
+}
+
+impl WebhookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::Created => "booking.created",
+            WebhookEvent::Cancelled => "booking.cancelled",
+            WebhookEvent::Completed => "booking.completed",
+        }
+    }
+}
+
+/// URL of an integration (a CRM...) the booking events it subscribed to are
+/// POSTed to, signed with its secret. Registered with `POST /admin/webhooks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub _id: ObjectId,
+    pub url: String,
+    /// HMAC key of the `X-Webhook-Signature` header, never returned.
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime,
+}
+
+/// Body of `POST /admin/webhooks`.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct WebhookRequest {
+    #[validate(url(message = "must be an http(s) URL"))]
+    #[schema(example = "https://crm.example.com/hooks/dog-walks")]
+    pub url: String,
+    /// Shared with the integration, which checks the signature of each call with it.
+    #[validate(length(min = 16, max = 256, message = "must be 16 to 256 characters long"))]
+    pub secret: String,
+    #[validate(length(min = 1, message = "must contain at least one event"))]
+    pub events: Vec<WebhookEvent>,
+}
+
+/// HTTP view of a `Webhook`, without its secret.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookView {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: String,
+}
+
+impl From<Webhook> for WebhookView {
+    fn from(webhook: Webhook) -> Self {
+        WebhookView {
+            id: webhook._id.to_hex(),
+            url: webhook.url,
+            events: webhook.events,
+            created_at: webhook
+                .created_at
+                .try_to_rfc3339_string()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// One event to send to one webhook, with the outcome of its attempts.
+/// Listed by `GET /admin/webhooks/{id}/deliveries`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    #[schema(value_type = ObjectIdJson)]
+    pub _id: ObjectId,
+    #[schema(value_type = ObjectIdJson)]
+    pub webhook: ObjectId,
+    pub event: WebhookEvent,
+    #[schema(value_type = ObjectIdJson)]
+    pub booking: ObjectId,
+    /// Change of the booking stream the delivery was queued for, missing on the
+    /// deliveries queued before the stream was watched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_id: Option<String>,
+    /// JSON body sent, the same at every attempt.
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// When the next attempt is due while `pending`.
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub next_attempt_at: Option<DateTime>,
+    /// HTTP status of the last attempt, missing when the URL couldn't be reached.
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    #[schema(value_type = DateTimeJson)]
+    pub created_at: DateTime,
+    #[schema(value_type = Option<DateTimeJson>)]
+    pub delivered_at: Option<DateTime>,
+}
+
+impl HasObjectId for WebhookDelivery {
+    fn object_id(&self) -> ObjectId {
+        self._id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry.
+    Pending,
+    /// The URL answered with a 2xx.
+    Delivered,
+    /// Every attempt failed.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
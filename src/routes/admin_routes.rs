@@ -0,0 +1,633 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        api_key_model::{ApiKey, ApiKeyRequest, ApiKeyView, CreatedApiKey},
+        audit_model::{AuditEntry, AuditFilter, AuditQuery},
+        auth_model::{AccountRequest, Credentials, Role},
+        backup_model::{Backup, ImportMode, ImportQuery, ImportReport},
+        booking_model::{ArchiveFilter, ArchiveQuery, Booking, parse_rfc3339},
+        coupon_model::{Coupon, CouponRequest},
+        page_model::{Page, PageQuery},
+        payout_model::{PayoutBatch, SettleRequest},
+        serde_helpers::WithId,
+        webhook_model::{Webhook, WebhookDelivery, WebhookRequest, WebhookView},
+    },
+    routes::{
+        extractors::{AdminKey, AdminRole, ObjectIdPath, RequireRole, parse_object_id},
+        openapi::CreatedAccount,
+    },
+    services::{
+        auth::{Authenticator, one_time_token},
+        cache::CacheStats,
+        db::Database,
+        jobs::{JobStats, Scheduler},
+        repository::{AuditRepository, BookingRepository},
+    },
+};
+use actix_web::{
+    HttpResponse, delete,
+    error::ErrorInternalServerError,
+    get, post,
+    web::{self, Bytes, Data, Json, Query},
+};
+use futures_util::{Stream, StreamExt, stream};
+use mongodb::{
+    Cursor,
+    bson::{DateTime, oid::ObjectId},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::json;
+use validator::Validate;
+
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 200, description = "Owner cache counters", body = CacheStats),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/cache/stats")]
+pub async fn get_cache_stats(db: Data<Database>, _admin: RequireRole<AdminRole>) -> HttpResponse {
+    HttpResponse::Ok().json(db.owner_cache().stats())
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 204, description = "Cache emptied"),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[delete("/admin/cache")]
+pub async fn purge_cache(db: Data<Database>, _admin: RequireRole<AdminRole>) -> HttpResponse {
+    db.owner_cache().purge();
+    HttpResponse::NoContent().finish()
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    responses(
+        (status = 204, description = "Owner evicted from the cache"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[delete("/admin/cache/owner/{id}")]
+pub async fn purge_cached_owner(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> HttpResponse {
+    db.owner_cache().invalidate(&path.0);
+    HttpResponse::NoContent().finish()
+}
+
+/// Runs of each background job on the replica answering, with its schedule and
+/// when it runs next.
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 200, description = "Counters of each job", body = [JobStats]),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/jobs")]
+pub async fn get_jobs(scheduler: Data<Scheduler>, _admin: RequireRole<AdminRole>) -> HttpResponse {
+    HttpResponse::Ok().json(scheduler.stats())
+}
+
+/// Stream the whole dataset as one JSON object `{"owners": [...], "dogs": [...], "bookings": [...]}`
+/// without loading the collections in memory.
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 200, description = "Whole dataset, streamed", body = Backup),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/export")]
+pub async fn export_data(db: Data<Database>, _admin: RequireRole<AdminRole>) -> ApiResponse {
+    let (owners, dogs, bookings) = db.export_cursors().await?;
+
+    let body = stream::once(async { Ok(Bytes::from_static(b"{\"owners\":[")) })
+        .chain(json_array(owners))
+        .chain(stream::once(async {
+            Ok(Bytes::from_static(b"],\"dogs\":["))
+        }))
+        .chain(json_array(dogs))
+        .chain(stream::once(async {
+            Ok(Bytes::from_static(b"],\"bookings\":["))
+        }))
+        .chain(json_array(bookings))
+        .chain(stream::once(async { Ok(Bytes::from_static(b"]}")) }));
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .streaming(body))
+}
+
+/// Serialize every document of a cursor as a comma separated JSON array body (without brackets).
+fn json_array<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static>(
+    cursor: Cursor<T>,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    cursor.enumerate().map(|(index, document)| {
+        let document = document.map_err(ErrorInternalServerError)?;
+        let mut bytes = if index == 0 { Vec::new() } else { vec![b','] };
+        serde_json::to_writer(&mut bytes, &document).map_err(ErrorInternalServerError)?;
+        Ok(Bytes::from(bytes))
+    })
+}
+
+/// Import a backup produced by `GET /admin/export`.
+/// Refused with 409 when data already exists, unless `?mode=merge` is passed.
+#[utoipa::path(
+    tag = "admin",
+    request_body = Backup,
+    params(ImportQuery),
+    responses(
+        (status = 200, description = "Per collection import counts", body = ImportReport),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 409, description = "Data already exists and `mode` is not `merge`", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[post("/admin/import")]
+pub async fn import_data(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<ImportQuery>,
+    request: Json<Backup>,
+) -> ApiResponse {
+    let merge = query.mode == Some(ImportMode::Merge);
+
+    if !merge && !db.dataset_is_empty().await? {
+        return Err(AppError::conflict(
+            "Target collections are not empty, use ?mode=merge",
+        ));
+    }
+
+    let report = db.import_backup(request.into_inner(), merge).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Create the login of a walker or an admin, owners sign up with `POST /auth/register`.
+/// Guarded by the admin key so the first admin account can be bootstrapped.
+#[utoipa::path(
+    tag = "admin",
+    request_body = AccountRequest,
+    responses(
+        (status = 201, description = "Account created", body = CreatedAccount),
+        (status = 400, description = "Owner role or missing walker id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+        (status = 409, description = "Email already registered", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("admin_key" = []))
+)]
+#[post("/admin/accounts")]
+pub async fn create_account(
+    db: Data<Database>,
+    auth: Data<Authenticator>,
+    _admin: AdminKey,
+    request: Json<AccountRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let AccountRequest {
+        email,
+        password,
+        role,
+        user_id,
+    } = request.into_inner();
+
+    let user_id = match (role, user_id) {
+        (Role::Owner, _) => {
+            return Err(AppError::Validation(
+                "Owners register with POST /auth/register".to_string(),
+            ));
+        }
+        (Role::Walker, Some(walker_id)) => db.get_walker(&walker_id).await?._id,
+        (Role::Walker, None) => {
+            return Err(AppError::Validation(
+                "`user_id` of the walker is required".to_string(),
+            ));
+        }
+        (Role::Admin, _) => ObjectId::new(),
+    };
+
+    let hasher = auth.clone();
+    let password_hash = web::block(move || hasher.hash_password(&password)).await?;
+
+    db.create_credentials(Credentials {
+        _id: ObjectId::new(),
+        user_id,
+        email,
+        password_hash,
+        role,
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "user_id": user_id.to_hex(),
+        "role": role,
+    })))
+}
+
+/// Mint a key for a backend integration, sent back as `X-Api-Key`.
+/// The key is only returned here, the database keeps its SHA-256.
+#[utoipa::path(
+    tag = "admin",
+    request_body = ApiKeyRequest,
+    responses(
+        (status = 201, description = "Key created, `key` is only shown once", body = CreatedApiKey),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[post("/admin/api-keys")]
+pub async fn create_api_key(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    request: Json<ApiKeyRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let ApiKeyRequest { name, mut scopes } = request.into_inner();
+    scopes.sort();
+    scopes.dedup();
+    let (key, key_hash) = one_time_token();
+    let api_key = ApiKey {
+        _id: ObjectId::new(),
+        name,
+        key_hash,
+        prefix: key.chars().take(8).collect(),
+        scopes,
+        created_at: DateTime::now(),
+        revoked_at: None,
+    };
+    db.create_api_key(api_key.clone()).await?;
+
+    Ok(HttpResponse::Created().json(CreatedApiKey {
+        api_key: api_key.into(),
+        key,
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every key, revoked ones included", body = [ApiKeyView]),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/api-keys")]
+pub async fn get_api_keys(db: Data<Database>, _admin: RequireRole<AdminRole>) -> ApiResponse {
+    let api_keys: Vec<ApiKeyView> = db
+        .get_api_keys()
+        .await?
+        .into_iter()
+        .map(ApiKeyView::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+/// Revoke a key, requests sending it are refused from now on.
+#[utoipa::path(
+    tag = "admin",
+    params(("id" = String, Path, description = "ObjectId of the API key")),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "API key not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[delete("/admin/api-keys/{id}")]
+pub async fn revoke_api_key(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    db.revoke_api_key(&path.0).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Changes made through the API, newest first. Filter with `entity_id`
+/// to follow one owner, dog, walker or booking.
+#[utoipa::path(
+    tag = "admin",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Page of audit entries", body = Page<AuditEntry>),
+        (status = 400, description = "Malformed id, date, page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/audit")]
+pub async fn get_audit_log(
+    audit_log: Data<dyn AuditRepository>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<AuditQuery>,
+) -> ApiResponse {
+    let (page, limit) = PageQuery {
+        page: query.page,
+        limit: query.limit,
+    }
+    .resolve()
+    .map_err(AppError::Validation)?;
+    let filter = AuditFilter {
+        entity_id: query
+            .entity_id
+            .as_deref()
+            .map(parse_object_id)
+            .transpose()?,
+        from: query
+            .from
+            .as_deref()
+            .map(parse_rfc3339)
+            .transpose()
+            .map_err(AppError::Validation)?,
+        to: query
+            .to
+            .as_deref()
+            .map(parse_rfc3339)
+            .transpose()
+            .map_err(AppError::Validation)?,
+    };
+
+    let entries = audit_log.get_audit_log(&filter, page, limit).await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// Bookings still to walk that have no walker, soonest first: the matching found
+/// nobody free near the owner, or the owner has no `location`. Assign them with
+/// `POST /booking/{id}/assign/{walker_id}`.
+#[utoipa::path(
+    tag = "admin",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Page of bookings needing a manual assignment", body = Page<WithId<Booking>>),
+        (status = 400, description = "Invalid page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/bookings/unassigned")]
+pub async fn get_unassigned_bookings(
+    bookings: Data<dyn BookingRepository>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<PageQuery>,
+) -> ApiResponse {
+    let (page, limit) = query.resolve().map_err(AppError::Validation)?;
+
+    let unassigned = bookings.get_unassigned_bookings(page, limit).await?;
+    Ok(HttpResponse::Ok().json(unassigned))
+}
+
+/// Bookings moved out by the archive job once finished for `jobs.archive_after_days`,
+/// latest walk first.
+#[utoipa::path(
+    tag = "admin",
+    params(ArchiveQuery),
+    responses(
+        (status = 200, description = "Page of archived bookings", body = Page<WithId<Booking>>),
+        (status = 400, description = "Malformed id, date, page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/bookings/archive")]
+pub async fn get_archived_bookings(
+    bookings: Data<dyn BookingRepository>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<ArchiveQuery>,
+) -> ApiResponse {
+    let (page, limit) = PageQuery {
+        page: query.page,
+        limit: query.limit,
+    }
+    .resolve()
+    .map_err(AppError::Validation)?;
+    let filter = ArchiveFilter {
+        owner: query.owner.as_deref().map(parse_object_id).transpose()?,
+        from: query
+            .from
+            .as_deref()
+            .map(parse_rfc3339)
+            .transpose()
+            .map_err(AppError::Validation)?,
+        to: query
+            .to
+            .as_deref()
+            .map(parse_rfc3339)
+            .transpose()
+            .map_err(AppError::Validation)?,
+    };
+
+    let archived = bookings.get_archived_bookings(&filter, page, limit).await?;
+    Ok(HttpResponse::Ok().json(archived))
+}
+
+/// Hand out a discount code, redeemed by `POST /booking` with `coupon_code`.
+#[utoipa::path(
+    tag = "admin",
+    request_body = CouponRequest,
+    responses(
+        (status = 201, description = "Coupon created with all its uses left", body = WithId<Coupon>),
+        (status = 400, description = "Validity window ending before it starts", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 409, description = "The code is taken (`coupon_exists`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[post("/admin/coupons")]
+pub async fn create_coupon(
+    bookings: Data<dyn BookingRepository>,
+    _admin: RequireRole<AdminRole>,
+    request: Json<CouponRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let coupon = Coupon::try_from(request.into_inner()).map_err(AppError::Validation)?;
+    bookings.create_coupon(&coupon).await?;
+    Ok(HttpResponse::Created().json(WithId(coupon)))
+}
+
+/// Pay out the walkers: every ledger entry earned before `until` and not settled
+/// yet, of one walker or all of them, is marked settled under a new batch.
+#[utoipa::path(
+    tag = "admin",
+    request_body = SettleRequest,
+    responses(
+        (status = 200, description = "Batch of the settled entries, empty when nothing was due", body = PayoutBatch),
+        (status = 400, description = "Malformed walker id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[post("/admin/payouts/settle")]
+pub async fn settle_payouts(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    request: Json<SettleRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let walker_id = request.walker.as_deref().map(parse_object_id).transpose()?;
+    if let Some(walker_id) = &walker_id {
+        db.get_walker(walker_id).await?;
+    }
+    let until = parse_rfc3339(&request.until).map_err(AppError::Validation)?;
+
+    let batch = db.settle_payouts(walker_id.as_ref(), until).await?;
+    Ok(HttpResponse::Ok().json(batch))
+}
+
+/// Subscribe an integration to booking events: each one is POSTed to `url`
+/// with an `X-Webhook-Signature: t=<timestamp>,v1=<hex>` header, the HMAC-SHA256
+/// of `<timestamp>.<body>` under `secret`, and retried until it answers a 2xx.
+#[utoipa::path(
+    tag = "admin",
+    request_body = WebhookRequest,
+    responses(
+        (status = 201, description = "Webhook registered", body = WebhookView),
+        (status = 400, description = "The URL is not http(s)", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[post("/admin/webhooks")]
+pub async fn create_webhook(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    request: Json<WebhookRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let WebhookRequest {
+        url,
+        secret,
+        mut events,
+    } = request.into_inner();
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(AppError::Validation(
+            "url must be an http(s) URL".to_string(),
+        ));
+    }
+    events.sort();
+    events.dedup();
+    let webhook = Webhook {
+        _id: ObjectId::new(),
+        url,
+        secret,
+        events,
+        created_at: DateTime::now(),
+    };
+    db.create_webhook(&webhook).await?;
+
+    Ok(HttpResponse::Created().json(WebhookView::from(webhook)))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every webhook, newest first", body = [WebhookView]),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/webhooks")]
+pub async fn get_webhooks(db: Data<Database>, _admin: RequireRole<AdminRole>) -> ApiResponse {
+    let webhooks: Vec<WebhookView> = db
+        .get_webhooks()
+        .await?
+        .into_iter()
+        .map(WebhookView::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+/// Unsubscribe an integration, its pending deliveries and delivery log go with it.
+#[utoipa::path(
+    tag = "admin",
+    params(("id" = String, Path, description = "ObjectId of the webhook")),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Webhook not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[delete("/admin/webhooks/{id}")]
+pub async fn delete_webhook(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    db.delete_webhook(&path.0).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Delivery log of a webhook, newest first: the payload sent, the attempts
+/// made and the answer to the last one.
+#[utoipa::path(
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "ObjectId of the webhook"),
+        PageQuery,
+    ),
+    responses(
+        (status = 200, description = "Page of deliveries", body = Page<WithId<WebhookDelivery>>),
+        (status = 400, description = "Malformed id, page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Webhook not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/admin/webhooks/{id}/deliveries")]
+pub async fn get_webhook_deliveries(
+    db: Data<Database>,
+    _admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+    query: Query<PageQuery>,
+) -> ApiResponse {
+    let (page, limit) = query.resolve().map_err(AppError::Validation)?;
+    db.get_webhook(&path.0).await?;
+
+    let deliveries = db.get_webhook_deliveries(&path.0, page, limit).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
@@ -0,0 +1,209 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, AuditActor, EntityKind, EntityRef, snapshot},
+        auth_model::{
+            ForgotPasswordRequest, LoginRequest, PasswordReset, RegisterRequest,
+            ResetPasswordRequest, Role, TokenResponse,
+        },
+        owner_model::{Owner, OwnerRequest},
+    },
+    routes::{PublicUrl, audit, owner_routes::send_verification_email},
+    services::{
+        auth::{Authenticator, hash_one_time_token, one_time_token},
+        db::Database,
+        mailer::Mailer,
+        repository::OwnerRepository,
+    },
+};
+use actix_web::{
+    HttpResponse, post,
+    web::{self, Data, Json},
+};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use tracing::error;
+use validator::Validate;
+
+/// Create an owner with a password and log it in right away.
+#[utoipa::path(
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Owner created and logged in", body = TokenResponse),
+        (status = 409, description = "Email already registered", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    )
+)]
+#[post("/auth/register")]
+pub async fn register(
+    db: Data<Database>,
+    auth: Data<Authenticator>,
+    mailer: Data<dyn Mailer>,
+    public_url: Data<PublicUrl>,
+    request: Json<RegisterRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let RegisterRequest {
+        name,
+        email,
+        phone,
+        address,
+        password,
+    } = request.into_inner();
+
+    let owner = Owner::try_from(OwnerRequest {
+        name,
+        email,
+        phone,
+        address,
+        location: None,
+    })
+    .map_err(|err| AppError::Validation(err.to_string()))?;
+    let owner_id = owner._id;
+    let email = owner.email.clone();
+    let after = snapshot(&owner);
+
+    // Argon2 is deliberately slow, keep it off the async workers.
+    let hasher = auth.clone();
+    let password_hash = web::block(move || hasher.hash_password(&password)).await?;
+
+    let (_, verification_token) = db.register_owner(owner, password_hash).await?;
+    send_verification_email(mailer.get_ref(), &public_url, &email, &verification_token);
+    audit(
+        db.get_ref(),
+        AuditActor {
+            user_id: Some(owner_id),
+            role: Some(Role::Owner),
+        },
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Owner, owner_id),
+        None,
+        after,
+    )
+    .await;
+
+    Ok(HttpResponse::Created().json(auth.issue(owner_id, Role::Owner)))
+}
+
+/// Exchange an email and password for an access token.
+/// Unknown emails and wrong passwords get the same 401.
+#[utoipa::path(
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Access token", body = TokenResponse),
+        (status = 401, description = "Invalid email or password", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    )
+)]
+#[post("/auth/login")]
+pub async fn login(
+    db: Data<Database>,
+    auth: Data<Authenticator>,
+    request: Json<LoginRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let invalid = || AppError::Unauthorized("Invalid email or password".to_string());
+    let LoginRequest { email, password } = request.into_inner();
+
+    let credentials = db.find_credentials(&email).await?.ok_or_else(invalid)?;
+
+    let verifier = auth.clone();
+    let password_hash = credentials.password_hash;
+    let valid = web::block(move || verifier.verify_password(&password, &password_hash)).await?;
+    if !valid {
+        return Err(invalid());
+    }
+    // A deleted owner keeps its login for a restore, but can't use it meanwhile.
+    if credentials.role == Role::Owner && !db.owner_exists(&credentials.user_id, false).await? {
+        return Err(invalid());
+    }
+
+    Ok(HttpResponse::Ok().json(auth.issue(credentials.user_id, credentials.role)))
+}
+
+/// Email a single-use reset link. The answer is the same whether or not the
+/// email is registered, so the endpoint can't be used to probe for accounts.
+#[utoipa::path(
+    tag = "auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 202, description = "Reset link sent if the email is registered"),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    )
+)]
+#[post("/auth/forgot-password")]
+pub async fn forgot_password(
+    db: Data<Database>,
+    auth: Data<Authenticator>,
+    mailer: Data<dyn Mailer>,
+    public_url: Data<PublicUrl>,
+    request: Json<ForgotPasswordRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let accepted = HttpResponse::Accepted().finish();
+    let Some(credentials) = db.find_credentials(&request.email).await? else {
+        return Ok(accepted);
+    };
+
+    let (token, token_hash) = one_time_token();
+    let expires_at = DateTime::from_millis(
+        DateTime::now().timestamp_millis() + auth.reset_ttl().as_millis() as i64,
+    );
+    db.create_password_reset(PasswordReset {
+        _id: ObjectId::new(),
+        credentials: credentials._id,
+        token_hash,
+        expires_at,
+    })
+    .await?;
+
+    let link = public_url.to(&format!("/reset-password?token={}", token));
+    let body = format!(
+        "Use this link to choose a new password, it expires in {} minutes:\n{}",
+        auth.reset_ttl().as_secs() / 60,
+        link
+    );
+    // A delivery failure must not reveal that the account exists.
+    if let Err(err) = mailer.send(&credentials.email, "Reset your password", &body) {
+        error!(error = %err, "Failed to send the password reset email");
+    }
+
+    Ok(accepted)
+}
+
+/// Set a new password with the token from the reset link, the token is then spent.
+#[utoipa::path(
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Reset token invalid, expired or already used", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    )
+)]
+#[post("/auth/reset-password")]
+pub async fn reset_password(
+    db: Data<Database>,
+    auth: Data<Authenticator>,
+    request: Json<ResetPasswordRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let ResetPasswordRequest { token, password } = request.into_inner();
+
+    let reset = db
+        .consume_password_reset(&hash_one_time_token(&token))
+        .await?
+        .ok_or_else(|| AppError::Validation("Invalid or expired reset token".to_string()))?;
+
+    let hasher = auth.clone();
+    let password_hash = web::block(move || hasher.hash_password(&password)).await?;
+    db.update_password(&reset.credentials, password_hash)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
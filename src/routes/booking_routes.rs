@@ -1,42 +1,1367 @@
 use crate::{
-    models::booking_model::{Booking, BookingRequest},
-    services::db::Database,
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, AuditActor, EntityKind, EntityRef, snapshot},
+        auth_model::Role,
+        booking_model::{
+            Booking, BookingList, BookingListQuery, BookingRequest, BookingStatus,
+            BookingUpdateRequest, BulkCancelRequest, BulkCancelResult, FullBooking, ReportQuery,
+            WalkReport, parse_rfc3339,
+        },
+        dog_model::Dog,
+        geo_model::GeoLineString,
+        idempotency_model::request_hash,
+        incident_model::{Incident, IncidentRequest},
+        invoice_model::{Invoice, PaymentStatus},
+        owner_model::OwnerWithDogs,
+        payout_model::{Payout, TipRequest},
+        pricing_model::Quote,
+        result_model::{InsertedId, UpdatedCount},
+        review_model::{Review, ReviewRequest},
+        serde_helpers::WithId,
+        series_model::BookingSeries,
+        track_model::TrackRequest,
+        vaccination_model::RabiesPolicy,
+    },
+    routes::{
+        API_V1, PublicUrl, audit,
+        extractors::{
+            AdminRole, AuthenticatedUser, IdempotencyKey, IfMatch, IncludeDeleted, ObjectIdPath,
+            OwnerRole, RequireRole, WalkerRole, parse_object_id,
+        },
+        idempotent,
+        openapi::CancelLink,
+    },
+    services::{
+        db::{not_confirmed, not_enough_points},
+        matching::match_new_booking,
+        notifications::{BookingEvent, Notifier},
+        payments::PaymentProvider,
+        pricing,
+        repository::{AuditRepository, BookingRepository, IdempotencyRepository, OwnerRepository},
+        tokens::{TokenError, TokenSigner},
+    },
 };
 use actix_web::{
-    HttpResponse, get, post, put,
-    web::{Data, Json, Path},
+    HttpResponse, delete, get, post, put,
+    web::{Data, Json, Path, Query},
 };
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use serde_json::json;
+use tracing::error;
+use validator::Validate;
+/// Bookings as seen by the caller: owners get theirs,
+/// walkers the ones assigned to them, admins all of them.
+/// By default the upcoming active ones, soonest first, whole unless `fields` names
+/// the paths to return (e.g. `start_time,owner.name,dogs.name`).
+#[utoipa::path(
+    tag = "bookings",
+    params(
+        BookingListQuery,
+        ("include_deleted" = Option<bool>, Query, description = "Also return soft deleted documents, admins only"),
+    ),
+    responses(
+        (status = 200, description = "Page of the bookings visible to the caller", body = BookingList),
+        (status = 400, description = "Invalid date, owner, status, page, limit or fields", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "include_deleted asked by a non-admin, or another owner asked", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[get("/bookings")]
-pub async fn get_bookings(db: Data<Database>) -> HttpResponse {
-    match db.get_bookings().await {
-        Ok(bookings) => HttpResponse::Ok().json(bookings),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+pub async fn get_bookings(
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    query: Query<BookingListQuery>,
+    include_deleted: IncludeDeleted,
+) -> ApiResponse {
+    let mut filter = match user.role {
+        Role::Owner => doc! {"owner": user.user_id},
+        Role::Walker => doc! {"walker": user.user_id},
+        Role::Admin => doc! {},
+    };
+    if let Some(owner) = query.owner.as_deref() {
+        let owner = parse_object_id(owner)?;
+        if user.role == Role::Owner {
+            user.ensure_owns(&owner)?;
+        }
+        filter.insert("owner", owner);
     }
+    let query = query.resolve(filter).map_err(AppError::Validation)?;
+
+    let bookings = bookings.get_bookings(&query, include_deleted.0).await?;
+    Ok(HttpResponse::Ok().json(bookings))
+}
+#[utoipa::path(
+    tag = "bookings",
+    params(
+        ("id" = String, Path, description = "ObjectId of the booking"),
+        ("include_deleted" = Option<bool>, Query, description = "Also return soft deleted documents, admins only"),
+    ),
+    responses(
+        (status = 200, description = "Booking with its owner and dogs", body = FullBooking),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/booking/{id}")]
+pub async fn get_booking(
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    include_deleted: IncludeDeleted,
+) -> ApiResponse {
+    let booking = bookings
+        .get_full_booking(&path.0, include_deleted.0)
+        .await?;
+    ensure_booking_access(&user, &booking.owner._id, booking.walker.as_ref())?;
+    Ok(HttpResponse::Ok().json(booking))
+}
+#[utoipa::path(
+    tag = "bookings",
+    request_body = BookingUpdateRequest,
+    params(
+        ("id" = String, Path, description = "ObjectId of the booking"),
+        ("If-Match" = Option<String>, Header, description = "Current `version` of the booking, quoted, e.g. `\"3\"`"),
+    ),
+    responses(
+        (status = 200, description = "Updated booking", body = WithId<Booking>),
+        (status = 400, description = "Malformed id or If-Match", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Clashes with another booking", body = ApiErrorBody),
+        (status = 412, description = "The booking was updated in the meantime", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+        (status = 428, description = "No If-Match nor expected_version", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/booking/{id}")]
+pub async fn update_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    notifier: Data<Notifier>,
+    user: RequireRole<OwnerRole>,
+    path: ObjectIdPath,
+    if_match: IfMatch,
+    request: Json<BookingUpdateRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    let expected_version = if_match.expected_version(request.expected_version)?;
+
+    let before = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    let booking = bookings
+        .update_booking(&path.0, &request, expected_version)
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Booking, path.0),
+        snapshot(&before),
+        snapshot(&booking),
+    )
+    .await;
+    if booking.start_time != before.start_time
+        || booking.duration_in_minutes != before.duration_in_minutes
+    {
+        notifier
+            .booking_event(BookingEvent::Rescheduled, &booking)
+            .await;
+    }
+    Ok(HttpResponse::Ok().json(WithId(booking)))
 }
+
+/// Cancel under the cancellation policy: free with `free_cancel_notice_hours` of
+/// notice, `late_cancel_fee_percent` of the price within it, refused once the walk started.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Cancelled booking with the `cancellation_fee_cents` owed", body = WithId<Booking>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking can't be cancelled in its status, or the walk started (`cancellation_closed`)", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[put("/booking/{id}/cancel")]
-pub async fn cancel_booking(db: Data<Database>, path: Path<(String,)>) -> HttpResponse {
-    let id = path.into_inner().0;
+pub async fn cancel_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    notifier: Data<Notifier>,
+    user: RequireRole<OwnerRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let before = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    let booking = bookings.cancel_booking(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Cancel,
+        EntityRef::new(EntityKind::Booking, path.0),
+        snapshot(&before),
+        snapshot(&booking),
+    )
+    .await;
+    notifier
+        .booking_event(BookingEvent::Cancelled, &booking)
+        .await;
+    Ok(HttpResponse::Ok().json(WithId(booking)))
+}
+
+/// Soft delete, hiding the booking from every listing until it is restored.
+/// Bookings are normally cancelled instead.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 204, description = "Booking deleted"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[delete("/booking/{id}")]
+pub async fn delete_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let before = bookings.get_booking(&path.0).await?;
+    bookings.delete_booking(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Delete,
+        EntityRef::new(EntityKind::Booking, path.0),
+        snapshot(&before),
+        None,
+    )
+    .await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Undo `DELETE /booking/{id}`.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Restored booking", body = WithId<Booking>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "No deleted booking with this id", body = ApiErrorBody),
+        (status = 409, description = "Owner deleted, or the slot was taken meanwhile", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/restore")]
+pub async fn restore_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = bookings.restore_booking(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Restore,
+        EntityRef::new(EntityKind::Booking, path.0),
+        None,
+        snapshot(&booking),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(booking)))
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Confirmed booking", body = WithId<Booking>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Invalid status transition", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/confirm")]
+pub async fn confirm_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    payments: Data<dyn PaymentProvider>,
+    notifier: Data<Notifier>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = transition(
+        bookings.get_ref(),
+        audit_log.get_ref(),
+        &user,
+        path,
+        BookingStatus::Confirmed,
+        AuditAction::Confirm,
+    )
+    .await?;
+    let booking = authorize_payment(bookings.get_ref(), payments.get_ref(), booking).await;
+    notifier
+        .booking_event(BookingEvent::Confirmed, &booking)
+        .await;
+    Ok(HttpResponse::Ok().json(WithId(booking)))
+}
+
+/// Authorize the price of a booking that was just confirmed, returning it with its
+/// `payment`. The booking stays confirmed if the provider fails, which is only logged.
+async fn authorize_payment(
+    bookings: &dyn BookingRepository,
+    payments: &dyn PaymentProvider,
+    booking: Booking,
+) -> Booking {
+    let (Some(amount_cents), Some(currency)) = (booking.price_cents, &booking.currency) else {
+        return booking;
+    };
+    if booking.payment.is_some() {
+        return booking;
+    }
 
-    match db.cancel_booking(id.as_str()).await {
-        Ok(result) => HttpResponse::Ok().json(result),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    let authorized = async {
+        let payment = payments
+            .create_payment_intent(&booking, amount_cents, currency)
+            .await
+            .map_err(AppError::Internal)?;
+        bookings.set_booking_payment(&booking._id, &payment).await
+    }
+    .await;
+    match authorized {
+        Ok(booking) => booking,
+        Err(err) => {
+            error!(error = %err, booking_id = %booking._id, "Failed to authorize the payment");
+            booking
+        }
     }
 }
 
-#[post("/booking")]
-pub async fn create_booking(db: Data<Database>, request: Json<BookingRequest>) -> HttpResponse {
-    match db
-        .create_booking(
-            Booking::try_from(BookingRequest {
-                owner: request.owner.clone(),
-                start_time: request.start_time.clone(),
-                duration_in_minutes: request.duration_in_minutes.clone(),
-            })
-            .expect("Error converting BookingRequest to Booking."),
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Started booking", body = WithId<Booking>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Invalid status transition", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/start")]
+pub async fn start_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = transition(
+        bookings.get_ref(),
+        audit_log.get_ref(),
+        &user,
+        path,
+        BookingStatus::InProgress,
+        AuditAction::Start,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(WithId(booking)))
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Completed booking, its invoice is issued", body = WithId<Booking>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Invalid status transition", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/complete")]
+pub async fn complete_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    payments: Data<dyn PaymentProvider>,
+    notifier: Data<Notifier>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = transition(
+        bookings.get_ref(),
+        audit_log.get_ref(),
+        &user,
+        path,
+        BookingStatus::Completed,
+        AuditAction::Complete,
+    )
+    .await?;
+    booking_completed(bookings.get_ref(), payments.get_ref(), &notifier, &booking).await;
+    Ok(HttpResponse::Ok().json(WithId(booking)))
+}
+
+/// What follows a walk completing, by `POST /booking/{id}/complete` or by its first
+/// report: settle it and tell the owner. The dashboards of `GET /ws/bookings` hear
+/// of it from the status change itself.
+async fn booking_completed(
+    bookings: &dyn BookingRepository,
+    payments: &dyn PaymentProvider,
+    notifier: &Notifier,
+    booking: &Booking,
+) {
+    settle_booking(bookings, payments, booking).await;
+    notifier
+        .booking_event(BookingEvent::Completed, booking)
+        .await;
+}
+
+/// Tell the owner the walker left to pick the dogs up, with a push notification
+/// to its devices. The booking is left unchanged.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 202, description = "Owner notified"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not confirmed", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/on-the-way")]
+pub async fn walker_on_the_way(
+    bookings: Data<dyn BookingRepository>,
+    notifier: Data<Notifier>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    if booking.status != BookingStatus::Confirmed {
+        return Err(not_confirmed(booking.status));
+    }
+
+    notifier
+        .booking_event(BookingEvent::WalkerOnTheWay, &booking)
+        .await;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// Credit the owner's loyalty points for a booking that just completed, issue
+/// its invoice, priced by the pricing engine, credit its walker in the payout
+/// ledger and capture its authorized payment; the provider's webhook then marks it paid. The walk is completed either way,
+/// so failures are only logged, a failed capture also marks the invoice failed.
+async fn settle_booking(
+    bookings: &dyn BookingRepository,
+    payments: &dyn PaymentProvider,
+    booking: &Booking,
+) {
+    if let Err(err) = bookings.award_points(&booking._id).await {
+        error!(error = %err, booking_id = %booking._id, "Failed to credit the loyalty points");
+    }
+
+    let invoiced = async {
+        let quote = bookings.quote_booking(booking).await?;
+        let invoice = Invoice::new(booking, quote);
+        if !bookings.issue_invoice(&invoice).await? {
+            return Ok(());
+        }
+        if let Err(err) = bookings.record_payout(&invoice).await {
+            error!(error = %err, booking_id = %booking._id, "Failed to credit the walker");
+        }
+        let Some(payment) = &booking.payment else {
+            return Ok(());
+        };
+        if let Err(err) = payments.capture(payment, invoice.total_cents).await {
+            bookings
+                .update_payment(&invoice._id, PaymentStatus::Failed, None)
+                .await?;
+            return Err(AppError::Internal(format!(
+                "Failed to capture the payment: {}",
+                err
+            )));
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(err) = invoiced {
+        error!(error = %err, booking_id = %booking._id, "Failed to invoice the booking");
+    }
+}
+
+/// Settle a cancelled booking: its authorized payment is captured and invoiced,
+/// then refunded but the cancellation fee (fully, partially or not at all).
+/// Refunded once, the invoice records it.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Invoice of the booking with its refund", body = WithId<Invoice>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not cancelled, without a payment or already refunded", body = ApiErrorBody),
+        (status = 500, description = "The payment provider failed", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/refund")]
+pub async fn refund_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    payments: Data<dyn PaymentProvider>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = bookings.get_booking(&path.0).await?;
+    if booking.status != BookingStatus::Cancelled {
+        return Err(AppError::conflict(format!(
+            "Only cancelled bookings are refunded, this one is {}",
+            booking.status.as_str()
+        )));
+    }
+    let Some(payment) = &booking.payment else {
+        return Err(AppError::conflict("The booking has no payment to refund"));
+    };
+
+    let quote = bookings.quote_booking(&booking).await?;
+    bookings
+        .issue_invoice(&Invoice::new(&booking, quote))
+        .await?;
+    let mut before = bookings.get_booking_invoice(&booking._id).await?;
+    if before.refund.is_some() {
+        return Err(AppError::conflict("The booking was already refunded"));
+    }
+    if before.payment_status != PaymentStatus::Paid {
+        payments
+            .capture(payment, before.total_cents)
+            .await
+            .map_err(|err| AppError::Internal(format!("Failed to capture the payment: {}", err)))?;
+        before = bookings
+            .update_payment(&before._id, PaymentStatus::Paid, None)
+            .await?;
+    }
+
+    let rules = bookings.get_pricing_rules().await?;
+    let refund = pricing::refund(&rules, &booking, before.total_cents);
+    if refund.amount_cents > 0 {
+        payments
+            .refund(payment, refund.amount_cents)
+            .await
+            .map_err(|err| AppError::Internal(format!("Failed to refund the payment: {}", err)))?;
+    }
+    let invoice = bookings.record_refund(&before._id, &refund).await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Refund,
+        EntityRef::new(EntityKind::Invoice, invoice._id),
+        snapshot(&before),
+        snapshot(&invoice),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(invoice)))
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    params(
+        ("id" = String, Path, description = "ObjectId of the booking"),
+        ("walker_id" = String, Path, description = "ObjectId of the walker"),
+    ),
+    responses(
+        (status = 200, description = "Booking with its walker", body = WithId<Booking>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking or walker not found", body = ApiErrorBody),
+        (status = 409, description = "Booking can't get a walker in its status, or the walker is busy or outside its availability (`walker_unavailable`)", body = ApiErrorBody),
+        (status = 422, description = "The walker doesn't take the owner's dogs: too many for its `max_dogs`, or one of a size outside its `accepted_sizes`", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/assign/{walker_id}")]
+pub async fn assign_walker(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: Path<(String, String)>,
+) -> ApiResponse {
+    let (booking_id, walker_id) = path.into_inner();
+    let booking_id = parse_object_id(&booking_id)?;
+    let walker_id = parse_object_id(&walker_id)?;
+
+    let before = bookings.get_booking(&booking_id).await?;
+    let booking = bookings.assign_walker(&booking_id, &walker_id).await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::AssignWalker,
+        EntityRef::new(EntityKind::Booking, booking_id),
+        snapshot(&before),
+        snapshot(&booking),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(booking)))
+}
+
+async fn transition(
+    bookings: &dyn BookingRepository,
+    audit_log: &dyn AuditRepository,
+    user: &AuthenticatedUser,
+    path: ObjectIdPath,
+    next: BookingStatus,
+    action: AuditAction,
+) -> Result<Booking, AppError> {
+    let before = accessible_booking(bookings, user, &path).await?;
+    let booking = bookings
+        .transition_booking(&path.0, next, doc! {}, doc! {})
+        .await?;
+    audit(
+        audit_log,
+        user.actor(),
+        action,
+        EntityRef::new(EntityKind::Booking, path.0),
+        snapshot(&before),
+        snapshot(&booking),
+    )
+    .await;
+    Ok(booking)
+}
+
+/// Load the booking behind `{id}` if the caller may act on it.
+async fn accessible_booking(
+    bookings: &dyn BookingRepository,
+    user: &AuthenticatedUser,
+    path: &ObjectIdPath,
+) -> Result<Booking, AppError> {
+    let booking = bookings.get_booking(&path.0).await?;
+    ensure_booking_access(user, &booking.owner, booking.walker.as_ref())?;
+    Ok(booking)
+}
+
+/// Admins may act on any booking, owners on their own, walkers on the ones assigned to them.
+fn ensure_booking_access(
+    user: &AuthenticatedUser,
+    owner: &ObjectId,
+    walker: Option<&ObjectId>,
+) -> Result<(), AppError> {
+    match user.role {
+        Role::Admin | Role::Owner => user.ensure_owns(owner),
+        Role::Walker if walker == Some(&user.user_id) => Ok(()),
+        Role::Walker => Err(AppError::Forbidden(
+            "This booking is not assigned to you".to_string(),
+        )),
+    }
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    request_body = BulkCancelRequest,
+    responses(
+        (status = 200, description = "Bookings cancelled in the range", body = BulkCancelResult),
+        (status = 400, description = "Invalid range", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/bookings/cancel")]
+pub async fn cancel_bookings_in_range(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    notifier: Data<Notifier>,
+    admin: RequireRole<AdminRole>,
+    request: Json<BulkCancelRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let from = parse_rfc3339(&request.from).map_err(AppError::Validation)?;
+    let to = parse_rfc3339(&request.to).map_err(AppError::Validation)?;
+
+    // An empty or inverted range would either match nothing or be a client mistake.
+    if from >= to {
+        return Err(AppError::Validation(
+            "`from` must be strictly before `to`".to_string(),
+        ));
+    }
+
+    let result = bookings
+        .cancel_bookings_in_range(from, to, request.reason.as_str())
+        .await?;
+    for booking_id in &result.booking_ids {
+        audit(
+            audit_log.get_ref(),
+            admin.actor(),
+            AuditAction::Cancel,
+            EntityRef::new(EntityKind::Booking, *booking_id),
+            None,
+            None,
         )
+        .await;
+        if let Ok(booking) = bookings.get_booking(booking_id).await {
+            notifier
+                .booking_event(BookingEvent::Cancelled, &booking)
+                .await;
+        }
+    }
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Refuse a walk starting at `at` when one of the dogs fails the rabies vaccination `policy`,
+/// the dogs at fault are listed in the error details.
+pub fn ensure_vaccinated(
+    policy: RabiesPolicy,
+    dogs: &[WithId<Dog>],
+    at: DateTime,
+) -> Result<(), AppError> {
+    let unvaccinated: Vec<String> = dogs
+        .iter()
+        .filter(|dog| !policy.allows(&dog.0, at))
+        .map(|dog| dog.0._id.to_hex())
+        .collect();
+    if unvaccinated.is_empty() {
+        return Ok(());
+    }
+
+    Err(AppError::Conflict {
+        code: "vaccination_required",
+        message: "Some dogs have no valid rabies vaccination on the day of the walk".to_string(),
+        details: Some(json!({ "dogs": unvaccinated })),
+    })
+}
+
+/// The walker is picked by the matching: the best free walker near the owner who takes
+/// its dogs, else the booking waits for an admin in `GET /admin/bookings/unassigned`.
+/// With a `recurrence` the booking is the first walk of a new series, see `GET /series/{id}`.
+/// The next walks are booked `recurrence.horizon_weeks` ahead by a background job; one
+/// that clashes with another booking is left out of the series.
+#[utoipa::path(
+    tag = "bookings",
+    request_body = BookingRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Unique key of this request, retries sending it within 24h get the first response back"),
+    ),
+    responses(
+        (status = 200, description = "Booking created, or the replayed response of the first request with this Idempotency-Key", body = InsertedId),
+        (status = 400, description = "Malformed Idempotency-Key, an invalid recurrence, or a recurrence combined with a coupon or points", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 409, description = "Clashes with another booking, a dog's rabies vaccination is missing or expired (`vaccination_required`), the coupon can't be redeemed (`coupon_unavailable`), the points don't cover a free walk (`not_enough_points`), or the Idempotency-Key is in use by another request", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking")]
+pub async fn create_booking(
+    // Grouped to stay within the extractor count clippy allows.
+    (owners, notifier, rabies_policy): (
+        Data<dyn OwnerRepository>,
+        Data<Notifier>,
+        Data<RabiesPolicy>,
+    ),
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    idempotency: Data<dyn IdempotencyRepository>,
+    user: AuthenticatedUser,
+    idempotency_key: IdempotencyKey,
+    request: Json<BookingRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    let hash = request_hash(&*request);
+
+    let coupon_code = request.coupon_code.clone();
+    let redeem_points = request.redeem_points;
+    let recurrence = request.recurrence.clone();
+    if recurrence.is_some() && (coupon_code.is_some() || redeem_points) {
+        return Err(AppError::Validation(
+            "recurrence can't be combined with coupon_code or redeem_points".to_string(),
+        ));
+    }
+    let mut booking = Booking::try_from(request.into_inner())
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+    user.ensure_owns(&booking.owner)?;
+    let series = recurrence
+        .map(|recurrence| BookingSeries::new(&booking, &recurrence))
+        .transpose()
+        .map_err(AppError::Validation)?;
+    booking.series = series.as_ref().map(|series| series._id);
+
+    idempotent(
+        idempotency.get_ref(),
+        &user.user_id,
+        idempotency_key.0,
+        hash,
+        async {
+            let OwnerWithDogs { owner, dogs } =
+                owners.get_owner_full(&booking.owner, false).await?;
+            if !owner.0.email_verified {
+                return Err(AppError::Forbidden(
+                    "The owner must verify its email before booking".to_string(),
+                ));
+            }
+            ensure_vaccinated(**rabies_policy, &dogs, booking.start_time)?;
+
+            // The series goes first, the booking refers to it.
+            if let Some(series) = &series {
+                bookings.create_series(series).await?;
+            }
+            let booking_id = match bookings
+                .create_booking(booking, coupon_code.as_deref(), redeem_points)
+                .await
+            {
+                Ok(booking_id) => booking_id,
+                Err(err) => {
+                    if let Some(series) = &series
+                        && let Err(delete_err) = bookings.delete_series(&series._id).await
+                    {
+                        error!(error = %delete_err, series_id = %series._id, "Failed to remove the series of a walk not booked");
+                    }
+                    return Err(err);
+                }
+            };
+            let after = bookings.get_booking(&booking_id).await.ok();
+            audit(
+                audit_log.get_ref(),
+                user.actor(),
+                AuditAction::Create,
+                EntityRef::new(EntityKind::Booking, booking_id),
+                None,
+                after.as_ref().and_then(snapshot),
+            )
+            .await;
+            let matched = match_new_booking(bookings.get_ref(), &booking_id).await;
+            if let Some(matched) = &matched {
+                audit(
+                    audit_log.get_ref(),
+                    user.actor(),
+                    AuditAction::AssignWalker,
+                    EntityRef::new(EntityKind::Booking, booking_id),
+                    after.as_ref().and_then(snapshot),
+                    snapshot(matched),
+                )
+                .await;
+            }
+            if let Some(booking) = matched.as_ref().or(after.as_ref()) {
+                notifier.booking_event(BookingEvent::Created, booking).await;
+            }
+
+            if let Some(series) = &series {
+                audit(
+                    audit_log.get_ref(),
+                    user.actor(),
+                    AuditAction::Create,
+                    EntityRef::new(EntityKind::Series, series._id),
+                    None,
+                    snapshot(series),
+                )
+                .await;
+            }
+            Ok(InsertedId::from(booking_id))
+        },
+    )
+    .await
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    request_body = BookingRequest,
+    responses(
+        (status = 200, description = "Price the booking would be created at, nothing is stored and the coupon isn't redeemed", body = Quote),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+        (status = 409, description = "The coupon can't be redeemed (`coupon_unavailable`) or the points don't cover a free walk (`not_enough_points`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/quote")]
+pub async fn quote_booking(
+    owners: Data<dyn OwnerRepository>,
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    request: Json<BookingRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let coupon_code = request.coupon_code.clone();
+    let redeem_points = request.redeem_points;
+    let mut booking = Booking::try_from(request.into_inner())
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+    user.ensure_owns(&booking.owner)?;
+    let points = owners.get_owner_points(&booking.owner).await?;
+    if redeem_points {
+        if points.points_balance < points.points_per_free_walk {
+            return Err(not_enough_points(
+                points.points_balance,
+                points.points_per_free_walk,
+            ));
+        }
+        booking.points_redeemed = points.points_per_free_walk;
+    }
+    if let Some(code) = coupon_code {
+        booking.coupon = Some(bookings.get_usable_coupon(&code).await?.applied());
+    }
+
+    let quote = bookings.quote_booking(&booking).await?;
+    Ok(HttpResponse::Ok().json(quote))
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    request_body = WalkReport,
+    params(
+        ("id" = String, Path, description = "ObjectId of the booking"),
+        ReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Report saved, the booking is completed and invoiced", body = UpdatedCount),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not walked yet or already reported", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/report")]
+pub async fn submit_walk_report(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    // Paired to stay within the extractor count clippy allows.
+    (payments, notifier): (Data<dyn PaymentProvider>, Data<Notifier>),
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+    query: Query<ReportQuery>,
+    request: Json<WalkReport>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    let id = path.0;
+
+    if !matches!(
+        booking.status,
+        BookingStatus::InProgress | BookingStatus::Completed
+    ) {
+        return Err(AppError::conflict(format!(
+            "A {} booking can't receive a report",
+            booking.status.as_str()
+        )));
+    }
+    if booking.start_time > DateTime::now() {
+        return Err(AppError::conflict("Walk has not started yet"));
+    }
+    if booking.report.is_some() && !query.overwrite {
+        return Err(AppError::conflict(
+            "A report already exists, use ?overwrite=true",
+        ));
+    }
+
+    let result = bookings
+        .save_walk_report(&id, request.into_inner(), query.overwrite)
+        .await?;
+    let after = bookings.get_booking(&id).await.ok();
+    if booking.status != BookingStatus::Completed
+        && let Some(after) = &after
+    {
+        booking_completed(bookings.get_ref(), payments.get_ref(), &notifier, after).await;
+    }
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Report,
+        EntityRef::new(EntityKind::Booking, id),
+        snapshot(&booking),
+        after.as_ref().and_then(snapshot),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// The owner rates the walker of a completed booking, once.
+#[utoipa::path(
+    tag = "bookings",
+    request_body = ReviewRequest,
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Review saved", body = WithId<Review>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not completed, without a walker, or already reviewed (`review_exists`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/review")]
+pub async fn review_booking(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: RequireRole<OwnerRole>,
+    path: ObjectIdPath,
+    request: Json<ReviewRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    if booking.status != BookingStatus::Completed {
+        return Err(AppError::conflict(format!(
+            "A {} booking can't be reviewed",
+            booking.status.as_str()
+        )));
+    }
+    let Some(walker) = booking.walker else {
+        return Err(AppError::conflict("No walker was assigned to this booking"));
+    };
+
+    let review = request.into_inner().into_review(&booking, walker);
+    bookings.create_review(&review).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Review, review._id),
+        None,
+        snapshot(&review),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(review)))
+}
+
+/// The owner tips the walker of a completed booking, once and within
+/// `tip_window_days` of its invoice. Charged on the card of the booking's
+/// payment, then credited whole to the walker's payout ledger.
+#[utoipa::path(
+    tag = "bookings",
+    request_body = TipRequest,
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Tip charged, the walker's ledger entry", body = WithId<Payout>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking or its invoice not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not completed, without a walker or a payment, too old, or already tipped (`tip_exists`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+        (status = 500, description = "The payment provider failed", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/tip")]
+pub async fn tip_walker(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    payments: Data<dyn PaymentProvider>,
+    user: RequireRole<OwnerRole>,
+    path: ObjectIdPath,
+    request: Json<TipRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    if booking.status != BookingStatus::Completed {
+        return Err(AppError::conflict(format!(
+            "A {} booking can't be tipped",
+            booking.status.as_str()
+        )));
+    }
+    if booking.walker.is_none() {
+        return Err(AppError::conflict("No walker was assigned to this booking"));
+    }
+    let Some(payment) = &booking.payment else {
+        return Err(AppError::conflict(
+            "The booking has no payment to charge the tip on",
+        ));
+    };
+    let invoice = bookings.get_booking_invoice(&booking._id).await?;
+    let window_days = bookings.get_pricing_rules().await?.tip_window_days;
+    let deadline = invoice.issued_at.timestamp_millis() + window_days * 24 * 60 * 60 * 1000;
+    if DateTime::now().timestamp_millis() >= deadline {
+        return Err(AppError::conflict(format!(
+            "Walks can only be tipped within {} days",
+            window_days
+        )));
+    }
+
+    let reference = payments
+        .charge_tip(&booking, payment, request.amount_cents, &invoice.currency)
+        .await
+        .map_err(|err| AppError::Internal(format!("Failed to charge the tip: {}", err)))?;
+    let Some(tip) = Payout::tip(&invoice, request.amount_cents, reference) else {
+        return Err(AppError::conflict("No walker was assigned to this booking"));
+    };
+    bookings.record_tip(&tip).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Payout, tip._id),
+        None,
+        snapshot(&tip),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(tip)))
+}
+
+/// The walker files an incident that happened during the walk, triaged by the admins.
+#[utoipa::path(
+    tag = "incidents",
+    request_body = IncidentRequest,
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Incident filed, open", body = WithId<Incident>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not walked yet", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/incident")]
+pub async fn report_incident(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+    request: Json<IncidentRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    if !matches!(
+        booking.status,
+        BookingStatus::InProgress | BookingStatus::Completed
+    ) {
+        return Err(AppError::conflict(format!(
+            "A {} booking can't have incidents",
+            booking.status.as_str()
+        )));
+    }
+
+    let incident = request.into_inner().into_incident(&booking, user.user_id);
+    bookings.create_incident(&incident).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Incident, incident._id),
+        None,
+        snapshot(&incident),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(incident)))
+}
+
+/// Report of a walked booking, for its owner and walker.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Walk report", body = WalkReport),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found or not reported yet", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/booking/{id}/report")]
+pub async fn get_walk_report(
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    let report = booking
+        .report
+        .ok_or_else(|| AppError::NotFound("The walk has no report yet".to_string()))?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Positions of the assigned walker, only accepted while the walk is in progress.
+/// They are streamed to the owner on `GET /owner/{id}/events`.
+#[utoipa::path(
+    tag = "bookings",
+    request_body = TrackRequest,
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 204, description = "Pings stored"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+        (status = 409, description = "Booking not in progress", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/track")]
+pub async fn add_track_pings(
+    bookings: Data<dyn BookingRepository>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+    request: Json<TrackRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let booking = accessible_booking(bookings.get_ref(), &user, &path).await?;
+    if booking.status != BookingStatus::InProgress {
+        return Err(AppError::conflict(format!(
+            "A {} booking can't be tracked",
+            booking.status.as_str()
+        )));
+    }
+
+    let pings = request.into_inner().into_pings(path.0);
+    bookings.add_track_pings(pings.clone()).await?;
+    bookings
+        .owner_events()
+        .walker_locations(booking.owner, &pings);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Route walked so far, as a GeoJSON LineString in ping order.
+/// It holds fewer than two positions until the walker has sent them.
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Route of the walk", body = GeoLineString),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/booking/{id}/track")]
+pub async fn get_track(
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    accessible_booking(bookings.get_ref(), &user, &path).await?;
+
+    let track = bookings.get_track(&path.0).await?;
+    let route: GeoLineString = track.into_iter().map(|ping| ping.location).collect();
+    Ok(HttpResponse::Ok().json(route))
+}
+
+#[utoipa::path(
+    tag = "bookings",
+    params(("id" = String, Path, description = "ObjectId of the booking")),
+    responses(
+        (status = 200, description = "Signed cancel link for the owner", body = CancelLink),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Booking not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/booking/{id}/cancel-link")]
+pub async fn create_cancel_link(
+    bookings: Data<dyn BookingRepository>,
+    signer: Data<TokenSigner>,
+    public_url: Data<PublicUrl>,
+    _admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let booking = bookings.get_booking(&path.0).await?;
+
+    let (token, claims) = signer.issue(booking._id, booking.owner);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "url": public_url.to(&format!("{}/cancel/{}", API_V1, token)),
+        "expires_at": claims.exp,
+    })))
+}
+
+/// Public endpoint behind the emailed cancel link.
+/// Tampered and expired tokens are rejected before any database access,
+/// and a valid token for a booking that no longer exists gets the same 410 as an expired one.
+#[utoipa::path(
+    tag = "bookings",
+    params(("token" = String, Path, description = "Token of the emailed cancel link")),
+    responses(
+        (status = 200, description = "Cancelled booking with the `cancellation_fee_cents` owed", body = WithId<Booking>),
+        (status = 401, description = "Tampered link", body = ApiErrorBody),
+        (status = 409, description = "The walk started (`cancellation_closed`)", body = ApiErrorBody),
+        (status = 410, description = "Link expired", body = ApiErrorBody),
+    )
+)]
+#[get("/cancel/{token}")]
+pub async fn cancel_with_token(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    notifier: Data<Notifier>,
+    signer: Data<TokenSigner>,
+    path: Path<(String,)>,
+) -> ApiResponse {
+    let expired = || AppError::Gone("This link has expired".to_string());
+
+    let claims = signer
+        .verify(&path.into_inner().0)
+        .map_err(|err| match err {
+            TokenError::Invalid => AppError::Unauthorized("Invalid link".to_string()),
+            TokenError::Expired => expired(),
+        })?;
+
+    match bookings
+        .cancel_booking_for_owner(&claims.booking_id, &claims.owner_id)
         .await
     {
-        Ok(booking) => HttpResponse::Ok().json(booking),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Ok(booking) => {
+            // Whoever holds the link acts as the owner it was issued for.
+            let actor = AuditActor {
+                user_id: Some(claims.owner_id),
+                role: Some(Role::Owner),
+            };
+            audit(
+                audit_log.get_ref(),
+                actor,
+                AuditAction::Cancel,
+                EntityRef::new(EntityKind::Booking, booking._id),
+                None,
+                snapshot(&booking),
+            )
+            .await;
+            notifier
+                .booking_event(BookingEvent::Cancelled, &booking)
+                .await;
+            Ok(HttpResponse::Ok().json(WithId(booking)))
+        }
+        Err(AppError::NotFound(_)) => Err(expired()),
+        Err(err) => Err(err),
     }
 }
@@ -0,0 +1,25 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::breed_model::{BreedList, BreedQuery},
+    services::repository::DogRepository,
+};
+use actix_web::{
+    HttpResponse, get,
+    web::{Data, Query},
+};
+
+/// Breed names for the autocomplete of the dog forms, `Other` included.
+#[utoipa::path(
+    tag = "dogs",
+    params(BreedQuery),
+    responses(
+        (status = 200, description = "Matching breeds, alphabetically", body = BreedList),
+        (status = 400, description = "Too long q, invalid limit", body = ApiErrorBody),
+    )
+)]
+#[get("/breeds")]
+pub async fn get_breeds(dogs: Data<dyn DogRepository>, query: Query<BreedQuery>) -> ApiResponse {
+    let (prefix, limit) = query.resolve().map_err(AppError::Validation)?;
+    let breeds = dogs.search_breeds(prefix.as_deref(), limit).await?;
+    Ok(HttpResponse::Ok().json(BreedList { breeds }))
+}
@@ -1,28 +1,557 @@
 use crate::{
-    models::dog_model::{Dog, DogRequest},
-    services::db::Database,
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        breed_model::OTHER_BREED,
+        dog_model::{Dog, DogProfileRequest, DogRequest, DogUpdateRequest},
+        photo_model::{MAX_PHOTO_BYTES, image_content_type},
+        result_model::InsertedId,
+        serde_helpers::WithId,
+        vaccination_model::{Vaccination, VaccinationRequest},
+    },
+    routes::{
+        audit,
+        extractors::{AdminRole, AuthenticatedUser, ObjectIdPath, RequireRole, parse_object_id},
+    },
+    services::{
+        db::Database,
+        repository::{AuditRepository, DogRepository},
+    },
 };
+use actix_multipart::Multipart;
 use actix_web::{
-    HttpResponse, post,
-    web::{Data, Json},
+    HttpRequest, HttpResponse, delete,
+    error::ErrorInternalServerError,
+    get,
+    http::header::{self, CacheControl, CacheDirective, EntityTag, HttpDate},
+    patch, post, put,
+    web::{Bytes, Data, Json, Path},
 };
+use futures_util::{Stream, TryStreamExt, io::AsyncReadExt, stream};
+use mongodb::{bson::oid::ObjectId, gridfs::GridFsDownloadStream};
+use validator::{Validate, ValidationError, ValidationErrors};
 
+/// Catalog spelling of `breed`, refused on `field` when it is not in the catalog.
+/// Breeds missing from it are recorded as `Other`.
+pub async fn catalog_breed(
+    dogs: &dyn DogRepository,
+    field: &'static str,
+    breed: Option<String>,
+) -> Result<Option<String>, AppError> {
+    let Some(breed) = breed else {
+        return Ok(None);
+    };
+    match dogs.find_breed(&breed).await? {
+        Some(name) => Ok(Some(name)),
+        None => {
+            let mut errors = ValidationErrors::new();
+            errors.add(
+                field,
+                ValidationError::new("breed").with_message(
+                    format!(
+                        "unknown breed `{}`, pick one from GET /breeds or `{}`",
+                        breed, OTHER_BREED
+                    )
+                    .into(),
+                ),
+            );
+            Err(AppError::InvalidFields(errors))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    request_body = DogRequest,
+    responses(
+        (status = 200, description = "Dog created", body = InsertedId),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[post("/dog")]
-pub async fn create_dog(db: Data<Database>, request: Json<DogRequest>) -> HttpResponse {
-    match db
-        .create_dog(
-            Dog::try_from(DogRequest {
-                owner: request.owner.clone(),
-                name: request.name.clone(),
-                age: request.age.clone(),
-                breed: request.breed.clone(),
+pub async fn create_dog(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    request: Json<DogRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    let mut request = request.into_inner();
+    request.breed = catalog_breed(dogs.get_ref(), "breed", request.breed).await?;
+
+    let dog = Dog::try_from(request).map_err(|err| AppError::Validation(err.to_string()))?;
+    user.ensure_owns(&dog.owner)?;
+    let after = snapshot(&dog);
+
+    let dog_id = dogs.create_dog(dog).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Dog, dog_id),
+        None,
+        after,
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(InsertedId::from(dog_id)))
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    request_body = DogUpdateRequest,
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "Updated dog", body = WithId<Dog>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/dog/{id}")]
+pub async fn update_dog(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    request: Json<DogUpdateRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    let mut request = request.into_inner();
+    request.breed = catalog_breed(dogs.get_ref(), "breed", request.breed).await?;
+
+    let before = dogs.get_dog(&path.0).await?;
+    user.ensure_owns(&before.owner)?;
+
+    let dog = dogs.update_dog(&path.0, &request).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Dog, path.0),
+        snapshot(&before),
+        snapshot(&dog),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(dog)))
+}
+
+/// Weight, allergies, medications and temperament flags, shown to the walker
+/// with the dogs of each booking.
+#[utoipa::path(
+    tag = "dogs",
+    request_body = DogProfileRequest,
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "Dog with its updated profile", body = WithId<Dog>),
+        (status = 400, description = "Malformed id, or no field provided", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[patch("/dog/{id}/profile")]
+pub async fn update_dog_profile(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    request: Json<DogProfileRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    if request.is_empty() {
+        return Err(AppError::Validation(
+            "At least one field must be provided".to_string(),
+        ));
+    }
+
+    let before = dogs.get_dog(&path.0).await?;
+    user.ensure_owns(&before.owner)?;
+
+    let profile = request.apply_to(before.profile.clone());
+    let dog = dogs.set_dog_profile(&path.0, &profile).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Dog, path.0),
+        snapshot(&before),
+        snapshot(&dog),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(dog)))
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 204, description = "Dog deleted"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[delete("/dog/{id}")]
+pub async fn delete_dog(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let before = dogs.get_dog(&path.0).await?;
+    user.ensure_owns(&before.owner)?;
+
+    dogs.delete_dog(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Delete,
+        EntityRef::new(EntityKind::Dog, path.0),
+        snapshot(&before),
+        None,
+    )
+    .await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Undo `DELETE /dog/{id}`, the owner must not be deleted itself.
+#[utoipa::path(
+    tag = "dogs",
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "Restored dog", body = WithId<Dog>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "No deleted dog with this id", body = ApiErrorBody),
+        (status = 409, description = "The owner is deleted", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/dog/{id}/restore")]
+pub async fn restore_dog(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let dog = dogs.restore_dog(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Restore,
+        EntityRef::new(EntityKind::Dog, path.0),
+        None,
+        snapshot(&dog),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(dog)))
+}
+
+/// Replace the photo of a dog. The image goes to GridFS, the dog keeps its `photo_id`.
+#[utoipa::path(
+    tag = "dogs",
+    request_body(
+        content_type = "multipart/form-data",
+        description = "`photo` field holding a JPEG, PNG or WebP image of at most 5 MiB"
+    ),
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "Dog with its new photo_id", body = WithId<Dog>),
+        (status = 400, description = "Malformed id or multipart body, no `photo` field", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog not found", body = ApiErrorBody),
+        (status = 413, description = "Photo over 5 MiB", body = ApiErrorBody),
+        (status = 415, description = "Not a JPEG, PNG or WebP image", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/dog/{id}/photo")]
+pub async fn upload_dog_photo(
+    db: Data<Database>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    mut payload: Multipart,
+) -> ApiResponse {
+    let dog = db.get_dog(&path.0).await?;
+    user.ensure_owns(&dog.owner)?;
+
+    let bytes = read_photo(&mut payload).await?;
+    let content_type = image_content_type(&bytes).ok_or_else(|| {
+        AppError::UnsupportedMediaType("The photo must be a JPEG, PNG or WebP image".to_string())
+    })?;
+
+    let (before, dog) = db.save_dog_photo(&path.0, content_type, &bytes).await?;
+    audit(
+        db.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Dog, path.0),
+        snapshot(&before),
+        snapshot(&dog),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(dog)))
+}
+
+/// Content of the `photo` field, refused as soon as it grows over `MAX_PHOTO_BYTES`.
+async fn read_photo(payload: &mut Multipart) -> Result<Vec<u8>, AppError> {
+    let malformed = |err: actix_multipart::MultipartError| AppError::Validation(err.to_string());
+    while let Some(mut field) = payload.try_next().await.map_err(malformed)? {
+        if field.name() != Some("photo") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(malformed)? {
+            if bytes.len() + chunk.len() > MAX_PHOTO_BYTES {
+                return Err(AppError::PayloadTooLarge(format!(
+                    "The photo must be at most {} MiB",
+                    MAX_PHOTO_BYTES / (1024 * 1024)
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok(bytes);
+    }
+    Err(AppError::Validation(
+        "The multipart body has no `photo` field".to_string(),
+    ))
+}
+
+/// Photo of a dog, streamed from GridFS. A new upload gets a new id, which is the
+/// `ETag`: clients revalidate with `If-None-Match` and get a 304 while it is unchanged.
+#[utoipa::path(
+    tag = "dogs",
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "The image", content_type = "image/*"),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 404, description = "Dog not found or without photo", body = ApiErrorBody),
+    )
+)]
+#[get("/dog/{id}/photo")]
+pub async fn get_dog_photo(
+    db: Data<Database>,
+    request: HttpRequest,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let dog = db.get_dog(&path.0).await?;
+    let photo_id = dog
+        .photo_id
+        .ok_or_else(|| AppError::NotFound("The dog has no photo".to_string()))?;
+    let photo = db.get_dog_photo(&photo_id).await?;
+
+    let etag = EntityTag::new_strong(photo.id.to_hex());
+    let cache_control = CacheControl(vec![CacheDirective::Public, CacheDirective::NoCache]);
+    let unchanged = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.parse::<EntityTag>().is_ok_and(|tag| tag.weak_eq(&etag))
             })
-            .expect("Error converting DogRequest to Dog."),
-        )
-        .await
-    {
-        Ok(dog) => HttpResponse::Ok().json(dog),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        });
+    if unchanged {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .insert_header(cache_control)
+            .finish());
     }
-    
+
+    let download = db.open_dog_photo(&photo_id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(photo.content_type)
+        .insert_header(header::ETag(etag))
+        .insert_header(header::LastModified(HttpDate::from(
+            photo.uploaded_at.to_system_time(),
+        )))
+        .insert_header(cache_control)
+        .no_chunking(photo.length)
+        .streaming(chunks(download)))
+}
+
+/// The GridFS file as a body stream of 64 KiB chunks.
+fn chunks(download: GridFsDownloadStream) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    stream::unfold(Some(download), |download| async move {
+        let mut download = download?;
+        let mut buffer = vec![0; 64 * 1024];
+        match download.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(Bytes::from(buffer)), Some(download)))
+            }
+            Err(err) => Some((Err(ErrorInternalServerError(err)), None)),
+        }
+    })
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "Vaccinations of the dog", body = Vec<Vaccination>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/dog/{id}/vaccinations")]
+pub async fn get_vaccinations(
+    dogs: Data<dyn DogRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let dog = dogs.get_dog(&path.0).await?;
+    user.ensure_owns(&dog.owner)?;
+    Ok(HttpResponse::Ok().json(dog.vaccinations))
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    request_body = VaccinationRequest,
+    params(("id" = String, Path, description = "ObjectId of the dog")),
+    responses(
+        (status = 200, description = "Vaccination added", body = Vaccination),
+        (status = 400, description = "Malformed id, expiry before administration", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/dog/{id}/vaccinations")]
+pub async fn add_vaccination(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    request: Json<VaccinationRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    let vaccination = request
+        .into_inner()
+        .into_vaccination(ObjectId::new())
+        .map_err(AppError::Validation)?;
+
+    let before = dogs.get_dog(&path.0).await?;
+    user.ensure_owns(&before.owner)?;
+
+    let dog = dogs.add_vaccination(&path.0, vaccination.clone()).await?;
+    audit_vaccinations(audit_log.get_ref(), &user, &before, &dog).await;
+    Ok(HttpResponse::Ok().json(vaccination))
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    request_body = VaccinationRequest,
+    params(
+        ("id" = String, Path, description = "ObjectId of the dog"),
+        ("vaccination_id" = String, Path, description = "ObjectId of the vaccination"),
+    ),
+    responses(
+        (status = 200, description = "Vaccination replaced", body = Vaccination),
+        (status = 400, description = "Malformed id, expiry before administration", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog or vaccination not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/dog/{id}/vaccinations/{vaccination_id}")]
+pub async fn replace_vaccination(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: Path<(String, String)>,
+    request: Json<VaccinationRequest>,
+) -> ApiResponse {
+    let (dog_id, vaccination_id) = path.into_inner();
+    let dog_id = parse_object_id(&dog_id)?;
+    let vaccination_id = parse_object_id(&vaccination_id)?;
+
+    request.validate()?;
+    let vaccination = request
+        .into_inner()
+        .into_vaccination(vaccination_id)
+        .map_err(AppError::Validation)?;
+
+    let before = dogs.get_dog(&dog_id).await?;
+    user.ensure_owns(&before.owner)?;
+
+    let dog = dogs
+        .replace_vaccination(&dog_id, vaccination.clone())
+        .await?;
+    audit_vaccinations(audit_log.get_ref(), &user, &before, &dog).await;
+    Ok(HttpResponse::Ok().json(vaccination))
+}
+
+#[utoipa::path(
+    tag = "dogs",
+    params(
+        ("id" = String, Path, description = "ObjectId of the dog"),
+        ("vaccination_id" = String, Path, description = "ObjectId of the vaccination"),
+    ),
+    responses(
+        (status = 204, description = "Vaccination deleted"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Dog or vaccination not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[delete("/dog/{id}/vaccinations/{vaccination_id}")]
+pub async fn delete_vaccination(
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: Path<(String, String)>,
+) -> ApiResponse {
+    let (dog_id, vaccination_id) = path.into_inner();
+    let dog_id = parse_object_id(&dog_id)?;
+    let vaccination_id = parse_object_id(&vaccination_id)?;
+
+    let before = dogs.get_dog(&dog_id).await?;
+    user.ensure_owns(&before.owner)?;
+
+    let dog = dogs.delete_vaccination(&dog_id, &vaccination_id).await?;
+    audit_vaccinations(audit_log.get_ref(), &user, &before, &dog).await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Vaccination changes are dog updates in the audit log.
+async fn audit_vaccinations(
+    audit_log: &dyn AuditRepository,
+    user: &AuthenticatedUser,
+    before: &Dog,
+    after: &Dog,
+) {
+    audit(
+        audit_log,
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Dog, after._id),
+        snapshot(before),
+        snapshot(after),
+    )
+    .await;
 }
@@ -0,0 +1,365 @@
+use std::{
+    future::{Ready, ready},
+    marker::PhantomData,
+    ops::Deref,
+};
+
+use actix_web::{
+    FromRequest, HttpMessage, HttpRequest,
+    dev::Payload,
+    http::header,
+    web::{Data, Query},
+};
+use mongodb::bson::oid::ObjectId;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    errors::AppError,
+    models::{audit_model::AuditActor, auth_model::Role},
+    services::{auth::Authenticator, tokens::TokenError},
+};
+
+/// Header carrying the admin key on admin-only requests.
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// `auth.admin_api_key`, registered as app data for `AdminKey`.
+pub struct AdminApiKey(pub Option<String>);
+
+/// Extractor guarding `POST /admin/accounts`, which creates the first admin login;
+/// every other admin endpoint takes `RequireRole<AdminRole>`.
+/// The request must send an `X-Admin-Key` header equal to `AdminApiKey`; when no
+/// key is configured every request is refused.
+pub struct AdminKey;
+
+impl FromRequest for AdminKey {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let expected = req
+            .app_data::<Data<AdminApiKey>>()
+            .and_then(|key| key.0.clone());
+        let provided = req
+            .headers()
+            .get(ADMIN_KEY_HEADER)
+            .and_then(|v| v.to_str().ok());
+
+        // Compare digests in constant time so the answer time leaks neither the key
+        // nor its length.
+        let matches = |expected: &str, provided: &str| -> bool {
+            Sha256::digest(expected)
+                .ct_eq(&Sha256::digest(provided))
+                .into()
+        };
+        match (expected, provided) {
+            (Some(expected), Some(provided)) if matches(&expected, provided) => ready(Ok(AdminKey)),
+            _ => ready(Err(AppError::Unauthorized(
+                "Missing or invalid admin key".to_string(),
+            ))),
+        }
+    }
+}
+
+/// Header carrying the key of a backend integration, see `POST /admin/api-keys`.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Backend integration authenticated by `middleware::api_key_auth`,
+/// stored in the request extensions once its key and scopes are checked.
+#[derive(Debug, Clone)]
+pub struct ApiKeyIdentity {
+    pub id: ObjectId,
+}
+
+/// User authenticated by an `Authorization: Bearer <jwt>` header
+/// issued by `POST /auth/login`.
+///
+/// A request authenticated by an API key instead gets the admin role, under the
+/// key's id: its scopes were already enforced per route by `api_key_auth`.
+pub struct AuthenticatedUser {
+    /// Owner or walker `_id` depending on `role`.
+    pub user_id: ObjectId,
+    pub role: Role,
+}
+
+impl AuthenticatedUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// This user as the author of an audit log entry.
+    pub fn actor(&self) -> AuditActor {
+        AuditActor {
+            user_id: Some(self.user_id),
+            role: Some(self.role),
+        }
+    }
+
+    /// Refuse access to a resource belonging to another owner, admins may touch anything.
+    pub fn ensure_owns(&self, owner_id: &ObjectId) -> Result<(), AppError> {
+        if self.is_admin() || (self.role == Role::Owner && self.user_id == *owner_id) {
+            return Ok(());
+        }
+        Err(AppError::Forbidden(
+            "This resource belongs to another owner".to_string(),
+        ))
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let Some(token) = token else {
+            if let Some(identity) = req.extensions().get::<ApiKeyIdentity>() {
+                return ready(Ok(AuthenticatedUser {
+                    user_id: identity.id,
+                    role: Role::Admin,
+                }));
+            }
+            return ready(Err(AppError::Unauthorized(
+                "Missing bearer token".to_string(),
+            )));
+        };
+
+        let authenticator = req
+            .app_data::<Data<Authenticator>>()
+            .expect("Authenticator is registered as app data");
+
+        ready(
+            authenticator
+                .verify(token)
+                .map(|claims| AuthenticatedUser {
+                    user_id: claims.sub,
+                    role: claims.role,
+                })
+                .map_err(|err| {
+                    AppError::Unauthorized(match err {
+                        TokenError::Invalid => "Invalid token".to_string(),
+                        TokenError::Expired => "Token has expired".to_string(),
+                    })
+                }),
+        )
+    }
+}
+
+/// Author of a change for the audit log: the authenticated caller,
+/// or an anonymous actor when the request carries no valid credentials.
+/// Never refuses the request, the route keeps its own guards.
+pub struct Actor(pub AuditActor);
+
+impl FromRequest for Actor {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let actor = AuthenticatedUser::from_request(req, payload)
+            .into_inner()
+            .map(|user| user.actor())
+            .unwrap_or_else(|_| AuditActor::anonymous());
+        ready(Ok(Actor(actor)))
+    }
+}
+
+/// Roles accepted by a `RequireRole` guard.
+pub trait AllowedRoles {
+    const ROLES: &'static [Role];
+}
+
+/// Admins only.
+pub struct AdminRole;
+
+/// Walkers, and admins acting on their behalf.
+pub struct WalkerRole;
+
+/// Owners, and admins acting on their behalf.
+pub struct OwnerRole;
+
+impl AllowedRoles for AdminRole {
+    const ROLES: &'static [Role] = &[Role::Admin];
+}
+
+impl AllowedRoles for WalkerRole {
+    const ROLES: &'static [Role] = &[Role::Walker, Role::Admin];
+}
+
+impl AllowedRoles for OwnerRole {
+    const ROLES: &'static [Role] = &[Role::Owner, Role::Admin];
+}
+
+/// Route level guard: an `AuthenticatedUser` whose role is one of `R::ROLES`,
+/// any other role gets a 403 before the handler runs.
+pub struct RequireRole<R: AllowedRoles> {
+    user: AuthenticatedUser,
+    _roles: PhantomData<R>,
+}
+
+impl<R: AllowedRoles> Deref for RequireRole<R> {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &AuthenticatedUser {
+        &self.user
+    }
+}
+
+impl<R: AllowedRoles> FromRequest for RequireRole<R> {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = match AuthenticatedUser::from_request(req, payload).into_inner() {
+            Ok(user) => user,
+            Err(err) => return ready(Err(err)),
+        };
+
+        if !R::ROLES.contains(&user.role) {
+            return ready(Err(AppError::Forbidden(format!(
+                "This action is not allowed for the {} role",
+                user.role.as_str()
+            ))));
+        }
+
+        ready(Ok(RequireRole {
+            user,
+            _roles: PhantomData,
+        }))
+    }
+}
+
+/// Extractor for the `{id}` segment of a route, parsed as an ObjectId.
+/// A malformed id is answered with a 400 `AppError::Validation` before the handler runs.
+pub struct ObjectIdPath(pub ObjectId);
+
+impl FromRequest for ObjectIdPath {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let raw = req.match_info().get("id").unwrap_or_default();
+
+        ready(parse_object_id(raw).map(ObjectIdPath))
+    }
+}
+
+/// Parse a path segment as an ObjectId, for routes with more than one id.
+pub fn parse_object_id(raw: &str) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(raw)
+        .map_err(|err| AppError::Validation(format!("`{}` is not a valid id: {}", raw, err)))
+}
+
+/// Extractor for an `If-Match: "<version>"` header, `None` when it isn't sent.
+/// A weak `W/` prefix is accepted, anything else than a quoted version is a 400.
+pub struct IfMatch(pub Option<i64>);
+
+impl IfMatch {
+    /// Version the update applies to: the header, or else `expected_version` from the body.
+    /// Sending neither is a 428, an update must say which version it was based on.
+    pub fn expected_version(&self, from_body: Option<i64>) -> Result<i64, AppError> {
+        self.0.or(from_body).ok_or_else(|| {
+            AppError::PreconditionRequired(
+                "Send the current version in an If-Match header or as expected_version".to_string(),
+            )
+        })
+    }
+}
+
+impl FromRequest for IfMatch {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(value) = req.headers().get(header::IF_MATCH) else {
+            return ready(Ok(IfMatch(None)));
+        };
+
+        let version = value
+            .to_str()
+            .ok()
+            .map(|v| v.trim())
+            .map(|v| v.strip_prefix("W/").unwrap_or(v))
+            .and_then(|v| v.strip_prefix('"')?.strip_suffix('"'))
+            .and_then(|v| v.parse::<i64>().ok());
+
+        ready(match version {
+            Some(version) => Ok(IfMatch(Some(version))),
+            None => Err(AppError::Validation(
+                "If-Match must be the quoted version of the resource, e.g. \"3\"".to_string(),
+            )),
+        })
+    }
+}
+
+/// Header naming a retried request, see `routes::idempotent`.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Longest `Idempotency-Key` accepted, a UUID is the usual choice.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Extractor for the `Idempotency-Key` header, `None` when it isn't sent.
+/// The key must be 1 to 255 visible ASCII characters, anything else is a 400.
+pub struct IdempotencyKey(pub Option<String>);
+
+impl FromRequest for IdempotencyKey {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+            return ready(Ok(IdempotencyKey(None)));
+        };
+
+        let key = value
+            .to_str()
+            .ok()
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty() && v.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+            .filter(|v| v.bytes().all(|b| b.is_ascii_graphic()));
+
+        ready(match key {
+            Some(key) => Ok(IdempotencyKey(Some(key.to_string()))),
+            None => Err(AppError::Validation(format!(
+                "{} must be 1 to {} visible ASCII characters",
+                IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN
+            ))),
+        })
+    }
+}
+
+/// Extractor for `?include_deleted=true`, asking reads to also return soft deleted
+/// documents. Only admins may ask: anyone else gets the 401 or 403 of
+/// `RequireRole<AdminRole>`, without the parameter nobody is checked.
+pub struct IncludeDeleted(pub bool);
+
+#[derive(Deserialize)]
+struct IncludeDeletedQuery {
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+impl FromRequest for IncludeDeleted {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let query = match Query::<IncludeDeletedQuery>::from_query(req.query_string()) {
+            Ok(query) => query,
+            Err(err) => return ready(Err(AppError::Validation(err.to_string()))),
+        };
+        if !query.include_deleted {
+            return ready(Ok(IncludeDeleted(false)));
+        }
+
+        ready(
+            RequireRole::<AdminRole>::from_request(req, payload)
+                .into_inner()
+                .map(|_| IncludeDeleted(true)),
+        )
+    }
+}
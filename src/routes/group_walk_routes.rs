@@ -0,0 +1,239 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        auth_model::Role,
+        group_walk_model::{
+            FullGroupWalk, GroupParticipant, GroupWalk, GroupWalkRequest, JoinGroupWalkRequest,
+        },
+        owner_model::OwnerWithDogs,
+        serde_helpers::WithId,
+        vaccination_model::RabiesPolicy,
+    },
+    routes::{
+        audit,
+        booking_routes::ensure_vaccinated,
+        extractors::{
+            AuthenticatedUser, ObjectIdPath, OwnerRole, RequireRole, WalkerRole, parse_object_id,
+        },
+    },
+    services::{
+        db::{Database, walker_refuses},
+        repository::OwnerRepository,
+    },
+};
+use actix_web::{
+    HttpResponse, delete, get, post,
+    web::{Data, Json, Path},
+};
+use mongodb::bson::DateTime;
+use validator::Validate;
+
+/// Open a group walk of the walker, who must have a `group_capacity`. Owners then
+/// join it with their dogs until the capacity is reached.
+#[utoipa::path(
+    tag = "group walks",
+    request_body = GroupWalkRequest,
+    responses(
+        (status = 201, description = "Group walk opened", body = WithId<GroupWalk>),
+        (status = 400, description = "The walk is in the past", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Group walk of another walker", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+        (status = 409, description = "The walker takes no group walks (`group_walks_disabled`), or is off or busy at that time (`walker_unavailable`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/group-walk")]
+pub async fn create_group_walk(
+    db: Data<Database>,
+    user: RequireRole<WalkerRole>,
+    request: Json<GroupWalkRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    if !user.is_admin() && user.user_id != request.walker {
+        return Err(AppError::Forbidden(
+            "Group walks are opened by their walker".to_string(),
+        ));
+    }
+
+    let walker = db.get_walker(&request.walker).await?;
+    let capacity = walker.group_capacity.ok_or(AppError::Conflict {
+        code: "group_walks_disabled",
+        message: "The walker has no group_capacity and takes no group walks".to_string(),
+        details: None,
+    })?;
+    let walk = GroupWalk::new(&request, capacity).map_err(AppError::Validation)?;
+    db.create_group_walk(&walk).await?;
+    audit(
+        db.get_ref(),
+        user.actor(),
+        AuditAction::Create,
+        EntityRef::new(EntityKind::GroupWalk, walk._id),
+        None,
+        snapshot(&walk),
+    )
+    .await;
+    Ok(HttpResponse::Created().json(WithId(walk)))
+}
+
+/// The group walk with its walker, and every owner with its dogs and share of the price.
+/// Only for its walker, its participants and admins.
+#[utoipa::path(
+    tag = "group walks",
+    params(("id" = String, Path, description = "ObjectId of the group walk")),
+    responses(
+        (status = 200, description = "Group walk with its walker, owners and dogs", body = FullGroupWalk),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Group walk not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/group-walk/{id}")]
+pub async fn get_group_walk(
+    db: Data<Database>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let walk = db.get_group_walk(&path.0).await?;
+    let allowed = match user.role {
+        Role::Admin => true,
+        Role::Walker => walk.walker == user.user_id,
+        Role::Owner => walk
+            .participants
+            .iter()
+            .any(|participant| participant.owner == user.user_id),
+    };
+    if !allowed {
+        return Err(AppError::Forbidden(
+            "Only the walker and the owners of a group walk can see it".to_string(),
+        ));
+    }
+
+    let walk = db.get_full_group_walk(&path.0).await?;
+    Ok(HttpResponse::Ok().json(walk))
+}
+
+/// Bring some of the owner's dogs on the group walk. The price of the walk is split
+/// again between the owners by number of dogs.
+#[utoipa::path(
+    tag = "group walks",
+    request_body = JoinGroupWalkRequest,
+    params(("id" = String, Path, description = "ObjectId of the group walk")),
+    responses(
+        (status = 200, description = "Group walk with the new shares", body = WithId<GroupWalk>),
+        (status = 400, description = "Malformed id, or a dog is not one of the owner's", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user, or the owner's email is not verified", body = ApiErrorBody),
+        (status = 404, description = "Group walk or owner not found", body = ApiErrorBody),
+        (status = 409, description = "The walk started or changed in the meantime, has no room for the dogs (`group_walk_full`), the owner already takes part (`already_joined`), has another walk at that time (`booking_conflict`) or a dog's rabies vaccination is missing or expired (`vaccination_required`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid, or a dog is of a size outside the walker's `accepted_sizes`", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/group-walk/{id}/join")]
+pub async fn join_group_walk(
+    db: Data<Database>,
+    rabies_policy: Data<RabiesPolicy>,
+    user: RequireRole<OwnerRole>,
+    path: ObjectIdPath,
+    request: Json<JoinGroupWalkRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    user.ensure_owns(&request.owner)?;
+
+    let OwnerWithDogs { owner, dogs } = db.get_owner_full(&request.owner, false).await?;
+    if !owner.0.email_verified {
+        return Err(AppError::Forbidden(
+            "The owner must verify its email before booking".to_string(),
+        ));
+    }
+    let mut dog_ids = request.dogs.clone();
+    dog_ids.sort();
+    dog_ids.dedup();
+    let brought = dog_ids
+        .iter()
+        .map(|dog_id| {
+            dogs.iter()
+                .find(|dog| dog.0._id == *dog_id)
+                .cloned()
+                .ok_or_else(|| {
+                    AppError::Validation(format!("Dog {} is not one of the owner's", dog_id))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let before = db.get_group_walk(&path.0).await?;
+    let walker = db.get_walker(&before.walker).await?;
+    let brought_dogs = brought.iter().map(|dog| dog.0.clone()).collect::<Vec<_>>();
+    if let Some(refusal) = walker.size_refusal(&brought_dogs) {
+        return Err(walker_refuses(refusal));
+    }
+    ensure_vaccinated(**rabies_policy, &brought, before.start_time)?;
+    let walk = db
+        .join_group_walk(
+            &path.0,
+            GroupParticipant {
+                owner: request.owner,
+                dogs: dog_ids,
+                price_cents: 0,
+                joined_at: DateTime::now(),
+            },
+        )
+        .await?;
+    audit(
+        db.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::GroupWalk, path.0),
+        snapshot(&before),
+        snapshot(&walk),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(walk)))
+}
+
+/// Take the owner and its dogs off a group walk that hasn't started, free of charge.
+#[utoipa::path(
+    tag = "group walks",
+    params(
+        ("id" = String, Path, description = "ObjectId of the group walk"),
+        ("owner_id" = String, Path, description = "ObjectId of the owner"),
+    ),
+    responses(
+        (status = 200, description = "Group walk with the new shares", body = WithId<GroupWalk>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Group walk not found, or the owner doesn't take part", body = ApiErrorBody),
+        (status = 409, description = "The walk started (`cancellation_closed`) or changed in the meantime", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[delete("/group-walk/{id}/participants/{owner_id}")]
+pub async fn leave_group_walk(
+    db: Data<Database>,
+    user: RequireRole<OwnerRole>,
+    path: Path<(String, String)>,
+) -> ApiResponse {
+    let (walk_id, owner_id) = path.into_inner();
+    let walk_id = parse_object_id(&walk_id)?;
+    let owner_id = parse_object_id(&owner_id)?;
+    user.ensure_owns(&owner_id)?;
+
+    let before = db.get_group_walk(&walk_id).await?;
+    let walk = db.leave_group_walk(&walk_id, &owner_id).await?;
+    audit(
+        db.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::GroupWalk, walk_id),
+        snapshot(&before),
+        snapshot(&walk),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(walk)))
+}
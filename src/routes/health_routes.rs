@@ -0,0 +1,99 @@
+use std::time::Instant;
+
+use actix_web::{HttpResponse, get, web::Data};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use utoipa::ToSchema;
+
+use crate::{
+    routes::openapi::{HealthStatus, Readiness},
+    services::{db::Database, rate_limit::RateLimiter},
+};
+
+/// Outcome of one dependency check of `GET /ready`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    status: &'static str,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    fn from_result(result: Result<(), String>, started_at: Instant) -> Self {
+        DependencyStatus {
+            status: if result.is_ok() { "up" } else { "down" },
+            latency_ms: started_at.elapsed().as_millis(),
+            error: result.err(),
+        }
+    }
+
+    fn is_up(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Liveness probe: the process answers, nothing else is checked
+/// so a MongoDB outage doesn't get every pod restarted. The state of the
+/// MongoDB circuit breaker is only reported, not with `--in-memory`.
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is up", body = HealthStatus),
+    )
+)]
+#[get("/health")]
+pub async fn health(db: Option<Data<Database>>) -> HttpResponse {
+    let mut body = json!({"status": "ok"});
+    if let Some(db) = db {
+        body["database"] = json!(db.breaker_stats());
+    }
+    HttpResponse::Ok().json(body)
+}
+
+/// Readiness probe: 200 when every dependency answers, 503 otherwise,
+/// with the status of each one so the failing dependency is obvious.
+/// MongoDB is not checked when the server runs with `--in-memory`, and is down
+/// until the migrations ran when the server started degraded.
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency answers", body = Readiness),
+        (status = 503, description = "A dependency is down", body = Readiness),
+    )
+)]
+#[get("/ready")]
+pub async fn ready(db: Option<Data<Database>>, limiter: Data<RateLimiter>) -> HttpResponse {
+    let mut checks = Map::new();
+    let mut ready = true;
+
+    if let Some(db) = db {
+        let started_at = Instant::now();
+        let result = if db.is_prepared() {
+            db.ping().await.map_err(|err| err.to_string())
+        } else {
+            Err("Out of reach since the start, waiting for it".to_string())
+        };
+        let mongo = DependencyStatus::from_result(result, started_at);
+        ready &= mongo.is_up();
+        checks.insert("mongo".to_string(), json!(mongo));
+    }
+
+    let started_at = Instant::now();
+    if let Some(result) = limiter.ping().await {
+        let redis =
+            DependencyStatus::from_result(result.map_err(|err| err.to_string()), started_at);
+        ready &= redis.is_up();
+        checks.insert("redis".to_string(), json!(redis));
+    }
+
+    let body = json!({
+        "status": if ready { "ready" } else { "unavailable" },
+        "checks": Value::Object(checks),
+    });
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
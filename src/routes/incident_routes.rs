@@ -0,0 +1,115 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        incident_model::{Incident, IncidentQuery, IncidentTriageRequest},
+        page_model::{Page, PageQuery},
+        serde_helpers::WithId,
+    },
+    routes::{
+        audit,
+        extractors::{AdminRole, ObjectIdPath, RequireRole},
+    },
+    services::repository::{AuditRepository, BookingRepository},
+};
+use actix_web::{
+    HttpResponse, get, patch,
+    web::{Data, Json, Query},
+};
+use validator::Validate;
+
+/// Incidents waiting for triage by default, newest first.
+#[utoipa::path(
+    tag = "incidents",
+    params(IncidentQuery),
+    responses(
+        (status = 200, description = "Page of incidents", body = Page<WithId<Incident>>),
+        (status = 400, description = "Invalid status, severity, page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/incidents")]
+pub async fn get_incidents(
+    bookings: Data<dyn BookingRepository>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<IncidentQuery>,
+) -> ApiResponse {
+    let (page, limit) = PageQuery {
+        page: query.page,
+        limit: query.limit,
+    }
+    .resolve()
+    .map_err(AppError::Validation)?;
+    let incidents = bookings
+        .get_incidents(&query.statuses(), query.severity, page, limit)
+        .await?;
+    Ok(HttpResponse::Ok().json(incidents))
+}
+
+#[utoipa::path(
+    tag = "incidents",
+    params(("id" = String, Path, description = "ObjectId of the incident")),
+    responses(
+        (status = 200, description = "Incident", body = WithId<Incident>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Incident not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/incident/{id}")]
+pub async fn get_incident(
+    bookings: Data<dyn BookingRepository>,
+    _admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let incident = bookings.get_incident(&path.0).await?;
+    Ok(HttpResponse::Ok().json(WithId(incident)))
+}
+
+/// Move an incident along `open -> investigating -> resolved | dismissed`,
+/// optionally recording the resolution.
+#[utoipa::path(
+    tag = "incidents",
+    request_body = IncidentTriageRequest,
+    params(("id" = String, Path, description = "ObjectId of the incident")),
+    responses(
+        (status = 200, description = "Triaged incident", body = WithId<Incident>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Incident not found", body = ApiErrorBody),
+        (status = 409, description = "Illegal status transition", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[patch("/incident/{id}")]
+pub async fn triage_incident(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+    request: Json<IncidentTriageRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let before = bookings.get_incident(&path.0).await?;
+    let request = request.into_inner();
+    let incident = bookings
+        .triage_incident(&path.0, request.status, request.resolution)
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Incident, path.0),
+        snapshot(&before),
+        snapshot(&incident),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(incident)))
+}
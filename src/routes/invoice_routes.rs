@@ -0,0 +1,85 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        invoice_model::{Invoice, PaymentUpdateRequest},
+        serde_helpers::WithId,
+    },
+    routes::{
+        audit,
+        extractors::{AdminRole, AuthenticatedUser, ObjectIdPath, RequireRole},
+    },
+    services::repository::{AuditRepository, BookingRepository},
+};
+use actix_web::{
+    HttpResponse, get, patch,
+    web::{Data, Json},
+};
+use validator::Validate;
+
+#[utoipa::path(
+    tag = "invoices",
+    params(("id" = String, Path, description = "ObjectId of the invoice")),
+    responses(
+        (status = 200, description = "Invoice", body = WithId<Invoice>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Invoice not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/invoice/{id}")]
+pub async fn get_invoice(
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let invoice = bookings.get_invoice(&path.0).await?;
+    user.ensure_owns(&invoice.owner)?;
+    Ok(HttpResponse::Ok().json(WithId(invoice)))
+}
+
+/// Called by the payment integration, with an `invoices:write` API key, when the
+/// provider reports a payment: `pending -> paid | failed`, `failed -> paid`, `paid -> refunded`.
+#[utoipa::path(
+    tag = "invoices",
+    request_body = PaymentUpdateRequest,
+    params(("id" = String, Path, description = "ObjectId of the invoice")),
+    responses(
+        (status = 200, description = "Invoice with its new payment status", body = WithId<Invoice>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Invoice not found", body = ApiErrorBody),
+        (status = 409, description = "Illegal status transition", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[patch("/invoice/{id}/payment")]
+pub async fn update_payment(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+    request: Json<PaymentUpdateRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let before = bookings.get_invoice(&path.0).await?;
+    let request = request.into_inner();
+    let invoice = bookings
+        .update_payment(&path.0, request.status, request.reference)
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Invoice, path.0),
+        snapshot(&before),
+        snapshot(&invoice),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(invoice)))
+}
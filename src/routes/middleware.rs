@@ -0,0 +1,291 @@
+use actix_web::{
+    Error, FromRequest, HttpMessage,
+    body::{BodySize, BoxBody, EitherBody, MessageBody, to_bytes},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method, StatusCode,
+        header::{self, HeaderName, HeaderValue},
+    },
+    middleware::Next,
+    web::Data,
+};
+use sha2::{Digest, Sha256};
+use tracing::{Span, field::Empty, info_span};
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
+use uuid::Uuid;
+
+use crate::{
+    errors::{ApiResponse, AppError, ErrorFormat},
+    models::api_key_model::ApiKeyScope,
+    routes::extractors::{API_KEY_HEADER, ApiKeyIdentity, AuthenticatedUser},
+    services::{auth::hash_one_time_token, db::Database, rate_limit::RateLimiter},
+    telemetry,
+};
+
+/// Header identifying a request across the gateway, this API and the logs.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest `X-Request-Id` accepted from a caller, longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Id of the current request, stored in the request extensions by `request_id`.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Give every request an id: the caller's `X-Request-Id` when it is a sane token,
+/// a fresh UUID otherwise. The id is recorded on the request span (see `RequestSpan`),
+/// so every log line of the request carries it, and echoed back in the response header.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+
+    Ok(res)
+}
+
+/// Root span of every request, created by `TracingLogger`.
+/// Like tracing-actix-web's default span but carrying our `RequestId`; the request
+/// latency is logged as `time.busy` + `time.idle` when the span closes, and the span
+/// is exported to OpenTelemetry as a child of the caller's `traceparent`.
+pub struct RequestSpan;
+
+impl RootSpanBuilder for RequestSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .map(|request_id| request_id.0.clone())
+            .unwrap_or_default();
+
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| "default".to_string());
+        let span = info_span!(
+            "HTTP request",
+            http.method = %request.method(),
+            http.route = %route,
+            http.target = %request.uri(),
+            http.status_code = Empty,
+            otel.name = %format!("{} {}", request.method(), route),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            request_id = %request_id,
+            exception.message = Empty,
+            exception.details = Empty,
+        );
+        telemetry::set_remote_parent(request, &span);
+        span
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+/// Complete the error body of failed requests with what only the request knows:
+/// its `RequestId`, the `ErrorFormat` of the app and, for problem+json, the path
+/// as `instance`.
+/// `AppError` can't see the request while building its response, so the body is
+/// rebuilt here from the error carried by the response.
+pub async fn error_context(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|request_id| request_id.0.clone());
+    let instance = req.path().to_string();
+    let format = req
+        .app_data::<Data<ErrorFormat>>()
+        .map_or(ErrorFormat::Envelope, |format| *format.get_ref());
+
+    let res = next.call(req).await?;
+    let rebuilt = res
+        .response()
+        .error()
+        .and_then(|err| err.as_error::<AppError>())
+        .map(|err| err.to_response(format, request_id, Some(instance)));
+
+    Ok(match rebuilt {
+        Some(response) => res.into_response(response).map_into_right_body(),
+        None => res.map_into_left_body(),
+    })
+}
+
+/// Authenticate backend integrations sending `X-Api-Key` instead of a bearer token.
+/// The key must exist, not be revoked, and carry the scope of the route
+/// (see `ApiKeyScope::required_for`); the caller is then an `ApiKeyIdentity`.
+/// Requests without the header go through untouched.
+pub async fn api_key_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(key) = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+    else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+
+    // Keys live in MongoDB, there are none with `--in-memory`.
+    let Some(db) = req.app_data::<Data<Database>>().cloned() else {
+        let err = AppError::Unauthorized("Invalid or revoked API key".to_string());
+        return Ok(req.error_response(err).map_into_right_body());
+    };
+    let api_key = match db.find_active_api_key(&hash_one_time_token(&key)).await {
+        Ok(Some(api_key)) => api_key,
+        Ok(None) => {
+            let err = AppError::Unauthorized("Invalid or revoked API key".to_string());
+            return Ok(req.error_response(err).map_into_right_body());
+        }
+        Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+    };
+
+    let denied = match ApiKeyScope::required_for(req.method(), req.path()) {
+        Some(scope) if api_key.scopes.contains(&scope) => None,
+        Some(scope) => Some(format!("This API key lacks the `{}` scope", scope.as_str())),
+        None => Some("This endpoint can't be called with an API key".to_string()),
+    };
+    if let Some(message) = denied {
+        return Ok(req
+            .error_response(AppError::Forbidden(message))
+            .map_into_right_body());
+    }
+
+    req.extensions_mut()
+        .insert(ApiKeyIdentity { id: api_key._id });
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+/// Refuse the request with a 429 once the client has used up its token bucket.
+/// A request authenticated by an API key or a valid bearer token is counted against
+/// that key or user, so it keeps one limit whatever replica or network it comes from;
+/// anything else against its IP.
+/// The error is returned as a response (not an `Err`) so `error_context` still sees it.
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let limiter = req
+        .app_data::<Data<RateLimiter>>()
+        .expect("RateLimiter is registered as app data")
+        .clone();
+
+    if limiter.is_enabled()
+        && let Some(client) = rate_limit_key(&req)
+        && let Err(retry_after) = limiter.check(&client).await
+    {
+        let err = AppError::RateLimited {
+            retry_after_secs: retry_after.as_secs_f64().ceil() as u64,
+        };
+        return Ok(req.error_response(err).map_into_right_body());
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+fn rate_limit_key(req: &ServiceRequest) -> Option<String> {
+    if let Some(identity) = req.extensions().get::<ApiKeyIdentity>() {
+        return Some(format!("api_key:{}", identity.id.to_hex()));
+    }
+    let user = AuthenticatedUser::extract(req.request()).into_inner().ok();
+    match user {
+        Some(user) => Some(format!("user:{}", user.user_id.to_hex())),
+        None => req.peer_addr().map(|addr| format!("ip:{}", addr.ip())),
+    }
+}
+
+/// Tag successful GET responses with a weak `ETag`, a hash of the body, and answer
+/// a bodiless 304 when the caller's `If-None-Match` already names it. The handler
+/// still runs, this saves the bandwidth of polling clients, not the queries.
+/// Streamed bodies (`GET /admin/export`) are left alone rather than buffered.
+pub async fn etag(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let is_get = req.method() == Method::GET;
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let res = next.call(req).await?;
+    if !is_get
+        || res.status() != StatusCode::OK
+        || matches!(res.response().body().size(), BodySize::Stream)
+    {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let body = to_bytes(body)
+        .await
+        .map_err(|err| AppError::Internal(err.into().to_string()))?;
+
+    let etag = weak_etag(&body);
+    let fresh = if_none_match.is_some_and(|tags| etag_matches(&tags, &etag));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        res.headers_mut().insert(header::ETAG, value);
+    }
+    let res = if fresh {
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        res.headers_mut().remove(header::CONTENT_TYPE);
+        res.set_body(BoxBody::new(()))
+    } else {
+        res.set_body(BoxBody::new(body))
+    };
+
+    Ok(ServiceResponse::new(req, res))
+}
+
+/// `W/"<hash>"`, the first 16 bytes of the SHA-256 of `body` in hex.
+fn weak_etag(body: &[u8]) -> String {
+    let hash: String = Sha256::digest(body)[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("W/\"{}\"", hash)
+}
+
+/// Weak comparison of an `If-None-Match` list (or `*`) with `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// Fallback service, unknown routes get the same JSON error as the rest of the API.
+pub async fn route_not_found() -> ApiResponse {
+    Err(AppError::NotFound("Route not found".to_string()))
+}
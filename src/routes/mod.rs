@@ -1,3 +1,288 @@
+pub mod admin_routes;
+pub mod auth_routes;
 pub mod booking_routes;
+pub mod breed_routes;
 pub mod dog_routes;
+pub mod extractors;
+pub mod group_walk_routes;
+pub mod health_routes;
+pub mod incident_routes;
+pub mod invoice_routes;
+pub mod middleware;
+pub mod openapi;
 pub mod owner_routes;
+pub mod search_routes;
+pub mod series_routes;
+pub mod waitlist_routes;
+pub mod walker_routes;
+pub mod webhook_routes;
+pub mod ws_routes;
+
+use std::future::Future;
+
+use actix_web::{
+    HttpResponse,
+    http::{StatusCode, header::ContentType},
+    web::ServiceConfig,
+};
+use mongodb::bson::{Document, oid::ObjectId};
+use serde::Serialize;
+use tracing::error;
+
+use self::{
+    admin_routes::{
+        create_account, create_api_key, create_coupon, create_webhook, delete_webhook, export_data,
+        get_api_keys, get_archived_bookings, get_audit_log, get_cache_stats, get_jobs,
+        get_unassigned_bookings, get_webhook_deliveries, get_webhooks, import_data, purge_cache,
+        purge_cached_owner, revoke_api_key, settle_payouts,
+    },
+    auth_routes::{forgot_password, login, register, reset_password},
+    booking_routes::{
+        add_track_pings, assign_walker, cancel_booking, cancel_bookings_in_range,
+        cancel_with_token, complete_booking, confirm_booking, create_booking, create_cancel_link,
+        delete_booking, get_booking, get_bookings, get_track, get_walk_report, quote_booking,
+        refund_booking, report_incident, restore_booking, review_booking, start_booking,
+        submit_walk_report, tip_walker, update_booking, walker_on_the_way,
+    },
+    breed_routes::get_breeds,
+    dog_routes::{
+        add_vaccination, create_dog, delete_dog, delete_vaccination, get_dog_photo,
+        get_vaccinations, replace_vaccination, restore_dog, update_dog, update_dog_profile,
+        upload_dog_photo,
+    },
+    group_walk_routes::{create_group_walk, get_group_walk, join_group_walk, leave_group_walk},
+    incident_routes::{get_incident, get_incidents, triage_incident},
+    invoice_routes::{get_invoice, update_payment},
+    owner_routes::{
+        create_owner, create_owner_with_dogs, delete_owner, get_owner, get_owner_dogs,
+        get_owner_invoices, get_owner_points, get_owner_waitlist, get_owners, owner_events,
+        register_device, restore_owner, update_notification_preferences, update_owner,
+        verify_owner_email,
+    },
+    search_routes::search,
+    series_routes::{cancel_occurrence, cancel_series, get_series},
+    waitlist_routes::join_waitlist,
+    walker_routes::{
+        create_walker, delete_walker, get_walker, get_walker_availability, get_walker_earnings,
+        get_walkers, get_walkers_near, set_walker_availability, update_walker,
+    },
+    webhook_routes::stripe_webhook,
+    ws_routes::booking_updates,
+};
+use crate::{
+    errors::{ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, AuditActor, EntityRef},
+        idempotency_model::{IdempotencyRecord, StoredResponse},
+    },
+    services::repository::{AuditRepository, IdempotencyRepository},
+};
+
+/// Scope of the current API version, every resource endpoint lives under it.
+pub const API_V1: &str = "/api/v1";
+
+/// Register the `/api/v1` endpoints, mounted with `web::scope(API_V1).configure(v1)`.
+///
+/// A breaking change ships as a `v2` function mounted under `/api/v2` next to this one:
+/// it registers the new handlers and the unchanged ones again, so v1 clients keep working.
+pub fn v1(cfg: &mut ServiceConfig) {
+    cfg.service(register)
+        .service(login)
+        .service(forgot_password)
+        .service(reset_password)
+        .service(create_owner)
+        .service(create_owner_with_dogs)
+        .service(get_owners)
+        .service(verify_owner_email)
+        .service(get_owner)
+        .service(update_owner)
+        .service(delete_owner)
+        .service(restore_owner)
+        .service(get_owner_dogs)
+        .service(create_dog)
+        .service(update_dog)
+        .service(update_dog_profile)
+        .service(delete_dog)
+        .service(restore_dog)
+        .service(upload_dog_photo)
+        .service(get_dog_photo)
+        .service(get_breeds)
+        .service(get_vaccinations)
+        .service(add_vaccination)
+        .service(replace_vaccination)
+        .service(delete_vaccination)
+        .service(create_walker)
+        .service(get_walkers)
+        .service(get_walkers_near)
+        .service(get_walker)
+        .service(update_walker)
+        .service(delete_walker)
+        .service(get_walker_earnings)
+        .service(set_walker_availability)
+        .service(get_walker_availability)
+        .service(create_booking)
+        .service(quote_booking)
+        .service(get_bookings)
+        .service(get_booking)
+        .service(update_booking)
+        .service(cancel_booking)
+        .service(delete_booking)
+        .service(restore_booking)
+        .service(confirm_booking)
+        .service(start_booking)
+        .service(complete_booking)
+        .service(walker_on_the_way)
+        .service(refund_booking)
+        .service(tip_walker)
+        .service(assign_walker)
+        .service(cancel_bookings_in_range)
+        .service(submit_walk_report)
+        .service(get_walk_report)
+        .service(review_booking)
+        .service(report_incident)
+        .service(get_series)
+        .service(cancel_series)
+        .service(cancel_occurrence)
+        .service(join_waitlist)
+        .service(create_group_walk)
+        .service(get_group_walk)
+        .service(join_group_walk)
+        .service(leave_group_walk)
+        .service(get_incidents)
+        .service(get_incident)
+        .service(triage_incident)
+        .service(get_owner_invoices)
+        .service(get_owner_points)
+        .service(get_owner_waitlist)
+        .service(register_device)
+        .service(update_notification_preferences)
+        .service(owner_events)
+        .service(get_invoice)
+        .service(update_payment)
+        .service(stripe_webhook)
+        .service(add_track_pings)
+        .service(get_track)
+        .service(create_cancel_link)
+        .service(cancel_with_token)
+        .service(get_cache_stats)
+        .service(purge_cache)
+        .service(purge_cached_owner)
+        .service(get_jobs)
+        .service(get_archived_bookings)
+        .service(export_data)
+        .service(import_data)
+        .service(create_account)
+        .service(create_api_key)
+        .service(get_api_keys)
+        .service(revoke_api_key)
+        .service(get_audit_log)
+        .service(get_unassigned_bookings)
+        .service(create_coupon)
+        .service(settle_payouts)
+        .service(create_webhook)
+        .service(get_webhooks)
+        .service(delete_webhook)
+        .service(get_webhook_deliveries)
+        .service(search)
+        .service(booking_updates);
+}
+
+/// Record a change in the audit log. The change itself is already stored, so a
+/// failing write is only logged instead of turning a success into an error.
+pub async fn audit(
+    audit_log: &dyn AuditRepository,
+    actor: AuditActor,
+    action: AuditAction,
+    entity: EntityRef,
+    before: Option<Document>,
+    after: Option<Document>,
+) {
+    if let Err(err) = audit_log
+        .record_audit(actor, action, entity, before, after)
+        .await
+    {
+        error!(error = %err, entity_id = %entity.id, "Failed to record the audit entry");
+    }
+}
+
+/// Header set on the responses replayed for an `Idempotency-Key`.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Run `action` once per `Idempotency-Key` of `caller`: until the key expires, sending it
+/// again replays the first successful response instead of running `action` a second time.
+/// A failed `action` releases the key, so the request can be retried as is.
+/// Without a key, `action` simply runs.
+pub async fn idempotent<T: Serialize>(
+    store: &dyn IdempotencyRepository,
+    caller: &ObjectId,
+    key: Option<String>,
+    request_hash: String,
+    action: impl Future<Output = Result<T, AppError>>,
+) -> ApiResponse {
+    let Some(key) = key else {
+        return Ok(HttpResponse::Ok().json(action.await?));
+    };
+
+    let record = IdempotencyRecord::claim(&caller.to_hex(), &key, request_hash);
+    let id = record._id.clone();
+    let hash = record.request_hash.clone();
+    if let Some(existing) = store.claim_idempotency_key(record).await? {
+        if existing.request_hash != hash {
+            return Err(AppError::Conflict {
+                code: "idempotency_key_reused",
+                message: "This Idempotency-Key was already sent with another request".to_string(),
+                details: None,
+            });
+        }
+        return match existing.response {
+            Some(response) => Ok(HttpResponse::build(
+                StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK),
+            )
+            .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+            .content_type(ContentType::json())
+            .body(response.body)),
+            None => Err(AppError::Conflict {
+                code: "idempotency_key_in_use",
+                message: "A request with this Idempotency-Key is still in progress".to_string(),
+                details: None,
+            }),
+        };
+    }
+
+    let value = match action.await {
+        Ok(value) => value,
+        Err(err) => {
+            if let Err(release_err) = store.release_idempotency_key(&id).await {
+                error!(error = %release_err, "Failed to release the idempotency key");
+            }
+            return Err(err);
+        }
+    };
+
+    let body = serde_json::to_string(&value).map_err(|err| AppError::Internal(err.to_string()))?;
+    let response = StoredResponse {
+        status: StatusCode::OK.as_u16(),
+        body: body.clone(),
+    };
+    // The action is done, a replay running it again is the lesser evil next to a 500.
+    if let Err(err) = store.complete_idempotency_key(&id, response).await {
+        error!(error = %err, "Failed to store the idempotent response");
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(body))
+}
+
+/// `server.public_base_url`, registered as app data to build the links sent by email.
+pub struct PublicUrl(String);
+
+impl PublicUrl {
+    pub fn new(base_url: &str) -> Self {
+        PublicUrl(base_url.trim_end_matches('/').to_string())
+    }
+
+    /// Absolute URL of a path on this API.
+    pub fn to(&self, path: &str) -> String {
+        format!("{}{}", self.0, path)
+    }
+}
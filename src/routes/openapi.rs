@@ -0,0 +1,447 @@
+use serde::Serialize;
+use utoipa::{
+    Modify, OpenApi, ToSchema,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::{
+    errors::ApiErrorBody,
+    models::{
+        api_key_model::{ApiKeyRequest, ApiKeyScope, ApiKeyView, CreatedApiKey},
+        audit_model::{AuditAction, AuditActor, AuditEntry, EntityKind, EntityRef},
+        auth_model::{
+            AccountRequest, ForgotPasswordRequest, LoginRequest, RegisterRequest,
+            ResetPasswordRequest, Role, TokenResponse,
+        },
+        availability_model::{
+            AvailabilityBlock, AvailabilityRequest, BlockRequest, BookedRange, TimeRange,
+            WalkerAvailability, WeekAvailability, WeeklySlot,
+        },
+        backup_model::{Backup, CollectionImport, ImportMode, ImportReport},
+        booking_model::{
+            Booking, BookingList, BookingRequest, BookingSort, BookingStatus, BookingUpdate,
+            BookingUpdateKind, BookingUpdateRequest, BulkCancelRequest, BulkCancelResult,
+            FullBooking, ListedBooking, WalkReport,
+        },
+        breed_model::BreedList,
+        coupon_model::{BookingCoupon, Coupon, CouponRequest, Discount},
+        device_model::{Device, DevicePlatform, DeviceRequest},
+        dog_model::{
+            Dog, DogProfile, DogProfileRequest, DogRequest, DogSize, DogUpdateRequest, NewOwnerDog,
+            TemperamentFlag,
+        },
+        geo_model::{GeoLineString, GeoLineStringType, GeoPoint, GeoPointType, NearbyWalker},
+        group_walk_model::{
+            FullGroupParticipant, FullGroupWalk, GroupParticipant, GroupWalk, GroupWalkRequest,
+            JoinGroupWalkRequest,
+        },
+        incident_model::{
+            Incident, IncidentKind, IncidentRequest, IncidentSeverity, IncidentStatus,
+            IncidentTriageRequest,
+        },
+        invoice_model::{
+            Invoice, InvoiceRefund, PaymentStatus, PaymentUpdateRequest, RefundPolicy,
+        },
+        owner_event_model::OwnerEventData,
+        owner_model::{
+            CreatedOwnerWithDogs, EventPreferences, EventPreferencesUpdate,
+            NotificationPreferences, NotificationPreferencesUpdate, Owner, OwnerDeletion,
+            OwnerPoints, OwnerRequest, OwnerSort, OwnerUpdateRequest, OwnerWithDogs,
+            OwnerWithDogsRequest,
+        },
+        payment_model::BookingPayment,
+        payout_model::{
+            EarningsBucket, EarningsPeriod, Payout, PayoutBatch, PayoutKind, SettleRequest,
+            TipRequest, WalkerEarnings,
+        },
+        pricing_model::{PriceLine, PriceLineKind, Quote},
+        result_model::{InsertedId, UpdatedCount},
+        review_model::{RatedWalker, Review, ReviewRequest, WalkerRating},
+        search_model::{SearchHit, SearchResults},
+        serde_helpers::{DateTimeJson, NumberLongJson, ObjectIdJson},
+        series_model::{
+            BookingSeries, DayOfWeek, OccurrenceCancellation, RecurrenceRequest, SeriesCancellation,
+        },
+        track_model::{PingRequest, TrackRequest},
+        vaccination_model::{Vaccination, VaccinationRequest},
+        waitlist_model::{WaitlistEntry, WaitlistPlace, WaitlistRequest, WaitlistStatus},
+        walker_model::{Walker, WalkerRequest, WalkerTier, WalkerUpdateRequest},
+        webhook_model::{
+            DeliveryStatus, WebhookDelivery, WebhookEvent, WebhookRequest, WebhookView,
+        },
+    },
+    routes::{
+        admin_routes, auth_routes, booking_routes, breed_routes, dog_routes, extractors,
+        group_walk_routes,
+        health_routes::{self, DependencyStatus},
+        incident_routes, invoice_routes, owner_routes, search_routes, series_routes,
+        waitlist_routes, walker_routes, webhook_routes, ws_routes,
+    },
+    services::{
+        cache::CacheStats,
+        circuit_breaker::{BreakerState, BreakerStats},
+        jobs::{JobKind, JobStats},
+    },
+};
+
+/// OpenAPI 3 description of the API, served as `/openapi.json` and browsable at `/docs/`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Dog walking API",
+        description = "Owners, their dogs, walkers and the walks booked between them.\n\n\
+            Successful GET responses carry a weak `ETag`, send it back in `If-None-Match` \
+            to get an empty 304 while the resource is unchanged."
+    ),
+    paths(health_routes::health, health_routes::ready),
+    nest((path = "/api/v1", api = ApiV1)),
+    components(schemas(
+        HealthStatus,
+        BreakerState,
+        BreakerStats,
+        Readiness,
+        ReadinessChecks,
+        DependencyStatus
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "owners", description = "Owner profiles and their dogs"),
+        (name = "dogs"),
+        (name = "walkers"),
+        (name = "bookings", description = "Walks, their lifecycle and reports"),
+        (name = "series", description = "Recurring weekly bookings"),
+        (name = "waitlist", description = "Queues for fully booked time slots"),
+        (name = "group walks", description = "Walks of the dogs of several owners with one walker"),
+        (name = "incidents", description = "Incidents filed by walkers and their triage"),
+        (name = "invoices", description = "Invoices of completed walks and their payment"),
+        (name = "search", description = "Find owners and dogs by name"),
+        (name = "auth", description = "Accounts and access tokens"),
+        (name = "admin", description = "Operations guarded by the admin key"),
+        (name = "health", description = "Liveness and readiness probes"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Endpoints of `/api/v1`, see `routes::v1`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        owner_routes::create_owner,
+        owner_routes::create_owner_with_dogs,
+        owner_routes::verify_owner_email,
+        owner_routes::get_owners,
+        owner_routes::get_owner,
+        owner_routes::update_owner,
+        owner_routes::delete_owner,
+        owner_routes::restore_owner,
+        owner_routes::get_owner_dogs,
+        dog_routes::create_dog,
+        dog_routes::update_dog,
+        dog_routes::update_dog_profile,
+        dog_routes::delete_dog,
+        dog_routes::restore_dog,
+        dog_routes::upload_dog_photo,
+        dog_routes::get_dog_photo,
+        breed_routes::get_breeds,
+        dog_routes::get_vaccinations,
+        dog_routes::add_vaccination,
+        dog_routes::replace_vaccination,
+        dog_routes::delete_vaccination,
+        walker_routes::create_walker,
+        walker_routes::get_walkers,
+        walker_routes::get_walkers_near,
+        walker_routes::get_walker,
+        walker_routes::update_walker,
+        walker_routes::delete_walker,
+        walker_routes::get_walker_earnings,
+        walker_routes::set_walker_availability,
+        walker_routes::get_walker_availability,
+        booking_routes::get_bookings,
+        booking_routes::create_booking,
+        booking_routes::quote_booking,
+        booking_routes::get_booking,
+        booking_routes::update_booking,
+        booking_routes::cancel_booking,
+        booking_routes::delete_booking,
+        booking_routes::restore_booking,
+        booking_routes::confirm_booking,
+        booking_routes::start_booking,
+        booking_routes::complete_booking,
+        booking_routes::walker_on_the_way,
+        booking_routes::refund_booking,
+        booking_routes::tip_walker,
+        booking_routes::assign_walker,
+        booking_routes::submit_walk_report,
+        booking_routes::get_walk_report,
+        booking_routes::review_booking,
+        booking_routes::report_incident,
+        series_routes::get_series,
+        series_routes::cancel_series,
+        series_routes::cancel_occurrence,
+        waitlist_routes::join_waitlist,
+        group_walk_routes::create_group_walk,
+        group_walk_routes::get_group_walk,
+        group_walk_routes::join_group_walk,
+        group_walk_routes::leave_group_walk,
+        incident_routes::get_incidents,
+        incident_routes::get_incident,
+        incident_routes::triage_incident,
+        owner_routes::get_owner_invoices,
+        owner_routes::get_owner_points,
+        owner_routes::get_owner_waitlist,
+        owner_routes::register_device,
+        owner_routes::update_notification_preferences,
+        owner_routes::owner_events,
+        invoice_routes::get_invoice,
+        invoice_routes::update_payment,
+        webhook_routes::stripe_webhook,
+        ws_routes::booking_updates,
+        booking_routes::add_track_pings,
+        booking_routes::get_track,
+        booking_routes::cancel_bookings_in_range,
+        booking_routes::create_cancel_link,
+        booking_routes::cancel_with_token,
+        auth_routes::register,
+        auth_routes::login,
+        auth_routes::forgot_password,
+        auth_routes::reset_password,
+        admin_routes::get_cache_stats,
+        admin_routes::purge_cache,
+        admin_routes::purge_cached_owner,
+        admin_routes::get_jobs,
+        admin_routes::get_archived_bookings,
+        admin_routes::export_data,
+        admin_routes::import_data,
+        admin_routes::create_account,
+        admin_routes::create_api_key,
+        admin_routes::get_api_keys,
+        admin_routes::revoke_api_key,
+        admin_routes::get_audit_log,
+        admin_routes::get_unassigned_bookings,
+        admin_routes::create_coupon,
+        admin_routes::settle_payouts,
+        admin_routes::create_webhook,
+        admin_routes::get_webhooks,
+        admin_routes::delete_webhook,
+        admin_routes::get_webhook_deliveries,
+        search_routes::search,
+    ),
+    components(schemas(
+        ObjectIdJson,
+        DateTimeJson,
+        NumberLongJson,
+        ApiErrorBody,
+        Owner,
+        OwnerRequest,
+        OwnerUpdateRequest,
+        OwnerWithDogs,
+        OwnerDeletion,
+        OwnerPoints,
+        OwnerWithDogsRequest,
+        CreatedOwnerWithDogs,
+        OwnerSort,
+        Dog,
+        DogRequest,
+        DogUpdateRequest,
+        NewOwnerDog,
+        DogProfile,
+        DogProfileRequest,
+        TemperamentFlag,
+        DogSize,
+        BreedList,
+        Vaccination,
+        VaccinationRequest,
+        Walker,
+        WalkerTier,
+        WalkerRequest,
+        GeoPoint,
+        GeoPointType,
+        NearbyWalker,
+        GeoLineString,
+        GeoLineStringType,
+        TrackRequest,
+        PingRequest,
+        WalkerUpdateRequest,
+        WalkerRating,
+        RatedWalker,
+        WalkerAvailability,
+        WeeklySlot,
+        AvailabilityBlock,
+        AvailabilityRequest,
+        BlockRequest,
+        WeekAvailability,
+        TimeRange,
+        BookedRange,
+        WalkerEarnings,
+        EarningsPeriod,
+        EarningsBucket,
+        SettleRequest,
+        PayoutBatch,
+        Payout,
+        PayoutKind,
+        TipRequest,
+        WebhookEvent,
+        WebhookRequest,
+        WebhookView,
+        WebhookDelivery,
+        DeliveryStatus,
+        Review,
+        ReviewRequest,
+        Incident,
+        IncidentKind,
+        IncidentSeverity,
+        IncidentStatus,
+        IncidentRequest,
+        IncidentTriageRequest,
+        Invoice,
+        PaymentStatus,
+        PaymentUpdateRequest,
+        InvoiceRefund,
+        RefundPolicy,
+        BookingPayment,
+        Booking,
+        BookingStatus,
+        BookingRequest,
+        BookingUpdateRequest,
+        RecurrenceRequest,
+        DayOfWeek,
+        BookingSeries,
+        SeriesCancellation,
+        OccurrenceCancellation,
+        WaitlistRequest,
+        WaitlistEntry,
+        WaitlistStatus,
+        WaitlistPlace,
+        Device,
+        DevicePlatform,
+        DeviceRequest,
+        NotificationPreferences,
+        EventPreferences,
+        NotificationPreferencesUpdate,
+        OwnerEventData,
+        EventPreferencesUpdate,
+        GroupWalkRequest,
+        GroupWalk,
+        GroupParticipant,
+        JoinGroupWalkRequest,
+        FullGroupWalk,
+        FullGroupParticipant,
+        Quote,
+        PriceLine,
+        PriceLineKind,
+        Coupon,
+        CouponRequest,
+        Discount,
+        BookingCoupon,
+        FullBooking,
+        BookingList,
+        ListedBooking,
+        BookingSort,
+        WalkReport,
+        BulkCancelRequest,
+        BulkCancelResult,
+        BookingUpdate,
+        BookingUpdateKind,
+        Role,
+        RegisterRequest,
+        AccountRequest,
+        LoginRequest,
+        ForgotPasswordRequest,
+        ResetPasswordRequest,
+        TokenResponse,
+        Backup,
+        ImportMode,
+        CollectionImport,
+        ImportReport,
+        ApiKeyScope,
+        ApiKeyRequest,
+        ApiKeyView,
+        CreatedApiKey,
+        AuditEntry,
+        AuditActor,
+        AuditAction,
+        EntityKind,
+        EntityRef,
+        SearchHit,
+        SearchResults,
+        CacheStats,
+        JobKind,
+        JobStats,
+        InsertedId,
+        UpdatedCount,
+        CancelLink,
+        CreatedAccount,
+    ))
+)]
+struct ApiV1;
+
+/// `bearer` (JWT from `POST /auth/login`), `admin_key` and `api_key`,
+/// the names used by the `security(...)` of each path.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "admin_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(
+                extractors::ADMIN_KEY_HEADER,
+            ))),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(extractors::API_KEY_HEADER))),
+        );
+    }
+}
+
+// The schemas below only describe bodies built with `json!`.
+
+/// Answer of `POST /booking/{id}/cancel-link`.
+#[derive(Serialize, ToSchema)]
+pub struct CancelLink {
+    pub url: String,
+    /// Unix timestamp in seconds.
+    pub expires_at: i64,
+}
+
+/// Answer of `POST /admin/accounts`.
+#[derive(Serialize, ToSchema)]
+pub struct CreatedAccount {
+    pub user_id: String,
+    pub role: Role,
+}
+
+/// Answer of `GET /health`.
+#[derive(Serialize, ToSchema)]
+pub struct HealthStatus {
+    #[schema(example = "ok")]
+    pub status: String,
+    /// MongoDB circuit breaker, not with `--in-memory`.
+    pub database: Option<BreakerStats>,
+}
+
+/// Answer of `GET /ready`, `status` is `ready` or `unavailable`.
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    #[schema(example = "ready")]
+    pub status: String,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessChecks {
+    /// Not checked with `--in-memory`.
+    pub mongo: Option<DependencyStatus>,
+    /// Only checked with the `redis` rate limit backend.
+    pub redis: Option<DependencyStatus>,
+}
@@ -1,27 +1,658 @@
 use crate::{
-    models::owner_model::{Owner, OwnerRequest},
-    services::db::Database,
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        device_model::{Device, DeviceRequest},
+        dog_model::Dog,
+        invoice_model::Invoice,
+        owner_event_model::OwnerEventData,
+        owner_model::{
+            CreatedOwnerWithDogs, NotificationPreferencesUpdate, Owner, OwnerDeletion,
+            OwnerListQuery, OwnerPoints, OwnerRequest, OwnerUpdateRequest, OwnerWithDogs,
+            OwnerWithDogsRequest,
+        },
+        page_model::{Page, PageQuery},
+        result_model::InsertedId,
+        serde_helpers::WithId,
+        waitlist_model::WaitlistPlace,
+    },
+    routes::{
+        API_V1, PublicUrl, audit,
+        dog_routes::catalog_breed,
+        extractors::{
+            Actor, AdminRole, AuthenticatedUser, IfMatch, IncludeDeleted, ObjectIdPath, RequireRole,
+        },
+        waitlist_routes::waitlist_places,
+    },
+    services::{
+        auth::hash_one_time_token,
+        mailer::Mailer,
+        repository::{AuditRepository, BookingRepository, DogRepository, OwnerRepository},
+    },
 };
 use actix_web::{
-    HttpResponse, post,
-    web::{Data, Json},
+    HttpRequest, HttpResponse, delete, get,
+    http::header,
+    patch, post, put,
+    web::{Bytes, Data, Json, Path, Query},
 };
+use futures_util::stream;
+use std::{convert::Infallible, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::error;
+use validator::Validate;
 
+/// Interval of the comments keeping `GET /owner/{id}/events` open, under the
+/// idle timeout of the usual proxies.
+const SSE_HEARTBEAT: Duration = Duration::from_secs(15);
+
+#[utoipa::path(
+    tag = "owners",
+    request_body = OwnerRequest,
+    responses(
+        (status = 200, description = "Owner created, a verification email is sent", body = InsertedId),
+        (status = 409, description = "Email already registered", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    )
+)]
 #[post("/owner")]
-pub async fn create_owner(db: Data<Database>, request: Json<OwnerRequest>) -> HttpResponse {
-    match db
-        .create_owner(
-            Owner::try_from(OwnerRequest {
-                name: request.name.clone(),
-                email: request.email.clone(),
-                phone: request.phone.clone(),
-                address: request.address.clone(),
-            })
-            .expect("Error converting OwnerRequest to Owner."),
+pub async fn create_owner(
+    owners: Data<dyn OwnerRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    mailer: Data<dyn Mailer>,
+    public_url: Data<PublicUrl>,
+    actor: Actor,
+    request: Json<OwnerRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let owner = Owner::try_from(request.into_inner())
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+    let email = owner.email.clone();
+    let after = snapshot(&owner);
+
+    let (owner_id, verification_token) = owners.create_owner(owner).await?;
+    send_verification_email(mailer.get_ref(), &public_url, &email, &verification_token);
+    audit(
+        audit_log.get_ref(),
+        actor.0,
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Owner, owner_id),
+        None,
+        after,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(InsertedId::from(owner_id)))
+}
+
+/// One call instead of `POST /owner` followed by a `POST /dog` per dog,
+/// either everything is created or nothing is.
+#[utoipa::path(
+    tag = "owners",
+    request_body = OwnerWithDogsRequest,
+    responses(
+        (status = 200, description = "Owner and dogs created, a verification email is sent", body = CreatedOwnerWithDogs),
+        (status = 409, description = "Email already registered", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    )
+)]
+#[post("/owner/with-dogs")]
+pub async fn create_owner_with_dogs(
+    owners: Data<dyn OwnerRepository>,
+    dogs: Data<dyn DogRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    mailer: Data<dyn Mailer>,
+    public_url: Data<PublicUrl>,
+    actor: Actor,
+    request: Json<OwnerWithDogsRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let mut request = request.into_inner();
+    for dog in &mut request.dogs {
+        dog.breed = catalog_breed(dogs.get_ref(), "dogs", dog.breed.take()).await?;
+    }
+    let owner =
+        Owner::try_from(request.owner).map_err(|err| AppError::Validation(err.to_string()))?;
+    let dogs: Vec<Dog> = request
+        .dogs
+        .into_iter()
+        .map(|dog| dog.into_dog(owner._id))
+        .collect::<Result<_, _>>()
+        .map_err(AppError::Validation)?;
+    let email = owner.email.clone();
+    let dog_ids = dogs.iter().map(|dog| dog._id).collect();
+    let mut created = vec![(
+        EntityRef::new(EntityKind::Owner, owner._id),
+        snapshot(&owner),
+    )];
+    created.extend(
+        dogs.iter()
+            .map(|dog| (EntityRef::new(EntityKind::Dog, dog._id), snapshot(dog))),
+    );
+
+    let (owner_id, verification_token) = owners.create_owner_with_dogs(owner, dogs).await?;
+    send_verification_email(mailer.get_ref(), &public_url, &email, &verification_token);
+    for (entity, after) in created {
+        audit(
+            audit_log.get_ref(),
+            actor.0.clone(),
+            AuditAction::Create,
+            entity,
+            None,
+            after,
         )
-        .await
-    {
-        Ok(booking) => HttpResponse::Ok().json(booking),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        .await;
+    }
+
+    Ok(HttpResponse::Ok().json(CreatedOwnerWithDogs { owner_id, dog_ids }))
+}
+
+/// Target of the link emailed at signup.
+#[utoipa::path(
+    tag = "owners",
+    params(("token" = String, Path, description = "Token of the emailed verification link")),
+    responses(
+        (status = 200, description = "Owner with a verified email", body = WithId<Owner>),
+        (status = 410, description = "Link expired or already used", body = ApiErrorBody),
+    )
+)]
+#[get("/owner/verify/{token}")]
+pub async fn verify_owner_email(
+    owners: Data<dyn OwnerRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    actor: Actor,
+    path: Path<(String,)>,
+) -> ApiResponse {
+    let owner = owners
+        .verify_owner_email(&hash_one_time_token(&path.into_inner().0))
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        actor.0,
+        AuditAction::VerifyEmail,
+        EntityRef::new(EntityKind::Owner, owner._id),
+        None,
+        snapshot(&owner),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(owner)))
+}
+
+/// Email the verification link of a new owner. A delivery failure is only logged,
+/// the owner is created either way.
+pub fn send_verification_email(
+    mailer: &dyn Mailer,
+    public_url: &PublicUrl,
+    email: &str,
+    token: &str,
+) {
+    let body = format!(
+        "Welcome! Confirm your email address to start booking walks:\n{}",
+        public_url.to(&format!("{}/owner/verify/{}", API_V1, token))
+    );
+    if let Err(err) = mailer.send(email, "Confirm your email address", &body) {
+        error!(error = %err, "Failed to send the verification email");
+    }
+}
+
+#[utoipa::path(
+    tag = "owners",
+    params(OwnerListQuery, ("include_deleted" = Option<bool>, Query, description = "Also return soft deleted documents, admins only")),
+    responses(
+        (status = 200, description = "Page of owners", body = Page<WithId<Owner>>),
+        (status = 400, description = "Invalid page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owners")]
+pub async fn get_owners(
+    owners: Data<dyn OwnerRepository>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<OwnerListQuery>,
+    include_deleted: IncludeDeleted,
+) -> ApiResponse {
+    let (page, limit) = PageQuery {
+        page: query.page,
+        limit: query.limit,
+    }
+    .resolve()
+    .map_err(AppError::Validation)?;
+
+    let owners = owners
+        .get_owners(page, limit, query.sort, include_deleted.0)
+        .await?;
+    Ok(HttpResponse::Ok().json(owners))
+}
+
+#[utoipa::path(
+    tag = "owners",
+    params(
+        ("id" = String, Path, description = "ObjectId of the owner"),
+        ("include_deleted" = Option<bool>, Query, description = "Also return soft deleted documents, admins only"),
+    ),
+    responses(
+        (status = 200, description = "Owner with their dogs", body = OwnerWithDogs),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Another owner, or include_deleted asked by a non-admin", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owner/{id}")]
+pub async fn get_owner(
+    owners: Data<dyn OwnerRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    include_deleted: IncludeDeleted,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    let owner = owners.get_owner_full(&path.0, include_deleted.0).await?;
+    Ok(HttpResponse::Ok().json(owner))
+}
+
+#[utoipa::path(
+    tag = "owners",
+    request_body = OwnerUpdateRequest,
+    params(
+        ("id" = String, Path, description = "ObjectId of the owner"),
+        ("If-Match" = Option<String>, Header, description = "Current `version` of the owner, quoted, e.g. `\"3\"`"),
+    ),
+    responses(
+        (status = 200, description = "Updated owner", body = WithId<Owner>),
+        (status = 400, description = "Malformed id or If-Match", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+        (status = 409, description = "Email already registered", body = ApiErrorBody),
+        (status = 412, description = "The owner was updated in the meantime", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+        (status = 428, description = "No If-Match nor expected_version", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/owner/{id}")]
+pub async fn update_owner(
+    owners: Data<dyn OwnerRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    if_match: IfMatch,
+    request: Json<OwnerUpdateRequest>,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    request.validate()?;
+    let expected_version = if_match.expected_version(request.expected_version)?;
+
+    let before = owners.get_owner_by_id(&path.0).await?;
+    let owner = owners
+        .update_owner(&path.0, &request, expected_version)
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Owner, path.0),
+        snapshot(&before),
+        snapshot(&owner),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(owner)))
+}
+
+#[utoipa::path(
+    tag = "owners",
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    responses(
+        (status = 200, description = "Owner deleted with their dogs and bookings", body = OwnerDeletion),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[delete("/owner/{id}")]
+pub async fn delete_owner(
+    owners: Data<dyn OwnerRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    let before = owners.get_owner_by_id(&path.0).await?;
+    let deletion = owners.delete_owner_cascade(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Delete,
+        EntityRef::new(EntityKind::Owner, path.0),
+        snapshot(&before),
+        None,
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(deletion))
+}
+
+/// Undo `DELETE /owner/{id}`, the dogs deleted with the owner come back too.
+/// Bookings cancelled by the deletion stay cancelled.
+#[utoipa::path(
+    tag = "owners",
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    responses(
+        (status = 200, description = "Restored owner", body = WithId<Owner>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "No deleted owner with this id", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/owner/{id}/restore")]
+pub async fn restore_owner(
+    owners: Data<dyn OwnerRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let owner = owners.restore_owner(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        admin.actor(),
+        AuditAction::Restore,
+        EntityRef::new(EntityKind::Owner, path.0),
+        None,
+        snapshot(&owner),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(owner)))
+}
+
+#[utoipa::path(
+    tag = "owners",
+    params(
+        ("id" = String, Path, description = "ObjectId of the owner"),
+        ("include_deleted" = Option<bool>, Query, description = "Also return soft deleted documents, admins only"),
+    ),
+    responses(
+        (status = 200, description = "Dogs of the owner", body = [WithId<Dog>]),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Another owner, or include_deleted asked by a non-admin", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owner/{id}/dogs")]
+pub async fn get_owner_dogs(
+    owners: Data<dyn OwnerRepository>,
+    dogs: Data<dyn DogRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    include_deleted: IncludeDeleted,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    if !owners.owner_exists(&path.0, include_deleted.0).await? {
+        return Err(AppError::NotFound("Owner not found".to_string()));
+    }
+
+    let dogs = dogs.get_dogs_by_owner(&path.0, include_deleted.0).await?;
+    Ok(HttpResponse::Ok().json(dogs))
+}
+
+/// Invoices of the owner, newest first.
+#[utoipa::path(
+    tag = "invoices",
+    params(
+        ("id" = String, Path, description = "ObjectId of the owner"),
+        PageQuery,
+    ),
+    responses(
+        (status = 200, description = "Page of invoices", body = Page<WithId<Invoice>>),
+        (status = 400, description = "Malformed id, page or limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owner/{id}/invoices")]
+pub async fn get_owner_invoices(
+    owners: Data<dyn OwnerRepository>,
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    query: Query<PageQuery>,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    let (page, limit) = query.resolve().map_err(AppError::Validation)?;
+    if !owners.owner_exists(&path.0, false).await? {
+        return Err(AppError::NotFound("Owner not found".to_string()));
+    }
+
+    let invoices = bookings.get_owner_invoices(&path.0, page, limit).await?;
+    Ok(HttpResponse::Ok().json(invoices))
+}
+
+/// Loyalty points of the owner: every completed walk earns `points_per_booking`,
+/// a booking made with `redeem_points` spends `points_per_free_walk` on a free
+/// walk of `free_walk_minutes`.
+#[utoipa::path(
+    tag = "owners",
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    responses(
+        (status = 200, description = "Balance and redemption rules", body = OwnerPoints),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owner/{id}/points")]
+pub async fn get_owner_points(
+    owners: Data<dyn OwnerRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    let points = owners.get_owner_points(&path.0).await?;
+    Ok(HttpResponse::Ok().json(points))
+}
+
+/// Waitlist entries of the owner for walks still to come, soonest first, each
+/// with its place in the queue while waiting.
+#[utoipa::path(
+    tag = "owners",
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    responses(
+        (status = 200, description = "Waitlist entries with their places", body = Vec<WaitlistPlace>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owner/{id}/waitlist")]
+pub async fn get_owner_waitlist(
+    owners: Data<dyn OwnerRepository>,
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    if !owners.owner_exists(&path.0, false).await? {
+        return Err(AppError::NotFound("Owner not found".to_string()));
+    }
+
+    let entries = bookings.get_owner_waitlist(&path.0).await?;
+    let places = waitlist_places(bookings.get_ref(), entries).await?;
+    Ok(HttpResponse::Ok().json(places))
+}
+
+/// Register the push notification token of a phone of the owner. Registering a
+/// token again (the app does so at every start) refreshes it, possibly for
+/// another owner signed in on the same phone.
+#[utoipa::path(
+    tag = "owners",
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    request_body = DeviceRequest,
+    responses(
+        (status = 201, description = "Device registered", body = WithId<Device>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/owner/{id}/devices")]
+pub async fn register_device(
+    owners: Data<dyn OwnerRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    request: Json<DeviceRequest>,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    request.validate()?;
+    if !owners.owner_exists(&path.0, false).await? {
+        return Err(AppError::NotFound("Owner not found".to_string()));
+    }
+
+    let device = owners
+        .register_device(Device::new(path.0, request.into_inner()))
+        .await?;
+    Ok(HttpResponse::Created().json(WithId(device)))
+}
+
+/// Turn booking notifications on or off per channel (email, SMS, push) and per
+/// event. Unlike `PUT /owner/{id}`, no version is expected: the toggles don't
+/// depend on the rest of the owner.
+#[utoipa::path(
+    tag = "owners",
+    request_body = NotificationPreferencesUpdate,
+    params(("id" = String, Path, description = "ObjectId of the owner")),
+    responses(
+        (status = 200, description = "Owner with its updated preferences", body = WithId<Owner>),
+        (status = 400, description = "Malformed id, or no preference provided", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[patch("/owner/{id}/preferences")]
+pub async fn update_notification_preferences(
+    owners: Data<dyn OwnerRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    request: Json<NotificationPreferencesUpdate>,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    if request.is_empty() {
+        return Err(AppError::Validation(
+            "At least one preference must be provided".to_string(),
+        ));
     }
+
+    let before = owners.get_owner_by_id(&path.0).await?;
+    let preferences = request.apply_to(before.notification_preferences);
+    let owner = owners
+        .set_notification_preferences(&path.0, &preferences)
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Owner, path.0),
+        snapshot(&before),
+        snapshot(&owner),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(owner)))
+}
+
+/// Live updates of the owner for clients that can't use WebSockets, as Server-Sent
+/// Events: `booking_status` when one of its bookings is created or changes status,
+/// `walker_location` for each position sent by the walker during a walk. A comment
+/// keeps the connection open every `SSE_HEARTBEAT`. Reconnecting with the
+/// `Last-Event-ID` header replays the recent events missed in between.
+#[utoipa::path(
+    tag = "owners",
+    params(
+        ("id" = String, Path, description = "ObjectId of the owner"),
+        ("Last-Event-ID" = Option<u64>, Header, description = "Id of the last event received, to resume after it"),
+    ),
+    responses(
+        (status = 200, description = "Stream of events, the data of each one as JSON", body = OwnerEventData, content_type = "text/event-stream"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/owner/{id}/events")]
+pub async fn owner_events(
+    owners: Data<dyn OwnerRepository>,
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+    req: HttpRequest,
+) -> ApiResponse {
+    user.ensure_owns(&path.0)?;
+    if !owners.owner_exists(&path.0, false).await? {
+        return Err(AppError::NotFound("Owner not found".to_string()));
+    }
+
+    let owner = path.0;
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    let (missed, events) = bookings.owner_events().subscribe(&owner, last_event_id);
+    let heartbeat = actix_web::rt::time::interval(SSE_HEARTBEAT);
+
+    let stream = stream::unfold(
+        (missed.into_iter(), events, heartbeat),
+        move |(mut missed, mut events, mut heartbeat)| async move {
+            if let Some(event) = missed.next() {
+                let frame = Bytes::from(event.to_sse());
+                return Some((Ok::<_, Infallible>(frame), (missed, events, heartbeat)));
+            }
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(event) if event.owner == owner => {
+                            let frame = Bytes::from(event.to_sse());
+                            return Some((Ok(frame), (missed, events, heartbeat)));
+                        }
+                        Ok(_) => {}
+                        // Closing makes the client reconnect with `Last-Event-ID`,
+                        // which replays what it skipped.
+                        Err(RecvError::Lagged(_)) | Err(RecvError::Closed) => return None,
+                    },
+                    _ = heartbeat.tick() => {
+                        let frame = Bytes::from_static(b": keepalive\n\n");
+                        return Some((Ok(frame), (missed, events, heartbeat)));
+                    }
+                }
+            }
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Keeps nginx from buffering the stream.
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream))
 }
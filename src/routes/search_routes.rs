@@ -0,0 +1,38 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::search_model::{SearchQuery, SearchResults},
+    routes::extractors::{AdminRole, IncludeDeleted, RequireRole},
+    services::repository::SearchRepository,
+};
+use actix_web::{
+    HttpResponse, get,
+    web::{Data, Query},
+};
+
+/// Owners and dogs matching the words of `q`, whichever collection they live in.
+/// Owners match on name or email, dogs on name or breed; names weigh more.
+#[utoipa::path(
+    tag = "search",
+    params(
+        SearchQuery,
+        ("include_deleted" = Option<bool>, Query, description = "Also return soft deleted documents, admins only"),
+    ),
+    responses(
+        (status = 200, description = "Best matches first", body = SearchResults),
+        (status = 400, description = "Empty or too long q, invalid limit", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/search")]
+pub async fn search(
+    index: Data<dyn SearchRepository>,
+    _admin: RequireRole<AdminRole>,
+    query: Query<SearchQuery>,
+    include_deleted: IncludeDeleted,
+) -> ApiResponse {
+    let (text, limit) = query.resolve().map_err(AppError::Validation)?;
+    let results = index.search(text, limit, include_deleted.0).await?;
+    Ok(HttpResponse::Ok().json(SearchResults { results }))
+}
@@ -0,0 +1,193 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        serde_helpers::WithId,
+        series_model::{BookingSeries, OccurrenceCancellation, SeriesCancellation},
+    },
+    routes::{
+        audit,
+        extractors::{AuthenticatedUser, ObjectIdPath, OwnerRole, RequireRole, parse_object_id},
+    },
+    services::{
+        db::cancellation_closed,
+        repository::{AuditRepository, BookingRepository},
+    },
+};
+use actix_web::{
+    HttpResponse, get, put,
+    web::{Data, Path},
+};
+use chrono::NaiveDate;
+use mongodb::bson::DateTime;
+use tracing::warn;
+
+#[utoipa::path(
+    tag = "series",
+    params(("id" = String, Path, description = "ObjectId of the series")),
+    responses(
+        (status = 200, description = "Recurring series", body = WithId<BookingSeries>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Series not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/series/{id}")]
+pub async fn get_series(
+    bookings: Data<dyn BookingRepository>,
+    user: AuthenticatedUser,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let series = bookings.get_series(&path.0).await?;
+    user.ensure_owns(&series.owner)?;
+    Ok(HttpResponse::Ok().json(WithId(series)))
+}
+
+/// Stop the series: no more walks are booked and the upcoming booked ones are
+/// cancelled under the cancellation policy, each with its fee. A walk that has
+/// already started is left alone.
+#[utoipa::path(
+    tag = "series",
+    params(("id" = String, Path, description = "ObjectId of the series")),
+    responses(
+        (status = 200, description = "Cancelled series with the walks cancelled", body = SeriesCancellation),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Series not found", body = ApiErrorBody),
+        (status = 409, description = "The series is already cancelled (`series_cancelled`)", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/series/{id}/cancel")]
+pub async fn cancel_series(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: RequireRole<OwnerRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let before = bookings.get_series(&path.0).await?;
+    user.ensure_owns(&before.owner)?;
+    let series = bookings.cancel_series(&path.0).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Cancel,
+        EntityRef::new(EntityKind::Series, path.0),
+        snapshot(&before),
+        snapshot(&series),
+    )
+    .await;
+
+    let mut cancelled = Vec::new();
+    for booking in bookings.get_series_bookings(&path.0).await? {
+        match bookings.cancel_booking(&booking._id).await {
+            Ok(after) => {
+                audit(
+                    audit_log.get_ref(),
+                    user.actor(),
+                    AuditAction::Cancel,
+                    EntityRef::new(EntityKind::Booking, booking._id),
+                    snapshot(&booking),
+                    snapshot(&after),
+                )
+                .await;
+                cancelled.push(WithId(after));
+            }
+            // Started or changed in the meantime, it stays as it is.
+            Err(AppError::Conflict { .. }) => {}
+            Err(err) => {
+                warn!(error = %err, booking_id = %booking._id, "Walk of the cancelled series not cancelled")
+            }
+        }
+    }
+    Ok(HttpResponse::Ok().json(SeriesCancellation {
+        series: WithId(series),
+        cancelled,
+    }))
+}
+
+/// Cancel the walk of one day (`YYYY-MM-DD`, UTC) and keep the rest of the series.
+/// A booked walk is cancelled under the cancellation policy, one not booked yet is
+/// skipped and never booked.
+#[utoipa::path(
+    tag = "series",
+    params(
+        ("id" = String, Path, description = "ObjectId of the series"),
+        ("date" = String, Path, description = "Day of the walk, `YYYY-MM-DD` in UTC"),
+    ),
+    responses(
+        (status = 200, description = "Walk cancelled or skipped", body = OccurrenceCancellation),
+        (status = 400, description = "Malformed id or date", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Series not found, or no walk of the series on that day", body = ApiErrorBody),
+        (status = 409, description = "The series is cancelled (`series_cancelled`), the walk started (`cancellation_closed`) or is already cancelled", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/series/{id}/occurrences/{date}/cancel")]
+pub async fn cancel_occurrence(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    user: RequireRole<OwnerRole>,
+    path: Path<(String, String)>,
+) -> ApiResponse {
+    let (series_id, date) = path.into_inner();
+    let series_id = parse_object_id(&series_id)?;
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|err| AppError::Validation(format!("`{}` is not a date: {}", date, err)))?;
+
+    let before = bookings.get_series(&series_id).await?;
+    user.ensure_owns(&before.owner)?;
+    let start = before
+        .occurrence_on(date)
+        .ok_or_else(|| AppError::NotFound("The series has no walk on this day".to_string()))?;
+    if start <= DateTime::now() {
+        return Err(cancellation_closed());
+    }
+
+    if start > before.materialized_until
+        && let Some(series) = bookings.skip_occurrence(&series_id, start).await?
+    {
+        audit(
+            audit_log.get_ref(),
+            user.actor(),
+            AuditAction::Cancel,
+            EntityRef::new(EntityKind::Series, series_id),
+            snapshot(&before),
+            snapshot(&series),
+        )
+        .await;
+        return Ok(HttpResponse::Ok().json(OccurrenceCancellation {
+            series: WithId(series),
+            booking: None,
+        }));
+    }
+
+    // Booked already, possibly by the job in the meantime.
+    let booking = bookings
+        .get_series_bookings(&series_id)
+        .await?
+        .into_iter()
+        .find(|booking| booking.start_time == start)
+        .ok_or_else(|| {
+            AppError::conflict("The walk of this day is not booked or already cancelled")
+        })?;
+    let after = bookings.cancel_booking(&booking._id).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Cancel,
+        EntityRef::new(EntityKind::Booking, booking._id),
+        snapshot(&booking),
+        snapshot(&after),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(OccurrenceCancellation {
+        series: WithId(bookings.get_series(&series_id).await?),
+        booking: Some(WithId(after)),
+    }))
+}
@@ -0,0 +1,101 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        owner_model::OwnerWithDogs,
+        serde_helpers::WithId,
+        vaccination_model::RabiesPolicy,
+        waitlist_model::{WaitlistEntry, WaitlistPlace, WaitlistRequest, WaitlistStatus},
+    },
+    routes::{audit, booking_routes::ensure_vaccinated, extractors::AuthenticatedUser},
+    services::repository::{AuditRepository, BookingRepository, OwnerRepository},
+};
+use actix_web::{
+    HttpResponse, post,
+    web::{Data, Json},
+};
+use validator::Validate;
+
+/// Queue for a walk `POST /booking` refused with `capacity_reached`. The walk is
+/// booked for the oldest waiting owner once a walker frees up, and the owner is
+/// emailed unless turned off in its `waitlist_booked` preference; `GET /owner/{id}/waitlist` shows the place in the queue.
+#[utoipa::path(
+    tag = "waitlist",
+    request_body = WaitlistRequest,
+    responses(
+        (status = 201, description = "Owner queued for the slot", body = WaitlistPlace),
+        (status = 400, description = "The walk is in the past", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user, or the owner's email is not verified", body = ApiErrorBody),
+        (status = 404, description = "Owner not found", body = ApiErrorBody),
+        (status = 409, description = "The slot has room, book it instead (`slot_available`), the owner already waits for an overlapping slot (`already_waitlisted`) or a dog's rabies vaccination is missing or expired (`vaccination_required`)", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/waitlist")]
+pub async fn join_waitlist(
+    owners: Data<dyn OwnerRepository>,
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    rabies_policy: Data<RabiesPolicy>,
+    user: AuthenticatedUser,
+    request: Json<WaitlistRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    let entry = WaitlistEntry::try_from(request.into_inner()).map_err(AppError::Validation)?;
+    user.ensure_owns(&entry.owner)?;
+
+    let OwnerWithDogs { owner, dogs } = owners.get_owner_full(&entry.owner, false).await?;
+    if !owner.0.email_verified {
+        return Err(AppError::Forbidden(
+            "The owner must verify its email before booking".to_string(),
+        ));
+    }
+    ensure_vaccinated(**rabies_policy, &dogs, entry.start_time)?;
+    if !bookings
+        .is_slot_full(entry.start_time, entry.end_time())
+        .await?
+    {
+        return Err(AppError::Conflict {
+            code: "slot_available",
+            message: "A walker is free for this time slot, book it with POST /booking".to_string(),
+            details: None,
+        });
+    }
+
+    bookings.create_waitlist_entry(&entry).await?;
+    audit(
+        audit_log.get_ref(),
+        user.actor(),
+        AuditAction::Create,
+        EntityRef::new(EntityKind::Waitlist, entry._id),
+        None,
+        snapshot(&entry),
+    )
+    .await;
+    let position = bookings.waitlist_position(&entry).await?;
+    Ok(HttpResponse::Created().json(WaitlistPlace {
+        entry: WithId(entry),
+        position: Some(position),
+    }))
+}
+
+/// Places of the entries of an owner, `None` for the ones no longer waiting.
+pub async fn waitlist_places(
+    bookings: &dyn BookingRepository,
+    entries: Vec<WaitlistEntry>,
+) -> Result<Vec<WaitlistPlace>, AppError> {
+    let mut places = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let position = match entry.status {
+            WaitlistStatus::Waiting => Some(bookings.waitlist_position(&entry).await?),
+            _ => None,
+        };
+        places.push(WaitlistPlace {
+            entry: WithId(entry),
+            position,
+        });
+    }
+    Ok(places)
+}
@@ -0,0 +1,350 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, EntityKind, EntityRef, snapshot},
+        availability_model::{
+            AvailabilityRequest, BookedRange, TimeRange, WalkerAvailability, WeekAvailability,
+            WeekQuery, parse_week, week_name,
+        },
+        booking_model::parse_rfc3339,
+        geo_model::{NearQuery, NearbyWalker},
+        page_model::{Page, PageQuery},
+        payout_model::{EarningsQuery, WalkerEarnings},
+        result_model::InsertedId,
+        review_model::RatedWalker,
+        serde_helpers::WithId,
+        walker_model::{Walker, WalkerRequest, WalkerUpdateRequest},
+    },
+    routes::{
+        audit,
+        extractors::{AdminRole, AuthenticatedUser, ObjectIdPath, RequireRole, WalkerRole},
+    },
+    services::db::{Database, booking_end},
+};
+use actix_web::{
+    HttpResponse, delete, get, post, put,
+    web::{Data, Json, Query},
+};
+use mongodb::bson::DateTime;
+use validator::Validate;
+
+#[utoipa::path(
+    tag = "walkers",
+    request_body = WalkerRequest,
+    responses(
+        (status = 200, description = "Walker created", body = InsertedId),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/walker")]
+pub async fn create_walker(
+    db: Data<Database>,
+    admin: RequireRole<AdminRole>,
+    request: Json<WalkerRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let walker = Walker::try_from(request.into_inner())
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+    let entity = EntityRef::new(EntityKind::Walker, walker._id);
+    let after = snapshot(&walker);
+
+    let result = db.create_walker(walker).await?;
+    audit(
+        db.get_ref(),
+        admin.actor(),
+        AuditAction::Create,
+        entity,
+        None,
+        after,
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[utoipa::path(
+    tag = "walkers",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Page of walkers", body = Page<WithId<Walker>>),
+        (status = 400, description = "Invalid page or limit", body = ApiErrorBody),
+    )
+)]
+#[get("/walkers")]
+pub async fn get_walkers(db: Data<Database>, query: Query<PageQuery>) -> ApiResponse {
+    let (page, limit) = query.resolve().map_err(AppError::Validation)?;
+
+    let walkers = db.get_walkers(page, limit).await?;
+    Ok(HttpResponse::Ok().json(walkers))
+}
+
+/// Walkers who set a `location` within `max_km` of a point, closest first.
+#[utoipa::path(
+    tag = "walkers",
+    params(NearQuery),
+    responses(
+        (status = 200, description = "Walkers in range", body = Vec<NearbyWalker>),
+        (status = 400, description = "Invalid coordinates, max_km or limit", body = ApiErrorBody),
+    )
+)]
+#[get("/walkers/near")]
+pub async fn get_walkers_near(db: Data<Database>, query: Query<NearQuery>) -> ApiResponse {
+    let (center, max_meters, limit) = query.resolve().map_err(AppError::Validation)?;
+
+    let walkers = db.get_walkers_near(center, max_meters, limit).await?;
+    Ok(HttpResponse::Ok().json(walkers))
+}
+
+#[utoipa::path(
+    tag = "walkers",
+    params(("id" = String, Path, description = "ObjectId of the walker")),
+    responses(
+        (status = 200, description = "Walker with the average of its reviews", body = RatedWalker),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+    )
+)]
+#[get("/walker/{id}")]
+pub async fn get_walker(db: Data<Database>, path: ObjectIdPath) -> ApiResponse {
+    let walker = db.get_walker(&path.0).await?;
+    let rating = db.get_walker_rating(&path.0).await?;
+    Ok(HttpResponse::Ok().json(RatedWalker {
+        walker: WithId(walker),
+        rating,
+    }))
+}
+
+#[utoipa::path(
+    tag = "walkers",
+    request_body = WalkerUpdateRequest,
+    params(("id" = String, Path, description = "ObjectId of the walker")),
+    responses(
+        (status = 200, description = "Updated walker", body = WithId<Walker>),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[put("/walker/{id}")]
+pub async fn update_walker(
+    db: Data<Database>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+    request: Json<WalkerUpdateRequest>,
+) -> ApiResponse {
+    request.validate()?;
+
+    let before = db.get_walker(&path.0).await?;
+    let walker = db.update_walker(&path.0, &request).await?;
+    audit(
+        db.get_ref(),
+        admin.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Walker, path.0),
+        snapshot(&before),
+        snapshot(&walker),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(walker)))
+}
+
+#[utoipa::path(
+    tag = "walkers",
+    params(("id" = String, Path, description = "ObjectId of the walker")),
+    responses(
+        (status = 204, description = "Walker deleted"),
+        (status = 400, description = "Malformed id", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[delete("/walker/{id}")]
+pub async fn delete_walker(
+    db: Data<Database>,
+    admin: RequireRole<AdminRole>,
+    path: ObjectIdPath,
+) -> ApiResponse {
+    let before = db.get_walker(&path.0).await?;
+    db.delete_walker(&path.0).await?;
+    audit(
+        db.get_ref(),
+        admin.actor(),
+        AuditAction::Delete,
+        EntityRef::new(EntityKind::Walker, path.0),
+        snapshot(&before),
+        None,
+    )
+    .await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// What the walker earned per day, week or month, from the payout ledger credited
+/// when their walks complete, with the part already settled.
+#[utoipa::path(
+    tag = "walkers",
+    params(
+        ("id" = String, Path, description = "ObjectId of the walker"),
+        EarningsQuery,
+    ),
+    responses(
+        (status = 200, description = "Earnings of the walker, oldest period first", body = WalkerEarnings),
+        (status = 400, description = "Malformed id, period or dates", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Earnings of another walker", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/walker/{id}/earnings")]
+pub async fn get_walker_earnings(
+    db: Data<Database>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+    query: Query<EarningsQuery>,
+) -> ApiResponse {
+    if !user.is_admin() && user.user_id != path.0 {
+        return Err(AppError::Forbidden(
+            "These earnings belong to another walker".to_string(),
+        ));
+    }
+    let from = query
+        .from
+        .as_deref()
+        .map(parse_rfc3339)
+        .transpose()
+        .map_err(AppError::Validation)?;
+    let to = query
+        .to
+        .as_deref()
+        .map(parse_rfc3339)
+        .transpose()
+        .map_err(AppError::Validation)?;
+
+    db.get_walker(&path.0).await?;
+    let buckets = db
+        .get_walker_earnings(&path.0, query.period, from, to)
+        .await?;
+    Ok(HttpResponse::Ok().json(WalkerEarnings {
+        walker: path.0,
+        period: query.period,
+        buckets,
+    }))
+}
+
+/// Replace the weekly hours and the time off of the walker, checked by
+/// `POST /booking/{id}/assign/{walker_id}`. Bookings already assigned are kept.
+#[utoipa::path(
+    tag = "walkers",
+    request_body = AvailabilityRequest,
+    params(("id" = String, Path, description = "ObjectId of the walker")),
+    responses(
+        (status = 200, description = "New availability of the walker", body = WithId<WalkerAvailability>),
+        (status = 400, description = "Malformed id, slots or blocks ending before they start, or overlapping slots", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Availability of another walker", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+        (status = 422, description = "Some fields are invalid", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[post("/walker/{id}/availability")]
+pub async fn set_walker_availability(
+    db: Data<Database>,
+    user: RequireRole<WalkerRole>,
+    path: ObjectIdPath,
+    request: Json<AvailabilityRequest>,
+) -> ApiResponse {
+    request.validate()?;
+    if !user.is_admin() && user.user_id != path.0 {
+        return Err(AppError::Forbidden(
+            "This availability belongs to another walker".to_string(),
+        ));
+    }
+
+    db.get_walker(&path.0).await?;
+    let availability =
+        WalkerAvailability::new(path.0, request.into_inner()).map_err(AppError::Validation)?;
+    let before = db.get_walker_availability(&path.0).await?;
+    db.set_walker_availability(&availability).await?;
+    audit(
+        db.get_ref(),
+        user.actor(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Walker, path.0),
+        before.as_ref().and_then(snapshot),
+        snapshot(&availability),
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(WithId(availability)))
+}
+
+/// Working hours, time off and bookings of the walker over one week, in UTC.
+#[utoipa::path(
+    tag = "walkers",
+    params(
+        ("id" = String, Path, description = "ObjectId of the walker"),
+        WeekQuery,
+    ),
+    responses(
+        (status = 200, description = "Week of the walker", body = WeekAvailability),
+        (status = 400, description = "Malformed id or week", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 404, description = "Walker not found", body = ApiErrorBody),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[get("/walker/{id}/availability")]
+pub async fn get_walker_availability(
+    db: Data<Database>,
+    _user: AuthenticatedUser,
+    path: ObjectIdPath,
+    query: Query<WeekQuery>,
+) -> ApiResponse {
+    let monday = parse_week(query.week.as_deref()).map_err(AppError::Validation)?;
+    let from = DateTime::from_millis(
+        monday
+            .and_hms_opt(0, 0, 0)
+            .map(|midnight| midnight.and_utc().timestamp_millis())
+            .unwrap_or_default(),
+    );
+    let to = DateTime::from_millis(from.timestamp_millis() + 7 * 24 * 60 * 60 * 1000);
+
+    db.get_walker(&path.0).await?;
+    let availability = db.get_walker_availability(&path.0).await?;
+    let booked = db
+        .get_walker_bookings(&path.0, from, to)
+        .await?
+        .into_iter()
+        .map(|booking| BookedRange {
+            booking: booking._id,
+            from: booking.start_time,
+            to: booking_end(&booking),
+        })
+        .collect();
+    let (hours, blocks) = match availability {
+        Some(availability) => (
+            availability.hours(from),
+            availability
+                .blocks
+                .into_iter()
+                .filter(|block| block.from < to && from < block.to)
+                .collect(),
+        ),
+        None => (vec![TimeRange { from, to }], Vec::new()),
+    };
+    Ok(HttpResponse::Ok().json(WeekAvailability {
+        walker: path.0,
+        week: week_name(monday),
+        hours,
+        blocks,
+        booked,
+    }))
+}
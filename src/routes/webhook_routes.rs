@@ -0,0 +1,77 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::{
+        audit_model::{AuditAction, AuditActor, EntityKind, EntityRef, snapshot},
+        invoice_model::PaymentStatus,
+    },
+    routes::audit,
+    services::{
+        payments::{PaymentEvent, PaymentProvider},
+        repository::{AuditRepository, BookingRepository},
+    },
+};
+use actix_web::{
+    HttpRequest, HttpResponse, post,
+    web::{Bytes, Data},
+};
+
+/// Payment outcomes reported by Stripe: `payment_intent.succeeded` marks the
+/// invoice paid, `payment_intent.payment_failed` failed. Other events, intents
+/// without an invoice yet and repeated deliveries are acknowledged and ignored.
+#[utoipa::path(
+    tag = "invoices",
+    request_body(content = Object, description = "Stripe event"),
+    params(
+        ("Stripe-Signature" = String, Header, description = "Signature of the body under `STRIPE_WEBHOOK_SECRET`, not checked by the mock provider"),
+    ),
+    responses(
+        (status = 204, description = "Event handled or ignored"),
+        (status = 400, description = "Missing or invalid signature, or malformed event", body = ApiErrorBody),
+        (status = 409, description = "The invoice can't take this payment status", body = ApiErrorBody),
+    )
+)]
+#[post("/webhooks/stripe")]
+pub async fn stripe_webhook(
+    bookings: Data<dyn BookingRepository>,
+    audit_log: Data<dyn AuditRepository>,
+    payments: Data<dyn PaymentProvider>,
+    req: HttpRequest,
+    body: Bytes,
+) -> ApiResponse {
+    let signature = req
+        .headers()
+        .get("Stripe-Signature")
+        .and_then(|value| value.to_str().ok());
+    let event = payments
+        .webhook_event(&body, signature)
+        .map_err(AppError::Validation)?;
+    let (intent_id, next) = match event {
+        Some(PaymentEvent::Succeeded { intent_id }) => (intent_id, PaymentStatus::Paid),
+        Some(PaymentEvent::Failed { intent_id }) => (intent_id, PaymentStatus::Failed),
+        None => return Ok(HttpResponse::NoContent().finish()),
+    };
+
+    // A card declined before the walk completed has no invoice yet.
+    let before = match bookings.get_invoice_by_payment_reference(&intent_id).await {
+        Ok(invoice) => invoice,
+        Err(AppError::NotFound(_)) => return Ok(HttpResponse::NoContent().finish()),
+        Err(err) => return Err(err),
+    };
+    if before.payment_status == next {
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    let invoice = bookings
+        .update_payment(&before._id, next, Some(intent_id))
+        .await?;
+    audit(
+        audit_log.get_ref(),
+        AuditActor::anonymous(),
+        AuditAction::Update,
+        EntityRef::new(EntityKind::Invoice, invoice._id),
+        snapshot(&before),
+        snapshot(&invoice),
+    )
+    .await;
+    Ok(HttpResponse::NoContent().finish())
+}
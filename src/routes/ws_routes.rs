@@ -0,0 +1,75 @@
+use crate::{
+    errors::{ApiErrorBody, ApiResponse, AppError},
+    models::booking_model::BookingUpdate,
+    routes::extractors::{AdminRole, RequireRole},
+    services::repository::BookingRepository,
+};
+use actix_web::{
+    HttpRequest, get,
+    web::{Data, Payload},
+};
+use actix_ws::Message;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+/// Live feed of the dashboards: once upgraded to a WebSocket, one JSON text message
+/// per booking created, assigned, cancelled or completed from then on. Messages
+/// sent by the client are ignored, pings are answered.
+#[utoipa::path(
+    tag = "bookings",
+    responses(
+        (status = 101, description = "Switched to a WebSocket carrying the updates", body = BookingUpdate),
+        (status = 400, description = "Not a WebSocket handshake", body = ApiErrorBody),
+        (status = 401, description = "Missing or invalid credentials", body = ApiErrorBody),
+        (status = 403, description = "Not allowed for this user", body = ApiErrorBody),
+    ),
+    security(("bearer" = []))
+)]
+#[get("/ws/bookings")]
+pub async fn booking_updates(
+    req: HttpRequest,
+    body: Payload,
+    bookings: Data<dyn BookingRepository>,
+    _admin: RequireRole<AdminRole>,
+) -> ApiResponse {
+    let (response, mut session, mut messages) =
+        actix_ws::handle(&req, body).map_err(|err| AppError::Validation(err.to_string()))?;
+    let mut updates = bookings.booking_updates().subscribe();
+
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                update = updates.recv() => match update {
+                    Ok(update) => {
+                        let Ok(text) = serde_json::to_string(&update) else {
+                            continue;
+                        };
+                        if session.text(text).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Booking updates skipped, the WebSocket client is too slow");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                message = messages.recv() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(reason))) => {
+                        let _ = session.close(reason).await;
+                        return;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => break,
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::SaltString};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::ErrorKind,
+};
+use mongodb::bson::oid::ObjectId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    config::AuthConfig,
+    models::{
+        auth_model::{Role, TokenResponse},
+        serde_helpers::{deserialize_object_id, serialize_object_id_as_hex},
+    },
+    services::tokens::TokenError,
+};
+
+/// Validity of a password reset link when `auth.password_reset_ttl_secs` is not set (1 hour).
+const DEFAULT_RESET_TTL_SECS: u64 = 60 * 60;
+
+/// Claims of the JWT returned by `POST /auth/login`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessClaims {
+    /// Id of the authenticated owner, walker or admin.
+    #[serde(
+        serialize_with = "serialize_object_id_as_hex",
+        deserialize_with = "deserialize_object_id"
+    )]
+    pub sub: ObjectId,
+    pub role: Role,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Hashes passwords with Argon2id and issues/verifies HS256 access tokens.
+pub struct Authenticator {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    ttl: Duration,
+    reset_ttl: Duration,
+}
+
+impl Authenticator {
+    pub fn new(secret: &[u8], ttl: Duration) -> Self {
+        Authenticator {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            ttl,
+            reset_ttl: Duration::from_secs(DEFAULT_RESET_TTL_SECS),
+        }
+    }
+
+    /// Signing secret and lifetimes of `config`. Without a secret, which `Config::load`
+    /// only allows with `--in-memory`, a random one is generated, so every session
+    /// ends with the process.
+    pub fn from_config(config: &AuthConfig) -> Self {
+        let secret = match &config.jwt_secret {
+            Some(secret) => secret.clone().into_bytes(),
+            None => {
+                warn!("JWT_SECRET is not set, using a random secret for this process");
+                let mut secret = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut secret);
+                secret
+            }
+        };
+
+        Authenticator {
+            reset_ttl: Duration::from_secs(config.password_reset_ttl_secs),
+            ..Authenticator::new(&secret, Duration::from_secs(config.jwt_ttl_secs))
+        }
+    }
+
+    pub fn reset_ttl(&self) -> Duration {
+        self.reset_ttl
+    }
+
+    /// Hash a password with a random salt, the result embeds the salt and parameters.
+    pub fn hash_password(&self, password: &str) -> String {
+        let mut salt = [0u8; 16];
+        rand::rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt).expect("16 bytes is a valid salt length");
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("Argon2 with default parameters can't fail")
+            .to_string()
+    }
+
+    /// False for a wrong password as well as for a hash that can't be parsed.
+    pub fn verify_password(&self, password: &str, password_hash: &str) -> bool {
+        PasswordHash::new(password_hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Issue an access token for this user, valid for the configured TTL.
+    pub fn issue(&self, user_id: ObjectId, role: Role) -> TokenResponse {
+        let now = chrono::Utc::now().timestamp();
+        let claims = AccessClaims {
+            sub: user_id,
+            role,
+            iat: now,
+            exp: now + self.ttl.as_secs() as i64,
+        };
+
+        TokenResponse {
+            access_token: encode(&Header::default(), &claims, &self.encoding_key)
+                .expect("Failed to encode access token"),
+            role,
+            token_type: "Bearer",
+            expires_in: self.ttl.as_secs(),
+        }
+    }
+
+    pub fn verify(&self, token: &str) -> Result<AccessClaims, TokenError> {
+        decode::<AccessClaims>(
+            token,
+            &self.decoding_key,
+            &Validation::new(Algorithm::HS256),
+        )
+        .map(|data| data.claims)
+        .map_err(|err| match err.kind() {
+            ErrorKind::ExpiredSignature => TokenError::Expired,
+            _ => TokenError::Invalid,
+        })
+    }
+}
+
+/// Random single-use token for an emailed link (password reset, email verification),
+/// returned with its SHA-256 (hex) to store instead of the token itself.
+pub fn one_time_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    let hash = hash_one_time_token(&token);
+    (token, hash)
+}
+
+/// SHA-256 (hex) of a one-time token, the form it is stored and looked up in.
+pub fn hash_one_time_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,40 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use tokio::sync::broadcast;
+
+use crate::models::booking_model::{BookingUpdate, BookingUpdateKind};
+
+/// Updates a subscriber can fall behind by before it skips the oldest ones.
+const CAPACITY: usize = 256;
+
+/// Booking changes, read from the booking change stream with MongoDB so every
+/// replica sees every change whoever made it, and forwarded to the dashboards
+/// subscribed on `GET /ws/bookings`. Nothing is kept: a change made while nobody
+/// listens is dropped.
+#[derive(Debug, Clone)]
+pub struct BookingUpdates {
+    sender: broadcast::Sender<BookingUpdate>,
+}
+
+impl Default for BookingUpdates {
+    fn default() -> Self {
+        BookingUpdates {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl BookingUpdates {
+    pub fn publish(&self, event: BookingUpdateKind, booking_id: &ObjectId) {
+        // Only fails when nobody is subscribed.
+        let _ = self.sender.send(BookingUpdate {
+            event,
+            booking_id: booking_id.to_hex(),
+            at: DateTime::now().try_to_rfc3339_string().unwrap_or_default(),
+        });
+    }
+
+    /// Every update published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<BookingUpdate> {
+        self.sender.subscribe()
+    }
+}
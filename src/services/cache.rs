@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::owner_model::Owner;
+
+/// Snapshot of the cache counters, returned by `GET /admin/cache/stats`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub ttl_secs: u64,
+}
+
+/// In-process cache of owner documents keyed by their ObjectId.
+/// Owners change rarely but are read on every booking validation,
+/// so we keep them in memory for `ttl` and drop them as soon as
+/// an owner is updated or deleted (see `invalidate`).
+pub struct OwnerCache {
+    entries: RwLock<HashMap<ObjectId, (Owner, Instant)>>,
+    /// Bumped by every `invalidate` and `purge`, so an owner read before one of
+    /// them is not cached after it (see `insert`).
+    generation: AtomicU64,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl OwnerCache {
+    pub fn new(ttl: Duration) -> Self {
+        OwnerCache {
+            entries: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Return the cached owner if present and not expired.
+    /// Expired entries count as a miss and are evicted.
+    pub fn get(&self, id: &ObjectId) -> Option<Owner> {
+        {
+            let entries = self.entries.read().unwrap();
+            if let Some((owner, inserted_at)) = entries.get(id)
+                && inserted_at.elapsed() < self.ttl
+            {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Some(owner.clone());
+            }
+        }
+
+        self.entries.write().unwrap().remove(id);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// To read before fetching an owner from MongoDB, and hand to `insert` with it.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Cache `owner`, fetched when the cache was at `generation`. Skipped when an
+    /// owner was invalidated since: the fetch may have raced with its update or
+    /// delete and returned the document as it was before.
+    pub fn insert(&self, owner: Owner, generation: u64) {
+        let mut entries = self.entries.write().unwrap();
+        if self.generation.load(Ordering::Acquire) == generation {
+            entries.insert(owner._id, (owner, Instant::now()));
+        }
+    }
+
+    /// Drop a single owner, must be called by every path that updates or deletes an
+    /// owner, once the write is done.
+    pub fn invalidate(&self, id: &ObjectId) {
+        let mut entries = self.entries.write().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.remove(id);
+    }
+
+    /// Drop every cached owner.
+    pub fn purge(&self) {
+        let mut entries = self.entries.write().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.read().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            ttl_secs: self.ttl.as_secs(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::{doc, from_document};
+
+    use super::*;
+
+    fn owner(id: ObjectId, email: &str) -> Owner {
+        from_document(doc! {
+            "_id": id,
+            "name": "Jane",
+            "email": email,
+            "phone": "+33600000000",
+            "address": "1 rue de la Paix",
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn hit_returns_the_cached_owner() {
+        let cache = OwnerCache::new(Duration::from_secs(60));
+        let id = ObjectId::new();
+        cache.insert(owner(id, "jane@example.com"), cache.generation());
+
+        assert_eq!(cache.get(&id).unwrap().email, "jane@example.com");
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 0, 1));
+    }
+
+    #[test]
+    fn miss_on_unknown_or_expired_owner() {
+        let cache = OwnerCache::new(Duration::ZERO);
+        let id = ObjectId::new();
+        assert!(cache.get(&id).is_none());
+
+        cache.insert(owner(id, "jane@example.com"), cache.generation());
+        assert!(cache.get(&id).is_none());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (0, 2, 0));
+    }
+
+    #[test]
+    fn invalidate_drops_the_owner() {
+        let cache = OwnerCache::new(Duration::from_secs(60));
+        let id = ObjectId::new();
+        cache.insert(owner(id, "jane@example.com"), cache.generation());
+
+        cache.invalidate(&id);
+        assert!(cache.get(&id).is_none());
+        cache.insert(owner(id, "jane@example.org"), cache.generation());
+        assert_eq!(cache.get(&id).unwrap().email, "jane@example.org");
+    }
+
+    #[test]
+    fn owner_read_before_an_invalidation_is_not_cached() {
+        let cache = OwnerCache::new(Duration::from_secs(60));
+        let id = ObjectId::new();
+        let generation = cache.generation();
+        // The owner is updated and invalidated while the stale document is in flight.
+        cache.invalidate(&id);
+        cache.insert(owner(id, "jane@example.com"), generation);
+        assert!(cache.get(&id).is_none());
+
+        let generation = cache.generation();
+        cache.purge();
+        cache.insert(owner(id, "jane@example.com"), generation);
+        assert!(cache.get(&id).is_none());
+    }
+}
@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use actix_web::web::Data;
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{Bson, Document, from_document, to_bson},
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    error::ErrorKind,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    errors::AppError,
+    models::{
+        booking_model::{Booking, BookingStatus, BookingUpdateKind},
+        webhook_model::WebhookEvent,
+    },
+    services::{db::Database, repository::BookingRepository, webhooks},
+};
+
+/// Name the resume token of the booking stream is saved under.
+const BOOKINGS: &str = "booking";
+/// Wait before watching again once the stream failed.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Watch the changes of the `booking` collection, whoever makes them (this
+/// replica, another service or a fix by hand), and feed them to the dashboards
+/// (`GET /ws/bookings`), the owners (`GET /owner/{id}/events`) and the webhooks.
+/// The stream resumes where it stopped after a failure or a restart; once the
+/// oplog no longer goes back that far, it starts over from now.
+pub fn spawn_booking_watcher(db: Data<Database>) {
+    actix_web::rt::spawn(async move {
+        loop {
+            if let Err(err) = watch(&db).await {
+                if history_lost(&err) {
+                    warn!(error = %err, "Booking changes lost, watching from now on");
+                    if let Err(err) = db.delete_resume_token(BOOKINGS).await {
+                        error!(error = %err, "Failed to forget the booking change stream position");
+                    }
+                } else {
+                    error!(error = %err, "Booking change stream failed");
+                }
+            }
+            actix_web::rt::time::sleep(RETRY_DELAY).await;
+        }
+    });
+}
+
+async fn watch(db: &Database) -> Result<(), AppError> {
+    let resume_token = db.get_resume_token(BOOKINGS).await?;
+    let mut changes = db.watch_bookings(resume_token).await?;
+    info!("Watching the booking changes");
+    while let Some(change) = changes.next().await {
+        let change = change?;
+        dispatch(db, &change).await;
+        db.save_resume_token(BOOKINGS, &change.id).await?;
+    }
+    Ok(())
+}
+
+/// Publish one change of a booking. Deletions have nobody left to tell.
+async fn dispatch(db: &Database, change: &ChangeStreamEvent<Document>) {
+    let Some(booking) = change.full_document.clone() else {
+        return;
+    };
+    let booking: Booking = match from_document(booking) {
+        Ok(booking) => booking,
+        Err(err) => {
+            warn!(error = %err, "Skipped a booking change that doesn't read as a booking");
+            return;
+        }
+    };
+    let change_id = change_id(&change.id);
+    match change.operation_type {
+        OperationType::Insert => {
+            db.booking_updates()
+                .publish(BookingUpdateKind::Created, &booking._id);
+            db.owner_events()
+                .booking_status(booking.owner, &booking._id, booking.status);
+            webhooks::queue(db, WebhookEvent::Created, &booking, &change_id).await;
+        }
+        OperationType::Update => {
+            let Some(updated) = change
+                .update_description
+                .as_ref()
+                .map(|description| &description.updated_fields)
+            else {
+                return;
+            };
+            if matches!(updated.get("walker"), Some(Bson::ObjectId(_))) {
+                db.booking_updates()
+                    .publish(BookingUpdateKind::Assigned, &booking._id);
+            }
+            if updated.contains_key("status") {
+                status_changed(db, &booking, &change_id).await;
+            }
+        }
+        OperationType::Replace => status_changed(db, &booking, &change_id).await,
+        _ => {}
+    }
+}
+
+/// `booking` now has its current status, whether it changed or not.
+async fn status_changed(db: &Database, booking: &Booking, change_id: &str) {
+    db.owner_events()
+        .booking_status(booking.owner, &booking._id, booking.status);
+    if let Some(event) = booking.status.update_kind() {
+        db.booking_updates().publish(event, &booking._id);
+    }
+    let webhook_event = match booking.status {
+        BookingStatus::Cancelled => WebhookEvent::Cancelled,
+        BookingStatus::Completed => WebhookEvent::Completed,
+        _ => return,
+    };
+    webhooks::queue(db, webhook_event, booking, change_id).await;
+}
+
+/// The same for a change on every replica: the opaque data of its resume token.
+fn change_id(resume_token: &ResumeToken) -> String {
+    match to_bson(resume_token) {
+        Ok(Bson::Document(token)) => match token.get("_data") {
+            Some(Bson::String(data)) => data.clone(),
+            _ => token.to_string(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// The position to resume from is gone from the oplog (ChangeStreamHistoryLost)
+/// or can't be resumed from (ChangeStreamFatalError).
+fn history_lost(err: &AppError) -> bool {
+    matches!(
+        err,
+        AppError::Database(err) if matches!(
+            err.kind.as_ref(),
+            ErrorKind::Command(command_error) if command_error.code == 286 || command_error.code == 280
+        )
+    )
+}
@@ -0,0 +1,312 @@
+use std::{future::Future, sync::Mutex, time::Duration};
+
+use mongodb::bson::DateTime;
+use serde::Serialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{config::MongoConfig, errors::AppError, services::retry::is_transient};
+
+tokio::task_local! {
+    /// Set while a call let through by the breaker runs, so the `Database` calls it
+    /// makes are neither refused nor counted a second time.
+    static ADMITTED: ();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// MongoDB answers, every call goes through.
+    Closed,
+    /// MongoDB failed `mongo.breaker_failures` calls in a row, calls are refused
+    /// with 503 until `retry_at`.
+    Open,
+    /// One probe call is let through, its outcome closes or opens the breaker again.
+    HalfOpen,
+}
+
+/// State and counters of the breaker since the start, returned by `GET /health`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BreakerStats {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    /// Times the breaker opened.
+    pub opened: u64,
+    /// Calls refused without reaching MongoDB.
+    pub rejected: u64,
+    pub last_opened_at: Option<String>,
+    /// When the next probe goes through, while open.
+    pub retry_at: Option<String>,
+}
+
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    probing: bool,
+    opened: u64,
+    rejected: u64,
+    last_opened_at: Option<DateTime>,
+    retry_at: Option<DateTime>,
+}
+
+/// Fails the `Database` calls fast while MongoDB is down, rather than letting each
+/// request wait for its server selection timeout: opens after `mongo.breaker_failures`
+/// consecutive calls failed on MongoDB being out of reach, refuses every call for
+/// `mongo.breaker_open_secs`, then lets one probe through.
+pub struct CircuitBreaker {
+    failures: u32,
+    open_for: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &MongoConfig) -> Self {
+        CircuitBreaker {
+            failures: config.breaker_failures.max(1),
+            open_for: Duration::from_secs(config.breaker_open_secs),
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                probing: false,
+                opened: 0,
+                rejected: 0,
+                last_opened_at: None,
+                retry_at: None,
+            }),
+        }
+    }
+
+    /// Run `operation` unless the breaker is open, and count its outcome: only the
+    /// errors of MongoDB being out of reach are failures, a missing document or a
+    /// conflict means it answered.
+    pub async fn call<T>(
+        &self,
+        operation: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        if ADMITTED.try_with(|_| ()).is_ok() {
+            return operation.await;
+        }
+        let probe = self.admit()?;
+        let mut pending = PendingProbe {
+            breaker: self,
+            probe,
+        };
+        let result = ADMITTED.scope((), operation).await;
+        pending.probe = false;
+        self.record(result.as_ref().err().is_some_and(is_outage), probe);
+        result
+    }
+
+    pub fn stats(&self) -> BreakerStats {
+        let inner = self.inner.lock().unwrap();
+        let rfc3339 = |at: DateTime| at.try_to_rfc3339_string().unwrap_or_default();
+        BreakerStats {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            opened: inner.opened,
+            rejected: inner.rejected,
+            last_opened_at: inner.last_opened_at.map(rfc3339),
+            retry_at: inner
+                .retry_at
+                .filter(|_| inner.state == BreakerState::Open)
+                .map(rfc3339),
+        }
+    }
+
+    /// Whether the call can go, and whether it is the probe of a half-open breaker.
+    fn admit(&self) -> Result<bool, AppError> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = DateTime::now();
+        match inner.state {
+            BreakerState::Closed => Ok(false),
+            BreakerState::Open => match inner.retry_at {
+                Some(retry_at) if retry_at > now => {
+                    inner.rejected += 1;
+                    let wait = retry_at.timestamp_millis() - now.timestamp_millis();
+                    Err(refused(wait.max(0) as u64))
+                }
+                _ => {
+                    inner.state = BreakerState::HalfOpen;
+                    inner.probing = true;
+                    Ok(true)
+                }
+            },
+            BreakerState::HalfOpen if inner.probing => {
+                inner.rejected += 1;
+                Err(refused(0))
+            }
+            BreakerState::HalfOpen => {
+                inner.probing = true;
+                Ok(true)
+            }
+        }
+    }
+
+    fn record(&self, failed: bool, probe: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if probe {
+            inner.probing = false;
+        }
+        if !failed {
+            if inner.state != BreakerState::Closed {
+                info!("Database circuit breaker closed, MongoDB answers again");
+            }
+            inner.state = BreakerState::Closed;
+            inner.consecutive_failures = 0;
+            return;
+        }
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let open = match inner.state {
+            BreakerState::Closed => inner.consecutive_failures >= self.failures,
+            BreakerState::HalfOpen => probe,
+            BreakerState::Open => false,
+        };
+        if open {
+            let now = DateTime::now();
+            inner.state = BreakerState::Open;
+            inner.opened += 1;
+            inner.last_opened_at = Some(now);
+            inner.retry_at = Some(DateTime::from_millis(
+                now.timestamp_millis() + self.open_for.as_millis() as i64,
+            ));
+            warn!(
+                consecutive_failures = inner.consecutive_failures,
+                open_secs = self.open_for.as_secs(),
+                "Database circuit breaker opened, failing fast"
+            );
+        }
+    }
+}
+
+/// Gives the probe back when the call is dropped before it finished (the client
+/// went away), so the next call probes instead of the breaker staying half-open.
+struct PendingProbe<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl Drop for PendingProbe<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.inner.lock().unwrap().probing = false;
+        }
+    }
+}
+
+/// MongoDB out of reach, as opposed to an error of the call itself.
+fn is_outage(err: &AppError) -> bool {
+    match err {
+        AppError::DatabaseUnavailable { error, .. } => error.is_some(),
+        AppError::Database(err) => is_transient(err),
+        _ => false,
+    }
+}
+
+fn refused(wait_ms: u64) -> AppError {
+    AppError::DatabaseUnavailable {
+        error: None,
+        retry_after_secs: wait_ms.div_ceil(1000).max(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(&MongoConfig {
+            breaker_failures: 2,
+            breaker_open_secs: open_secs,
+            ..MongoConfig::default()
+        })
+    }
+
+    async fn fail(breaker: &CircuitBreaker) -> Result<(), AppError> {
+        breaker
+            .call(async { Err(mongodb::error::Error::from(std::io::Error::other("down")).into()) })
+            .await
+    }
+
+    async fn succeed(breaker: &CircuitBreaker) -> Result<(), AppError> {
+        breaker.call(async { Ok(()) }).await
+    }
+
+    #[actix_web::test]
+    async fn opens_after_consecutive_outages() {
+        let breaker = breaker(60);
+        assert!(fail(&breaker).await.is_err());
+        assert_eq!(breaker.stats().state, BreakerState::Closed);
+        // A call that reached MongoDB isn't an outage.
+        let not_found = breaker
+            .call(async { Err::<(), _>(AppError::NotFound("Booking".to_string())) })
+            .await;
+        assert!(matches!(not_found, Err(AppError::NotFound(_))));
+        assert_eq!(breaker.stats().state, BreakerState::Closed);
+        assert!(fail(&breaker).await.is_err());
+        assert!(fail(&breaker).await.is_err());
+
+        let stats = breaker.stats();
+        assert_eq!(stats.state, BreakerState::Open);
+        assert_eq!(stats.opened, 1);
+        assert!(stats.retry_at.is_some());
+        let refused = succeed(&breaker).await;
+        assert!(matches!(
+            refused,
+            Err(AppError::DatabaseUnavailable { error: None, .. })
+        ));
+        assert_eq!(breaker.stats().rejected, 1);
+    }
+
+    #[actix_web::test]
+    async fn probe_closes_on_success() {
+        let breaker = breaker(0);
+        for _ in 0..2 {
+            assert!(fail(&breaker).await.is_err());
+        }
+        assert_eq!(breaker.stats().state, BreakerState::Open);
+
+        assert!(succeed(&breaker).await.is_ok());
+        let stats = breaker.stats();
+        assert_eq!(stats.state, BreakerState::Closed);
+        assert_eq!(stats.consecutive_failures, 0);
+        assert_eq!(stats.rejected, 0);
+    }
+
+    #[actix_web::test]
+    async fn failed_probe_opens_again() {
+        let breaker = breaker(0);
+        for _ in 0..2 {
+            assert!(fail(&breaker).await.is_err());
+        }
+        assert!(fail(&breaker).await.is_err());
+        let stats = breaker.stats();
+        assert_eq!(stats.state, BreakerState::Open);
+        assert_eq!(stats.opened, 2);
+    }
+
+    #[actix_web::test]
+    async fn only_one_probe_at_a_time() {
+        let breaker = breaker(0);
+        for _ in 0..2 {
+            assert!(fail(&breaker).await.is_err());
+        }
+        assert!(breaker.admit().unwrap(), "the first call probes");
+        assert!(breaker.admit().is_err(), "the others wait for its outcome");
+        breaker.record(false, true);
+        assert_eq!(breaker.stats().state, BreakerState::Closed);
+        assert!(!breaker.admit().unwrap());
+    }
+
+    #[actix_web::test]
+    async fn nested_calls_are_counted_once() {
+        let breaker = breaker(60);
+        let outer = breaker.call(async {
+            let _ = fail(&breaker).await;
+            fail(&breaker).await
+        });
+        assert!(outer.await.is_err());
+        let stats = breaker.stats();
+        assert_eq!(stats.state, BreakerState::Closed);
+        assert_eq!(stats.consecutive_failures, 1);
+    }
+}
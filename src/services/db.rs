@@ -1,187 +1,5417 @@
-use std::{env, str::FromStr, time::SystemTime};
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
-use chrono::Utc;
-use futures_util::StreamExt;
+use async_trait::async_trait;
+use futures_util::{StreamExt, TryStreamExt, io::AsyncWriteExt};
 use mongodb::{
-    Client, Collection,
-    bson::{DateTime, datetime::Error, doc, from_document, oid::ObjectId},
-    results::{InsertOneResult, UpdateResult},
+    Client, ClientSession, Collection, Cursor, IndexModel,
+    action::{
+        Aggregate, CountDocuments, Find, FindOne, FindOneAndDelete, FindOneAndReplace,
+        FindOneAndUpdate,
+    },
+    bson::{
+        Bson, DateTime, Document, doc, from_bson, from_document, oid::ObjectId, to_bson,
+        to_document,
+    },
+    change_stream::{
+        ChangeStream,
+        event::{ChangeStreamEvent, ResumeToken},
+    },
+    error::{ErrorKind, WriteFailure},
+    gridfs::{GridFsBucket, GridFsDownloadStream},
+    options::{
+        Acknowledgment, ChangeStreamOptions, ClientOptions, CollectionOptions, FullDocumentType,
+        GridFsBucketOptions, IndexOptions, ReadPreference, ReturnDocument, SelectionCriteria,
+    },
+    results::InsertOneResult,
 };
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::json;
+use tracing::{error, info, instrument, warn};
+use validator::{ValidationError, ValidationErrors};
 
-use crate::models::{
-    booking_model::{Booking, FullBooking},
-    dog_model::Dog,
-    owner_model::Owner,
+use crate::{
+    config::{BookingsConfig, MongoConfig, ReadPreferenceMode},
+    errors::AppError,
+    models::{
+        api_key_model::ApiKey,
+        audit_model::{AuditAction, AuditActor, AuditEntry, AuditFilter, EntityRef},
+        auth_model::{Credentials, PasswordReset, Role},
+        availability_model::WalkerAvailability,
+        backup_model::{Backup, CollectionImport, ImportReport},
+        booking_model::{
+            ArchiveFilter, Booking, BookingCursor, BookingList, BookingQuery, BookingSort,
+            BookingStatus, BookingUpdateRequest, BulkCancelResult, FullBooking, ListedBooking,
+            WalkReport, parse_rfc3339, projection, status_list,
+        },
+        breed_model::{Breed, SEED_BREEDS, breed_key},
+        coupon_model::{Coupon, coupon_key},
+        device_model::Device,
+        dog_model::{Dog, DogProfile, DogUpdateRequest},
+        geo_model::{GeoPoint, NearbyWalker},
+        group_walk_model::{FullGroupWalk, GroupParticipant, GroupWalk},
+        idempotency_model::{IdempotencyRecord, StoredResponse, claim_cutoff},
+        incident_model::{Incident, IncidentSeverity, IncidentStatus},
+        invoice_model::{Invoice, InvoiceRefund, PaymentStatus},
+        owner_model::{
+            EmailVerification, NotificationPreferences, Owner, OwnerDeletion, OwnerPoints,
+            OwnerSort, OwnerUpdateRequest, OwnerWithDogs,
+        },
+        page_model::Page,
+        payment_model::BookingPayment,
+        payout_model::{EarningsBucket, EarningsPeriod, Payout, PayoutBatch, PayoutKind},
+        photo_model::{DOG_PHOTO_BUCKET, StoredPhoto},
+        pricing_model::{PRICING_RULES_ID, PricingRules, Quote},
+        result_model::UpdatedCount,
+        review_model::{Review, WalkerRating},
+        search_model::{SearchHit, rank},
+        serde_helpers::{HasObjectId, WithId},
+        series_model::BookingSeries,
+        track_model::TrackPing,
+        vaccination_model::Vaccination,
+        waitlist_model::{WaitlistEntry, WaitlistStatus},
+        walker_model::{Walker, WalkerTier, WalkerUpdateRequest},
+        webhook_model::{DeliveryStatus, Webhook, WebhookDelivery, WebhookEvent},
+    },
+    services::{
+        auth::one_time_token,
+        booking_updates::BookingUpdates,
+        cache::OwnerCache,
+        circuit_breaker::{BreakerStats, CircuitBreaker},
+        matching::{CANDIDATES, Candidate, MAX_DISTANCE_KM},
+        owner_events::OwnerEvents,
+        pricing,
+        repository::{
+            AuditRepository, BookingRepository, DogRepository, IdempotencyRepository,
+            OwnerRepository, SearchRepository,
+        },
+        retry::{RetryPolicy, is_transient},
+        schema::validators,
+    },
 };
 
-/// Database struct holds typed collections for booking, dog, and owner.
+/// Database struct holds typed collections for booking, dog, owner, walker, credentials,
+/// password resets, email verifications, API keys and the audit log.
 /// Each collection is strongly typed with its respective Rust struct,
 /// which makes serialization/deserialization easier and safer.
 pub struct Database {
+    client: Client,
     booking: Collection<Booking>,
+    /// `booking` read with `mongo.read_preference`, for the listing aggregation.
+    booking_listing: Collection<Booking>,
+    /// Finished bookings moved out by `archive_bookings`, never read by the API.
+    booking_archive: Collection<Document>,
     dog: Collection<Dog>,
     owner: Collection<Owner>,
+    walker: Collection<Walker>,
+    walker_availability: Collection<WalkerAvailability>,
+    group_walk: Collection<GroupWalk>,
+    credentials: Collection<Credentials>,
+    password_reset: Collection<PasswordReset>,
+    email_verification: Collection<EmailVerification>,
+    api_keys: Collection<ApiKey>,
+    audit_log: Collection<AuditEntry>,
+    idempotency: Collection<IdempotencyRecord>,
+    walk_track: Collection<TrackPing>,
+    breeds: Collection<Breed>,
+    review: Collection<Review>,
+    incident: Collection<Incident>,
+    invoice: Collection<Invoice>,
+    payout: Collection<Payout>,
+    pricing_rules: Collection<PricingRules>,
+    coupon: Collection<Coupon>,
+    series: Collection<BookingSeries>,
+    waitlist: Collection<WaitlistEntry>,
+    devices: Collection<Device>,
+    webhooks: Collection<Webhook>,
+    webhook_deliveries: Collection<WebhookDelivery>,
+    /// Where each change stream stopped, by stream name.
+    resume_tokens: Collection<Document>,
+    owner_cache: OwnerCache,
+    booking_updates: BookingUpdates,
+    owner_events: OwnerEvents,
+    max_results: u64,
+    max_concurrent_bookings: Option<usize>,
+    pending_expiry: Duration,
+    /// Tries of the reads while MongoDB is out of reach, from the `mongo` config.
+    retry: RetryPolicy,
+    /// Set once `prepare` succeeded.
+    prepared: AtomicBool,
+    breaker: CircuitBreaker,
+    /// `mongo.max_time_ms`, the `maxTimeMS` of each query, aggregation, count and
+    /// find-and-modify, enforced by the server so an operation is never abandoned
+    /// halfway by the client.
+    max_time: Option<Duration>,
+}
+
+/// Validity of the link emailed by `create_owner`.
+pub const EMAIL_VERIFICATION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Owners, dogs and bookings cursors streamed by `GET /admin/export`.
+pub type ExportCursors = (Cursor<Owner>, Cursor<Dog>, Cursor<Booking>);
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// When `booking` expires unless confirmed: `expiry` from now, or when its walk
+/// starts if sooner. `None` for a booking that isn't pending.
+pub fn pending_expires_at(booking: &Booking, expiry: Duration) -> Option<DateTime> {
+    (booking.status == BookingStatus::Pending).then(|| {
+        let expires_at =
+            DateTime::from_millis(DateTime::now().timestamp_millis() + expiry.as_millis() as i64);
+        expires_at.min(booking.start_time)
+    })
 }
 
+// Every public query method runs in a `debug` span named after it, so the duration of
+// each MongoDB round trip shows up in the request logs at debug level. Each method
+// body runs through `read` or `write`, hence through the `CircuitBreaker`, so an
+// outage fails it at once: reads, which can safely run twice, also go through
+// `RetryPolicy` so a failover costs a delay rather than an error; writes only get the
+// single retry of the driver. The exceptions are the startup steps (`prepare` and
+// what it runs, retried by `RetryPolicy::startup`), `ping`, which probes MongoDB
+// itself for `GET /ready`, the `*_in_session` steps of a transaction already inside
+// a `write`, and the compensations `release_points` and `release_coupon`, which must
+// run even once the breaker opened. Neither reads nor writes are cancelled from the
+// client side: the queries and find-and-modify commands carry `mongo.max_time_ms` as
+// their `maxTimeMS` (`TimeLimited`), so a multi-step write and its compensation always
+// run to the end.
 impl Database {
     /// Initialize the database connection.
-    /// It checks if `MONGO_URI` exists as an environment variable.
-    /// If not, it falls back to a default local URI.
-    /// Then, it connects to the "dog_walking" database
-    /// and stores references to the three collections.
-    pub async fn init() -> Self {
-        let uri = match env::var("MONGO_URI") {
-            Ok(v) => v.to_string(),
-            Err(_) => "mongodb://localhost:27017/?directConnection=true".to_string(),
-        };
-
+    /// It creates a client for the configured URI with the configured pool sizes,
+    /// opens the configured database (`dog_walking` by default)
+    /// and stores references to the collections. The client connects lazily, so
+    /// this only fails on an invalid URI; `prepare` is the first round trip.
+    pub async fn connect(
+        config: &MongoConfig,
+        bookings: &BookingsConfig,
+    ) -> Result<Self, AppError> {
         // Create a new MongoDB client from the connection string.
-        let client = Client::with_uri_str(uri).await.unwrap();
-        let db = client.database("dog_walking");
+        // The `mongo` config wins over the same options given in the URI.
+        let mut options = ClientOptions::parse(&config.uri).await?;
+        options.min_pool_size = config.min_pool_size.or(options.min_pool_size);
+        options.max_pool_size = config.max_pool_size.or(options.max_pool_size);
+        options.connect_timeout = config
+            .connect_timeout_ms
+            .map(Duration::from_millis)
+            .or(options.connect_timeout);
+        options.server_selection_timeout = config
+            .server_selection_timeout_ms
+            .map(Duration::from_millis)
+            .or(options.server_selection_timeout);
+        options.retry_writes = config.retry_writes.or(options.retry_writes);
+        if config.write_concern.is_some() || config.write_concern_timeout_ms.is_some() {
+            let mut concern = options.write_concern.take().unwrap_or_default();
+            if let Some(write_concern) = &config.write_concern {
+                concern.w = Some(match write_concern.parse::<u32>() {
+                    Ok(members) => Acknowledgment::Nodes(members),
+                    Err(_) => Acknowledgment::from(write_concern.as_str()),
+                });
+            }
+            if let Some(timeout_ms) = config.write_concern_timeout_ms {
+                concern.w_timeout = Some(Duration::from_millis(timeout_ms));
+            }
+            options.write_concern = Some(concern);
+        }
+        let client = Client::with_options(options)?;
+        let db = client.database(&config.database);
 
         // Typed collections
         let booking: Collection<Booking> = db.collection("booking");
+        let booking_listing: Collection<Booking> = match config.read_preference {
+            Some(mode) => db.collection_with_options(
+                "booking",
+                CollectionOptions::builder()
+                    .selection_criteria(SelectionCriteria::ReadPreference(read_preference(mode)))
+                    .build(),
+            ),
+            None => booking.clone(),
+        };
+        let booking_archive: Collection<Document> = db.collection("booking_archive");
         let dog: Collection<Dog> = db.collection("dog");
         let owner: Collection<Owner> = db.collection("owner");
+        let walker: Collection<Walker> = db.collection("walker");
+        let walker_availability: Collection<WalkerAvailability> =
+            db.collection("walker_availability");
+        let group_walk: Collection<GroupWalk> = db.collection("group_walks");
+        let credentials: Collection<Credentials> = db.collection("credentials");
+        let password_reset: Collection<PasswordReset> = db.collection("password_reset");
+        let email_verification: Collection<EmailVerification> = db.collection("email_verification");
+        let api_keys: Collection<ApiKey> = db.collection("api_keys");
+        let audit_log: Collection<AuditEntry> = db.collection("audit_log");
+        let idempotency: Collection<IdempotencyRecord> = db.collection("idempotency_keys");
+        let walk_track: Collection<TrackPing> = db.collection("walk_track");
+        let breeds: Collection<Breed> = db.collection("breeds");
+        let review: Collection<Review> = db.collection("reviews");
+        let incident: Collection<Incident> = db.collection("incidents");
+        let invoice: Collection<Invoice> = db.collection("invoices");
+        let payout: Collection<Payout> = db.collection("payouts");
+        let pricing_rules: Collection<PricingRules> = db.collection("pricing_rules");
+        let coupon: Collection<Coupon> = db.collection("coupons");
+        let series: Collection<BookingSeries> = db.collection("booking_series");
+        let waitlist: Collection<WaitlistEntry> = db.collection("waitlist");
+        let devices: Collection<Device> = db.collection("devices");
+        let webhooks: Collection<Webhook> = db.collection("webhooks");
+        let webhook_deliveries: Collection<WebhookDelivery> = db.collection("webhook_deliveries");
+        let resume_tokens: Collection<Document> = db.collection("change_stream_resume_tokens");
 
-        Database {
+        Ok(Database {
+            client,
             booking,
+            booking_listing,
+            booking_archive,
             dog,
             owner,
+            walker,
+            walker_availability,
+            group_walk,
+            credentials,
+            password_reset,
+            email_verification,
+            api_keys,
+            audit_log,
+            idempotency,
+            walk_track,
+            breeds,
+            review,
+            incident,
+            invoice,
+            payout,
+            pricing_rules,
+            coupon,
+            series,
+            waitlist,
+            devices,
+            webhooks,
+            webhook_deliveries,
+            resume_tokens,
+            owner_cache: OwnerCache::new(Duration::from_secs(config.owner_cache_ttl_secs)),
+            booking_updates: BookingUpdates::default(),
+            owner_events: OwnerEvents::default(),
+            max_results: bookings.max_results,
+            max_concurrent_bookings: bookings.max_concurrent_bookings,
+            pending_expiry: bookings.pending_expiry(),
+            retry: RetryPolicy::new(config),
+            prepared: AtomicBool::new(false),
+            breaker: CircuitBreaker::new(config),
+            max_time: config.max_time_ms.map(Duration::from_millis),
+        })
+    }
+
+    /// Migrate the documents written by older versions, seed the catalogs, create the
+    /// indexes and, with `apply_validators`, the collection validators. Every step can
+    /// run again, so a start that failed halfway is simply retried.
+    pub async fn prepare(&self, apply_validators: bool) -> Result<(), AppError> {
+        migrate_email_verified(&self.owner).await?;
+        migrate_booking_status(&self.booking).await?;
+        migrate_versions(&self.owner, &self.booking).await?;
+        seed_breeds(&self.breeds).await?;
+        seed_pricing_rules(&self.pricing_rules).await?;
+        self.ensure_indexes().await?;
+        if apply_validators {
+            self.apply_validators().await?;
+            info!("Collection validators applied");
         }
+        self.prepared.store(true, Ordering::Release);
+        Ok(())
     }
 
-    /// Insert a new owner into the "owner" collection.
-    /// Returns the result of the insertion (including the inserted_id).
-    pub async fn create_owner(&self, owner: Owner) -> Result<InsertOneResult, Error> {
-        let result = self
-            .owner
-            .insert_one(owner) // Insert the Owner struct into MongoDB
+    /// A read through the circuit breaker, tried again while MongoDB is out of reach.
+    async fn read<T, F, Fut>(&self, operation: F) -> Result<T, AppError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        self.breaker.call(self.retry.run(operation)).await
+    }
+
+    /// A write through the circuit breaker, only retried by the driver since it may
+    /// not be safe to run twice. Failing on MongoDB being out of reach, it is answered
+    /// with 503 and a `Retry-After`, like a read that used up its tries.
+    async fn write<T>(
+        &self,
+        operation: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        self.breaker
+            .call(async {
+                operation.await.map_err(|err| match err {
+                    AppError::Database(err) if is_transient(&err) => {
+                        AppError::DatabaseUnavailable {
+                            error: Some(err),
+                            retry_after_secs: self.retry.retry_after_secs(),
+                        }
+                    }
+                    err => err,
+                })
+            })
             .await
-            .ok()
-            .expect("Error creating owner");
+    }
 
-        Ok(result)
+    /// State of the circuit breaker, for `GET /health`.
+    pub fn breaker_stats(&self) -> BreakerStats {
+        self.breaker.stats()
     }
 
-    /// Insert a new dog into the "dog" collection.
-    pub async fn create_dog(&self, dog: Dog) -> Result<InsertOneResult, Error> {
-        let result = self
-            .dog
-            .insert_one(dog)
-            .await
-            .ok()
-            .expect("Error creating dog");
+    /// Whether `prepare` succeeded, `GET /ready` reports MongoDB down until then.
+    pub fn is_prepared(&self) -> bool {
+        self.prepared.load(Ordering::Acquire)
+    }
 
-        Ok(result)
+    /// Keep trying `prepare` in the background, for a server started degraded
+    /// because MongoDB was out of reach.
+    pub fn spawn_prepare(db: Arc<Database>, retry: RetryPolicy, apply_validators: bool) {
+        actix_web::rt::spawn(async move {
+            loop {
+                match retry.run(|| db.prepare(apply_validators)).await {
+                    Ok(()) => {
+                        info!("MongoDB is up, the server is ready");
+                        return;
+                    }
+                    Err(err) => error!(error = %err, "MongoDB is still out of reach"),
+                }
+                actix_web::rt::time::sleep(retry.max_delay()).await;
+            }
+        });
     }
 
-    /// Insert a new booking into the "booking" collection.
-    pub async fn create_booking(&self, booking: Booking) -> Result<InsertOneResult, Error> {
-        let result = self
-            .booking
-            .insert_one(booking)
-            .await
-            .ok()
-            .expect("Error creating booking");
+    /// Create the indexes the queries rely on, a no-op for the ones that already exist.
+    /// Runs after the migrations so the status index covers migrated bookings.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        // `get_bookings` matches statuses over a start_time range, optionally for one
+        // owner or walker; the overlap checks add the same fields. The expiry sweep
+        // looks for the pending bookings past their `expires_at`.
+        self.booking
+            .create_indexes([
+                index(doc! {"start_time": 1}),
+                index(doc! {"status": 1, "start_time": 1}),
+                index(doc! {"owner": 1, "start_time": 1}),
+                index(doc! {"walker": 1, "start_time": 1}),
+                index(doc! {"series": 1, "start_time": 1}),
+                index(doc! {"status": 1, "expires_at": 1}),
+            ])
+            .await?;
 
-        Ok(result)
+        // The archive is listed by walk, latest first, optionally for one owner.
+        self.booking_archive
+            .create_indexes([
+                index(doc! {"start_time": -1}),
+                index(doc! {"owner": 1, "start_time": -1}),
+            ])
+            .await?;
+
+        // Dogs are listed and joined by owner.
+        self.dog.create_index(index(doc! {"owner": 1})).await?;
+
+        // `GET /search`, a collection has at most one text index: names weigh
+        // more than emails and breeds.
+        self.owner
+            .create_index(text_index(doc! {"name": 3, "email": 1}))
+            .await?;
+        self.dog
+            .create_index(text_index(doc! {"name": 3, "breed": 1}))
+            .await?;
+
+        // A route is read for one booking, in ping order.
+        self.walk_track
+            .create_index(index(doc! {"booking": 1, "at": 1}))
+            .await?;
+
+        // One review per booking, averaged per walker on `GET /walker/{id}`.
+        self.review
+            .create_indexes([unique_index(doc! {"booking": 1}), index(doc! {"walker": 1})])
+            .await?;
+
+        // `GET /incidents` lists the unresolved ones, newest first.
+        self.incident
+            .create_index(index(doc! {"status": 1, "reported_at": -1}))
+            .await?;
+
+        // One invoice per booking, listed per owner newest first and found by
+        // the payment webhooks from their payment intent.
+        self.invoice
+            .create_indexes([
+                unique_index(doc! {"booking": 1}),
+                index(doc! {"owner": 1, "issued_at": -1}),
+                index(doc! {"payment_reference": 1}),
+            ])
+            .await?;
+
+        // One ledger entry of each kind per booking, summed per walker over time
+        // and settled in batches of the unsettled entries. The index on the booking
+        // alone predates tips.
+        match self.payout.drop_index("booking_1").await {
+            Ok(()) => {}
+            Err(err) if is_index_not_found(&err) => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.payout
+            .create_indexes([
+                unique_index(doc! {"booking": 1, "kind": 1}),
+                index(doc! {"walker": 1, "earned_at": 1}),
+                index(doc! {"settled_at": 1, "earned_at": 1}),
+            ])
+            .await?;
+
+        // Coupons are redeemed by code, at most one coupon per code.
+        self.coupon
+            .create_index(unique_index(doc! {"code": 1}))
+            .await?;
+
+        // The materializer looks for the running series not booked far enough ahead.
+        self.series
+            .create_index(index(doc! {"cancelled_at": 1, "materialized_until": 1}))
+            .await?;
+
+        // The queue of a slot is read in insertion order among the waiting entries,
+        // and listed per owner.
+        self.waitlist
+            .create_indexes([
+                index(doc! {"status": 1, "start_time": 1}),
+                index(doc! {"owner": 1, "start_time": 1}),
+            ])
+            .await?;
+
+        // A token belongs to one phone, registered again when the app restarts;
+        // the notifications read the devices of an owner.
+        self.devices
+            .create_indexes([unique_index(doc! {"token": 1}), index(doc! {"owner": 1})])
+            .await?;
+
+        // Group walks are checked for clashes per walker and per participant.
+        self.group_walk
+            .create_indexes([
+                index(doc! {"walker": 1, "start_time": 1}),
+                index(doc! {"participants.owner": 1, "start_time": 1}),
+            ])
+            .await?;
+
+        // `GET /walkers/near` runs `$geoNear`, which needs a geospatial index.
+        self.walker
+            .create_index(index(doc! {"location": "2dsphere"}))
+            .await?;
+        self.owner
+            .create_index(index(doc! {"location": "2dsphere"}))
+            .await?;
+
+        // One owner per email, fails on a database already holding duplicates.
+        self.owner
+            .create_index(unique_index(doc! {"email": 1}))
+            .await?;
+
+        // One login per email, also guards concurrent registrations.
+        self.credentials
+            .create_index(unique_index(doc! {"email": 1}))
+            .await?;
+
+        // Expired reset and verification tokens are removed by MongoDB itself.
+        self.password_reset
+            .create_index(expires_at_ttl_index())
+            .await?;
+        self.email_verification
+            .create_index(expires_at_ttl_index())
+            .await?;
+        self.idempotency
+            .create_index(expires_at_ttl_index())
+            .await?;
+
+        // Keys are looked up by hash on every request sending `X-Api-Key`.
+        self.api_keys
+            .create_index(unique_index(doc! {"key_hash": 1}))
+            .await?;
+
+        // Webhooks are looked up by the event to send, their deliveries claimed
+        // when due and listed per webhook, newest first. Every replica watches the
+        // booking changes, a change is queued once per webhook whichever is first.
+        self.webhooks
+            .create_index(index(doc! {"events": 1}))
+            .await?;
+        self.webhook_deliveries
+            .create_indexes([
+                index(doc! {"status": 1, "next_attempt_at": 1}),
+                index(doc! {"webhook": 1, "created_at": -1}),
+                IndexModel::builder()
+                    .keys(doc! {"webhook": 1, "change_id": 1})
+                    .options(
+                        IndexOptions::builder()
+                            .unique(true)
+                            .partial_filter_expression(doc! {"change_id": {"$exists": true}})
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .await?;
+
+        // The audit log is read newest first, for one entity or over a date range.
+        self.audit_log
+            .create_indexes([
+                index(doc! {"entity.id": 1, "at": -1}),
+                index(doc! {"at": -1}),
+            ])
+            .await?;
+
+        Ok(())
     }
 
-    /// Cancel a booking by updating its "cancelled" field to true.
-    /// Takes the booking_id as a &str, parses it to ObjectId,
-    /// and runs an update operation.
-    pub async fn cancel_booking(&self, booking_id: &str) -> Result<UpdateResult, Error> {
-        let result = self
-            .booking
+    /// Install the `$jsonSchema` validators of `schema::validators` (`--init-schema`),
+    /// creating the collections that don't exist yet. Writes breaking a schema are rejected;
+    /// with the `moderate` level, documents that are already invalid can still be updated.
+    /// Needs the `collMod` privilege.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn apply_validators(&self) -> Result<(), AppError> {
+        let db = self.client.database(&self.owner.namespace().db);
+        let existing = db.list_collection_names().await?;
+
+        for (collection, schema) in validators() {
+            let command = if existing.iter().any(|name| name == collection) {
+                "collMod"
+            } else {
+                "create"
+            };
+            db.run_command(doc! {
+                command: collection,
+                "validator": { "$jsonSchema": schema },
+                "validationLevel": "moderate",
+                "validationAction": "error"
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Round trip to the server, used by the readiness probe.
+    pub async fn ping(&self) -> Result<(), AppError> {
+        self.client
+            .database("admin")
+            .run_command(doc! {"ping": 1})
+            .await?;
+        Ok(())
+    }
+
+    /// Close the connection pool, to be called once the HTTP server has stopped.
+    pub async fn shutdown(&self) {
+        self.client.clone().shutdown().await;
+    }
+
+    pub fn owner_cache(&self) -> &OwnerCache {
+        &self.owner_cache
+    }
+
+    /// Create an owner together with its login.
+    /// An email already registered is a 409 `email_taken`; when two registrations
+    /// race, the unique index rejects the second login and its owner is removed again.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn register_owner(
+        &self,
+        owner: Owner,
+        password_hash: String,
+    ) -> Result<(ObjectId, String), AppError> {
+        self.write(async move {
+            let email = owner.email.to_lowercase();
+            if self.find_credentials(&email).await?.is_some() {
+                return Err(email_taken());
+            }
+
+            let owner_id = owner._id;
+            let result = self.create_owner(owner).await?;
+
+            let credentials = Credentials {
+                _id: ObjectId::new(),
+                user_id: owner_id,
+                email,
+                password_hash,
+                role: Role::Owner,
+            };
+            if let Err(err) = self.create_credentials(credentials).await {
+                self.owner.delete_one(doc! {"_id": owner_id}).await?;
+                self.email_verification
+                    .delete_many(doc! {"owner": owner_id})
+                    .await?;
+                return Err(err);
+            }
+
+            Ok(result)
+        })
+        .await
+    }
+
+    /// Store a login, the email is lowercased and must not be registered yet (409 `email_taken`).
+    #[instrument(level = "debug", skip_all)]
+    pub async fn create_credentials(&self, mut credentials: Credentials) -> Result<(), AppError> {
+        self.write(async move {
+            credentials.email = credentials.email.to_lowercase();
+
+            match self.credentials.insert_one(credentials).await {
+                Ok(_) => Ok(()),
+                Err(err) if is_duplicate_key(&err) => Err(email_taken()),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    /// Login stored for this email (compared lowercased).
+    #[instrument(level = "debug", skip_all)]
+    pub async fn find_credentials(&self, email: &str) -> Result<Option<Credentials>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .credentials
+                .find_one(doc! {"email": email.to_lowercase()})
+                .time_limit(self.max_time)
+                .await?)
+        })
+        .await
+    }
+
+    /// Store a reset token, replacing any previous one for the same login.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn create_password_reset(&self, reset: PasswordReset) -> Result<(), AppError> {
+        self.write(async move {
+            self.password_reset
+                .delete_many(doc! {"credentials": reset.credentials})
+                .await?;
+            self.password_reset.insert_one(reset).await?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Use up a reset token: it is deleted in the same operation, so it works only once.
+    /// The TTL monitor runs about once a minute, hence the explicit expiry check.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn consume_password_reset(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<PasswordReset>, AppError> {
+        self.write(async move {
+            Ok(self
+                .password_reset
+                .find_one_and_delete(doc! {
+                    "token_hash": token_hash,
+                    "expires_at": { "$gt": DateTime::now() }
+                })
+                .time_limit(self.max_time)
+                .await?)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn update_password(
+        &self,
+        credentials_id: &ObjectId,
+        password_hash: String,
+    ) -> Result<(), AppError> {
+        self.write(async move {
+            let result = self
+                .credentials
+                .update_one(
+                    doc! {"_id": credentials_id},
+                    doc! {"$set": {"password_hash": password_hash}},
+                )
+                .await?;
+            if result.matched_count == 0 {
+                return Err(AppError::NotFound("Account not found".to_string()));
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn create_api_key(&self, api_key: ApiKey) -> Result<(), AppError> {
+        self.write(async move {
+            self.api_keys.insert_one(api_key).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every key, revoked ones included, newest first.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_api_keys(&self) -> Result<Vec<ApiKey>, AppError> {
+        self.read(|| async move {
+            let mut cursor = self
+                .api_keys
+                .find(doc! {})
+                .time_limit(self.max_time)
+                .sort(doc! {"created_at": -1})
+                .await?;
+
+            let mut api_keys = Vec::new();
+            while let Some(api_key) = cursor.next().await {
+                api_keys.push(api_key?);
+            }
+
+            Ok(api_keys)
+        })
+        .await
+    }
+
+    /// Key matching this hash, unless it was revoked.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn find_active_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .api_keys
+                .find_one(doc! {"key_hash": key_hash, "revoked_at": null})
+                .time_limit(self.max_time)
+                .await?)
+        })
+        .await
+    }
+
+    /// Revoke a key, revoking it again keeps the first revocation date.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn revoke_api_key(&self, id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            let result = self
+                .api_keys
+                .update_one(
+                    doc! {"_id": id, "revoked_at": null},
+                    doc! {"$set": {"revoked_at": DateTime::now()}},
+                )
+                .await?;
+            if result.matched_count == 0
+                && self
+                    .api_keys
+                    .find_one(doc! {"_id": id})
+                    .time_limit(self.max_time)
+                    .await?
+                    .is_none()
+            {
+                return Err(AppError::NotFound("API key not found".to_string()));
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn create_webhook(&self, webhook: &Webhook) -> Result<(), AppError> {
+        self.write(async move {
+            self.webhooks.insert_one(webhook).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Every webhook, newest first.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_webhooks(&self) -> Result<Vec<Webhook>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .webhooks
+                .find(doc! {})
+                .time_limit(self.max_time)
+                .sort(doc! {"created_at": -1})
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_webhook(&self, id: &ObjectId) -> Result<Webhook, AppError> {
+        self.read(|| async move {
+            self.webhooks
+                .find_one(doc! {"_id": id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))
+        })
+        .await
+    }
+
+    /// Webhooks subscribed to `event`.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_webhooks_for(&self, event: WebhookEvent) -> Result<Vec<Webhook>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .webhooks
+                .find(doc! {"events": event.as_str()})
+                .time_limit(self.max_time)
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    /// Delete a webhook with its delivery log, the events still pending are dropped.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn delete_webhook(&self, id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            let result = self.webhooks.delete_one(doc! {"_id": id}).await?;
+            if result.deleted_count == 0 {
+                return Err(AppError::NotFound("Webhook not found".to_string()));
+            }
+            self.webhook_deliveries
+                .delete_many(doc! {"webhook": id})
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deliveries another replica already queued for the same change are skipped.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn queue_webhook_deliveries(
+        &self,
+        deliveries: &[WebhookDelivery],
+    ) -> Result<(), AppError> {
+        self.write(async move {
+            if deliveries.is_empty() {
+                return Ok(());
+            }
+            match self
+                .webhook_deliveries
+                .insert_many(deliveries)
+                .ordered(false)
+                .await
+            {
+                Ok(_) => Ok(()),
+                Err(err) if is_duplicate_key(&err) => Ok(()),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    /// Changes of the bookings from `resume_token` on, or from now without one.
+    /// Updates come with the whole booking as it is once the update is read.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn watch_bookings(
+        &self,
+        resume_token: Option<ResumeToken>,
+    ) -> Result<ChangeStream<ChangeStreamEvent<Document>>, AppError> {
+        let resume_token = &resume_token;
+        self.read(|| async move {
+            let options = ChangeStreamOptions::builder()
+                .full_document(Some(FullDocumentType::UpdateLookup))
+                .start_after(resume_token.clone())
+                .build();
+            Ok(self
+                .booking
+                .clone_with_type::<Document>()
+                .watch()
+                .with_options(options)
+                .await?)
+        })
+        .await
+    }
+
+    /// Where the change stream `stream` stopped, `None` the first time.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_resume_token(&self, stream: &str) -> Result<Option<ResumeToken>, AppError> {
+        self.read(|| async move {
+            match self
+                .resume_tokens
+                .find_one(doc! {"_id": stream})
+                .time_limit(self.max_time)
+                .await?
+            {
+                Some(saved) => Ok(saved.get("token").cloned().map(from_bson).transpose()?),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn save_resume_token(
+        &self,
+        stream: &str,
+        token: &ResumeToken,
+    ) -> Result<(), AppError> {
+        self.write(async move {
+            self.resume_tokens
+                .update_one(
+                    doc! {"_id": stream},
+                    doc! {"$set": {"token": to_bson(token)?, "saved_at": DateTime::now()}},
+                )
+                .upsert(true)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn delete_resume_token(&self, stream: &str) -> Result<(), AppError> {
+        self.write(async move {
+            self.resume_tokens.delete_one(doc! {"_id": stream}).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The pending delivery due the longest, pushed back to `lease_until` so no
+    /// other replica sends it meanwhile; the attempt then records its outcome.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn claim_webhook_delivery(
+        &self,
+        lease_until: DateTime,
+    ) -> Result<Option<WebhookDelivery>, AppError> {
+        self.write(async move {
+            Ok(self
+                .webhook_deliveries
+                .find_one_and_update(
+                    doc! {
+                        "status": DeliveryStatus::Pending.as_str(),
+                        "next_attempt_at": {"$lte": DateTime::now()},
+                    },
+                    doc! {"$set": {"next_attempt_at": lease_until}},
+                )
+                .time_limit(self.max_time)
+                .sort(doc! {"next_attempt_at": 1})
+                .await?)
+        })
+        .await
+    }
+
+    /// Store the outcome of an attempt: its status, attempt count, next attempt and last answer.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn record_webhook_attempt(&self, delivery: &WebhookDelivery) -> Result<(), AppError> {
+        self.write(async move {
+            self.webhook_deliveries
+                .update_one(
+                    doc! {"_id": delivery._id},
+                    doc! {"$set": {
+                        "status": delivery.status.as_str(),
+                        "attempts": delivery.attempts,
+                        "next_attempt_at": delivery.next_attempt_at,
+                        "last_status_code": delivery.last_status_code.map(i32::from),
+                        "last_error": &delivery.last_error,
+                        "delivered_at": delivery.delivered_at,
+                    }},
+                )
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deliveries of a webhook, newest first.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_webhook_deliveries(
+        &self,
+        webhook_id: &ObjectId,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<WebhookDelivery>>, AppError> {
+        self.read(|| async move {
+            let filter = doc! {"webhook": webhook_id};
+            let total = self
+                .webhook_deliveries
+                .count_documents(filter.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let items = self
+                .webhook_deliveries
+                .find(filter)
+                .time_limit(self.max_time)
+                .sort(doc! {"created_at": -1, "_id": -1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?
+                .map_ok(WithId)
+                .try_collect()
+                .await?;
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    /// Writes of `create_owner_with_dogs`, all bound to its session.
+    async fn create_owner_in_session(
+        &self,
+        session: &mut ClientSession,
+        owner: Owner,
+        dogs: Vec<Dog>,
+    ) -> Result<(ObjectId, String), AppError> {
+        let owner_id = owner._id;
+        match self.owner.insert_one(owner).session(&mut *session).await {
+            Ok(_) => {}
+            Err(err) if is_duplicate_key(&err) => return Err(email_taken()),
+            Err(err) => return Err(err.into()),
+        }
+
+        if !dogs.is_empty() {
+            self.dog.insert_many(dogs).session(&mut *session).await?;
+        }
+
+        let (token, token_hash) = one_time_token();
+        self.email_verification
+            .insert_one(email_verification(owner_id, token_hash))
+            .session(&mut *session)
+            .await?;
+
+        Ok((owner_id, token))
+    }
+
+    async fn delete_owner_in_session(
+        &self,
+        session: &mut ClientSession,
+        owner_id: &ObjectId,
+    ) -> Result<OwnerDeletion, AppError> {
+        // The dogs get the same `deleted_at`, that's how `restore_owner` finds them.
+        let deleted_at = DateTime::now();
+        let deleted = self
+            .owner
             .update_one(
-                // Filter: find by ObjectId
-                doc! {"_id":ObjectId::from_str(booking_id).expect("Failed to parse booking id")},
-                // Update: set "cancelled" = true
-                doc! {
-                    "$set":doc! {
-                        "cancelled":true
-                    }
-                },
+                doc! {"_id": owner_id, "deleted_at": null},
+                doc! {"$set": {"deleted_at": deleted_at}, "$inc": {"version": 1}},
             )
-            .await
-            .ok()
-            .expect("Error cancelling booking");
+            .session(&mut *session)
+            .await?;
+        if deleted.matched_count == 0 {
+            return Err(AppError::NotFound("Owner not found".to_string()));
+        }
 
-        Ok(result)
-    }
+        let dogs = self
+            .dog
+            .update_many(
+                doc! {"owner": owner_id, "deleted_at": null},
+                doc! {"$set": {"deleted_at": deleted_at}},
+            )
+            .session(&mut *session)
+            .await?;
 
-    /// Get all upcoming bookings (not cancelled, start_time >= now).
-    /// The query uses an aggregation pipeline to:
-    /// 1. $match: filter only active bookings in the future
-    /// 2. $lookup: join with owner collection to get owner details
-    /// 3. $unwind: flatten the "owner" array into a single object
-    /// 4. $lookup: join with dog collection to fetch all dogs belonging to the owner
-    pub async fn get_bookings(&self) -> Result<Vec<FullBooking>, Error> {
-        let now: SystemTime = Utc::now().into();
-
-        let mut results = self
+        let bookings = self
             .booking
-            .aggregate(vec![
-                // Step 1: Filter only bookings that are not cancelled
-                // and whose start_time is greater or equal to now.
-                doc! {
-                    "$match" :{
-                        "cancelled":false,
-                        "start_time":{
-                            "$gte":DateTime::from_system_time(now)
-                        }
-                    }
-                },
-                // Step 2: Lookup to join booking.owner with owner._id
-                doc! {
-                    "$lookup":doc! {
-                        "from":"owner",
-                        "localField":"owner",
-                        "foreignField": "_id",
-                        "as" : "owner"
-                    }
-                },
-                // Step 3: Unwind the owner array so that "owner": [ {...} ]
-                // becomes "owner": { ... }
+            .update_many(
                 doc! {
-                    "$unwind":doc! {
-                        "path":"$owner"
-                    }
+                    "owner": owner_id,
+                    "status": status_in(BookingStatus::Cancelled.allowed_from()),
+                    "start_time": { "$gte": DateTime::now() }
                 },
-                // Step 4: Lookup dogs whose "owner" field matches owner._id
-                // and put them in an array called "dogs".
                 doc! {
-                    "$lookup":{
-                        "from":"dog",
-                        "localField":"owner._id",
-                        "foreignField":"owner",
-                        "as":"dogs"
-                    }
+                    "$set": {
+                        "status": BookingStatus::Cancelled,
+                        "cancelled_at": DateTime::now(),
+                        "cancellation_reason": "Owner deleted"
+                    },
+                    "$inc": {"version": 1}
                 },
-            ])
+            )
+            .session(&mut *session)
+            .await?;
+
+        Ok(OwnerDeletion {
+            deleted_dogs: dogs.modified_count,
+            cancelled_bookings: bookings.modified_count,
+        })
+    }
+
+    /// Insert a new walker into the "walker" collection.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn create_walker(&self, walker: Walker) -> Result<InsertOneResult, AppError> {
+        self.write(async move { Ok(self.walker.insert_one(walker).await?) })
             .await
-            .ok()
-            .expect("Error getting bookings");
+    }
 
-        let mut bookings: Vec<FullBooking> = Vec::new();
+    /// List walkers one page at a time, sorted by name.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walkers(
+        &self,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Walker>>, AppError> {
+        self.read(|| async move {
+            let total = self
+                .walker
+                .count_documents(doc! {})
+                .time_limit(self.max_time)
+                .await?;
+            let mut cursor = self
+                .walker
+                .find(doc! {})
+                .time_limit(self.max_time)
+                .sort(doc! {"name": 1, "_id": 1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?;
 
-        // Iterate over the aggregation cursor (stream of documents).
-        while let Some(result) = results.next().await {
-            match result {
-                // If the document was retrieved successfully:
-                Ok(doc) => {
-                    // Deserialize BSON document into FullBooking struct.
-                    let booking: FullBooking =
-                        from_document(doc).expect("Error converting document to FullBookin");
-                    bookings.push(booking); // Add to results vector
+            let mut items = Vec::new();
+            while let Some(walker) = cursor.next().await {
+                items.push(WithId(walker?));
+            }
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    /// Walkers with a `location` within `max_meters` of `center`, closest first.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walkers_near(
+        &self,
+        center: GeoPoint,
+        max_meters: f64,
+        limit: u64,
+    ) -> Result<Vec<NearbyWalker>, AppError> {
+        self.read(|| async move {
+            let mut cursor = self
+                .walker
+                .aggregate([
+                    doc! {"$geoNear": {
+                        "near": to_bson(&center)?,
+                        "key": "location",
+                        "distanceField": "distance",
+                        "maxDistance": max_meters,
+                        "spherical": true,
+                    }},
+                    doc! {"$limit": limit as i64},
+                ])
+                .time_limit(self.max_time)
+                .await?;
+
+            let mut walkers = Vec::new();
+            while let Some(mut document) = cursor.next().await.transpose()? {
+                let meters = document.get_f64("distance").unwrap_or_default();
+                document.remove("distance");
+                walkers.push(NearbyWalker {
+                    distance_km: meters / 1000.0,
+                    walker: WithId(from_document(document)?),
+                });
+            }
+            Ok(walkers)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walker(&self, walker_id: &ObjectId) -> Result<Walker, AppError> {
+        self.read(|| async move {
+            self.walker
+                .find_one(doc! {"_id": walker_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Walker not found".to_string()))
+        })
+        .await
+    }
+
+    /// Average stars and number of reviews of a walker, grouped by MongoDB.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walker_rating(&self, walker_id: &ObjectId) -> Result<WalkerRating, AppError> {
+        self.read(|| async move {
+            let mut cursor = self
+                .review
+                .aggregate(vec![
+                    doc! {"$match": {"walker": walker_id}},
+                    doc! {"$group": {
+                        "_id": null,
+                        "average": {"$avg": "$stars"},
+                        "count": {"$sum": 1_i64},
+                    }},
+                    doc! {"$project": {"_id": 0}},
+                ])
+                .time_limit(self.max_time)
+                .with_type::<WalkerRating>()
+                .await?;
+
+            Ok(cursor.next().await.transpose()?.unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Ledger entries of a walker earned in `[from, to)`, summed per `period`
+    /// and currency, oldest first.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walker_earnings(
+        &self,
+        walker_id: &ObjectId,
+        period: EarningsPeriod,
+        from: Option<DateTime>,
+        to: Option<DateTime>,
+    ) -> Result<Vec<EarningsBucket>, AppError> {
+        self.read(|| async move {
+            let mut filter = doc! {"walker": walker_id};
+            let mut earned_at = Document::new();
+            if let Some(from) = from {
+                earned_at.insert("$gte", from);
+            }
+            if let Some(to) = to {
+                earned_at.insert("$lt", to);
+            }
+            if !earned_at.is_empty() {
+                filter.insert("earned_at", earned_at);
+            }
+
+            let buckets = self
+                .payout
+                .aggregate(vec![
+                    doc! {"$match": filter},
+                    doc! {"$group": {
+                        "_id": {
+                            "start": {"$dateTrunc": {
+                                "date": "$earned_at",
+                                "unit": period.unit(),
+                                "startOfWeek": "monday",
+                            }},
+                            "currency": "$currency",
+                        },
+                        "walks": {"$sum": 1_i64},
+                        "earned_cents": {"$sum": "$amount_cents"},
+                        "tips_cents": {"$sum": {
+                            "$cond": [{"$eq": ["$kind", PayoutKind::Tip]}, "$amount_cents", 0_i64]
+                        }},
+                        "settled_cents": {"$sum": {
+                            "$cond": [{"$ifNull": ["$settled_at", false]}, "$amount_cents", 0_i64]
+                        }},
+                    }},
+                    doc! {"$sort": {"_id.start": 1, "_id.currency": 1}},
+                    doc! {"$project": {
+                        "_id": 0,
+                        "start": "$_id.start",
+                        "currency": "$_id.currency",
+                        "walks": 1,
+                        "earned_cents": 1,
+                        "tips_cents": 1,
+                        "settled_cents": 1,
+                    }},
+                ])
+                .time_limit(self.max_time)
+                .with_type::<EarningsBucket>()
+                .await?
+                .try_collect()
+                .await?;
+            Ok(buckets)
+        })
+        .await
+    }
+
+    /// Settle every unsettled entry earned before `until`, of one walker or all
+    /// of them, as one new batch. An empty batch when nothing is due.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn settle_payouts(
+        &self,
+        walker_id: Option<&ObjectId>,
+        until: DateTime,
+    ) -> Result<PayoutBatch, AppError> {
+        self.write(async move {
+            let batch = ObjectId::new();
+            let settled_at = DateTime::now();
+            let mut filter = doc! {"settled_at": null, "earned_at": {"$lt": until}};
+            if let Some(walker_id) = walker_id {
+                filter.insert("walker", walker_id);
+            }
+            self.payout
+                .update_many(
+                    filter,
+                    doc! {"$set": {"batch": batch, "settled_at": settled_at}},
+                )
+                .await?;
+
+            // Totalled from the ledger, so entries settled concurrently by another batch aren't counted.
+            let mut cursor = self
+                .payout
+                .aggregate(vec![
+                    doc! {"$match": {"batch": batch}},
+                    doc! {"$group": {
+                        "_id": null,
+                        "payouts": {"$sum": 1_i64},
+                        "amount_cents": {"$sum": "$amount_cents"},
+                    }},
+                ])
+                .time_limit(self.max_time)
+                .await?;
+            let (payouts, amount_cents) = match cursor.next().await.transpose()? {
+                Some(totals) => (
+                    totals.get_i64("payouts").unwrap_or_default() as u64,
+                    totals.get_i64("amount_cents").unwrap_or_default(),
+                ),
+                None => (0, 0),
+            };
+            Ok(PayoutBatch {
+                batch,
+                settled_at,
+                payouts,
+                amount_cents,
+            })
+        })
+        .await
+    }
+
+    /// Partially update a walker and return the updated document.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn update_walker(
+        &self,
+        walker_id: &ObjectId,
+        update: &WalkerUpdateRequest,
+    ) -> Result<Walker, AppError> {
+        self.write(async move {
+            let set = update.to_set_document();
+            if set.is_empty() {
+                return Err(AppError::Validation(
+                    "At least one field must be provided".to_string(),
+                ));
+            }
+
+            self.walker
+                .find_one_and_update(doc! {"_id": walker_id}, doc! {"$set": set})
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Walker not found".to_string()))
+        })
+        .await
+    }
+
+    /// Delete a walker and its login, and unassign it from the bookings still holding
+    /// their slot, past bookings keep the id as a record of who walked.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn delete_walker(&self, walker_id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            let result = self.walker.delete_one(doc! {"_id": walker_id}).await?;
+            if result.deleted_count == 0 {
+                return Err(AppError::NotFound("Walker not found".to_string()));
+            }
+
+            self.credentials
+                .delete_many(doc! {"user_id": walker_id, "role": Role::Walker})
+                .await?;
+            self.walker_availability
+                .delete_one(doc! {"_id": walker_id})
+                .await?;
+
+            self.booking
+                .update_many(
+                    doc! {
+                        "walker": walker_id,
+                        "status": status_in(RESCHEDULABLE)
+                    },
+                    doc! {"$set": {"walker": null}, "$inc": {"version": 1}},
+                )
+                .await?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Replace the availability of a walker, stored under its id.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn set_walker_availability(
+        &self,
+        availability: &WalkerAvailability,
+    ) -> Result<(), AppError> {
+        self.write(async move {
+            self.walker_availability
+                .replace_one(doc! {"_id": availability._id}, availability)
+                .upsert(true)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// `None` when the walker never set one.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walker_availability(
+        &self,
+        walker_id: &ObjectId,
+    ) -> Result<Option<WalkerAvailability>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .walker_availability
+                .find_one(doc! {"_id": walker_id})
+                .time_limit(self.max_time)
+                .await?)
+        })
+        .await
+    }
+
+    /// Active bookings of a walker overlapping `[from, to)`, soonest first.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_walker_bookings(
+        &self,
+        walker_id: &ObjectId,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<Vec<Booking>, AppError> {
+        self.read(|| async move {
+            let mut bookings = self
+                .find_overlapping_bookings(from, to, doc! {"walker": walker_id})
+                .await?;
+            bookings.sort_by_key(|booking| booking.start_time);
+            Ok(bookings)
+        })
+        .await
+    }
+
+    /// Group walks whose `[start_time, start_time + duration)` intersects `[start, end)`,
+    /// `extra` merged into the filter.
+    async fn find_overlapping_group_walks(
+        &self,
+        start: DateTime,
+        end: DateTime,
+        extra: Document,
+    ) -> Result<Vec<GroupWalk>, AppError> {
+        let extra = &extra;
+        self.read(|| async move {
+            let mut filter = doc! {
+                "start_time": { "$lt": end },
+                "$expr": {
+                    "$gt": [
+                        { "$add": ["$start_time", { "$multiply": ["$duration_in_minutes", 60_000] }] },
+                        start
+                    ]
                 }
-                // If there was an error while fetching the document:
-                Err(err) => panic!("Error getting booking: {}", err),
+            };
+            filter.extend(extra.clone());
+            Ok(self
+                .group_walk
+                .find(filter)
+                .time_limit(self.max_time)
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    /// Open a group walk, refused outside the availability of the walker or over
+    /// another of its bookings or group walks.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn create_group_walk(&self, walk: &GroupWalk) -> Result<(), AppError> {
+        self.write(async move {
+            let (start, end) = (walk.start_time, walk.end_time());
+            if let Some(availability) = self.get_walker_availability(&walk.walker).await?
+                && !availability.allows(start, end)
+            {
+                return Err(outside_availability());
             }
-        }
+            let clashing = self
+                .find_overlapping_bookings(start, end, doc! {"walker": walk.walker})
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(overlap_conflict(
+                    "walker_unavailable",
+                    "The walker already has a booking overlapping this time slot",
+                    clashing,
+                ));
+            }
+            let clashing = self
+                .find_overlapping_group_walks(start, end, doc! {"walker": walk.walker})
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(group_walk_conflict(
+                    "walker_unavailable",
+                    "The walker already has a group walk overlapping this time slot",
+                    clashing,
+                ));
+            }
+
+            self.group_walk.insert_one(walk).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_group_walk(&self, walk_id: &ObjectId) -> Result<GroupWalk, AppError> {
+        self.read(|| async move {
+            self.group_walk
+                .find_one(doc! {"_id": walk_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Group walk not found".to_string()))
+        })
+        .await
+    }
+
+    /// Get a group walk with its walker, and each participant with its owner and
+    /// the live dogs it brings. The pipeline:
+    /// 1. $lookup / $unwind: join the walker
+    /// 2. $lookup: join the owners, and the dogs of every participant at once
+    /// 3. $set: hand each participant its owner and dogs, then drop the joined arrays
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_full_group_walk(
+        &self,
+        walk_id: &ObjectId,
+    ) -> Result<WithId<FullGroupWalk>, AppError> {
+        self.read(|| async move {
+            let pipeline = vec![
+                doc! {"$match": {"_id": walk_id}},
+                // Step 1
+                doc! {"$lookup": {
+                    "from": "walker",
+                    "localField": "walker",
+                    "foreignField": "_id",
+                    "as": "walker",
+                }},
+                doc! {"$unwind": {"path": "$walker"}},
+                // Step 2
+                doc! {"$lookup": {
+                    "from": "owner",
+                    "localField": "participants.owner",
+                    "foreignField": "_id",
+                    "as": "owners",
+                }},
+                doc! {"$lookup": {
+                    "from": "dog",
+                    "let": {"dogs": {"$reduce": {
+                        "input": "$participants.dogs",
+                        "initialValue": [],
+                        "in": {"$concatArrays": ["$$value", "$$this"]},
+                    }}},
+                    "pipeline": [{"$match": {
+                        "deleted_at": null,
+                        "$expr": {"$in": ["$_id", "$$dogs"]},
+                    }}],
+                    "as": "dogs",
+                }},
+                // Step 3
+                doc! {"$set": {"participants": {"$map": {
+                    "input": "$participants",
+                    "as": "participant",
+                    "in": {
+                        "owner": {"$first": {"$filter": {
+                            "input": "$owners",
+                            "cond": {"$eq": ["$$this._id", "$$participant.owner"]},
+                        }}},
+                        "dogs": {"$filter": {
+                            "input": "$dogs",
+                            "cond": {"$in": ["$$this._id", "$$participant.dogs"]},
+                        }},
+                        "price_cents": "$$participant.price_cents",
+                        "joined_at": "$$participant.joined_at",
+                    },
+                }}}},
+                doc! {"$unset": ["owners", "dogs"]},
+            ];
+
+            let mut results = self
+                .group_walk
+                .aggregate(pipeline)
+                .time_limit(self.max_time)
+                .await?;
+            match results.next().await {
+                Some(doc) => Ok(from_document(doc?)?),
+                None => Err(AppError::NotFound("Group walk not found".to_string())),
+            }
+        })
+        .await
+    }
+
+    /// Add an owner and its dogs to a group walk that hasn't started and has room
+    /// for them, then split the price again. Refused when the owner has another
+    /// booking or group walk at that time.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn join_group_walk(
+        &self,
+        walk_id: &ObjectId,
+        participant: GroupParticipant,
+    ) -> Result<GroupWalk, AppError> {
+        self.write(async move {
+            let walk = self.get_group_walk(walk_id).await?;
+            if walk.start_time <= DateTime::now() {
+                return Err(AppError::conflict("The group walk has started"));
+            }
+            if walk
+                .participants
+                .iter()
+                .any(|other| other.owner == participant.owner)
+            {
+                return Err(AppError::Conflict {
+                    code: "already_joined",
+                    message: "The owner already takes part in this group walk".to_string(),
+                    details: None,
+                });
+            }
+            let dogs = participant.dogs.len() as u32;
+            if walk.dog_count + dogs > walk.capacity {
+                return Err(AppError::Conflict {
+                    code: "group_walk_full",
+                    message: "The group walk has no room left for these dogs".to_string(),
+                    details: Some(json!({
+                        "capacity": walk.capacity,
+                        "dog_count": walk.dog_count,
+                    })),
+                });
+            }
+
+            let (start, end) = (walk.start_time, walk.end_time());
+            let clashing = self
+                .find_overlapping_bookings(start, end, doc! {"owner": participant.owner})
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(overlap_conflict(
+                    "booking_conflict",
+                    "The owner already has a booking overlapping this time slot",
+                    clashing,
+                ));
+            }
+            let clashing = self
+                .find_overlapping_group_walks(
+                    start,
+                    end,
+                    doc! {"participants.owner": participant.owner, "_id": {"$ne": walk_id}},
+                )
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(group_walk_conflict(
+                    "booking_conflict",
+                    "The owner already takes part in a group walk overlapping this time slot",
+                    clashing,
+                ));
+            }
+
+            let joined = self
+                .group_walk
+                .find_one_and_update(
+                    doc! {"_id": walk_id, "version": walk.version},
+                    doc! {
+                        "$push": {"participants": to_bson(&participant)?},
+                        "$inc": {"dog_count": i64::from(dogs), "version": 1},
+                    },
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| {
+                    AppError::conflict("The group walk changed in the meantime, retry")
+                })?;
+            self.reprice_group_walk(joined).await
+        })
+        .await
+    }
+
+    /// Take an owner and its dogs out of a group walk that hasn't started, then
+    /// split the price again between the others.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn leave_group_walk(
+        &self,
+        walk_id: &ObjectId,
+        owner_id: &ObjectId,
+    ) -> Result<GroupWalk, AppError> {
+        self.write(async move {
+            let walk = self.get_group_walk(walk_id).await?;
+            let participant = walk
+                .participants
+                .iter()
+                .find(|participant| participant.owner == *owner_id)
+                .ok_or_else(|| {
+                    AppError::NotFound("The owner doesn't take part in this group walk".to_string())
+                })?;
+            if walk.start_time <= DateTime::now() {
+                return Err(cancellation_closed());
+            }
+
+            let left = self
+                .group_walk
+                .find_one_and_update(
+                    doc! {"_id": walk_id, "version": walk.version},
+                    doc! {
+                        "$pull": {"participants": {"owner": owner_id}},
+                        "$inc": {
+                            "dog_count": -(participant.dogs.len() as i64),
+                            "version": 1,
+                        },
+                    },
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| {
+                    AppError::conflict("The group walk changed in the meantime, retry")
+                })?;
+            self.reprice_group_walk(left).await
+        })
+        .await
+    }
+
+    /// Write the shares of the participants of `walk`, for its version only: a
+    /// join or leave that moved it writes its own shares.
+    async fn reprice_group_walk(&self, mut walk: GroupWalk) -> Result<GroupWalk, AppError> {
+        self.write(async move {
+            let tier = self.get_walker(&walk.walker).await?.tier;
+            let rules = self.pricing_rules().await?;
+            let shares = pricing::group_shares(&rules, &walk, tier);
+
+            let mut set = doc! {
+                "price_cents": shares.iter().sum::<i64>(),
+                "currency": &rules.currency,
+            };
+            for (index, (participant, share)) in
+                walk.participants.iter_mut().zip(shares).enumerate()
+            {
+                participant.price_cents = share;
+                set.insert(format!("participants.{}.price_cents", index), share);
+            }
+            walk.price_cents = walk.participants.iter().map(|p| p.price_cents).sum();
+            walk.currency = rules.currency;
+
+            self.group_walk
+                .update_one(
+                    doc! {"_id": walk._id, "version": walk.version},
+                    doc! {"$set": set},
+                )
+                .await?;
+            Ok(walk)
+        })
+        .await
+    }
+
+    /// GridFS bucket of the dog photos.
+    fn dog_photos(&self) -> GridFsBucket {
+        self.client
+            .database(&self.owner.namespace().db)
+            .gridfs_bucket(
+                GridFsBucketOptions::builder()
+                    .bucket_name(DOG_PHOTO_BUCKET.to_string())
+                    .build(),
+            )
+    }
+
+    /// Store a photo in GridFS and make it the dog's photo, the previous one is removed.
+    /// Returns the dog before and after the change.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn save_dog_photo(
+        &self,
+        dog_id: &ObjectId,
+        content_type: &str,
+        bytes: &[u8],
+    ) -> Result<(Dog, Dog), AppError> {
+        self.write(async move {
+            let bucket = self.dog_photos();
+            let photo_id = ObjectId::new();
+            let mut upload = bucket
+                .open_upload_stream(format!("dog-{}", dog_id.to_hex()))
+                .id(photo_id.into())
+                .metadata(doc! {"dog": dog_id, "content_type": content_type})
+                .await?;
+            if let Err(err) = upload.write_all(bytes).await {
+                upload.abort().await.ok();
+                return Err(AppError::Internal(format!(
+                    "Failed to store the photo: {}",
+                    err
+                )));
+            }
+            upload
+                .close()
+                .await
+                .map_err(|err| AppError::Internal(format!("Failed to store the photo: {}", err)))?;
+
+            let before = self
+                .dog
+                .find_one_and_update(
+                    doc! {"_id": dog_id, "deleted_at": null},
+                    doc! {"$set": {"photo_id": photo_id}},
+                )
+                .time_limit(self.max_time)
+                .await?;
+            let Some(before) = before else {
+                bucket.delete(photo_id.into()).await.ok();
+                return Err(AppError::NotFound("Dog not found".to_string()));
+            };
+
+            if let Some(previous) = before.photo_id
+                && let Err(err) = bucket.delete(previous.into()).await
+            {
+                warn!(photo_id = %previous, error = %err, "Failed to remove the previous dog photo");
+            }
+            let after = Dog {
+                photo_id: Some(photo_id),
+                ..before.clone()
+            };
+            Ok((before, after))
+        })
+        .await
+    }
+
+    /// The GridFS file of a photo, without reading its content.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_dog_photo(&self, photo_id: &ObjectId) -> Result<StoredPhoto, AppError> {
+        self.read(|| async move {
+            let file = self
+                .dog_photos()
+                .find_one(doc! {"_id": photo_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Photo not found".to_string()))?;
+            let content_type = file
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get_str("content_type").ok())
+                .unwrap_or("application/octet-stream")
+                .to_string();
+            Ok(StoredPhoto {
+                id: *photo_id,
+                content_type,
+                length: file.length,
+                uploaded_at: file.upload_date,
+            })
+        })
+        .await
+    }
+
+    /// Read a photo from GridFS chunk by chunk.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn open_dog_photo(
+        &self,
+        photo_id: &ObjectId,
+    ) -> Result<GridFsDownloadStream, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .dog_photos()
+                .open_download_stream((*photo_id).into())
+                .await?)
+        })
+        .await
+    }
+
+    /// Active bookings (see `BookingStatus::active`) whose `[start_time, start_time + duration)` intersects `[start, end)`.
+    /// `extra` is merged into the filter, e.g. `{"_id": {"$lt": id}}` to only keep
+    /// bookings inserted earlier, or `{"_id": {"$ne": id}}` to ignore the booking being moved.
+    async fn find_overlapping_bookings(
+        &self,
+        start: DateTime,
+        end: DateTime,
+        extra: Document,
+    ) -> Result<Vec<Booking>, AppError> {
+        let mut filter = doc! {
+            "status": status_in(BookingStatus::active()),
+            "deleted_at": null,
+            "start_time": { "$lt": end },
+            // booking end = start_time + duration_in_minutes * 60 000 ms
+            "$expr": {
+                "$gt": [
+                    { "$add": ["$start_time", { "$multiply": ["$duration_in_minutes", 60_000] }] },
+                    start
+                ]
+            }
+        };
+        filter.extend(extra);
+
+        let filter = &filter;
+        self.read(|| async move {
+            let mut cursor = self
+                .booking
+                .find(filter.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let mut bookings = Vec::new();
+            while let Some(booking) = cursor.next().await {
+                bookings.push(booking?);
+            }
+            Ok(bookings)
+        })
+        .await
+    }
+
+    /// Price and insert a booking that passed the overlap checks of `create_booking`,
+    /// then repeat the capacity check against the older bookings.
+    async fn insert_booking(&self, booking: Booking) -> Result<ObjectId, AppError> {
+        self.write(async move {
+            let quote = self.quote_booking(&booking).await?;
+            let booking = booking.with_price(quote);
+            let booking_id = booking._id;
+            let start = booking.start_time;
+            let end = booking_end(&booking);
+            self.booking.insert_one(booking).await?;
+
+            if let Some(max) = self.max_concurrent_bookings {
+                let older = self
+                    .find_overlapping_bookings(start, end, doc! {"_id": { "$lt": booking_id }})
+                    .await?;
+                if older.len() >= max {
+                    self.booking.delete_one(doc! {"_id": booking_id}).await?;
+                    return Err(capacity_reached(&older));
+                }
+            }
+
+            Ok(booking_id)
+        })
+        .await
+    }
+
+    /// Take one use of a coupon, only if it is within its validity window and has
+    /// uses left; the filter and the decrement are a single atomic update.
+    async fn redeem_coupon(&self, code: &str) -> Result<Coupon, AppError> {
+        self.write(async move {
+            let code = coupon_key(code);
+            let now = DateTime::now();
+            let redeemed = self
+                .coupon
+                .find_one_and_update(
+                    doc! {
+                        "code": &code,
+                        "remaining_uses": {"$gt": 0},
+                        "$and": [
+                            {"$or": [{"valid_from": null}, {"valid_from": {"$lte": now}}]},
+                            {"$or": [{"valid_until": null}, {"valid_until": {"$gt": now}}]},
+                        ],
+                    },
+                    doc! {"$inc": {"remaining_uses": -1}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            match redeemed {
+                Some(coupon) => Ok(coupon),
+                // Nothing matched: explain why from the current coupon.
+                None => Err(self
+                    .get_usable_coupon(&code)
+                    .await
+                    .err()
+                    .unwrap_or_else(|| {
+                        coupon_unavailable(&code, "was used up by a concurrent booking")
+                    })),
+            }
+        })
+        .await
+    }
+
+    /// The rules are read on every call, so edits apply right away.
+    async fn pricing_rules(&self) -> Result<PricingRules, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .pricing_rules
+                .find_one(doc! {"_id": PRICING_RULES_ID})
+                .time_limit(self.max_time)
+                .await?
+                .unwrap_or_default())
+        })
+        .await
+    }
+
+    /// Cancel a booking for the fee of the cancellation policy, recorded on it, or refuse
+    /// once the walk started. The version filter makes a concurrent reschedule, which
+    /// changes the fee, fail the cancellation instead.
+    async fn cancel_under_policy(
+        &self,
+        booking_id: &ObjectId,
+        mut extra_filter: Document,
+    ) -> Result<Booking, AppError> {
+        self.write(async move {
+            let current = self.get_booking(booking_id).await?;
+            let now = DateTime::now();
+            let fee = pricing::cancellation_fee(
+                &self.pricing_rules().await?,
+                current.start_time,
+                current.price_cents.unwrap_or_default(),
+                now,
+            );
+            if fee.is_none()
+                && BookingStatus::Cancelled
+                    .allowed_from()
+                    .contains(&current.status)
+            {
+                return Err(cancellation_closed());
+            }
+
+            extra_filter.insert("version", current.version);
+            self.transition_booking(
+                booking_id,
+                BookingStatus::Cancelled,
+                extra_filter,
+                doc! {"cancelled_at": now, "cancellation_fee_cents": fee},
+            )
+            .await
+        })
+        .await
+    }
+
+    /// Take the price of a free walk from the points of an owner, only if the
+    /// balance covers it; returns the points spent.
+    async fn redeem_points(&self, owner_id: &ObjectId) -> Result<i64, AppError> {
+        self.write(async move {
+            let cost = self.pricing_rules().await?.points_per_free_walk;
+            if cost <= 0 {
+                return Err(AppError::Validation(
+                    "Loyalty points can't be redeemed".to_string(),
+                ));
+            }
+
+            let redeemed = self
+                .owner
+                .update_one(
+                    doc! {"_id": owner_id, "deleted_at": null, "points_balance": {"$gte": cost}},
+                    doc! {"$inc": {"points_balance": -cost}},
+                )
+                .await?;
+            self.owner_cache.invalidate(owner_id);
+            if redeemed.matched_count == 0 {
+                let owner = self.get_owner_by_id(owner_id).await?;
+                return Err(not_enough_points(owner.points_balance, cost));
+            }
+            Ok(cost)
+        })
+        .await
+    }
+
+    /// Give back the points taken by `redeem_points` for a booking that wasn't stored.
+    /// A failure is only logged, the points are lost.
+    async fn release_points(&self, owner_id: &ObjectId, points: i64) {
+        let released = self
+            .owner
+            .update_one(
+                doc! {"_id": owner_id},
+                doc! {"$inc": {"points_balance": points}},
+            )
+            .await;
+        self.owner_cache.invalidate(owner_id);
+        if let Err(err) = released {
+            error!(error = %err, owner_id = %owner_id, points, "Failed to give back loyalty points");
+        }
+    }
+
+    /// Writes of `award_points`, all bound to its session. Returns the credited
+    /// owner, `None` when the booking was already credited.
+    async fn award_points_in_session(
+        &self,
+        session: &mut ClientSession,
+        booking_id: &ObjectId,
+        points: i64,
+    ) -> Result<Option<ObjectId>, AppError> {
+        let booking = self
+            .booking
+            .find_one_and_update(
+                doc! {
+                    "_id": booking_id,
+                    "status": BookingStatus::Completed,
+                    "points_earned": null,
+                },
+                doc! {"$set": {"points_earned": points}},
+            )
+            .time_limit(self.max_time)
+            .session(&mut *session)
+            .await?;
+        let Some(booking) = booking else {
+            return Ok(None);
+        };
+
+        self.owner
+            .update_one(
+                doc! {"_id": booking.owner},
+                doc! {"$inc": {"points_balance": points}},
+            )
+            .session(&mut *session)
+            .await?;
+        Ok(Some(booking.owner))
+    }
+
+    /// Give back the use taken by `redeem_coupon` for a booking that wasn't stored.
+    /// A failure is only logged, the use is lost.
+    async fn release_coupon(&self, code: &str) {
+        let released = self
+            .coupon
+            .update_one(
+                doc! {
+                    "code": coupon_key(code),
+                    "$expr": {"$lt": ["$remaining_uses", "$max_uses"]},
+                },
+                doc! {"$inc": {"remaining_uses": 1}},
+            )
+            .await;
+        if let Err(err) = released {
+            error!(error = %err, code, "Failed to give back a coupon use");
+        }
+    }
+
+    /// Plain cursors over the three collections, consumed by `GET /admin/export`.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn export_cursors(&self) -> Result<ExportCursors, AppError> {
+        self.read(|| async move {
+            Ok((
+                self.owner.find(doc! {}).time_limit(self.max_time).await?,
+                self.dog.find(doc! {}).time_limit(self.max_time).await?,
+                self.booking.find(doc! {}).time_limit(self.max_time).await?,
+            ))
+        })
+        .await
+    }
+
+    /// True when the owner, dog and booking collections hold no document at all.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn dataset_is_empty(&self) -> Result<bool, AppError> {
+        self.read(|| async move {
+            let owners = self
+                .owner
+                .count_documents(doc! {})
+                .time_limit(self.max_time)
+                .limit(1)
+                .await?;
+            let dogs = self
+                .dog
+                .count_documents(doc! {})
+                .time_limit(self.max_time)
+                .limit(1)
+                .await?;
+            let bookings = self
+                .booking
+                .count_documents(doc! {})
+                .time_limit(self.max_time)
+                .limit(1)
+                .await?;
+
+            Ok(owners + dogs + bookings == 0)
+        })
+        .await
+    }
+
+    /// Insert a backup with `insert_many`, collection by collection.
+    /// With `merge`, documents whose `_id` already exists are skipped and counted as such.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn import_backup(
+        &self,
+        backup: Backup,
+        merge: bool,
+    ) -> Result<ImportReport, AppError> {
+        self.write(async move {
+            let report = ImportReport {
+                owners: import_collection(&self.owner, backup.owners, merge).await?,
+                dogs: import_collection(&self.dog, backup.dogs, merge).await?,
+                bookings: import_collection(&self.booking, backup.bookings, merge).await?,
+            };
+            // Merged owners never replace cached ones, but a full import starts from scratch.
+            self.owner_cache.purge();
+
+            Ok(report)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl OwnerRepository for Database {
+    /// Find an owner by its ObjectId, going through the in-process owner cache first.
+    /// Only owners actually found in the "owner" collection are cached, never deleted ones,
+    /// nor ones invalidated while they were being read.
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owner_by_id(&self, owner_id: &ObjectId) -> Result<Owner, AppError> {
+        self.read(|| async move {
+            if let Some(owner) = self.owner_cache.get(owner_id) {
+                return Ok(owner);
+            }
+
+            let generation = self.owner_cache.generation();
+            let owner = self
+                .read(|| async move {
+                    Ok(self
+                        .owner
+                        .find_one(doc! {"_id": owner_id, "deleted_at": null})
+                        .time_limit(self.max_time)
+                        .await?)
+                })
+                .await?
+                .ok_or_else(|| AppError::NotFound("Owner not found".to_string()))?;
+            self.owner_cache.insert(owner.clone(), generation);
+
+            Ok(owner)
+        })
+        .await
+    }
+
+    /// Check that an owner exists, used to validate bookings before inserting them.
+    #[instrument(level = "debug", skip_all)]
+    async fn owner_exists(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<bool, AppError> {
+        self.read(|| async move {
+            if include_deleted {
+                let count = self
+                    .owner
+                    .count_documents(doc! {"_id": owner_id})
+                    .time_limit(self.max_time)
+                    .await?;
+                return Ok(count > 0);
+            }
+
+            match self.get_owner_by_id(owner_id).await {
+                Ok(_) => Ok(true),
+                Err(AppError::NotFound(_)) => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+        .await
+    }
+
+    /// Get one owner with its dogs.
+    /// Same `$lookup` pattern as `get_bookings`:
+    /// 1. $match: the owner by _id
+    /// 2. $lookup: join with dog collection on dog.owner
+    /// 3. $project: nest the owner document next to its "dogs" array
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owner_full(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<OwnerWithDogs, AppError> {
+        self.read(|| async move {
+            let mut owner_match = doc! {"_id": owner_id};
+            owner_match.extend(visible(include_deleted));
+
+            let mut results = self
+                .owner
+                .aggregate(vec![
+                    doc! {
+                        "$match": owner_match
+                    },
+                    doc! {
+                        "$lookup": {
+                            "from": "dog",
+                            "localField": "_id",
+                            "foreignField": "owner",
+                            "pipeline": [{ "$match": visible(include_deleted) }],
+                            "as": "dogs"
+                        }
+                    },
+                    doc! {
+                        "$project": {
+                            "owner": "$$ROOT",
+                            "dogs": 1
+                        }
+                    },
+                ])
+                .time_limit(self.max_time)
+                .await?;
+
+            match results.next().await {
+                Some(doc) => Ok(from_document(doc?)?),
+                None => Err(AppError::NotFound("Owner not found".to_string())),
+            }
+        })
+        .await
+    }
+
+    /// List owners one page at a time, sorted by name or creation date,
+    /// with the total number of owners for the pagination metadata.
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owners(
+        &self,
+        page: u64,
+        limit: u64,
+        sort: OwnerSort,
+        include_deleted: bool,
+    ) -> Result<Page<WithId<Owner>>, AppError> {
+        self.read(|| async move {
+            let sort = match sort {
+                OwnerSort::Name => doc! {"name": 1, "_id": 1},
+                OwnerSort::CreatedAt => doc! {"_id": 1},
+            };
+
+            let filter = visible(include_deleted);
+            let total = self
+                .owner
+                .count_documents(filter.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let mut cursor = self
+                .owner
+                .find(filter)
+                .time_limit(self.max_time)
+                .sort(sort)
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?;
+
+            let mut items = Vec::new();
+            while let Some(owner) = cursor.next().await {
+                items.push(WithId(owner?));
+            }
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    /// Insert a new owner into the "owner" collection.
+    /// Returns its id and the email verification token to send to the owner.
+    /// An email already used by another owner hits the unique index and is a 409 `email_taken`.
+    #[instrument(level = "debug", skip_all)]
+    async fn create_owner(&self, owner: Owner) -> Result<(ObjectId, String), AppError> {
+        self.write(async move {
+            let owner_id = owner._id;
+            match self.owner.insert_one(owner).await {
+                Ok(_) => {}
+                Err(err) if is_duplicate_key(&err) => return Err(email_taken()),
+                Err(err) => return Err(err.into()),
+            }
+
+            let (token, token_hash) = one_time_token();
+            self.email_verification
+                .insert_one(email_verification(owner_id, token_hash))
+                .await?;
+
+            Ok((owner_id, token))
+        })
+        .await
+    }
+
+    /// Insert an owner and its dogs in one transaction (MongoDB must run as a replica set),
+    /// so a failing insert leaves neither the owner nor some of its dogs behind.
+    #[instrument(level = "debug", skip_all)]
+    async fn create_owner_with_dogs(
+        &self,
+        owner: Owner,
+        dogs: Vec<Dog>,
+    ) -> Result<(ObjectId, String), AppError> {
+        self.write(async move {
+            let mut session = self.client.start_session().await?;
+            session.start_transaction().await?;
+
+            match self
+                .create_owner_in_session(&mut session, owner, dogs)
+                .await
+            {
+                Ok(created) => {
+                    session.commit_transaction().await?;
+                    Ok(created)
+                }
+                Err(err) => {
+                    // The original error is more useful than a failed abort.
+                    let _ = session.abort_transaction().await;
+                    Err(err)
+                }
+            }
+        })
+        .await
+    }
+
+    /// Mark the owner behind a verification token as verified, the token is spent.
+    #[instrument(level = "debug", skip_all)]
+    async fn verify_owner_email(&self, token_hash: &str) -> Result<Owner, AppError> {
+        self.write(async move {
+            let verification = self
+                .email_verification
+                .find_one_and_delete(doc! {
+                    "token_hash": token_hash,
+                    "expires_at": { "$gt": DateTime::now() }
+                })
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::Gone("This link is invalid or has expired".to_string()))?;
+
+            let owner = self
+                .owner
+                .find_one_and_update(
+                    doc! {"_id": verification.owner},
+                    doc! {"$set": {"email_verified": true}, "$inc": {"version": 1}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            self.owner_cache.invalidate(&verification.owner);
+
+            owner.ok_or_else(|| AppError::Gone("This link is invalid or has expired".to_string()))
+        })
+        .await
+    }
+
+    /// Partially update an owner with a `$set` built from the provided fields,
+    /// and return the updated document. The cached copy is dropped right away
+    /// so the next lookup sees the new values. Taking the email of another owner is a 409.
+    /// `expected_version` is part of the filter, a concurrent update makes this one a 412.
+    #[instrument(level = "debug", skip_all)]
+    async fn update_owner(
+        &self,
+        owner_id: &ObjectId,
+        update: &OwnerUpdateRequest,
+        expected_version: i64,
+    ) -> Result<Owner, AppError> {
+        self.write(async move {
+            let set = update.to_set_document();
+            if set.is_empty() {
+                return Err(AppError::Validation(
+                    "At least one field must be provided".to_string(),
+                ));
+            }
+
+            let owner = match self
+                .owner
+                .find_one_and_update(
+                    doc! {"_id": owner_id, "deleted_at": null, "version": expected_version},
+                    doc! {"$set": set, "$inc": {"version": 1}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await
+            {
+                Ok(owner) => owner,
+                Err(err) if is_duplicate_key(&err) => return Err(email_taken()),
+                Err(err) => return Err(err.into()),
+            };
+            self.owner_cache.invalidate(owner_id);
+
+            match owner {
+                Some(owner) => Ok(owner),
+                None => match self
+                    .owner
+                    .find_one(doc! {"_id": owner_id, "deleted_at": null})
+                    .time_limit(self.max_time)
+                    .await?
+                {
+                    Some(current) => {
+                        Err(version_mismatch("owner", expected_version, current.version))
+                    }
+                    None => Err(AppError::NotFound("Owner not found".to_string())),
+                },
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_notification_preferences(
+        &self,
+        owner_id: &ObjectId,
+        preferences: &NotificationPreferences,
+    ) -> Result<Owner, AppError> {
+        self.write(async move {
+            let owner = self
+                .owner
+                .find_one_and_update(
+                    doc! {"_id": owner_id, "deleted_at": null},
+                    doc! {
+                        "$set": {"notification_preferences": to_bson(preferences)?},
+                        "$inc": {"version": 1},
+                    },
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            self.owner_cache.invalidate(owner_id);
+            owner.ok_or_else(|| AppError::NotFound("Owner not found".to_string()))
+        })
+        .await
+    }
+
+    /// Soft delete an owner and its dogs, and cancel its upcoming bookings.
+    /// The login is kept for a restore, `POST /auth/login` refuses deleted owners.
+    /// Everything runs in one transaction (MongoDB must run as a replica set),
+    /// so either the whole cleanup is applied or nothing is.
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_owner_cascade(&self, owner_id: &ObjectId) -> Result<OwnerDeletion, AppError> {
+        self.write(async move {
+            let mut session = self.client.start_session().await?;
+            session.start_transaction().await?;
+
+            match self.delete_owner_in_session(&mut session, owner_id).await {
+                Ok(deletion) => {
+                    session.commit_transaction().await?;
+                    self.owner_cache.invalidate(owner_id);
+                    Ok(deletion)
+                }
+                Err(err) => {
+                    // The original error is more useful than a failed abort.
+                    let _ = session.abort_transaction().await;
+                    Err(err)
+                }
+            }
+        })
+        .await
+    }
+
+    /// Clear `deleted_at` on the owner and on the dogs deleted at the same instant,
+    /// i.e. by the same `DELETE /owner/{id}`: dogs deleted on their own stay deleted.
+    /// Bookings cancelled by the deletion stay cancelled.
+    #[instrument(level = "debug", skip_all)]
+    async fn restore_owner(&self, owner_id: &ObjectId) -> Result<Owner, AppError> {
+        self.write(async move {
+            let deleted = self
+                .owner
+                .find_one(doc! {"_id": owner_id, "deleted_at": {"$ne": null}})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("No deleted owner with this id".to_string()))?;
+
+            // Dogs first, so a failure in between is fixed by restoring again.
+            self.dog
+                .update_many(
+                    doc! {"owner": owner_id, "deleted_at": deleted.deleted_at},
+                    doc! {"$unset": {"deleted_at": ""}},
+                )
+                .await?;
+            let owner = self
+                .owner
+                .find_one_and_update(
+                    doc! {"_id": owner_id, "deleted_at": deleted.deleted_at},
+                    doc! {"$unset": {"deleted_at": ""}, "$inc": {"version": 1}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            self.owner_cache.invalidate(owner_id);
+
+            owner.ok_or_else(|| AppError::conflict("Owner changed while restoring"))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owner_points(&self, owner_id: &ObjectId) -> Result<OwnerPoints, AppError> {
+        self.read(|| async move {
+            let owner = self.get_owner_by_id(owner_id).await?;
+            Ok(OwnerPoints::new(
+                owner.points_balance,
+                &self.pricing_rules().await?,
+            ))
+        })
+        .await
+    }
+
+    /// Upsert by token: a phone moving to another account, or registering again,
+    /// keeps its single entry.
+    #[instrument(level = "debug", skip_all)]
+    async fn register_device(&self, device: Device) -> Result<Device, AppError> {
+        self.write(async move {
+            self.devices
+                .find_one_and_update(
+                    doc! {"token": &device.token},
+                    doc! {
+                        "$set": {
+                            "owner": device.owner,
+                            "platform": device.platform.as_str(),
+                            "registered_at": device.registered_at,
+                        },
+                        "$setOnInsert": {"_id": device._id, "created_at": device.created_at},
+                    },
+                )
+                .time_limit(self.max_time)
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::Internal("Device not stored".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owner_devices(&self, owner_id: &ObjectId) -> Result<Vec<Device>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .devices
+                .find(doc! {"owner": owner_id})
+                .time_limit(self.max_time)
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_devices(&self, tokens: &[String]) -> Result<u64, AppError> {
+        self.write(async move {
+            let result = self
+                .devices
+                .delete_many(doc! {"token": {"$in": tokens}})
+                .await?;
+            Ok(result.deleted_count)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl DogRepository for Database {
+    /// Insert a new dog into the "dog" collection.
+    #[instrument(level = "debug", skip_all)]
+    async fn create_dog(&self, dog: Dog) -> Result<ObjectId, AppError> {
+        self.write(async move {
+            let dog_id = dog._id;
+            self.dog.insert_one(dog).await?;
+            Ok(dog_id)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_dog(&self, dog_id: &ObjectId) -> Result<Dog, AppError> {
+        self.read(|| async move {
+            self.dog
+                .find_one(doc! {"_id": dog_id, "deleted_at": null})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+        })
+        .await
+    }
+
+    /// Partially update a dog and return the updated document.
+    #[instrument(level = "debug", skip_all)]
+    async fn update_dog(
+        &self,
+        dog_id: &ObjectId,
+        update: &DogUpdateRequest,
+    ) -> Result<Dog, AppError> {
+        self.write(async move {
+            let set = update.to_set_document();
+            if set.is_empty() {
+                return Err(AppError::Validation(
+                    "At least one field must be provided".to_string(),
+                ));
+            }
+
+            self.dog
+                .find_one_and_update(doc! {"_id": dog_id, "deleted_at": null}, doc! {"$set": set})
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+        })
+        .await
+    }
+
+    /// Soft delete a dog.
+    /// Bookings don't store dog ids, their "dogs" array is joined from the owner
+    /// at read time (see `get_bookings`), so the dog disappears from every
+    /// pending booking as soon as it is deleted.
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_dog(&self, dog_id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            let result = self
+                .dog
+                .update_one(
+                    doc! {"_id": dog_id, "deleted_at": null},
+                    doc! {"$set": {"deleted_at": DateTime::now()}},
+                )
+                .await?;
+            if result.matched_count == 0 {
+                return Err(AppError::NotFound("Dog not found".to_string()));
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Clear `deleted_at` on a dog, refused while its owner is deleted.
+    #[instrument(level = "debug", skip_all)]
+    async fn restore_dog(&self, dog_id: &ObjectId) -> Result<Dog, AppError> {
+        self.write(async move {
+            let deleted = self
+                .dog
+                .find_one(doc! {"_id": dog_id, "deleted_at": {"$ne": null}})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("No deleted dog with this id".to_string()))?;
+            if !self.owner_exists(&deleted.owner, false).await? {
+                return Err(owner_deleted());
+            }
+
+            self.dog
+                .find_one_and_update(doc! {"_id": dog_id}, doc! {"$unset": {"deleted_at": ""}})
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+        })
+        .await
+    }
+
+    /// All dogs belonging to an owner, read with a filtered `find` cursor.
+    #[instrument(level = "debug", skip_all)]
+    async fn get_dogs_by_owner(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<Vec<WithId<Dog>>, AppError> {
+        self.read(|| async move {
+            let mut filter = doc! {"owner": owner_id};
+            filter.extend(visible(include_deleted));
+            let mut cursor = self.dog.find(filter).time_limit(self.max_time).await?;
+
+            let mut dogs = Vec::new();
+            while let Some(dog) = cursor.next().await {
+                dogs.push(WithId(dog?));
+            }
+
+            Ok(dogs)
+        })
+        .await
+    }
+
+    /// Case insensitive regex anchored at the start of a word of the name.
+    #[instrument(level = "debug", skip_all)]
+    async fn search_breeds(
+        &self,
+        prefix: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<String>, AppError> {
+        self.read(|| async move {
+            let filter = match prefix {
+                Some(prefix) => doc! {
+                    "name": {"$regex": format!("(^|\\s){}", escape_regex(prefix)), "$options": "i"}
+                },
+                None => doc! {},
+            };
+            let breeds: Vec<Breed> = self
+                .breeds
+                .find(filter)
+                .time_limit(self.max_time)
+                .sort(doc! {"name": 1})
+                .limit(limit as i64)
+                .await?
+                .try_collect()
+                .await?;
+            Ok(breeds.into_iter().map(|breed| breed.name).collect())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_breed(&self, name: &str) -> Result<Option<String>, AppError> {
+        self.read(|| async move {
+            let breed = self
+                .breeds
+                .find_one(doc! {"_id": breed_key(name)})
+                .time_limit(self.max_time)
+                .await?;
+            Ok(breed.map(|breed| breed.name))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_dog_profile(
+        &self,
+        dog_id: &ObjectId,
+        profile: &DogProfile,
+    ) -> Result<Dog, AppError> {
+        self.write(async move {
+            self.dog
+                .find_one_and_update(
+                    doc! {"_id": dog_id, "deleted_at": null},
+                    doc! {"$set": {"profile": to_bson(profile)?}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn add_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination: Vaccination,
+    ) -> Result<Dog, AppError> {
+        self.write(async move {
+            self.dog
+                .find_one_and_update(
+                    doc! {"_id": dog_id, "deleted_at": null},
+                    doc! {"$push": {"vaccinations": to_bson(&vaccination)?}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+        })
+        .await
+    }
+
+    /// Swap the array element in place through the positional `$` operator.
+    #[instrument(level = "debug", skip_all)]
+    async fn replace_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination: Vaccination,
+    ) -> Result<Dog, AppError> {
+        self.write(async move {
+            self.dog
+                .find_one_and_update(
+                    doc! {"_id": dog_id, "deleted_at": null, "vaccinations._id": vaccination._id},
+                    doc! {"$set": {"vaccinations.$": to_bson(&vaccination)?}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Vaccination not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination_id: &ObjectId,
+    ) -> Result<Dog, AppError> {
+        self.write(async move {
+            self.dog
+                .find_one_and_update(
+                    doc! {"_id": dog_id, "deleted_at": null, "vaccinations._id": vaccination_id},
+                    doc! {"$pull": {"vaccinations": {"_id": vaccination_id}}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Vaccination not found".to_string()))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl BookingRepository for Database {
+    /// Insert a new booking into the "booking" collection.
+    /// An owner can't have two active bookings overlapping each other: the clashing
+    /// booking is reported in a `booking_conflict` 409.
+    /// When `bookings.max_concurrent_bookings` is set, the number of non-cancelled bookings
+    /// overlapping the new one (any owner) is checked before inserting.
+    /// To narrow the race between two concurrent inserts, the check is repeated
+    /// after inserting: if more than the limit of older bookings (smaller ObjectId)
+    /// overlap ours, our booking is removed again and the slot is reported full.
+    /// The points and the coupon are redeemed once the overlap checks pass, and given
+    /// back when the insert fails or is undone.
+    #[instrument(level = "debug", skip_all)]
+    async fn create_booking(
+        &self,
+        mut booking: Booking,
+        coupon_code: Option<&str>,
+        redeem_points: bool,
+    ) -> Result<ObjectId, AppError> {
+        self.write(async move {
+            let start = booking.start_time;
+            let end = booking_end(&booking);
+            booking.expires_at = pending_expires_at(&booking, self.pending_expiry);
+
+            let clashing = self
+                .find_overlapping_bookings(start, end, doc! {"owner": booking.owner})
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(overlap_conflict(
+                    "booking_conflict",
+                    "The owner already has a booking overlapping this time slot",
+                    clashing,
+                ));
+            }
+
+            if let Some(max) = self.max_concurrent_bookings {
+                let overlapping = self.find_overlapping_bookings(start, end, doc! {}).await?;
+                if overlapping.len() >= max {
+                    return Err(capacity_reached(&overlapping));
+                }
+            }
+
+            let owner_id = booking.owner;
+            if redeem_points {
+                booking.points_redeemed = self.redeem_points(&owner_id).await?;
+            }
+            let points_redeemed = booking.points_redeemed;
+
+            let inserted = match coupon_code {
+                Some(code) => match self.redeem_coupon(code).await {
+                    Ok(coupon) => {
+                        booking.coupon = Some(coupon.applied());
+                        let inserted = self.insert_booking(booking).await;
+                        if inserted.is_err() {
+                            self.release_coupon(code).await;
+                        }
+                        inserted
+                    }
+                    Err(err) => Err(err),
+                },
+                None => self.insert_booking(booking).await,
+            };
+            if inserted.is_err() && points_redeemed > 0 {
+                self.release_points(&owner_id, points_redeemed).await;
+            }
+            inserted
+        })
+        .await
+    }
+
+    /// Find a single booking by its ObjectId (no lookups).
+    #[instrument(level = "debug", skip_all)]
+    async fn get_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError> {
+        self.read(|| async move {
+            self.booking
+                .find_one(doc! {"_id": booking_id, "deleted_at": null})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Booking not found".to_string()))
+        })
+        .await
+    }
+
+    /// The rules are read on every quote, so edits apply to the next booking.
+    /// A walker deleted since the assignment is priced as standard.
+    #[instrument(level = "debug", skip_all)]
+    async fn quote_booking(&self, booking: &Booking) -> Result<Quote, AppError> {
+        self.read(|| async move {
+            let rules = self.pricing_rules().await?;
+            let dogs = self
+                .dog
+                .count_documents(doc! {"owner": booking.owner, "deleted_at": null})
+                .time_limit(self.max_time)
+                .await?;
+            let tier = match booking.walker {
+                Some(walker_id) => self
+                    .walker
+                    .find_one(doc! {"_id": walker_id})
+                    .time_limit(self.max_time)
+                    .await?
+                    .map(|walker| walker.tier)
+                    .unwrap_or_default(),
+                None => WalkerTier::Standard,
+            };
+            Ok(pricing::quote(
+                &rules,
+                booking.start_time,
+                booking.duration_in_minutes,
+                dogs,
+                tier,
+                booking.points_redeemed,
+                booking.coupon.as_ref().map(|coupon| coupon.discount),
+            ))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_pricing_rules(&self) -> Result<PricingRules, AppError> {
+        self.read(|| async move { self.pricing_rules().await })
+            .await
+    }
+
+    /// Get one booking with its owner and dogs, same pipeline as `get_bookings`
+    /// but matched on `_id` instead of the upcoming filter.
+    #[instrument(level = "debug", skip_all)]
+    async fn get_full_booking(
+        &self,
+        booking_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<WithId<FullBooking>, AppError> {
+        self.read(|| async move {
+            let mut booking_match = doc! {"_id": booking_id};
+            booking_match.extend(visible(include_deleted));
+            let mut pipeline = vec![doc! {
+                "$match": booking_match
+            }];
+            pipeline.extend(full_booking_stages());
+
+            let mut results = self
+                .booking
+                .aggregate(pipeline)
+                .time_limit(self.max_time)
+                .await?;
+
+            match results.next().await {
+                Some(doc) => Ok(from_document(doc?)?),
+                None => Err(AppError::NotFound("Booking not found".to_string())),
+            }
+        })
+        .await
+    }
+
+    /// Get one page of the bookings matching `query`.
+    /// The query uses an aggregation pipeline to:
+    /// 1. $match: filter on status, the start_time range, the caller's filter
+    ///    and, with `after`, the position of the cursor
+    /// 2. $sort: order by start_time then _id
+    /// 3. $skip / $limit: cut the page, never more than `max_results` bookings
+    /// 4. $lookup / $unwind / $lookup: join the owner and its dogs, on the page only
+    ///
+    /// Offset pages run steps 3 and 4 in a $facet next to a $count of every match.
+    /// One booking more than the page is fetched to know whether a next page exists.
+    ///
+    /// Documents that fail to deserialize (e.g. legacy records missing a field)
+    /// are skipped with a warning and counted in `skipped` instead of failing the listing.
+    #[instrument(level = "debug", skip_all)]
+    async fn get_bookings(
+        &self,
+        query: &BookingQuery,
+        include_deleted: bool,
+    ) -> Result<BookingList, AppError> {
+        self.read(|| async move {
+            // Step 1: Filter the bookings with the asked statuses starting
+            // in [from, to), narrowed by the caller's filter (e.g. one owner).
+            let mut start_time = doc! {"$gte": query.from};
+            if let Some(to) = query.to {
+                start_time.insert("$lt", to);
+            }
+            let mut matched = doc! {
+                "status": status_in(&query.statuses),
+                "start_time": start_time,
+            };
+            matched.extend(visible(include_deleted));
+            matched.extend(query.filter.clone());
+
+            // Step 2: Sort, `_id` keeps the order of equal start times stable across pages.
+            let (direction, past) = match query.sort {
+                BookingSort::StartTimeAsc => (1, "$gt"),
+                BookingSort::StartTimeDesc => (-1, "$lt"),
+            };
+            if let Some(after) = query.after {
+                matched.insert(
+                    "$or",
+                    vec![
+                        doc! {"start_time": {past: after.start_time}},
+                        doc! {"start_time": after.start_time, "_id": {past: after.id}},
+                    ],
+                );
+            }
+
+            // Step 3: Cut the page, capped to guard against runaway queries,
+            // then join the owner and its dogs of the page only.
+            let limit = query.limit.min(self.max_results);
+            let mut page_stages = Vec::new();
+            if query.after.is_none() {
+                page_stages.push(doc! {"$skip": ((query.page - 1).saturating_mul(limit)) as i64});
+            }
+            page_stages.push(doc! {"$limit": limit.saturating_add(1) as i64});
+            page_stages.extend(full_booking_stages());
+            if let Some(fields) = &query.fields {
+                page_stages.push(doc! {"$project": projection(fields)});
+            }
+
+            let mut pipeline = vec![
+                doc! {"$match": matched},
+                doc! {"$sort": {"start_time": direction, "_id": direction}},
+            ];
+            let (mut items, total) = if query.after.is_some() {
+                pipeline.extend(page_stages);
+                let mut results = self
+                    .booking_listing
+                    .aggregate(pipeline)
+                    .time_limit(self.max_time)
+                    .await?;
+                let mut items = Vec::new();
+                while let Some(doc) = results.next().await {
+                    items.push(doc?);
+                }
+                (items, None)
+            } else {
+                // Step 4: Count every match in the same round trip.
+                pipeline.push(doc! {"$facet": {
+                    "items": page_stages,
+                    "total": [{"$count": "count"}],
+                }});
+                let facet = self
+                    .booking_listing
+                    .aggregate(pipeline)
+                    .time_limit(self.max_time)
+                    .await?
+                    .next()
+                    .await
+                    .transpose()?
+                    .unwrap_or_default();
+                let total = facet
+                    .get_array("total")
+                    .ok()
+                    .and_then(|total| total.first())
+                    .and_then(Bson::as_document)
+                    .and_then(|count| match count.get("count") {
+                        Some(Bson::Int32(count)) => Some(*count as u64),
+                        Some(Bson::Int64(count)) => Some(*count as u64),
+                        _ => None,
+                    })
+                    .unwrap_or(0);
+                let items = facet
+                    .get_array("items")
+                    .cloned()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Bson::Document(doc) => Some(doc),
+                        _ => None,
+                    })
+                    .collect();
+                (items, Some(total))
+            };
+
+            let has_next = items.len() as u64 > limit;
+            items.truncate(limit as usize);
+            let next_cursor = items
+                .last()
+                .filter(|_| has_next)
+                .and_then(|last| {
+                    Some(BookingCursor {
+                        start_time: last.get_datetime("start_time").ok().copied()?,
+                        id: last.get_object_id("_id").ok()?,
+                    })
+                })
+                .map(|cursor| cursor.encode());
+
+            let mut bookings = Vec::new();
+            let mut skipped = 0;
+            for doc in items {
+                if let Some(fields) = &query.fields {
+                    bookings.push(ListedBooking::partial(doc, fields));
+                    continue;
+                }
+                let id = doc.get_object_id("_id").ok();
+                // Deserialize BSON document into FullBooking struct.
+                match from_document::<WithId<FullBooking>>(doc) {
+                    Ok(booking) => bookings.push(ListedBooking::Full(Box::new(booking))),
+                    Err(err) => {
+                        warn!(booking_id = ?id, error = %err, "Skipping malformed booking");
+                        skipped += 1;
+                    }
+                }
+            }
+
+            Ok(BookingList {
+                bookings,
+                skipped,
+                page: query.after.is_none().then_some(query.page),
+                limit,
+                total,
+                next_cursor,
+            })
+        })
+        .await
+    }
+
+    /// Reschedule a booking: new start_time and/or duration.
+    /// The new slot must be in the future, cancelled or completed bookings can't move,
+    /// and the concurrent bookings limit applies to the new slot as for a new booking.
+    /// The booking must still be at `expected_version`, else the update is a 412.
+    #[instrument(level = "debug", skip_all)]
+    async fn update_booking(
+        &self,
+        booking_id: &ObjectId,
+        update: &BookingUpdateRequest,
+        expected_version: i64,
+    ) -> Result<Booking, AppError> {
+        self.write(async move {
+            if update.start_time.is_none() && update.duration_in_minutes.is_none() {
+                return Err(AppError::Validation(
+                    "At least one of start_time or duration_in_minutes must be provided"
+                        .to_string(),
+                ));
+            }
+
+            let current = self.get_booking(booking_id).await?;
+            if current.version != expected_version {
+                return Err(version_mismatch(
+                    "booking",
+                    expected_version,
+                    current.version,
+                ));
+            }
+            if !RESCHEDULABLE.contains(&current.status) {
+                return Err(AppError::conflict(format!(
+                    "A {} booking can't be rescheduled",
+                    current.status.as_str()
+                )));
+            }
+
+            let start_time = match &update.start_time {
+                Some(start_time) => parse_rfc3339(start_time).map_err(AppError::Validation)?,
+                None => current.start_time,
+            };
+            let duration_in_minutes = update
+                .duration_in_minutes
+                .unwrap_or(current.duration_in_minutes);
+            if start_time <= DateTime::now() {
+                return Err(AppError::Validation(
+                    "The new slot must be in the future".to_string(),
+                ));
+            }
+
+            if let Some(max) = self.max_concurrent_bookings {
+                let end = DateTime::from_millis(
+                    start_time.timestamp_millis() + duration_in_minutes as i64 * 60_000,
+                );
+                let overlapping = self
+                    .find_overlapping_bookings(start_time, end, doc! {"_id": { "$ne": booking_id }})
+                    .await?;
+                if overlapping.len() >= max {
+                    return Err(capacity_reached(&overlapping));
+                }
+            }
+
+            let quote = self
+                .quote_booking(&Booking {
+                    start_time,
+                    duration_in_minutes,
+                    ..current
+                })
+                .await?;
+
+            self.booking
+                .find_one_and_update(
+                    doc! {
+                        "_id": booking_id,
+                        "status": status_in(RESCHEDULABLE),
+                        "deleted_at": null,
+                        "version": expected_version
+                    },
+                    doc! {
+                        "$set": {
+                            "start_time": start_time,
+                            "duration_in_minutes": duration_in_minutes as i32,
+                            "price_cents": quote.price_cents,
+                            "currency": quote.currency
+                        },
+                        "$inc": {"version": 1}
+                    },
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| {
+                    AppError::PreconditionFailed(
+                        "The booking changed while rescheduling: reload it and retry".to_string(),
+                    )
+                })
+        })
+        .await
+    }
+
+    /// Soft delete a booking, unlike cancelling it hides the booking from every listing.
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_booking(&self, booking_id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            let result = self
+                .booking
+                .update_one(
+                    doc! {"_id": booking_id, "deleted_at": null},
+                    doc! {"$set": {"deleted_at": DateTime::now()}, "$inc": {"version": 1}},
+                )
+                .await?;
+            if result.matched_count == 0 {
+                return Err(AppError::NotFound("Booking not found".to_string()));
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Clear `deleted_at` on a booking. An active booking takes its slot back,
+    /// so the overlap and capacity checks of `create_booking` run again.
+    #[instrument(level = "debug", skip_all)]
+    async fn restore_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError> {
+        self.write(async move {
+            let deleted = self
+                .booking
+                .find_one(doc! {"_id": booking_id, "deleted_at": {"$ne": null}})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("No deleted booking with this id".to_string()))?;
+            if !self.owner_exists(&deleted.owner, false).await? {
+                return Err(owner_deleted());
+            }
+
+            if BookingStatus::active().contains(&deleted.status) {
+                let start = deleted.start_time;
+                let end = booking_end(&deleted);
+                let clashing = self
+                    .find_overlapping_bookings(start, end, doc! {"owner": deleted.owner})
+                    .await?;
+                if let Some(clashing) = clashing.first() {
+                    return Err(overlap_conflict(
+                        "booking_conflict",
+                        "The owner already has a booking overlapping this time slot",
+                        clashing,
+                    ));
+                }
+                if let Some(max) = self.max_concurrent_bookings {
+                    let overlapping = self.find_overlapping_bookings(start, end, doc! {}).await?;
+                    if overlapping.len() >= max {
+                        return Err(capacity_reached(&overlapping));
+                    }
+                }
+            }
+
+            self.booking
+                .find_one_and_update(
+                    doc! {"_id": booking_id, "deleted_at": {"$ne": null}},
+                    doc! {"$unset": {"deleted_at": ""}, "$inc": {"version": 1}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::conflict("Booking changed while restoring"))
+        })
+        .await
+    }
+
+    /// Assign a walker to a pending or confirmed booking.
+    /// The walker must exist, be available for the walk when it set an availability,
+    /// must take the owner's dogs (`max_dogs`, `accepted_sizes`), and must not
+    /// already walk another active booking overlapping this one.
+    #[instrument(level = "debug", skip_all)]
+    async fn assign_walker(
+        &self,
+        booking_id: &ObjectId,
+        walker_id: &ObjectId,
+    ) -> Result<Booking, AppError> {
+        self.write(async move {
+            let walker = self.get_walker(walker_id).await?;
+
+            let booking = self.get_booking(booking_id).await?;
+            if !RESCHEDULABLE.contains(&booking.status) {
+                return Err(AppError::conflict(format!(
+                    "A walker can't be assigned to a {} booking",
+                    booking.status.as_str()
+                )));
+            }
+            let dogs = self
+                .get_dogs_by_owner(&booking.owner, false)
+                .await?
+                .into_iter()
+                .map(|dog| dog.0)
+                .collect::<Vec<_>>();
+            if let Some(refusal) = walker.refusal(&dogs) {
+                return Err(walker_refuses(refusal));
+            }
+            if let Some(availability) = self.get_walker_availability(walker_id).await?
+                && !availability.allows(booking.start_time, booking_end(&booking))
+            {
+                return Err(outside_availability());
+            }
+
+            let clashing = self
+                .find_overlapping_bookings(
+                    booking.start_time,
+                    booking_end(&booking),
+                    doc! {"walker": walker_id, "_id": { "$ne": booking_id }},
+                )
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(overlap_conflict(
+                    "walker_unavailable",
+                    "The walker already has a booking overlapping this time slot",
+                    clashing,
+                ));
+            }
+
+            let clashing = self
+                .find_overlapping_group_walks(
+                    booking.start_time,
+                    booking_end(&booking),
+                    doc! {"walker": walker_id},
+                )
+                .await?;
+            if let Some(clashing) = clashing.first() {
+                return Err(group_walk_conflict(
+                    "walker_unavailable",
+                    "The walker already has a group walk overlapping this time slot",
+                    clashing,
+                ));
+            }
+
+            let quote = self
+                .quote_booking(&Booking {
+                    walker: Some(*walker_id),
+                    ..booking
+                })
+                .await?;
+
+            self.booking
+                .find_one_and_update(
+                    doc! {"_id": booking_id, "status": status_in(RESCHEDULABLE), "deleted_at": null},
+                    doc! {
+                        "$set": {
+                            "walker": walker_id,
+                            "match_score": null,
+                            "price_cents": quote.price_cents,
+                            "currency": quote.currency
+                        },
+                        "$inc": {"version": 1}
+                    },
+                ).time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::conflict("Booking changed while assigning the walker"))
+        })
+        .await
+    }
+
+    /// Score the walkers within `MAX_DISTANCE_KM` of the owner who take its dogs,
+    /// then try them best first: `assign_walker` refuses the ones busy or off then.
+    #[instrument(level = "debug", skip_all)]
+    async fn match_walker(&self, booking_id: &ObjectId) -> Result<Option<Booking>, AppError> {
+        self.write(async move {
+            let booking = self.get_booking(booking_id).await?;
+            if booking.walker.is_some() || !RESCHEDULABLE.contains(&booking.status) {
+                return Ok(None);
+            }
+            let Some(location) = self.get_owner_by_id(&booking.owner).await?.location else {
+                return Ok(None);
+            };
+            let dogs = self
+                .get_dogs_by_owner(&booking.owner, false)
+                .await?
+                .into_iter()
+                .map(|dog| dog.0)
+                .collect::<Vec<_>>();
+
+            let day_start = booking.start_time.timestamp_millis()
+                - booking.start_time.timestamp_millis().rem_euclid(DAY_MILLIS);
+            let (day_start, day_end) = (
+                DateTime::from_millis(day_start),
+                DateTime::from_millis(day_start + DAY_MILLIS),
+            );
+            let mut candidates = Vec::new();
+            for nearby in self
+                .get_walkers_near(location, MAX_DISTANCE_KM * 1000.0, CANDIDATES)
+                .await?
+            {
+                let walker = nearby.walker.0;
+                if walker.refusal(&dogs).is_some() {
+                    continue;
+                }
+                let walks_with_owner = self
+                    .booking
+                    .count_documents(doc! {
+                        "owner": booking.owner,
+                        "walker": walker._id,
+                        "status": BookingStatus::Completed.as_str(),
+                        "deleted_at": null,
+                    })
+                    .time_limit(self.max_time)
+                    .await?;
+                candidates.push(Candidate {
+                    walker: walker._id,
+                    distance_km: nearby.distance_km,
+                    rating: self.get_walker_rating(&walker._id).await?,
+                    walks_that_day: self
+                        .get_walker_bookings(&walker._id, day_start, day_end)
+                        .await?
+                        .len() as u64,
+                    walks_with_owner,
+                });
+            }
+            let mut candidates = candidates
+                .into_iter()
+                .map(|candidate| (candidate.score(), candidate.walker))
+                .collect::<Vec<_>>();
+            candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+            for (score, walker_id) in candidates {
+                match self.assign_walker(booking_id, &walker_id).await {
+                    Ok(_) => {}
+                    Err(AppError::Conflict { .. } | AppError::InvalidFields(_)) => continue,
+                    Err(err) => return Err(err),
+                }
+                let matched = self
+                    .booking
+                    .find_one_and_update(
+                        doc! {"_id": booking_id, "walker": walker_id},
+                        doc! {"$set": {"match_score": score}},
+                    )
+                    .time_limit(self.max_time)
+                    .return_document(ReturnDocument::After)
+                    .await?;
+                return Ok(matched);
+            }
+            Ok(None)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_unassigned_bookings(
+        &self,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Booking>>, AppError> {
+        self.read(|| async move {
+            let filter = doc! {
+                "status": status_in(RESCHEDULABLE),
+                "deleted_at": null,
+                "walker": null,
+                "start_time": {"$gt": DateTime::now()},
+            };
+            let total = self
+                .booking
+                .count_documents(filter.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let items = self
+                .booking
+                .find(filter)
+                .time_limit(self.max_time)
+                .sort(doc! {"start_time": 1, "_id": 1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?
+                .map_ok(WithId)
+                .try_collect()
+                .await?;
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_booking_payment(
+        &self,
+        booking_id: &ObjectId,
+        payment: &BookingPayment,
+    ) -> Result<Booking, AppError> {
+        self.write(async move {
+            self.booking
+                .find_one_and_update(
+                    doc! {"_id": booking_id, "deleted_at": null},
+                    doc! {"$set": {"payment": to_bson(payment)?}, "$inc": {"version": 1}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Booking not found".to_string()))
+        })
+        .await
+    }
+
+    /// Store the walk report on a booking and mark it completed.
+    /// The filter re-checks that the walk is in progress (or already completed) and started, and unless
+    /// `overwrite` is set, that no report exists yet, so a concurrent submission
+    /// ends up with `matched_count == 0` instead of silently replacing the first one.
+    #[instrument(level = "debug", skip_all)]
+    async fn save_walk_report(
+        &self,
+        booking_id: &ObjectId,
+        report: WalkReport,
+        overwrite: bool,
+    ) -> Result<UpdatedCount, AppError> {
+        self.write(async move {
+            let mut filter = doc! {
+                "_id": booking_id,
+                "status": status_in(REPORTABLE),
+                "deleted_at": null,
+                "start_time": { "$lte": DateTime::now() }
+            };
+            if !overwrite {
+                filter.insert("report", doc! { "$eq": null });
+            }
+
+            let report = mongodb::bson::to_bson(&report)?;
+            let result = self
+                .booking
+                .update_one(
+                    filter,
+                    doc! {
+                        "$set": {
+                            "report": report,
+                            "status": BookingStatus::Completed
+                        },
+                        "$inc": {"version": 1}
+                    },
+                )
+                .await?;
+
+            if result.matched_count == 0 {
+                return Err(AppError::conflict(
+                    "Booking changed while saving the report",
+                ));
+            }
+
+            Ok(result.into())
+        })
+        .await
+    }
+
+    /// Move a booking to `next` if the transition is legal from its current status.
+    /// The allowed current statuses are part of the update filter, so two concurrent
+    /// transitions can't both win. `extra_filter` and `extra_set` are merged into
+    /// the filter and the `$set` document.
+    #[instrument(level = "debug", skip_all)]
+    async fn transition_booking(
+        &self,
+        booking_id: &ObjectId,
+        next: BookingStatus,
+        extra_filter: Document,
+        extra_set: Document,
+    ) -> Result<Booking, AppError> {
+        self.write(async move {
+            let mut filter = doc! {
+                "_id": booking_id,
+                "status": status_in(next.allowed_from()),
+                "deleted_at": null
+            };
+            filter.extend(extra_filter);
+            let mut set = doc! {"status": next};
+            if next == BookingStatus::Confirmed {
+                set.insert("expires_at", Bson::Null);
+            }
+            set.extend(extra_set);
+
+            let updated = self
+                .booking
+                .find_one_and_update(filter.clone(), doc! {"$set": set, "$inc": {"version": 1}})
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            if let Some(booking) = updated {
+                return Ok(booking);
+            }
+
+            // Nothing matched: either the booking doesn't exist or the transition is illegal.
+            filter.remove("status");
+            let current = self
+                .booking
+                .find_one(filter)
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Booking not found".to_string()))?;
+
+            Err(AppError::Conflict {
+                code: "illegal_transition",
+                message: format!(
+                    "A {} booking can't become {}",
+                    current.status.as_str(),
+                    next.as_str()
+                ),
+                details: None,
+            })
+        })
+        .await
+    }
+
+    /// Cancel a booking by setting its status to "cancelled", under the cancellation policy.
+    /// Only pending and confirmed bookings can be cancelled.
+    #[instrument(level = "debug", skip_all)]
+    async fn cancel_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError> {
+        self.write(async move { self.cancel_under_policy(booking_id, doc! {}).await })
+            .await
+    }
+
+    /// Cancel a booking on behalf of its owner (signed cancel link).
+    /// The owner is part of the filter so a token can only cancel the booking it was issued for.
+    #[instrument(level = "debug", skip_all)]
+    async fn cancel_booking_for_owner(
+        &self,
+        booking_id: &ObjectId,
+        owner_id: &ObjectId,
+    ) -> Result<Booking, AppError> {
+        self.write(async move {
+            self.cancel_under_policy(booking_id, doc! {"owner": owner_id})
+                .await
+        })
+        .await
+    }
+
+    /// Cancel every cancellable (pending or confirmed) booking whose start_time is in `[from, to)`.
+    /// The ids are fetched first (projection on `_id` only) so the caller can notify
+    /// the owners, then a single `update_many` restricted to those ids and still
+    /// cancellable flips them, so already-cancelled bookings are never counted.
+    #[instrument(level = "debug", skip_all)]
+    async fn cancel_bookings_in_range(
+        &self,
+        from: DateTime,
+        to: DateTime,
+        reason: &str,
+    ) -> Result<BulkCancelResult, AppError> {
+        self.write(async move {
+            let filter = doc! {
+                "status": status_in(BookingStatus::Cancelled.allowed_from()),
+                "deleted_at": null,
+                "start_time": { "$gte": from, "$lt": to }
+            };
+            let cancelled_at = DateTime::now();
+            let result = self
+                .booking
+                .update_many(
+                    filter,
+                    doc! {
+                        "$set": {
+                            "status": BookingStatus::Cancelled,
+                            "cancelled_at": cancelled_at,
+                            "cancellation_reason": reason
+                        },
+                        "$inc": { "version": 1 }
+                    },
+                )
+                .await?;
+            if result.modified_count == 0 {
+                return Ok(BulkCancelResult {
+                    modified_count: 0,
+                    booking_ids: Vec::new(),
+                });
+            }
+
+            // Read back the bookings this update cancelled rather than listing them
+            // beforehand: one cancelled or moved in between would be reported (and
+            // its owner notified) without having been touched here.
+            let mut cursor = self
+                .booking
+                .clone_with_type::<Document>()
+                .find(doc! {
+                    "status": BookingStatus::Cancelled,
+                    "cancelled_at": cancelled_at,
+                    "cancellation_reason": reason,
+                    "start_time": { "$gte": from, "$lt": to }
+                })
+                .time_limit(self.max_time)
+                .projection(doc! {"_id": 1})
+                .await?;
+            let mut booking_ids: Vec<ObjectId> = Vec::new();
+            while let Some(doc) = cursor.next().await {
+                if let Ok(id) = doc?.get_object_id("_id") {
+                    booking_ids.push(id);
+                }
+            }
+
+            Ok(BulkCancelResult {
+                modified_count: result.modified_count,
+                booking_ids,
+            })
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn expire_pending_bookings(&self, now: DateTime) -> Result<BulkCancelResult, AppError> {
+        self.write(async move {
+            // Bookings made before `expires_at` existed expire when their walk starts.
+            let filter = doc! {
+                "status": BookingStatus::Pending,
+                "deleted_at": null,
+                "$or": [
+                    { "expires_at": { "$lte": now } },
+                    { "start_time": { "$lte": now } }
+                ]
+            };
+            let mut cursor = self
+                .booking
+                .clone_with_type::<Document>()
+                .find(filter.clone())
+                .time_limit(self.max_time)
+                .projection(doc! {"_id": 1})
+                .await?;
+            let mut booking_ids: Vec<ObjectId> = Vec::new();
+            while let Some(doc) = cursor.next().await {
+                if let Ok(id) = doc?.get_object_id("_id") {
+                    booking_ids.push(id);
+                }
+            }
+            if booking_ids.is_empty() {
+                return Ok(BulkCancelResult {
+                    modified_count: 0,
+                    booking_ids,
+                });
+            }
+
+            let mut filter = filter;
+            filter.insert("_id", doc! {"$in": &booking_ids});
+            let result = self
+                .booking
+                .update_many(
+                    filter,
+                    doc! {
+                        "$set": { "status": BookingStatus::Expired },
+                        "$inc": { "version": 1 }
+                    },
+                )
+                .await?;
+
+            Ok(BulkCancelResult {
+                modified_count: result.modified_count,
+                booking_ids,
+            })
+        })
+        .await
+    }
+
+    /// By batches: each is copied to "booking_archive" before it is deleted, so a
+    /// batch interrupted in between is copied again, the copies already made being
+    /// skipped, at the next run.
+    #[instrument(level = "debug", skip_all)]
+    async fn archive_bookings(&self, before: DateTime) -> Result<u64, AppError> {
+        self.write(async move {
+            const BATCH: i64 = 500;
+            let finished = doc! {
+                "status": status_in(&[
+                    BookingStatus::Completed,
+                    BookingStatus::Cancelled,
+                    BookingStatus::NoShow,
+                    BookingStatus::Expired,
+                ]),
+                "start_time": { "$lt": before }
+            };
+            let bookings = self.booking.clone_with_type::<Document>();
+            let mut archived = 0;
+            loop {
+                let batch: Vec<Document> = bookings
+                    .find(finished.clone())
+                    .time_limit(self.max_time)
+                    .limit(BATCH)
+                    .await?
+                    .try_collect()
+                    .await?;
+                if batch.is_empty() {
+                    return Ok(archived);
+                }
+                // Whatever their type, so no document is left behind to be found again.
+                let booking_ids: Vec<Bson> = batch
+                    .iter()
+                    .filter_map(|booking| booking.get("_id").cloned())
+                    .collect();
+                match self
+                    .booking_archive
+                    .insert_many(&batch)
+                    .ordered(false)
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(err) if is_duplicate_key(&err) => {}
+                    Err(err) => return Err(err.into()),
+                }
+                archived += bookings
+                    .delete_many(doc! {"_id": {"$in": &booking_ids}})
+                    .await?
+                    .deleted_count;
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_archived_bookings(
+        &self,
+        filter: &ArchiveFilter,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Booking>>, AppError> {
+        self.read(|| async move {
+            let mut query = doc! {};
+            if let Some(owner) = filter.owner {
+                query.insert("owner", owner);
+            }
+            let mut start_time = doc! {};
+            if let Some(from) = filter.from {
+                start_time.insert("$gte", from);
+            }
+            if let Some(to) = filter.to {
+                start_time.insert("$lt", to);
+            }
+            if !start_time.is_empty() {
+                query.insert("start_time", start_time);
+            }
+
+            let archive = self.booking_archive.clone_with_type::<Booking>();
+            let total = archive
+                .count_documents(query.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let items = archive
+                .find(query)
+                .time_limit(self.max_time)
+                .sort(doc! {"start_time": -1, "_id": -1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?
+                .map_ok(WithId)
+                .try_collect()
+                .await?;
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    fn booking_updates(&self) -> &BookingUpdates {
+        &self.booking_updates
+    }
+
+    fn owner_events(&self) -> &OwnerEvents {
+        &self.owner_events
+    }
+
+    /// One document per ping in the "walk_track" collection, a long walk
+    /// would otherwise grow a single document without bound.
+    #[instrument(level = "debug", skip_all)]
+    async fn add_track_pings(&self, pings: Vec<TrackPing>) -> Result<(), AppError> {
+        self.write(async move {
+            self.walk_track.insert_many(pings).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_track(&self, booking_id: &ObjectId) -> Result<Vec<TrackPing>, AppError> {
+        self.read(|| async move {
+            let mut cursor = self
+                .walk_track
+                .find(doc! {"booking": booking_id})
+                .time_limit(self.max_time)
+                .sort(doc! {"at": 1, "_id": 1})
+                .await?;
+
+            let mut pings = Vec::new();
+            while let Some(ping) = cursor.next().await {
+                pings.push(ping?);
+            }
+            Ok(pings)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_review(&self, review: &Review) -> Result<(), AppError> {
+        self.write(async move {
+            match self.review.insert_one(review).await {
+                Ok(_) => Ok(()),
+                Err(err) if is_duplicate_key(&err) => Err(review_exists()),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_incident(&self, incident: &Incident) -> Result<(), AppError> {
+        self.write(async move {
+            self.incident.insert_one(incident).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_incident(&self, incident_id: &ObjectId) -> Result<Incident, AppError> {
+        self.read(|| async move {
+            self.incident
+                .find_one(doc! {"_id": incident_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Incident not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_incidents(
+        &self,
+        statuses: &[IncidentStatus],
+        severity: Option<IncidentSeverity>,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Incident>>, AppError> {
+        self.read(|| async move {
+            let statuses: Vec<Bson> = statuses.iter().map(|status| Bson::from(*status)).collect();
+            let mut query = doc! {"status": {"$in": statuses}};
+            if let Some(severity) = severity {
+                query.insert("severity", to_bson(&severity)?);
+            }
+
+            let total = self
+                .incident
+                .count_documents(query.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let mut cursor = self
+                .incident
+                .find(query)
+                .time_limit(self.max_time)
+                .sort(doc! {"reported_at": -1, "_id": -1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?;
+
+            let mut items = Vec::new();
+            while let Some(incident) = cursor.next().await {
+                items.push(WithId(incident?));
+            }
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn triage_incident(
+        &self,
+        incident_id: &ObjectId,
+        next: IncidentStatus,
+        resolution: Option<String>,
+    ) -> Result<Incident, AppError> {
+        self.write(async move {
+            let allowed: Vec<Bson> = next
+                .allowed_from()
+                .iter()
+                .map(|status| Bson::from(*status))
+                .collect();
+            let mut set = doc! {"status": next, "updated_at": DateTime::now()};
+            if let Some(resolution) = resolution {
+                set.insert("resolution", resolution);
+            }
+
+            let updated = self
+                .incident
+                .find_one_and_update(
+                    doc! {"_id": incident_id, "status": {"$in": allowed}},
+                    doc! {"$set": set},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            if let Some(incident) = updated {
+                return Ok(incident);
+            }
+
+            // Nothing matched: either the incident doesn't exist or the transition is illegal.
+            let current = self.get_incident(incident_id).await?;
+            Err(illegal_incident_transition(current.status, next))
+        })
+        .await
+    }
+
+    /// Upserted on `booking`, so completing a booking twice keeps its first invoice.
+    #[instrument(level = "debug", skip_all)]
+    async fn issue_invoice(&self, invoice: &Invoice) -> Result<bool, AppError> {
+        self.write(async move {
+            let mut fields = to_document(invoice)?;
+            fields.remove("booking");
+            let result = self
+                .invoice
+                .update_one(
+                    doc! {"booking": invoice.booking},
+                    doc! {"$setOnInsert": fields},
+                )
+                .upsert(true)
+                .await;
+            match result {
+                Ok(result) => Ok(result.upserted_id.is_some()),
+                Err(err) if is_duplicate_key(&err) => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    /// Upserted on the booking like the invoice, a second call is a no-op.
+    #[instrument(level = "debug", skip_all)]
+    async fn record_payout(&self, invoice: &Invoice) -> Result<bool, AppError> {
+        self.write(async move {
+            let amount_cents = pricing::walker_earnings(&self.pricing_rules().await?, invoice);
+            let Some(payout) = Payout::new(invoice, amount_cents) else {
+                return Ok(false);
+            };
+            let mut fields = to_document(&payout)?;
+            fields.remove("booking");
+            fields.remove("kind");
+            let result = self
+                .payout
+                .update_one(
+                    doc! {"booking": payout.booking, "kind": payout.kind},
+                    doc! {"$setOnInsert": fields},
+                )
+                .upsert(true)
+                .await;
+            match result {
+                Ok(result) => Ok(result.upserted_id.is_some()),
+                Err(err) if is_duplicate_key(&err) => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_tip(&self, tip: &Payout) -> Result<(), AppError> {
+        self.write(async move {
+            match self.payout.insert_one(tip).await {
+                Ok(_) => Ok(()),
+                Err(err) if is_duplicate_key(&err) => Err(tip_exists()),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_invoice(&self, invoice_id: &ObjectId) -> Result<Invoice, AppError> {
+        self.read(|| async move {
+            self.invoice
+                .find_one(doc! {"_id": invoice_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_booking_invoice(&self, booking_id: &ObjectId) -> Result<Invoice, AppError> {
+        self.read(|| async move {
+            self.invoice
+                .find_one(doc! {"booking": booking_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_invoice_by_payment_reference(&self, reference: &str) -> Result<Invoice, AppError> {
+        self.read(|| async move {
+            self.invoice
+                .find_one(doc! {"payment_reference": reference})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owner_invoices(
+        &self,
+        owner_id: &ObjectId,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Invoice>>, AppError> {
+        self.read(|| async move {
+            let query = doc! {"owner": owner_id};
+            let total = self
+                .invoice
+                .count_documents(query.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let mut cursor = self
+                .invoice
+                .find(query)
+                .time_limit(self.max_time)
+                .sort(doc! {"issued_at": -1, "_id": -1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?;
+
+            let mut items = Vec::new();
+            while let Some(invoice) = cursor.next().await {
+                items.push(WithId(invoice?));
+            }
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn update_payment(
+        &self,
+        invoice_id: &ObjectId,
+        next: PaymentStatus,
+        reference: Option<String>,
+    ) -> Result<Invoice, AppError> {
+        self.write(async move {
+            let allowed: Vec<Bson> = next
+                .allowed_from()
+                .iter()
+                .map(|status| Bson::from(*status))
+                .collect();
+            let now = DateTime::now();
+            let mut set = doc! {"payment_status": next, "updated_at": now};
+            if let Some(reference) = reference {
+                set.insert("payment_reference", reference);
+            }
+            if next == PaymentStatus::Paid {
+                set.insert("paid_at", now);
+            }
+
+            let updated = self
+                .invoice
+                .find_one_and_update(
+                    doc! {"_id": invoice_id, "payment_status": {"$in": allowed}},
+                    doc! {"$set": set},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            if let Some(invoice) = updated {
+                return Ok(invoice);
+            }
+
+            // Nothing matched: either the invoice doesn't exist or the transition is illegal.
+            let current = self.get_invoice(invoice_id).await?;
+            Err(illegal_payment_transition(current.payment_status, next))
+        })
+        .await
+    }
+
+    /// Filtered on a paid invoice without a refund, so a refund is only recorded once.
+    #[instrument(level = "debug", skip_all)]
+    async fn record_refund(
+        &self,
+        invoice_id: &ObjectId,
+        refund: &InvoiceRefund,
+    ) -> Result<Invoice, AppError> {
+        self.write(async move {
+            let invoice = self.get_invoice(invoice_id).await?;
+            let mut set = doc! {"refund": to_bson(refund)?, "updated_at": DateTime::now()};
+            if refund.amount_cents >= invoice.total_cents {
+                set.insert("payment_status", PaymentStatus::Refunded);
+            }
+
+            self.invoice
+                .find_one_and_update(
+                    doc! {"_id": invoice_id, "payment_status": PaymentStatus::Paid, "refund": null},
+                    doc! {"$set": set},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?
+                .ok_or_else(|| refund_refused(&invoice))
+        })
+        .await
+    }
+
+    /// The booking and the balance are written in one transaction (MongoDB must
+    /// run as a replica set). `points_earned` is only set while missing, so
+    /// completing a booking through two routes credits it once.
+    #[instrument(level = "debug", skip_all)]
+    async fn award_points(&self, booking_id: &ObjectId) -> Result<i64, AppError> {
+        self.write(async move {
+            let points = self.pricing_rules().await?.points_per_booking;
+            if points <= 0 {
+                return Ok(0);
+            }
+
+            let mut session = self.client.start_session().await?;
+            session.start_transaction().await?;
+            match self
+                .award_points_in_session(&mut session, booking_id, points)
+                .await
+            {
+                Ok(Some(owner_id)) => {
+                    session.commit_transaction().await?;
+                    self.owner_cache.invalidate(&owner_id);
+                    Ok(points)
+                }
+                Ok(None) => {
+                    session.commit_transaction().await?;
+                    Ok(0)
+                }
+                Err(err) => {
+                    // The original error is more useful than a failed abort.
+                    let _ = session.abort_transaction().await;
+                    Err(err)
+                }
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_coupon(&self, coupon: &Coupon) -> Result<(), AppError> {
+        self.write(async move {
+            match self.coupon.insert_one(coupon).await {
+                Ok(_) => Ok(()),
+                Err(err) if is_duplicate_key(&err) => Err(coupon_exists(&coupon.code)),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_usable_coupon(&self, code: &str) -> Result<Coupon, AppError> {
+        self.read(|| async move {
+            let code = coupon_key(code);
+            let coupon = self
+                .coupon
+                .find_one(doc! {"code": &code})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| coupon_unavailable(&code, "doesn't exist"))?;
+            match coupon.unavailable_reason(DateTime::now()) {
+                Some(reason) => Err(coupon_unavailable(&code, reason)),
+                None => Ok(coupon),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_series(&self, series: &BookingSeries) -> Result<(), AppError> {
+        self.write(async move {
+            self.series.insert_one(series).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_series(&self, series_id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            self.series.delete_one(doc! {"_id": series_id}).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_series(&self, series_id: &ObjectId) -> Result<BookingSeries, AppError> {
+        self.read(|| async move {
+            self.series
+                .find_one(doc! {"_id": series_id})
+                .time_limit(self.max_time)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Series not found".to_string()))
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_series_due(&self, horizon: DateTime) -> Result<Vec<BookingSeries>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .series
+                .find(doc! {
+                    "cancelled_at": null,
+                    "materialized_until": {"$lt": horizon},
+                    "$expr": {"$lt": ["$materialized_until", "$until"]},
+                })
+                .time_limit(self.max_time)
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    /// The filter on the current `materialized_until` makes concurrent runs, on
+    /// other replicas, book each walk once.
+    #[instrument(level = "debug", skip_all)]
+    async fn claim_series_window(
+        &self,
+        series_id: &ObjectId,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<bool, AppError> {
+        self.write(async move {
+            let claimed = self
+                .series
+                .update_one(
+                    doc! {"_id": series_id, "cancelled_at": null, "materialized_until": from},
+                    doc! {"$set": {"materialized_until": to}},
+                )
+                .await?;
+            Ok(claimed.modified_count == 1)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn skip_occurrence(
+        &self,
+        series_id: &ObjectId,
+        start: DateTime,
+    ) -> Result<Option<BookingSeries>, AppError> {
+        self.write(async move {
+            let skipped = self
+                .series
+                .find_one_and_update(
+                    doc! {
+                        "_id": series_id,
+                        "cancelled_at": null,
+                        "materialized_until": {"$lt": start},
+                    },
+                    doc! {"$addToSet": {"skipped": start}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            match skipped {
+                Some(series) => Ok(Some(series)),
+                None => match self.get_series(series_id).await? {
+                    series if series.cancelled_at.is_some() => Err(series_cancelled()),
+                    _ => Ok(None),
+                },
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn cancel_series(&self, series_id: &ObjectId) -> Result<BookingSeries, AppError> {
+        self.write(async move {
+            let cancelled = self
+                .series
+                .find_one_and_update(
+                    doc! {"_id": series_id, "cancelled_at": null},
+                    doc! {"$set": {"cancelled_at": DateTime::now()}},
+                )
+                .time_limit(self.max_time)
+                .return_document(ReturnDocument::After)
+                .await?;
+            match cancelled {
+                Some(series) => Ok(series),
+                None => {
+                    self.get_series(series_id).await?;
+                    Err(series_cancelled())
+                }
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_series_bookings(&self, series_id: &ObjectId) -> Result<Vec<Booking>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .booking
+                .find(doc! {
+                    "series": series_id,
+                    "deleted_at": null,
+                    "status": status_in(BookingStatus::Cancelled.allowed_from()),
+                    "start_time": {"$gt": DateTime::now()},
+                })
+                .time_limit(self.max_time)
+                .sort(doc! {"start_time": 1})
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn is_slot_full(&self, start: DateTime, end: DateTime) -> Result<bool, AppError> {
+        self.read(|| async move {
+            match self.max_concurrent_bookings {
+                Some(max) => Ok(self
+                    .find_overlapping_bookings(start, end, doc! {})
+                    .await?
+                    .len()
+                    >= max),
+                None => Ok(false),
+            }
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn create_waitlist_entry(&self, entry: &WaitlistEntry) -> Result<(), AppError> {
+        self.write(async move {
+            let waiting = self
+                .waitlist
+                .find_one(waiting_overlapping(entry, doc! {"owner": entry.owner}))
+                .time_limit(self.max_time)
+                .await?;
+            if waiting.is_some() {
+                return Err(already_waitlisted());
+            }
+            self.waitlist.insert_one(entry).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_owner_waitlist(
+        &self,
+        owner_id: &ObjectId,
+    ) -> Result<Vec<WaitlistEntry>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .waitlist
+                .find(doc! {"owner": owner_id, "start_time": {"$gt": DateTime::now()}})
+                .time_limit(self.max_time)
+                .sort(doc! {"start_time": 1})
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn waitlist_position(&self, entry: &WaitlistEntry) -> Result<u64, AppError> {
+        self.read(|| async move {
+            let ahead = self
+                .waitlist
+                .count_documents(waiting_overlapping(entry, doc! {"_id": {"$lt": entry._id}}))
+                .time_limit(self.max_time)
+                .await?;
+            Ok(ahead + 1)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn get_waiting_entries(&self) -> Result<Vec<WaitlistEntry>, AppError> {
+        self.read(|| async move {
+            Ok(self
+                .waitlist
+                .find(doc! {
+                    "status": WaitlistStatus::Waiting.as_str(),
+                    "start_time": {"$gt": DateTime::now()},
+                })
+                .time_limit(self.max_time)
+                .sort(doc! {"_id": 1})
+                .await?
+                .try_collect()
+                .await?)
+        })
+        .await
+    }
+
+    /// The filter on the status makes concurrent runs, on other replicas, promote
+    /// each entry once.
+    #[instrument(level = "debug", skip_all)]
+    async fn claim_waitlist_entry(
+        &self,
+        entry_id: &ObjectId,
+        booking_id: &ObjectId,
+    ) -> Result<bool, AppError> {
+        self.write(async move {
+            let claimed = self
+                .waitlist
+                .update_one(
+                    doc! {"_id": entry_id, "status": WaitlistStatus::Waiting.as_str()},
+                    doc! {"$set": {
+                        "status": WaitlistStatus::Promoted.as_str(),
+                        "booking": booking_id,
+                        "promoted_at": DateTime::now(),
+                    }},
+                )
+                .await?;
+            Ok(claimed.modified_count == 1)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn release_waitlist_entry(&self, entry_id: &ObjectId) -> Result<(), AppError> {
+        self.write(async move {
+            self.waitlist
+                .update_one(
+                    doc! {"_id": entry_id, "status": WaitlistStatus::Promoted.as_str()},
+                    doc! {"$set": {
+                        "status": WaitlistStatus::Waiting.as_str(),
+                        "booking": null,
+                        "promoted_at": null,
+                    }},
+                )
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn expire_waitlist(&self) -> Result<u64, AppError> {
+        self.write(async move {
+            let expired = self
+                .waitlist
+                .update_many(
+                    doc! {
+                        "status": WaitlistStatus::Waiting.as_str(),
+                        "start_time": {"$lte": DateTime::now()},
+                    },
+                    doc! {"$set": {"status": WaitlistStatus::Expired.as_str()}},
+                )
+                .await?;
+            Ok(expired.modified_count)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn claim_next_reminder(
+        &self,
+        from: DateTime,
+        until: DateTime,
+    ) -> Result<Option<Booking>, AppError> {
+        self.write(async move {
+            Ok(self
+                .booking
+                .find_one_and_update(
+                    doc! {
+                        "status": status_in(RESCHEDULABLE),
+                        "deleted_at": null,
+                        "reminder_sent_at": null,
+                        "start_time": {"$gte": from, "$lte": until},
+                    },
+                    doc! {"$set": {"reminder_sent_at": DateTime::now()}},
+                )
+                .time_limit(self.max_time)
+                .sort(doc! {"start_time": 1})
+                .return_document(ReturnDocument::After)
+                .await?)
+        })
+        .await
+    }
+}
+
+/// Waiting entries whose slot overlaps the one of `entry`, `extra` merged in.
+fn waiting_overlapping(entry: &WaitlistEntry, extra: Document) -> Document {
+    let mut filter = doc! {
+        "status": WaitlistStatus::Waiting.as_str(),
+        "start_time": {"$lt": entry.end_time()},
+        "$expr": {
+            "$gt": [
+                { "$add": ["$start_time", { "$multiply": ["$duration_in_minutes", 60_000] }] },
+                entry.start_time
+            ]
+        }
+    };
+    filter.extend(extra);
+    filter
+}
+
+#[async_trait]
+impl AuditRepository for Database {
+    /// Insert one entry into the "audit_log" collection.
+    #[instrument(level = "debug", skip_all)]
+    async fn record_audit(
+        &self,
+        actor: AuditActor,
+        action: AuditAction,
+        entity: EntityRef,
+        before: Option<Document>,
+        after: Option<Document>,
+    ) -> Result<(), AppError> {
+        self.write(async move {
+            self.audit_log
+                .insert_one(AuditEntry {
+                    _id: ObjectId::new(),
+                    at: DateTime::now(),
+                    actor,
+                    action,
+                    entity,
+                    before,
+                    after,
+                })
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// One page of the audit log, newest first, with the total for the pagination metadata.
+    #[instrument(level = "debug", skip_all)]
+    async fn get_audit_log(
+        &self,
+        filter: &AuditFilter,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<AuditEntry>, AppError> {
+        self.read(|| async move {
+            let mut query = doc! {};
+            if let Some(entity_id) = filter.entity_id {
+                query.insert("entity.id", entity_id);
+            }
+            let mut at = doc! {};
+            if let Some(from) = filter.from {
+                at.insert("$gte", from);
+            }
+            if let Some(to) = filter.to {
+                at.insert("$lt", to);
+            }
+            if !at.is_empty() {
+                query.insert("at", at);
+            }
+
+            let total = self
+                .audit_log
+                .count_documents(query.clone())
+                .time_limit(self.max_time)
+                .await?;
+            let mut cursor = self
+                .audit_log
+                .find(query)
+                .time_limit(self.max_time)
+                .sort(doc! {"at": -1, "_id": -1})
+                .skip((page - 1) * limit)
+                .limit(limit as i64)
+                .await?;
+
+            let mut items = Vec::new();
+            while let Some(entry) = cursor.next().await {
+                items.push(entry?);
+            }
+
+            Ok(Page {
+                items,
+                page,
+                limit,
+                total,
+            })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl SearchRepository for Database {
+    /// `$text` query on owners then dogs, each through its text index,
+    /// the two lists are merged on the score MongoDB gave.
+    #[instrument(level = "debug", skip_all)]
+    async fn search(
+        &self,
+        text: &str,
+        limit: u64,
+        include_deleted: bool,
+    ) -> Result<Vec<SearchHit>, AppError> {
+        self.read(|| async move {
+            let owners = text_search(&self.owner, text, limit, include_deleted).await?;
+            let dogs = text_search(&self.dog, text, limit, include_deleted).await?;
+
+            let hits = owners
+                .into_iter()
+                .map(|(score, owner)| SearchHit::Owner {
+                    score,
+                    owner: WithId(owner),
+                })
+                .chain(dogs.into_iter().map(|(score, dog)| SearchHit::Dog {
+                    score,
+                    dog: WithId(dog),
+                }))
+                .collect();
+            Ok(rank(hits, limit))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for Database {
+    /// The `_id` being the key, the insert fails when it's taken, concurrent
+    /// requests included. Only one of them can then replace a stale record.
+    #[instrument(level = "debug", skip_all)]
+    async fn claim_idempotency_key(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, AppError> {
+        self.write(async move {
+            match self.idempotency.insert_one(&record).await {
+                Ok(_) => return Ok(None),
+                Err(err) if is_duplicate_key(&err) => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            // TTL removal runs about once a minute, an expired record may still be there.
+            let now = DateTime::now();
+            let stale = doc! {
+                "_id": &record._id,
+                "$or": [
+                    {"expires_at": {"$lte": now}},
+                    {"response": null, "claimed_at": {"$lte": claim_cutoff(now)}},
+                ],
+            };
+            if self
+                .idempotency
+                .find_one_and_replace(stale, &record)
+                .time_limit(self.max_time)
+                .await?
+                .is_some()
+            {
+                return Ok(None);
+            }
+
+            Ok(self
+                .idempotency
+                .find_one(doc! {"_id": &record._id})
+                .time_limit(self.max_time)
+                .await?)
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn complete_idempotency_key(
+        &self,
+        id: &str,
+        response: StoredResponse,
+    ) -> Result<(), AppError> {
+        self.write(async move {
+            self.idempotency
+                .update_one(
+                    doc! {"_id": id},
+                    doc! {"$set": {"response": {"status": response.status as i32, "body": response.body}}},
+                )
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn release_idempotency_key(&self, id: &str) -> Result<(), AppError> {
+        self.write(async move {
+            self.idempotency.delete_one(doc! {"_id": id}).await?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+async fn import_collection<T>(
+    collection: &Collection<T>,
+    documents: Vec<T>,
+    merge: bool,
+) -> Result<CollectionImport, AppError>
+where
+    T: Serialize + DeserializeOwned + HasObjectId + Send + Sync,
+{
+    let total = documents.len();
+    let documents: Vec<T> = if merge {
+        let ids: Vec<ObjectId> = documents.iter().map(HasObjectId::object_id).collect();
+        let existing: HashSet<ObjectId> = collection
+            .distinct("_id", doc! {"_id": {"$in": &ids}})
+            .await?
+            .into_iter()
+            .filter_map(|id| id.as_object_id())
+            .collect();
+
+        documents
+            .into_iter()
+            .filter(|document| !existing.contains(&document.object_id()))
+            .collect()
+    } else {
+        documents
+    };
+
+    let inserted = documents.len();
+    if inserted > 0 {
+        collection.insert_many(documents).await?;
+    }
+
+    Ok(CollectionImport {
+        inserted,
+        skipped: total - inserted,
+    })
+}
+
+/// Verification of a new owner's email, valid for `EMAIL_VERIFICATION_TTL`.
+fn email_verification(owner: ObjectId, token_hash: String) -> EmailVerification {
+    EmailVerification {
+        _id: ObjectId::new(),
+        owner,
+        token_hash,
+        expires_at: DateTime::from_millis(
+            DateTime::now().timestamp_millis() + EMAIL_VERIFICATION_TTL.as_millis() as i64,
+        ),
+    }
+}
+
+/// 412 for an update sent against `expected` while the stored document is at `current`.
+pub fn version_mismatch(entity: &str, expected: i64, current: i64) -> AppError {
+    AppError::PreconditionFailed(format!(
+        "The {entity} is at version {current}, not {expected}: reload it and retry"
+    ))
+}
+
+/// 409 when restoring a dog or booking of a deleted owner.
+pub fn owner_deleted() -> AppError {
+    AppError::Conflict {
+        code: "owner_deleted",
+        message: "The owner is deleted, restore it first".to_string(),
+        details: None,
+    }
+}
+
+pub fn email_taken() -> AppError {
+    AppError::Conflict {
+        code: "email_taken",
+        message: "An account already exists for this email".to_string(),
+        details: None,
+    }
+}
+
+pub fn review_exists() -> AppError {
+    AppError::Conflict {
+        code: "review_exists",
+        message: "This booking is already reviewed".to_string(),
+        details: None,
+    }
+}
+
+pub fn illegal_incident_transition(current: IncidentStatus, next: IncidentStatus) -> AppError {
+    AppError::Conflict {
+        code: "illegal_transition",
+        message: format!(
+            "The incident is {} and can't become {}",
+            current.as_str(),
+            next.as_str()
+        ),
+        details: None,
+    }
+}
+
+pub fn illegal_payment_transition(current: PaymentStatus, next: PaymentStatus) -> AppError {
+    AppError::Conflict {
+        code: "illegal_transition",
+        message: format!(
+            "The payment is {} and can't become {}",
+            current.as_str(),
+            next.as_str()
+        ),
+        details: None,
+    }
+}
+
+pub fn coupon_exists(code: &str) -> AppError {
+    AppError::Conflict {
+        code: "coupon_exists",
+        message: format!("A coupon with the code {} already exists", code),
+        details: None,
+    }
+}
+
+pub fn cancellation_closed() -> AppError {
+    AppError::Conflict {
+        code: "cancellation_closed",
+        message: "The walk has started and can no longer be cancelled".to_string(),
+        details: None,
+    }
+}
+
+pub fn not_confirmed(status: BookingStatus) -> AppError {
+    AppError::Conflict {
+        code: "not_confirmed",
+        message: format!("The booking is {}, not confirmed", status.as_str()),
+        details: None,
+    }
+}
+
+pub fn outside_availability() -> AppError {
+    AppError::Conflict {
+        code: "walker_unavailable",
+        message: "The walk is outside the availability of the walker".to_string(),
+        details: None,
+    }
+}
+
+/// The walker doesn't take the dogs of the walk, `refusal` says why.
+pub fn walker_refuses(refusal: String) -> AppError {
+    let mut errors = ValidationErrors::new();
+    errors.add(
+        "dogs",
+        ValidationError::new("walker_refuses").with_message(refusal.into()),
+    );
+    AppError::InvalidFields(errors)
+}
+
+pub fn already_waitlisted() -> AppError {
+    AppError::Conflict {
+        code: "already_waitlisted",
+        message: "The owner already waits for an overlapping time slot".to_string(),
+        details: None,
+    }
+}
+
+pub fn series_cancelled() -> AppError {
+    AppError::Conflict {
+        code: "series_cancelled",
+        message: "The series is already cancelled".to_string(),
+        details: None,
+    }
+}
+
+pub fn tip_exists() -> AppError {
+    AppError::Conflict {
+        code: "tip_exists",
+        message: "The walker was already tipped for this booking".to_string(),
+        details: None,
+    }
+}
+
+/// `record_refund` on an invoice that isn't paid or was already refunded.
+pub fn refund_refused(invoice: &Invoice) -> AppError {
+    let message = match invoice.refund {
+        Some(_) => "The invoice was already refunded".to_string(),
+        None => format!(
+            "The payment is {} and can't be refunded",
+            invoice.payment_status.as_str()
+        ),
+    };
+    AppError::Conflict {
+        code: "illegal_transition",
+        message,
+        details: None,
+    }
+}
+
+pub fn not_enough_points(balance: i64, cost: i64) -> AppError {
+    AppError::Conflict {
+        code: "not_enough_points",
+        message: format!(
+            "The owner has {} points, a free walk takes {}",
+            balance, cost
+        ),
+        details: None,
+    }
+}
+
+/// The coupon of a booking or a quote can't be redeemed, `reason` says why.
+pub fn coupon_unavailable(code: &str, reason: &str) -> AppError {
+    AppError::Conflict {
+        code: "coupon_unavailable",
+        message: format!("The coupon {} {}", code, reason),
+        details: None,
+    }
+}
+
+/// True when a write failed on a unique index (E11000).
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => write_error.code == 11000,
+        // Unordered `insert_many`: only duplicates were refused.
+        ErrorKind::InsertMany(insert_many) => {
+            insert_many.write_concern_error.is_none()
+                && insert_many
+                    .write_errors
+                    .as_ref()
+                    .is_some_and(|errors| errors.iter().all(|error| error.code == 11000))
+        }
+        _ => false,
+    }
+}
+
+/// True when `drop_index` found no such index, or no collection (codes 27 and 26).
+fn is_index_not_found(err: &mongodb::error::Error) -> bool {
+    matches!(
+        err.kind.as_ref(),
+        ErrorKind::Command(command_error) if command_error.code == 26 || command_error.code == 27
+    )
+}
+
+fn read_preference(mode: ReadPreferenceMode) -> ReadPreference {
+    match mode {
+        ReadPreferenceMode::Primary => ReadPreference::Primary,
+        ReadPreferenceMode::PrimaryPreferred => ReadPreference::PrimaryPreferred { options: None },
+        ReadPreferenceMode::Secondary => ReadPreference::Secondary { options: None },
+        ReadPreferenceMode::SecondaryPreferred => {
+            ReadPreference::SecondaryPreferred { options: None }
+        }
+        ReadPreferenceMode::Nearest => ReadPreference::Nearest { options: None },
+    }
+}
+
+/// `maxTimeMS` of the operations the driver can bound, set from `mongo.max_time_ms`.
+/// The server stops such an operation past the limit and answers `MaxTimeMSExpired`,
+/// so it either ran to completion or not at all. Plain inserts, updates and deletes
+/// have no such limit: they are bounded by the server selection timeout and, past
+/// it, by `mongo.write_concern_timeout_ms`.
+trait TimeLimited: Sized {
+    fn time_limit(self, limit: Option<Duration>) -> Self;
+}
+
+macro_rules! time_limited {
+    ($([$($generics:tt)*] $action:ty;)*) => {
+        $(impl<$($generics)*> TimeLimited for $action {
+            fn time_limit(self, limit: Option<Duration>) -> Self {
+                match limit {
+                    Some(limit) => self.max_time(limit),
+                    None => self,
+                }
+            }
+        })*
+    };
+}
+
+time_limited! {
+    ['a, T: Send + Sync, S] Find<'a, T, S>;
+    ['a, T: Send + Sync] FindOne<'a, T>;
+    ['a, S, T] Aggregate<'a, S, T>;
+    ['a] CountDocuments<'a>;
+    ['a] mongodb::action::gridfs::FindOne<'a>;
+    ['a, T: Send + Sync] FindOneAndUpdate<'a, T>;
+    ['a, T: Send + Sync] FindOneAndDelete<'a, T>;
+    ['a, T: Send + Sync] FindOneAndReplace<'a, T>;
+}
+
+fn index(keys: Document) -> IndexModel {
+    IndexModel::builder().keys(keys).build()
+}
+
+fn unique_index(keys: Document) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(true).build())
+        .build()
+}
+
+/// Text index over the fields of `weights`, with their relative weight.
+fn text_index(weights: Document) -> IndexModel {
+    let keys = weights
+        .keys()
+        .map(|field| (field.clone(), Bson::from("text")));
+    IndexModel::builder()
+        .keys(keys.collect::<Document>())
+        .options(IndexOptions::builder().weights(weights).build())
+        .build()
+}
+
+/// Best `$text` matches of a collection with their score, needs its text index.
+async fn text_search<T: DeserializeOwned + Send + Sync>(
+    collection: &Collection<T>,
+    text: &str,
+    limit: u64,
+    include_deleted: bool,
+) -> Result<Vec<(f64, T)>, AppError> {
+    let mut matched = doc! {"$text": {"$search": text}};
+    matched.extend(visible(include_deleted));
+    let mut cursor = collection
+        .aggregate([
+            doc! {"$match": matched},
+            doc! {"$sort": {"score": {"$meta": "textScore"}}},
+            doc! {"$limit": limit as i64},
+            doc! {"$addFields": {"score": {"$meta": "textScore"}}},
+        ])
+        .await?;
+
+    let mut hits = Vec::new();
+    while let Some(mut document) = cursor.next().await.transpose()? {
+        let score = document.get_f64("score").unwrap_or_default();
+        document.remove("score");
+        hits.push((score, from_document(document)?));
+    }
+    Ok(hits)
+}
+
+/// TTL index removing a document as soon as its `expires_at` is reached.
+fn expires_at_ttl_index() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {"expires_at": 1})
+        .options(
+            IndexOptions::builder()
+                .expire_after(Duration::from_secs(0))
+                .build(),
+        )
+        .build()
+}
+
+/// Owners created before email verification existed are considered verified,
+/// they could already book and must keep being able to.
+async fn migrate_email_verified(owner: &Collection<Owner>) -> Result<(), AppError> {
+    owner
+        .update_many(
+            doc! {"email_verified": {"$exists": false}},
+            doc! {"$set": {"email_verified": true}},
+        )
+        .await?;
+    Ok(())
+}
+
+/// Owners and bookings written before optimistic concurrency start at version 0,
+/// otherwise no `If-Match` could ever match them.
+async fn migrate_versions(
+    owner: &Collection<Owner>,
+    booking: &Collection<Booking>,
+) -> Result<(), AppError> {
+    let filter = doc! {"version": {"$exists": false}};
+    let update = doc! {"$set": {"version": 0_i64}};
+    owner.update_many(filter.clone(), update.clone()).await?;
+    booking.update_many(filter, update).await?;
+    Ok(())
+}
+
+/// Fill an empty `breeds` collection with `SEED_BREEDS`, a catalog edited
+/// by hand afterwards is left alone.
+async fn seed_breeds(breeds: &Collection<Breed>) -> Result<(), AppError> {
+    if breeds.estimated_document_count().await? > 0 {
+        return Ok(());
+    }
+    breeds
+        .insert_many(SEED_BREEDS.iter().map(|name| Breed::new(name)))
+        .await?;
+    Ok(())
+}
+
+/// Store the default `PricingRules` unless rules already exist.
+async fn seed_pricing_rules(pricing_rules: &Collection<PricingRules>) -> Result<(), AppError> {
+    let mut defaults = to_document(&PricingRules::default())?;
+    defaults.remove("_id");
+    pricing_rules
+        .update_one(
+            doc! {"_id": PRICING_RULES_ID},
+            doc! {"$setOnInsert": defaults},
+        )
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+/// `value` matched literally inside a `$regex`.
+fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Bookings written before the status lifecycle only had `cancelled` and `completed`
+/// booleans; give them the equivalent status once and drop the old fields.
+async fn migrate_booking_status(booking: &Collection<Booking>) -> Result<(), AppError> {
+    let legacy = [
+        (doc! {"cancelled": true}, BookingStatus::Cancelled),
+        (doc! {"completed": true}, BookingStatus::Completed),
+        (doc! {}, BookingStatus::Pending),
+    ];
+
+    for (condition, status) in legacy {
+        let mut filter = doc! {"status": {"$exists": false}};
+        filter.extend(condition);
+        booking
+            .update_many(
+                filter,
+                doc! {
+                    "$set": {"status": status},
+                    "$unset": {"cancelled": "", "completed": ""}
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Statuses from which a booking can be rescheduled.
+pub const RESCHEDULABLE: &[BookingStatus] = &[BookingStatus::Pending, BookingStatus::Confirmed];
+
+/// Statuses in which a walk report can be submitted (a completed walk only to overwrite it).
+pub const REPORTABLE: &[BookingStatus] = &[BookingStatus::InProgress, BookingStatus::Completed];
+
+/// `{"deleted_at": null}` (a missing field matches too) unless soft deleted
+/// documents are wanted as well.
+pub fn visible(include_deleted: bool) -> Document {
+    if include_deleted {
+        doc! {}
+    } else {
+        doc! {"deleted_at": null}
+    }
+}
+
+/// `{"$in": [...]}` filter on a list of statuses.
+fn status_in(statuses: &[BookingStatus]) -> Document {
+    doc! {"$in": status_list(statuses)}
+}
+
+/// Aggregation stages turning a booking into a `FullBooking`.
+fn full_booking_stages() -> Vec<Document> {
+    vec![
+        // Lookup to join booking.owner with owner._id
+        doc! {
+            "$lookup":doc! {
+                "from":"owner",
+                "localField":"owner",
+                "foreignField": "_id",
+                "as" : "owner"
+            }
+        },
+        // Unwind the owner array so that "owner": [ {...} ]
+        // becomes "owner": { ... }
+        doc! {
+            "$unwind":doc! {
+                "path":"$owner"
+            }
+        },
+        // Lookup dogs whose "owner" field matches owner._id
+        // and put them in an array called "dogs".
+        doc! {
+            "$lookup":{
+                "from":"dog",
+                "localField":"owner._id",
+                "foreignField":"owner",
+                "pipeline":[{ "$match":{ "deleted_at":null } }],
+                "as":"dogs"
+            }
+        },
+    ]
+}
+
+/// End of the walk: start_time + duration_in_minutes.
+pub fn booking_end(booking: &Booking) -> DateTime {
+    DateTime::from_millis(
+        booking.start_time.timestamp_millis() + booking.duration_in_minutes as i64 * 60_000,
+    )
+}
+
+/// Build the capacity error, the first slot frees up when the earliest overlapping walk ends.
+pub fn capacity_reached(overlapping: &[Booking]) -> AppError {
+    let next_available = overlapping
+        .iter()
+        .map(booking_end)
+        .min()
+        .unwrap_or_else(DateTime::now);
+
+    AppError::Conflict {
+        code: "capacity_reached",
+        message: "All walkers are already booked for this time slot".to_string(),
+        details: Some(json!({
+            "next_available_at": next_available.try_to_rfc3339_string().ok(),
+        })),
+    }
+}
+
+/// Same as `overlap_conflict` for a clashing group walk.
+pub fn group_walk_conflict(code: &'static str, message: &str, clashing: &GroupWalk) -> AppError {
+    AppError::Conflict {
+        code,
+        message: message.to_string(),
+        details: Some(json!({
+            "group_walk_id": clashing._id.to_hex(),
+            "start_time": clashing.start_time.try_to_rfc3339_string().ok(),
+            "end_time": clashing.end_time().try_to_rfc3339_string().ok(),
+        })),
+    }
+}
 
-        Ok(bookings)
+/// Build the error for a booking overlapping another booking of the same owner or walker.
+pub fn overlap_conflict(code: &'static str, message: &str, clashing: &Booking) -> AppError {
+    AppError::Conflict {
+        code,
+        message: message.to_string(),
+        details: Some(json!({
+            "booking_id": clashing._id.to_hex(),
+            "start_time": clashing.start_time.try_to_rfc3339_string().ok(),
+            "end_time": booking_end(clashing).try_to_rfc3339_string().ok(),
+            "status": clashing.status.as_str(),
+        })),
     }
 }
 
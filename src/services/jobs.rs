@@ -0,0 +1,223 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use actix_web::web::Data;
+use chrono::{SecondsFormat, Utc};
+use cron::Schedule;
+use mongodb::bson::DateTime;
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    config::JobsConfig,
+    errors::AppError,
+    models::vaccination_model::RabiesPolicy,
+    services::{
+        notifications::{self, BookingEvent, Notifier},
+        repository::{BookingRepository, OwnerRepository},
+        waitlist,
+    },
+};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// The background jobs, each run on its `jobs.*` schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Remind the owners whose walk starts in about `sms.reminder_hours`.
+    Reminders,
+    /// Expire the pending bookings not confirmed by their `expires_at`.
+    ExpirePending,
+    /// Expire the waitlist entries whose walk started, book the others that fit.
+    PromoteWaitlist,
+    /// Move the bookings finished for `jobs.archive_after_days` to the archive.
+    Archive,
+}
+
+/// Runs of a job on this replica since it started, returned by `GET /admin/jobs`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobStats {
+    pub job: JobKind,
+    /// Cron expression with seconds, in UTC.
+    pub schedule: String,
+    pub runs: u64,
+    pub failures: u64,
+    /// Walks reminded, bookings expired, walks booked from the waitlist or bookings
+    /// archived, over every run.
+    pub processed: u64,
+    pub last_started_at: Option<String>,
+    pub last_duration_ms: Option<u64>,
+    pub last_processed: Option<u64>,
+    /// Error of the last run, `None` once a run succeeds.
+    pub last_error: Option<String>,
+    pub next_run_at: Option<String>,
+}
+
+struct Job {
+    kind: JobKind,
+    schedule: Schedule,
+    stats: Mutex<JobStats>,
+}
+
+/// What the jobs work with.
+pub struct JobContext {
+    pub owners: Data<dyn OwnerRepository>,
+    pub bookings: Data<dyn BookingRepository>,
+    pub notifier: Data<Notifier>,
+    pub reminder_hours: u32,
+    pub reminder_window_minutes: u32,
+    pub archive_after_days: u32,
+    pub rabies_policy: RabiesPolicy,
+}
+
+/// Runs each job on its schedule, one run at a time per job, and keeps their
+/// counters. Every replica runs every job: they claim what they work on, so a
+/// booking is reminded, expired or archived once.
+pub struct Scheduler {
+    jobs: Vec<Arc<Job>>,
+}
+
+impl Scheduler {
+    /// Fails on a schedule that isn't a cron expression, which `Config::validate`
+    /// already reports at startup.
+    pub fn new(config: &JobsConfig) -> Result<Self, String> {
+        let jobs = [
+            (JobKind::Reminders, &config.reminders),
+            (JobKind::ExpirePending, &config.expire_pending),
+            (JobKind::PromoteWaitlist, &config.promote_waitlist),
+            (JobKind::Archive, &config.archive),
+        ]
+        .into_iter()
+        .map(|(kind, schedule)| {
+            let parsed = Schedule::from_str(schedule)
+                .map_err(|err| format!("{:?} schedule `{}`: {}", kind, schedule, err))?;
+            Ok(Arc::new(Job {
+                kind,
+                schedule: parsed,
+                stats: Mutex::new(JobStats {
+                    job: kind,
+                    schedule: schedule.clone(),
+                    runs: 0,
+                    failures: 0,
+                    processed: 0,
+                    last_started_at: None,
+                    last_duration_ms: None,
+                    last_processed: None,
+                    last_error: None,
+                    next_run_at: None,
+                }),
+            }))
+        })
+        .collect::<Result<_, String>>()?;
+        Ok(Scheduler { jobs })
+    }
+
+    /// Spawn one task per job, each waiting for its next time to run.
+    pub fn start(&self, context: JobContext) {
+        let context = Arc::new(context);
+        for job in &self.jobs {
+            let (job, context) = (job.clone(), context.clone());
+            actix_web::rt::spawn(async move {
+                while let Some(next) = job.schedule.upcoming(Utc).next() {
+                    job.stats.lock().unwrap().next_run_at =
+                        Some(next.to_rfc3339_opts(SecondsFormat::Millis, true));
+                    let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                    actix_web::rt::time::sleep(wait).await;
+                    job.run(&context).await;
+                }
+            });
+        }
+    }
+
+    pub fn stats(&self) -> Vec<JobStats> {
+        self.jobs
+            .iter()
+            .map(|job| job.stats.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+impl Job {
+    async fn run(&self, context: &JobContext) {
+        let started_at = DateTime::now().try_to_rfc3339_string().unwrap_or_default();
+        let started = Instant::now();
+        let result = self.kind.run(context).await;
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.runs += 1;
+        stats.last_started_at = Some(started_at);
+        stats.last_duration_ms = Some(duration_ms);
+        match result {
+            Ok(processed) => {
+                if processed > 0 {
+                    info!(job = ?self.kind, processed, duration_ms, "Job ran");
+                }
+                stats.processed += processed;
+                stats.last_processed = Some(processed);
+                stats.last_error = None;
+            }
+            Err(err) => {
+                error!(job = ?self.kind, error = %err, duration_ms, "Job failed");
+                stats.failures += 1;
+                stats.last_processed = None;
+                stats.last_error = Some(err.to_string());
+            }
+        }
+    }
+}
+
+impl JobKind {
+    async fn run(self, context: &JobContext) -> Result<u64, AppError> {
+        match self {
+            JobKind::Reminders => {
+                notifications::send_reminders(
+                    context.bookings.get_ref(),
+                    &context.notifier,
+                    context.reminder_hours,
+                    context.reminder_window_minutes,
+                )
+                .await
+            }
+            JobKind::ExpirePending => expire_pending(context).await,
+            JobKind::PromoteWaitlist => {
+                waitlist::promote_waitlist(
+                    context.owners.get_ref(),
+                    context.bookings.get_ref(),
+                    &context.notifier,
+                    context.rabies_policy,
+                )
+                .await
+            }
+            JobKind::Archive => {
+                let before = DateTime::from_millis(
+                    DateTime::now().timestamp_millis()
+                        - i64::from(context.archive_after_days) * DAY_MILLIS,
+                );
+                context.bookings.archive_bookings(before).await
+            }
+        }
+    }
+}
+
+/// Expire the pending bookings not confirmed in time and tell their owners.
+async fn expire_pending(context: &JobContext) -> Result<u64, AppError> {
+    let result = context
+        .bookings
+        .expire_pending_bookings(DateTime::now())
+        .await?;
+    for booking_id in &result.booking_ids {
+        if let Ok(booking) = context.bookings.get_booking(booking_id).await {
+            context
+                .notifier
+                .booking_event(BookingEvent::Expired, &booking)
+                .await;
+        }
+    }
+    Ok(result.modified_count)
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use lettre::{
+    Message, SmtpTransport, Transport, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use tracing::{error, info};
+
+use crate::config::{EmailConfig, EmailTransport, SmtpTls};
+
+/// Outgoing email, kept behind a trait so the transport can be swapped
+/// (SMTP, a provider API, or just the logs in development).
+pub trait Mailer: Send + Sync {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String>;
+}
+
+/// Mailer of `email.transport`.
+pub fn from_config(config: &EmailConfig) -> Result<Arc<dyn Mailer>, String> {
+    Ok(match config.transport {
+        EmailTransport::Log => Arc::new(LogMailer),
+        EmailTransport::Smtp => Arc::new(SmtpMailer::new(config)?),
+    })
+}
+
+/// Development mailer: writes the email to the logs instead of sending it.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        info!(to, subject, body, "Email not sent, logged instead");
+        Ok(())
+    }
+}
+
+/// Sends through an SMTP server with a pool of connections.
+/// `send` only builds the message: the delivery runs on the blocking thread pool
+/// and its failures are logged, so a slow server doesn't hold the request.
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &EmailConfig) -> Result<Self, String> {
+        let from = config
+            .from
+            .parse()
+            .map_err(|err| format!("Invalid email.from: {}", err))?;
+        let builder = match config.smtp_tls {
+            SmtpTls::Starttls => SmtpTransport::starttls_relay(&config.smtp_host),
+            SmtpTls::Implicit => SmtpTransport::relay(&config.smtp_host),
+            SmtpTls::None => Ok(SmtpTransport::builder_dangerous(&config.smtp_host)),
+        }
+        .map_err(|err| format!("Invalid email.smtp_host: {}", err))?;
+        let mut builder = builder.port(config.smtp_port);
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to
+                .parse()
+                .map_err(|err| format!("Invalid address `{}`: {}", to, err))?)
+            .subject(subject)
+            .body(body.to_string())
+            .map_err(|err| err.to_string())?;
+
+        let transport = self.transport.clone();
+        actix_web::rt::task::spawn_blocking(move || {
+            if let Err(err) = transport.send(&message) {
+                error!(error = %err, "Failed to send an email");
+            }
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use mongodb::bson::oid::ObjectId;
+use tracing::{error, info};
+
+use crate::{
+    models::{booking_model::Booking, review_model::WalkerRating},
+    services::repository::BookingRepository,
+};
+
+/// Walkers farther than this from the owner are not matched.
+pub const MAX_DISTANCE_KM: f64 = 15.0;
+/// Closest walkers scored for a booking.
+pub const CANDIDATES: u64 = 20;
+/// Walks together with the owner past which the history stops adding to the score.
+const FAMILIAR_WALKS: u64 = 5;
+/// Rating of a walker without reviews, as if rated three stars.
+const UNRATED: f64 = 0.6;
+
+const PROXIMITY_WEIGHT: f64 = 0.4;
+const RATING_WEIGHT: f64 = 0.3;
+const CAPACITY_WEIGHT: f64 = 0.15;
+const HISTORY_WEIGHT: f64 = 0.15;
+
+/// A walker near the owner who takes its dogs, scored for a new booking.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub walker: ObjectId,
+    pub distance_km: f64,
+    pub rating: WalkerRating,
+    /// Active bookings of the walker starting the same day (UTC) as the walk.
+    pub walks_that_day: u64,
+    /// Completed bookings of the owner the walker already walked.
+    pub walks_with_owner: u64,
+}
+
+impl Candidate {
+    /// Between 0 and 1, the best candidate has the highest: close to the owner,
+    /// well rated, with a light day, and who already knows the dogs.
+    pub fn score(&self) -> f64 {
+        let proximity = (1.0 - self.distance_km / MAX_DISTANCE_KM).clamp(0.0, 1.0);
+        let rating = self
+            .rating
+            .average
+            .map_or(UNRATED, |average| (average / 5.0).clamp(0.0, 1.0));
+        let capacity = 1.0 / (1.0 + self.walks_that_day as f64);
+        let history = self.walks_with_owner.min(FAMILIAR_WALKS) as f64 / FAMILIAR_WALKS as f64;
+        let score = PROXIMITY_WEIGHT * proximity
+            + RATING_WEIGHT * rating
+            + CAPACITY_WEIGHT * capacity
+            + HISTORY_WEIGHT * history;
+        (score * 1000.0).round() / 1000.0
+    }
+}
+
+/// Give a booking just created the best walker free for it. Without one (the owner
+/// has no `location`, nobody nearby is free or takes the dogs) the booking is left
+/// to the admins in `GET /admin/bookings/unassigned`, as it is when matching fails.
+pub async fn match_new_booking(
+    bookings: &dyn BookingRepository,
+    booking_id: &ObjectId,
+) -> Option<Booking> {
+    match bookings.match_walker(booking_id).await {
+        Ok(Some(booking)) => {
+            info!(booking_id = %booking_id, walker_id = ?booking.walker, score = ?booking.match_score, "Walker matched");
+            Some(booking)
+        }
+        Ok(None) => {
+            info!(booking_id = %booking_id, "No walker matched, the booking needs a manual assignment");
+            None
+        }
+        Err(err) => {
+            error!(error = %err, booking_id = %booking_id, "Failed to match a walker");
+            None
+        }
+    }
+}
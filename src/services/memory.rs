@@ -0,0 +1,2131 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use mongodb::bson::{
+    Bson, DateTime, Document, doc, from_document, oid::ObjectId, to_bson, to_document,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::warn;
+
+use crate::{
+    config::BookingsConfig,
+    errors::AppError,
+    models::{
+        audit_model::{AuditAction, AuditActor, AuditEntry, AuditFilter, EntityRef},
+        booking_model::{
+            ArchiveFilter, Booking, BookingCursor, BookingList, BookingQuery, BookingSort,
+            BookingStatus, BookingUpdateKind, BookingUpdateRequest, BulkCancelResult, FullBooking,
+            ListedBooking, WalkReport, parse_rfc3339, projection,
+        },
+        breed_model::{SEED_BREEDS, breed_key, matches_prefix},
+        coupon_model::{Coupon, coupon_key},
+        device_model::Device,
+        dog_model::{Dog, DogProfile, DogUpdateRequest},
+        idempotency_model::{IdempotencyRecord, StoredResponse},
+        incident_model::{Incident, IncidentSeverity, IncidentStatus},
+        invoice_model::{Invoice, InvoiceRefund, PaymentStatus},
+        owner_model::{
+            NotificationPreferences, Owner, OwnerDeletion, OwnerPoints, OwnerSort,
+            OwnerUpdateRequest, OwnerWithDogs,
+        },
+        page_model::Page,
+        payment_model::BookingPayment,
+        payout_model::{Payout, PayoutKind},
+        pricing_model::{PricingRules, Quote},
+        result_model::UpdatedCount,
+        review_model::Review,
+        search_model::{SearchHit, rank},
+        serde_helpers::WithId,
+        series_model::BookingSeries,
+        track_model::TrackPing,
+        vaccination_model::Vaccination,
+        waitlist_model::{WaitlistEntry, WaitlistStatus},
+        walker_model::WalkerTier,
+    },
+    services::{
+        auth::one_time_token,
+        booking_updates::BookingUpdates,
+        db::{
+            EMAIL_VERIFICATION_TTL, REPORTABLE, RESCHEDULABLE, already_waitlisted, booking_end,
+            cancellation_closed, capacity_reached, coupon_exists, coupon_unavailable, email_taken,
+            illegal_incident_transition, illegal_payment_transition, not_enough_points,
+            overlap_conflict, owner_deleted, pending_expires_at, refund_refused, review_exists,
+            series_cancelled, tip_exists, version_mismatch, visible,
+        },
+        owner_events::OwnerEvents,
+        pricing,
+        repository::{
+            AuditRepository, BookingRepository, DogRepository, IdempotencyRepository,
+            OwnerRepository, SearchRepository,
+        },
+    },
+};
+
+/// Documents of one collection by `_id`.
+type Collection = Mutex<HashMap<ObjectId, Document>>;
+
+/// Owners, dogs, bookings, walk tracks, devices, the audit log and idempotency keys kept in process memory, used by `--in-memory` to run
+/// the API without MongoDB. Nothing survives a restart.
+///
+/// Documents are stored as BSON, so the `$set` documents and the equality filters
+/// built for MongoDB (`{"owner": id}`, `{"walker": id}`) apply unchanged;
+/// query operators such as `$in` are not understood.
+/// Locks are taken one collection at a time, in the owner, dog, booking order;
+/// the coupons are locked while holding the bookings.
+#[derive(Default)]
+pub struct InMemoryDatabase {
+    owner: Collection,
+    dog: Collection,
+    booking: Collection,
+    /// Finished bookings moved out by `archive_bookings`.
+    booking_archive: Collection,
+    /// Hash of each pending verification token, to its owner and expiry.
+    email_verification: Mutex<HashMap<String, (ObjectId, DateTime)>>,
+    /// Oldest entry first.
+    audit_log: Mutex<Vec<AuditEntry>>,
+    /// Expired records are dropped when their key is claimed again.
+    idempotency: Mutex<HashMap<String, IdempotencyRecord>>,
+    /// In the order received.
+    walk_track: Mutex<Vec<TrackPing>>,
+    review: Mutex<Vec<Review>>,
+    /// Oldest report first.
+    incident: Mutex<Vec<Incident>>,
+    /// Oldest issued first.
+    invoice: Mutex<Vec<Invoice>>,
+    /// Walker earnings, in the order earned. Only written, earnings are aggregated by MongoDB.
+    payout: Mutex<Vec<Payout>>,
+    /// By code.
+    coupon: Mutex<HashMap<String, Coupon>>,
+    series: Mutex<HashMap<ObjectId, BookingSeries>>,
+    /// Oldest entry first, the order of the queues.
+    waitlist: Mutex<Vec<WaitlistEntry>>,
+    /// Push tokens, one entry per token.
+    devices: Mutex<Vec<Device>>,
+    /// Breed names by `breed_key`, alphabetical like the MongoDB sort.
+    breeds: Mutex<BTreeMap<String, String>>,
+    booking_updates: BookingUpdates,
+    owner_events: OwnerEvents,
+    max_concurrent_bookings: Option<usize>,
+    pending_expiry: Duration,
+}
+
+impl InMemoryDatabase {
+    /// Empty storage but the seeded breed catalog, `bookings.max_concurrent_bookings`
+    /// and `bookings.pending_expiry_minutes` apply like with MongoDB.
+    pub fn new(config: &BookingsConfig) -> Self {
+        InMemoryDatabase {
+            breeds: Mutex::new(
+                SEED_BREEDS
+                    .iter()
+                    .map(|name| (breed_key(name), name.to_string()))
+                    .collect(),
+            ),
+            max_concurrent_bookings: config.max_concurrent_bookings,
+            pending_expiry: config.pending_expiry(),
+            ..Default::default()
+        }
+    }
+
+    /// Store `document` in the bookings as is, around the models, like a record
+    /// written by an older version of the API. Its `_id` is generated when missing.
+    pub fn insert_raw_booking(&self, mut document: Document) -> ObjectId {
+        let id = match document.get_object_id("_id") {
+            Ok(id) => id,
+            Err(_) => {
+                let id = ObjectId::new();
+                document.insert("_id", id);
+                id
+            }
+        };
+        lock(&self.booking).insert(id, document);
+        id
+    }
+
+    /// Start the email verification of a new owner, returns the token to send.
+    fn email_verification_token(&self, owner_id: ObjectId) -> String {
+        let (token, token_hash) = one_time_token();
+        let expires_at = DateTime::from_millis(
+            DateTime::now().timestamp_millis() + EMAIL_VERIFICATION_TTL.as_millis() as i64,
+        );
+        lock(&self.email_verification).insert(token_hash, (owner_id, expires_at));
+        token
+    }
+
+    /// Cancel a booking for the fee of the default cancellation policy, recorded on
+    /// it, or refuse once the walk started. Filtered on the version like with MongoDB.
+    async fn cancel_under_policy(
+        &self,
+        booking_id: &ObjectId,
+        mut extra_filter: Document,
+    ) -> Result<Booking, AppError> {
+        let current = self.get_booking(booking_id).await?;
+        let now = DateTime::now();
+        let fee = pricing::cancellation_fee(
+            &PricingRules::default(),
+            current.start_time,
+            current.price_cents.unwrap_or_default(),
+            now,
+        );
+        if fee.is_none()
+            && BookingStatus::Cancelled
+                .allowed_from()
+                .contains(&current.status)
+        {
+            return Err(cancellation_closed());
+        }
+
+        extra_filter.insert("version", current.version);
+        self.transition_booking(
+            booking_id,
+            BookingStatus::Cancelled,
+            extra_filter,
+            doc! {"cancelled_at": now, "cancellation_fee_cents": fee},
+        )
+        .await
+    }
+
+    /// Dogs priced in the bookings of the owner. Counted before the booking lock
+    /// is taken, as the lock order requires.
+    fn live_dogs(&self, owner_id: &ObjectId) -> usize {
+        matching(&self.dog, &doc! {"owner": owner_id, "deleted_at": null}).len()
+    }
+
+    /// Join the owner and its dogs like the `$lookup` stages of `get_bookings`.
+    /// `None` when the owner is gone, as `$unwind` drops such bookings.
+    fn full_booking(&self, mut booking: Document) -> Result<Option<WithId<FullBooking>>, AppError> {
+        let Ok(owner_id) = booking.get_object_id("owner") else {
+            return Ok(None);
+        };
+        let Some(owner) = lock(&self.owner).get(&owner_id).cloned() else {
+            return Ok(None);
+        };
+        let dogs = matching(&self.dog, &doc! {"owner": owner_id, "deleted_at": null});
+
+        booking.insert("owner", owner);
+        booking.insert("dogs", dogs.into_iter().map(Bson::from).collect::<Vec<_>>());
+        Ok(Some(from_document(booking)?))
+    }
+}
+
+#[async_trait]
+impl OwnerRepository for InMemoryDatabase {
+    async fn get_owner_by_id(&self, owner_id: &ObjectId) -> Result<Owner, AppError> {
+        find(&self.owner, owner_id)?
+            .ok_or_else(|| AppError::NotFound("Owner not found".to_string()))
+    }
+
+    async fn owner_exists(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<bool, AppError> {
+        let owners = lock(&self.owner);
+        match include_deleted {
+            true => Ok(owners.contains_key(owner_id)),
+            false => Ok(live(&owners, owner_id).is_some()),
+        }
+    }
+
+    async fn get_owner_full(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<OwnerWithDogs, AppError> {
+        let owner = match include_deleted {
+            true => lock(&self.owner).get(owner_id).cloned(),
+            false => live(&lock(&self.owner), owner_id).cloned(),
+        };
+        let owner: Owner = match owner {
+            Some(owner) => from_document(owner)?,
+            None => return Err(AppError::NotFound("Owner not found".to_string())),
+        };
+        let dogs = self.get_dogs_by_owner(owner_id, include_deleted).await?;
+        Ok(OwnerWithDogs {
+            owner: WithId(owner),
+            dogs,
+        })
+    }
+
+    async fn get_owners(
+        &self,
+        page: u64,
+        limit: u64,
+        sort: OwnerSort,
+        include_deleted: bool,
+    ) -> Result<Page<WithId<Owner>>, AppError> {
+        let mut owners: Vec<Owner> =
+            deserialize_all(matching(&self.owner, &visible(include_deleted)))?;
+        if let OwnerSort::Name = sort {
+            owners.sort_by(|a, b| a.name.cmp(&b.name).then(a._id.cmp(&b._id)));
+        }
+
+        let total = owners.len() as u64;
+        let items = owners
+            .into_iter()
+            .skip(((page - 1) * limit) as usize)
+            .take(limit as usize)
+            .map(WithId)
+            .collect();
+
+        Ok(Page {
+            items,
+            page,
+            limit,
+            total,
+        })
+    }
+
+    /// Emails are unique like with the MongoDB index, a taken one is a 409 `email_taken`.
+    async fn create_owner(&self, owner: Owner) -> Result<(ObjectId, String), AppError> {
+        let owner_id = owner._id;
+        {
+            let mut owners = lock(&self.owner);
+            if email_owner(&owners, &owner.email).is_some() {
+                return Err(email_taken());
+            }
+            owners.insert(owner_id, to_document(&owner)?);
+        }
+
+        Ok((owner_id, self.email_verification_token(owner_id)))
+    }
+
+    async fn create_owner_with_dogs(
+        &self,
+        owner: Owner,
+        dogs: Vec<Dog>,
+    ) -> Result<(ObjectId, String), AppError> {
+        let owner_id = owner._id;
+        let owner_document = to_document(&owner)?;
+        let dog_documents = dogs
+            .iter()
+            .map(|dog| Ok((dog._id, to_document(dog)?)))
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        {
+            let mut owners = lock(&self.owner);
+            if email_owner(&owners, &owner.email).is_some() {
+                return Err(email_taken());
+            }
+            owners.insert(owner_id, owner_document);
+            lock(&self.dog).extend(dog_documents);
+        }
+
+        Ok((owner_id, self.email_verification_token(owner_id)))
+    }
+
+    async fn verify_owner_email(&self, token_hash: &str) -> Result<Owner, AppError> {
+        let gone = || AppError::Gone("This link is invalid or has expired".to_string());
+
+        let (owner_id, expires_at) = lock(&self.email_verification)
+            .remove(token_hash)
+            .ok_or_else(gone)?;
+        if expires_at <= DateTime::now() {
+            return Err(gone());
+        }
+
+        update(&self.owner, &owner_id, doc! {"email_verified": true})?.ok_or_else(gone)
+    }
+
+    async fn update_owner(
+        &self,
+        owner_id: &ObjectId,
+        update_request: &OwnerUpdateRequest,
+        expected_version: i64,
+    ) -> Result<Owner, AppError> {
+        let set = update_request.to_set_document();
+        if set.is_empty() {
+            return Err(AppError::Validation(
+                "At least one field must be provided".to_string(),
+            ));
+        }
+
+        let mut owners = lock(&self.owner);
+        if let Ok(email) = set.get_str("email")
+            && email_owner(&owners, email).is_some_and(|other| other != *owner_id)
+        {
+            return Err(email_taken());
+        }
+
+        let document = live_mut(&mut owners, owner_id)
+            .ok_or_else(|| AppError::NotFound("Owner not found".to_string()))?;
+        let current = document.get_i64("version").unwrap_or_default();
+        if current != expected_version {
+            return Err(version_mismatch("owner", expected_version, current));
+        }
+        apply(document, set);
+        Ok(from_document(document.clone())?)
+    }
+
+    async fn set_notification_preferences(
+        &self,
+        owner_id: &ObjectId,
+        preferences: &NotificationPreferences,
+    ) -> Result<Owner, AppError> {
+        update(
+            &self.owner,
+            owner_id,
+            doc! {"notification_preferences": to_bson(preferences)?},
+        )?
+        .ok_or_else(|| AppError::NotFound("Owner not found".to_string()))
+    }
+
+    /// Same cleanup as the MongoDB transaction.
+    async fn delete_owner_cascade(&self, owner_id: &ObjectId) -> Result<OwnerDeletion, AppError> {
+        let now = DateTime::now();
+        match live_mut(&mut lock(&self.owner), owner_id) {
+            Some(owner) => apply(owner, doc! {"deleted_at": now}),
+            None => return Err(AppError::NotFound("Owner not found".to_string())),
+        }
+
+        let mut deleted_dogs = 0;
+        for (_, dog) in lock(&self.dog).iter_mut() {
+            if dog.get_object_id("owner").ok() == Some(*owner_id) && !is_deleted(dog) {
+                apply(dog, doc! {"deleted_at": now});
+                deleted_dogs += 1;
+            }
+        }
+
+        let mut cancelled_bookings = 0;
+        for (_, document) in lock(&self.booking).iter_mut() {
+            let booking: Booking = from_document(document.clone())?;
+            if booking.owner == *owner_id
+                && BookingStatus::Cancelled
+                    .allowed_from()
+                    .contains(&booking.status)
+                && booking.start_time >= now
+            {
+                apply(
+                    document,
+                    doc! {
+                        "status": BookingStatus::Cancelled,
+                        "cancelled_at": now,
+                        "cancellation_reason": "Owner deleted"
+                    },
+                );
+                cancelled_bookings += 1;
+            }
+        }
+
+        Ok(OwnerDeletion {
+            deleted_dogs,
+            cancelled_bookings,
+        })
+    }
+
+    /// Same as MongoDB: the dogs sharing the owner's `deleted_at` come back with it.
+    async fn restore_owner(&self, owner_id: &ObjectId) -> Result<Owner, AppError> {
+        let mut owners = lock(&self.owner);
+        let document = owners
+            .get_mut(owner_id)
+            .filter(|document| is_deleted(document))
+            .ok_or_else(|| AppError::NotFound("No deleted owner with this id".to_string()))?;
+
+        let deleted_at = document.get("deleted_at").cloned();
+        for (_, dog) in lock(&self.dog).iter_mut() {
+            if dog.get_object_id("owner").ok() == Some(*owner_id)
+                && dog.get("deleted_at") == deleted_at.as_ref()
+            {
+                undelete(dog);
+            }
+        }
+        undelete(document);
+        Ok(from_document(document.clone())?)
+    }
+
+    async fn get_owner_points(&self, owner_id: &ObjectId) -> Result<OwnerPoints, AppError> {
+        let owner: Owner = find(&self.owner, owner_id)?
+            .ok_or_else(|| AppError::NotFound("Owner not found".to_string()))?;
+        Ok(OwnerPoints::new(
+            owner.points_balance,
+            &PricingRules::default(),
+        ))
+    }
+
+    async fn register_device(&self, device: Device) -> Result<Device, AppError> {
+        let mut devices = lock(&self.devices);
+        if let Some(stored) = devices.iter_mut().find(|d| d.token == device.token) {
+            stored.owner = device.owner;
+            stored.platform = device.platform;
+            stored.registered_at = device.registered_at;
+            return Ok(stored.clone());
+        }
+        devices.push(device.clone());
+        Ok(device)
+    }
+
+    async fn get_owner_devices(&self, owner_id: &ObjectId) -> Result<Vec<Device>, AppError> {
+        Ok(lock(&self.devices)
+            .iter()
+            .filter(|device| device.owner == *owner_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_devices(&self, tokens: &[String]) -> Result<u64, AppError> {
+        let mut devices = lock(&self.devices);
+        let before = devices.len();
+        devices.retain(|device| !tokens.contains(&device.token));
+        Ok((before - devices.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl DogRepository for InMemoryDatabase {
+    async fn create_dog(&self, dog: Dog) -> Result<ObjectId, AppError> {
+        let dog_id = dog._id;
+        insert(&self.dog, dog_id, &dog)?;
+        Ok(dog_id)
+    }
+
+    async fn get_dog(&self, dog_id: &ObjectId) -> Result<Dog, AppError> {
+        find(&self.dog, dog_id)?.ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+    }
+
+    async fn update_dog(
+        &self,
+        dog_id: &ObjectId,
+        update_request: &DogUpdateRequest,
+    ) -> Result<Dog, AppError> {
+        let set = update_request.to_set_document();
+        if set.is_empty() {
+            return Err(AppError::Validation(
+                "At least one field must be provided".to_string(),
+            ));
+        }
+
+        update(&self.dog, dog_id, set)?
+            .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+    }
+
+    async fn delete_dog(&self, dog_id: &ObjectId) -> Result<(), AppError> {
+        update::<Dog>(&self.dog, dog_id, doc! {"deleted_at": DateTime::now()})?
+            .map(|_| ())
+            .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+    }
+
+    async fn restore_dog(&self, dog_id: &ObjectId) -> Result<Dog, AppError> {
+        let owner_id = lock(&self.dog)
+            .get(dog_id)
+            .filter(|document| is_deleted(document))
+            .and_then(|document| document.get_object_id("owner").ok())
+            .ok_or_else(|| AppError::NotFound("No deleted dog with this id".to_string()))?;
+        if !self.owner_exists(&owner_id, false).await? {
+            return Err(owner_deleted());
+        }
+
+        let mut dogs = lock(&self.dog);
+        let document = dogs
+            .get_mut(dog_id)
+            .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))?;
+        undelete(document);
+        Ok(from_document(document.clone())?)
+    }
+
+    async fn get_dogs_by_owner(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<Vec<WithId<Dog>>, AppError> {
+        let mut filter = doc! {"owner": owner_id};
+        filter.extend(visible(include_deleted));
+        let dogs: Vec<Dog> = deserialize_all(matching(&self.dog, &filter))?;
+        Ok(dogs.into_iter().map(WithId).collect())
+    }
+
+    async fn search_breeds(
+        &self,
+        prefix: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<String>, AppError> {
+        let mut names: Vec<String> = lock(&self.breeds)
+            .values()
+            .filter(|name| prefix.is_none_or(|prefix| matches_prefix(name, prefix)))
+            .cloned()
+            .collect();
+        names.sort();
+        names.truncate(limit as usize);
+        Ok(names)
+    }
+
+    async fn find_breed(&self, name: &str) -> Result<Option<String>, AppError> {
+        Ok(lock(&self.breeds).get(&breed_key(name)).cloned())
+    }
+
+    async fn set_dog_profile(
+        &self,
+        dog_id: &ObjectId,
+        profile: &DogProfile,
+    ) -> Result<Dog, AppError> {
+        update(&self.dog, dog_id, doc! {"profile": to_bson(profile)?})?
+            .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))
+    }
+
+    async fn add_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination: Vaccination,
+    ) -> Result<Dog, AppError> {
+        edit_vaccinations(&self.dog, dog_id, |vaccinations| {
+            vaccinations.push(vaccination);
+            Ok(())
+        })
+    }
+
+    async fn replace_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination: Vaccination,
+    ) -> Result<Dog, AppError> {
+        edit_vaccinations(&self.dog, dog_id, |vaccinations| {
+            let existing = vaccinations
+                .iter_mut()
+                .find(|existing| existing._id == vaccination._id)
+                .ok_or_else(vaccination_not_found)?;
+            *existing = vaccination;
+            Ok(())
+        })
+    }
+
+    async fn delete_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination_id: &ObjectId,
+    ) -> Result<Dog, AppError> {
+        edit_vaccinations(&self.dog, dog_id, |vaccinations| {
+            let count = vaccinations.len();
+            vaccinations.retain(|vaccination| vaccination._id != *vaccination_id);
+            if vaccinations.len() == count {
+                return Err(vaccination_not_found());
+            }
+            Ok(())
+        })
+    }
+}
+
+#[async_trait]
+impl BookingRepository for InMemoryDatabase {
+    /// Same overlap and capacity rules as MongoDB, checked, redeemed and inserted under one lock.
+    /// The owners are only locked to spend points.
+    async fn create_booking(
+        &self,
+        mut booking: Booking,
+        coupon_code: Option<&str>,
+        redeem_points: bool,
+    ) -> Result<ObjectId, AppError> {
+        let mut owners = redeem_points.then(|| lock(&self.owner));
+        let dogs = self.live_dogs(&booking.owner);
+        let booking_id = booking._id;
+        let start = booking.start_time;
+        let end = booking_end(&booking);
+        booking.expires_at = pending_expires_at(&booking, self.pending_expiry);
+
+        let mut bookings = lock(&self.booking);
+        let clashing = overlapping(&bookings, start, end, |other| other.owner == booking.owner)?;
+        if let Some(clashing) = clashing.first() {
+            return Err(overlap_conflict(
+                "booking_conflict",
+                "The owner already has a booking overlapping this time slot",
+                clashing,
+            ));
+        }
+
+        if let Some(max) = self.max_concurrent_bookings {
+            let overlapping = overlapping(&bookings, start, end, |_| true)?;
+            if overlapping.len() >= max {
+                return Err(capacity_reached(&overlapping));
+            }
+        }
+
+        // Both redemptions are checked before either is spent.
+        let mut coupons = lock(&self.coupon);
+        let coupon = coupon_code
+            .map(|code| usable_coupon(&mut coupons, code))
+            .transpose()?;
+        let owner = match owners.as_mut() {
+            Some(owners) => {
+                let cost = PricingRules::default().points_per_free_walk;
+                let owner = live_mut(owners, &booking.owner)
+                    .ok_or_else(|| AppError::NotFound("Owner not found".to_string()))?;
+                let balance = owner.get_i64("points_balance").unwrap_or(0);
+                if balance < cost {
+                    return Err(not_enough_points(balance, cost));
+                }
+                Some((owner, balance - cost, cost))
+            }
+            None => None,
+        };
+
+        if let Some(coupon) = coupon {
+            coupon.remaining_uses -= 1;
+            booking.coupon = Some(coupon.applied());
+        }
+        if let Some((owner, balance, cost)) = owner {
+            owner.insert("points_balance", balance);
+            booking.points_redeemed = cost;
+        }
+        let quote = price(&booking, dogs);
+        let booking = booking.with_price(quote);
+        bookings.insert(booking_id, to_document(&booking)?);
+        self.booking_updates
+            .publish(BookingUpdateKind::Created, &booking_id);
+        self.owner_events
+            .booking_status(booking.owner, &booking_id, booking.status);
+        Ok(booking_id)
+    }
+
+    async fn get_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError> {
+        find(&self.booking, booking_id)?
+            .ok_or_else(|| AppError::NotFound("Booking not found".to_string()))
+    }
+
+    async fn quote_booking(&self, booking: &Booking) -> Result<Quote, AppError> {
+        Ok(price(booking, self.live_dogs(&booking.owner)))
+    }
+
+    async fn get_pricing_rules(&self) -> Result<PricingRules, AppError> {
+        Ok(PricingRules::default())
+    }
+
+    async fn get_full_booking(
+        &self,
+        booking_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<WithId<FullBooking>, AppError> {
+        let booking = match include_deleted {
+            true => lock(&self.booking).get(booking_id).cloned(),
+            false => live(&lock(&self.booking), booking_id).cloned(),
+        };
+        match booking {
+            Some(booking) => self
+                .full_booking(booking)?
+                .ok_or_else(|| AppError::NotFound("Booking not found".to_string())),
+            None => Err(AppError::NotFound("Booking not found".to_string())),
+        }
+    }
+
+    /// Bookings failing to deserialize are skipped and counted, as with MongoDB.
+    async fn get_bookings(
+        &self,
+        query: &BookingQuery,
+        include_deleted: bool,
+    ) -> Result<BookingList, AppError> {
+        let mut filter = query.filter.clone();
+        filter.extend(visible(include_deleted));
+
+        let mut skipped = 0;
+        let mut found = Vec::new();
+        for document in matching(&self.booking, &filter) {
+            let id = document.get_object_id("_id").ok();
+            let booking: Booking = match from_document(document.clone()) {
+                Ok(booking) => booking,
+                Err(err) => {
+                    warn!(booking_id = ?id, error = %err, "Skipping malformed booking");
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if query.statuses.contains(&booking.status)
+                && booking.start_time >= query.from
+                && query.to.is_none_or(|to| booking.start_time < to)
+            {
+                found.push((booking.start_time, booking._id, document));
+            }
+        }
+
+        found.sort_by_key(|(start_time, id, _)| (*start_time, *id));
+        if query.sort == BookingSort::StartTimeDesc {
+            found.reverse();
+        }
+        if let Some(after) = query.after {
+            let cursor = (after.start_time, after.id);
+            found.retain(|(start_time, id, _)| match query.sort {
+                BookingSort::StartTimeAsc => (*start_time, *id) > cursor,
+                BookingSort::StartTimeDesc => (*start_time, *id) < cursor,
+            });
+        }
+
+        let total = query.after.is_none().then_some(found.len() as u64);
+        let skip = match query.after {
+            Some(_) => 0,
+            None => (query.page - 1).saturating_mul(query.limit),
+        };
+        let mut page: Vec<_> = found
+            .into_iter()
+            .skip(skip as usize)
+            .take(query.limit.saturating_add(1) as usize)
+            .collect();
+        let has_next = page.len() as u64 > query.limit;
+        page.truncate(query.limit as usize);
+        let next_cursor = page.last().filter(|_| has_next).map(|(start_time, id, _)| {
+            BookingCursor {
+                start_time: *start_time,
+                id: *id,
+            }
+            .encode()
+        });
+
+        let mut bookings = Vec::new();
+        for (_, id, document) in page {
+            match self.full_booking(document) {
+                Ok(Some(booking)) => match &query.fields {
+                    Some(fields) => {
+                        let document = project(&to_document(&booking.0)?, &projection(fields));
+                        bookings.push(ListedBooking::partial(document, fields));
+                    }
+                    None => bookings.push(ListedBooking::Full(Box::new(booking))),
+                },
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(booking_id = %id, error = %err, "Skipping malformed booking");
+                    skipped += 1;
+                }
+            }
+        }
+
+        Ok(BookingList {
+            bookings,
+            skipped,
+            page: query.after.is_none().then_some(query.page),
+            limit: query.limit,
+            total,
+            next_cursor,
+        })
+    }
+
+    async fn update_booking(
+        &self,
+        booking_id: &ObjectId,
+        update_request: &BookingUpdateRequest,
+        expected_version: i64,
+    ) -> Result<Booking, AppError> {
+        if update_request.start_time.is_none() && update_request.duration_in_minutes.is_none() {
+            return Err(AppError::Validation(
+                "At least one of start_time or duration_in_minutes must be provided".to_string(),
+            ));
+        }
+
+        let dogs = self.live_dogs(&self.get_booking(booking_id).await?.owner);
+
+        let mut bookings = lock(&self.booking);
+        let current: Booking = match live(&bookings, booking_id) {
+            Some(document) => from_document(document.clone())?,
+            None => return Err(AppError::NotFound("Booking not found".to_string())),
+        };
+        if current.version != expected_version {
+            return Err(version_mismatch(
+                "booking",
+                expected_version,
+                current.version,
+            ));
+        }
+        if !RESCHEDULABLE.contains(&current.status) {
+            return Err(AppError::conflict(format!(
+                "A {} booking can't be rescheduled",
+                current.status.as_str()
+            )));
+        }
+
+        let start_time = match &update_request.start_time {
+            Some(start_time) => parse_rfc3339(start_time).map_err(AppError::Validation)?,
+            None => current.start_time,
+        };
+        let duration_in_minutes = update_request
+            .duration_in_minutes
+            .unwrap_or(current.duration_in_minutes);
+        if start_time <= DateTime::now() {
+            return Err(AppError::Validation(
+                "The new slot must be in the future".to_string(),
+            ));
+        }
+
+        if let Some(max) = self.max_concurrent_bookings {
+            let end = DateTime::from_millis(
+                start_time.timestamp_millis() + duration_in_minutes as i64 * 60_000,
+            );
+            let overlapping =
+                overlapping(&bookings, start_time, end, |other| other._id != *booking_id)?;
+            if overlapping.len() >= max {
+                return Err(capacity_reached(&overlapping));
+            }
+        }
+
+        let quote = price(
+            &Booking {
+                start_time,
+                duration_in_minutes,
+                ..current
+            },
+            dogs,
+        );
+        set(
+            &mut bookings,
+            booking_id,
+            doc! {
+                "start_time": start_time,
+                "duration_in_minutes": duration_in_minutes as i32,
+                "price_cents": quote.price_cents,
+                "currency": quote.currency
+            },
+        )
+    }
+
+    async fn delete_booking(&self, booking_id: &ObjectId) -> Result<(), AppError> {
+        set(
+            &mut lock(&self.booking),
+            booking_id,
+            doc! {"deleted_at": DateTime::now()},
+        )
+        .map(|_| ())
+    }
+
+    /// Same checks as MongoDB, run under the booking lock.
+    async fn restore_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError> {
+        let owner_id = lock(&self.booking)
+            .get(booking_id)
+            .filter(|document| is_deleted(document))
+            .and_then(|document| document.get_object_id("owner").ok())
+            .ok_or_else(|| AppError::NotFound("No deleted booking with this id".to_string()))?;
+        if !self.owner_exists(&owner_id, false).await? {
+            return Err(owner_deleted());
+        }
+
+        let mut bookings = lock(&self.booking);
+        let deleted: Booking = match bookings.get(booking_id) {
+            Some(document) => from_document(document.clone())?,
+            None => return Err(AppError::conflict("Booking changed while restoring")),
+        };
+        if BookingStatus::active().contains(&deleted.status) {
+            let start = deleted.start_time;
+            let end = booking_end(&deleted);
+            let clashing = overlapping(&bookings, start, end, |other| other.owner == owner_id)?;
+            if let Some(clashing) = clashing.first() {
+                return Err(overlap_conflict(
+                    "booking_conflict",
+                    "The owner already has a booking overlapping this time slot",
+                    clashing,
+                ));
+            }
+            if let Some(max) = self.max_concurrent_bookings {
+                let overlapping = overlapping(&bookings, start, end, |_| true)?;
+                if overlapping.len() >= max {
+                    return Err(capacity_reached(&overlapping));
+                }
+            }
+        }
+
+        let Some(document) = bookings.get_mut(booking_id) else {
+            return Err(AppError::conflict("Booking changed while restoring"));
+        };
+        undelete(document);
+        Ok(from_document(document.clone())?)
+    }
+
+    /// Walkers are not kept in memory, so `walker_id` is not checked for existence.
+    async fn assign_walker(
+        &self,
+        booking_id: &ObjectId,
+        walker_id: &ObjectId,
+    ) -> Result<Booking, AppError> {
+        let dogs = self.live_dogs(&self.get_booking(booking_id).await?.owner);
+
+        let mut bookings = lock(&self.booking);
+        let booking: Booking = match live(&bookings, booking_id) {
+            Some(document) => from_document(document.clone())?,
+            None => return Err(AppError::NotFound("Booking not found".to_string())),
+        };
+        if !RESCHEDULABLE.contains(&booking.status) {
+            return Err(AppError::conflict(format!(
+                "A walker can't be assigned to a {} booking",
+                booking.status.as_str()
+            )));
+        }
+
+        let clashing = overlapping(
+            &bookings,
+            booking.start_time,
+            booking_end(&booking),
+            |other| other.walker == Some(*walker_id) && other._id != *booking_id,
+        )?;
+        if let Some(clashing) = clashing.first() {
+            return Err(overlap_conflict(
+                "walker_unavailable",
+                "The walker already has a booking overlapping this time slot",
+                clashing,
+            ));
+        }
+
+        let quote = price(&booking, dogs);
+        set(
+            &mut bookings,
+            booking_id,
+            doc! {
+                "walker": walker_id,
+                "match_score": null,
+                "price_cents": quote.price_cents,
+                "currency": quote.currency
+            },
+        )
+        .inspect(|booking| {
+            self.booking_updates
+                .publish(BookingUpdateKind::Assigned, &booking._id)
+        })
+    }
+
+    /// No walker is kept in memory to match.
+    async fn match_walker(&self, _booking_id: &ObjectId) -> Result<Option<Booking>, AppError> {
+        Ok(None)
+    }
+
+    async fn get_unassigned_bookings(
+        &self,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Booking>>, AppError> {
+        let now = DateTime::now();
+        let mut bookings: Vec<Booking> = deserialize_all(matching(&self.booking, &visible(false)))?;
+        bookings.retain(|booking| {
+            booking.walker.is_none()
+                && RESCHEDULABLE.contains(&booking.status)
+                && booking.start_time > now
+        });
+        bookings.sort_by_key(|booking| (booking.start_time, booking._id));
+
+        Ok(Page {
+            total: bookings.len() as u64,
+            items: bookings
+                .into_iter()
+                .skip(((page - 1) * limit) as usize)
+                .take(limit as usize)
+                .map(WithId)
+                .collect(),
+            page,
+            limit,
+        })
+    }
+
+    async fn set_booking_payment(
+        &self,
+        booking_id: &ObjectId,
+        payment: &BookingPayment,
+    ) -> Result<Booking, AppError> {
+        set(
+            &mut lock(&self.booking),
+            booking_id,
+            doc! {"payment": to_bson(payment)?},
+        )
+    }
+
+    async fn save_walk_report(
+        &self,
+        booking_id: &ObjectId,
+        report: WalkReport,
+        overwrite: bool,
+    ) -> Result<UpdatedCount, AppError> {
+        let mut bookings = lock(&self.booking);
+        let booking: Booking = match live(&bookings, booking_id) {
+            Some(document) => from_document(document.clone())?,
+            None => return Err(AppError::NotFound("Booking not found".to_string())),
+        };
+        if !REPORTABLE.contains(&booking.status)
+            || booking.start_time > DateTime::now()
+            || (booking.report.is_some() && !overwrite)
+        {
+            return Err(AppError::conflict(
+                "Booking changed while saving the report",
+            ));
+        }
+
+        let completing = booking.status != BookingStatus::Completed;
+        let report = mongodb::bson::to_bson(&report)?;
+        let booking = set(
+            &mut bookings,
+            booking_id,
+            doc! {
+                "report": report,
+                "status": BookingStatus::Completed
+            },
+        )?;
+        // Like the change stream of MongoDB, which only sees a status that changed.
+        if completing {
+            self.booking_updates
+                .publish(BookingUpdateKind::Completed, booking_id);
+            self.owner_events
+                .booking_status(booking.owner, booking_id, BookingStatus::Completed);
+        }
+
+        Ok(UpdatedCount {
+            matched_count: 1,
+            modified_count: 1,
+            upserted_id: None,
+        })
+    }
+
+    async fn transition_booking(
+        &self,
+        booking_id: &ObjectId,
+        next: BookingStatus,
+        extra_filter: Document,
+        extra_set: Document,
+    ) -> Result<Booking, AppError> {
+        let mut bookings = lock(&self.booking);
+        let current: Booking = match live(&bookings, booking_id) {
+            Some(document) if matches(document, &extra_filter) => from_document(document.clone())?,
+            _ => return Err(AppError::NotFound("Booking not found".to_string())),
+        };
+
+        if !next.allowed_from().contains(&current.status) {
+            return Err(AppError::Conflict {
+                code: "illegal_transition",
+                message: format!(
+                    "A {} booking can't become {}",
+                    current.status.as_str(),
+                    next.as_str()
+                ),
+                details: None,
+            });
+        }
+
+        let mut changes = doc! {"status": next};
+        if next == BookingStatus::Confirmed {
+            changes.insert("expires_at", Bson::Null);
+        }
+        changes.extend(extra_set);
+        set(&mut bookings, booking_id, changes).inspect(|booking| {
+            if let Some(event) = next.update_kind() {
+                self.booking_updates.publish(event, &booking._id);
+            }
+            self.owner_events
+                .booking_status(booking.owner, &booking._id, next);
+        })
+    }
+
+    async fn cancel_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError> {
+        self.cancel_under_policy(booking_id, doc! {}).await
+    }
+
+    async fn cancel_booking_for_owner(
+        &self,
+        booking_id: &ObjectId,
+        owner_id: &ObjectId,
+    ) -> Result<Booking, AppError> {
+        self.cancel_under_policy(booking_id, doc! {"owner": owner_id})
+            .await
+    }
+
+    async fn cancel_bookings_in_range(
+        &self,
+        from: DateTime,
+        to: DateTime,
+        reason: &str,
+    ) -> Result<BulkCancelResult, AppError> {
+        let now = DateTime::now();
+        let mut booking_ids = Vec::new();
+
+        for (id, document) in lock(&self.booking).iter_mut() {
+            let booking: Booking = from_document(document.clone())?;
+            if booking.deleted_at.is_none()
+                && BookingStatus::Cancelled
+                    .allowed_from()
+                    .contains(&booking.status)
+                && booking.start_time >= from
+                && booking.start_time < to
+            {
+                apply(
+                    document,
+                    doc! {
+                        "status": BookingStatus::Cancelled,
+                        "cancelled_at": now,
+                        "cancellation_reason": reason
+                    },
+                );
+                booking_ids.push(*id);
+                self.booking_updates
+                    .publish(BookingUpdateKind::Cancelled, id);
+                self.owner_events
+                    .booking_status(booking.owner, id, BookingStatus::Cancelled);
+            }
+        }
+        booking_ids.sort();
+
+        Ok(BulkCancelResult {
+            modified_count: booking_ids.len() as u64,
+            booking_ids,
+        })
+    }
+
+    async fn expire_pending_bookings(&self, now: DateTime) -> Result<BulkCancelResult, AppError> {
+        let mut booking_ids = Vec::new();
+
+        for (id, document) in lock(&self.booking).iter_mut() {
+            let booking: Booking = from_document(document.clone())?;
+            if booking.deleted_at.is_none()
+                && booking.status == BookingStatus::Pending
+                && (booking
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= now)
+                    || booking.start_time <= now)
+            {
+                apply(document, doc! {"status": BookingStatus::Expired});
+                booking_ids.push(*id);
+                self.owner_events
+                    .booking_status(booking.owner, id, BookingStatus::Expired);
+            }
+        }
+        booking_ids.sort();
+
+        Ok(BulkCancelResult {
+            modified_count: booking_ids.len() as u64,
+            booking_ids,
+        })
+    }
+
+    async fn archive_bookings(&self, before: DateTime) -> Result<u64, AppError> {
+        let mut bookings = lock(&self.booking);
+        let mut finished = Vec::new();
+        for (id, document) in bookings.iter() {
+            let booking: Booking = from_document(document.clone())?;
+            if matches!(
+                booking.status,
+                BookingStatus::Completed
+                    | BookingStatus::Cancelled
+                    | BookingStatus::NoShow
+                    | BookingStatus::Expired
+            ) && booking.start_time < before
+            {
+                finished.push(*id);
+            }
+        }
+        let mut archive = lock(&self.booking_archive);
+        for id in &finished {
+            if let Some(document) = bookings.remove(id) {
+                archive.insert(*id, document);
+            }
+        }
+        Ok(finished.len() as u64)
+    }
+
+    async fn get_archived_bookings(
+        &self,
+        filter: &ArchiveFilter,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Booking>>, AppError> {
+        let mut bookings: Vec<Booking> =
+            deserialize_all(matching(&self.booking_archive, &doc! {}))?;
+        bookings.retain(|booking| {
+            filter.owner.is_none_or(|owner| booking.owner == owner)
+                && filter.from.is_none_or(|from| booking.start_time >= from)
+                && filter.to.is_none_or(|to| booking.start_time < to)
+        });
+        bookings.sort_by_key(|booking| std::cmp::Reverse((booking.start_time, booking._id)));
+
+        Ok(Page {
+            total: bookings.len() as u64,
+            items: bookings
+                .into_iter()
+                .skip(((page - 1) * limit) as usize)
+                .take(limit as usize)
+                .map(WithId)
+                .collect(),
+            page,
+            limit,
+        })
+    }
+
+    fn booking_updates(&self) -> &BookingUpdates {
+        &self.booking_updates
+    }
+
+    fn owner_events(&self) -> &OwnerEvents {
+        &self.owner_events
+    }
+
+    async fn add_track_pings(&self, pings: Vec<TrackPing>) -> Result<(), AppError> {
+        lock(&self.walk_track).extend(pings);
+        Ok(())
+    }
+
+    async fn get_track(&self, booking_id: &ObjectId) -> Result<Vec<TrackPing>, AppError> {
+        let mut pings: Vec<TrackPing> = lock(&self.walk_track)
+            .iter()
+            .filter(|ping| ping.booking == *booking_id)
+            .cloned()
+            .collect();
+        pings.sort_by_key(|ping| (ping.at, ping._id));
+        Ok(pings)
+    }
+
+    async fn create_review(&self, review: &Review) -> Result<(), AppError> {
+        let mut reviews = lock(&self.review);
+        if reviews.iter().any(|other| other.booking == review.booking) {
+            return Err(review_exists());
+        }
+        reviews.push(review.clone());
+        Ok(())
+    }
+
+    async fn create_incident(&self, incident: &Incident) -> Result<(), AppError> {
+        lock(&self.incident).push(incident.clone());
+        Ok(())
+    }
+
+    async fn get_incident(&self, incident_id: &ObjectId) -> Result<Incident, AppError> {
+        lock(&self.incident)
+            .iter()
+            .find(|incident| incident._id == *incident_id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Incident not found".to_string()))
+    }
+
+    async fn get_incidents(
+        &self,
+        statuses: &[IncidentStatus],
+        severity: Option<IncidentSeverity>,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Incident>>, AppError> {
+        let incidents: Vec<WithId<Incident>> = lock(&self.incident)
+            .iter()
+            .rev()
+            .filter(|incident| statuses.contains(&incident.status))
+            .filter(|incident| severity.is_none_or(|severity| incident.severity == severity))
+            .cloned()
+            .map(WithId)
+            .collect();
+
+        Ok(Page {
+            total: incidents.len() as u64,
+            items: incidents
+                .into_iter()
+                .skip(((page - 1) * limit) as usize)
+                .take(limit as usize)
+                .collect(),
+            page,
+            limit,
+        })
+    }
+
+    async fn triage_incident(
+        &self,
+        incident_id: &ObjectId,
+        next: IncidentStatus,
+        resolution: Option<String>,
+    ) -> Result<Incident, AppError> {
+        let mut incidents = lock(&self.incident);
+        let incident = incidents
+            .iter_mut()
+            .find(|incident| incident._id == *incident_id)
+            .ok_or_else(|| AppError::NotFound("Incident not found".to_string()))?;
+        if !next.allowed_from().contains(&incident.status) {
+            return Err(illegal_incident_transition(incident.status, next));
+        }
+
+        incident.status = next;
+        incident.updated_at = DateTime::now();
+        if resolution.is_some() {
+            incident.resolution = resolution;
+        }
+        Ok(incident.clone())
+    }
+
+    async fn award_points(&self, booking_id: &ObjectId) -> Result<i64, AppError> {
+        let points = PricingRules::default().points_per_booking;
+        let mut owners = lock(&self.owner);
+        let mut bookings = lock(&self.booking);
+        let Some(booking) = bookings.get_mut(booking_id) else {
+            return Ok(0);
+        };
+        if booking.get_str("status") != Ok(BookingStatus::Completed.as_str())
+            || !matches!(booking.get("points_earned"), None | Some(Bson::Null))
+        {
+            return Ok(0);
+        }
+
+        booking.insert("points_earned", points);
+        if let Some(owner) = booking
+            .get_object_id("owner")
+            .ok()
+            .and_then(|owner_id| owners.get_mut(&owner_id))
+        {
+            let balance = owner.get_i64("points_balance").unwrap_or(0);
+            owner.insert("points_balance", balance + points);
+        }
+        Ok(points)
+    }
+
+    async fn issue_invoice(&self, invoice: &Invoice) -> Result<bool, AppError> {
+        let mut invoices = lock(&self.invoice);
+        if invoices
+            .iter()
+            .any(|issued| issued.booking == invoice.booking)
+        {
+            return Ok(false);
+        }
+        invoices.push(invoice.clone());
+        Ok(true)
+    }
+
+    async fn get_invoice(&self, invoice_id: &ObjectId) -> Result<Invoice, AppError> {
+        lock(&self.invoice)
+            .iter()
+            .find(|invoice| invoice._id == *invoice_id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+    }
+
+    async fn record_payout(&self, invoice: &Invoice) -> Result<bool, AppError> {
+        let amount_cents = pricing::walker_earnings(&PricingRules::default(), invoice);
+        let Some(payout) = Payout::new(invoice, amount_cents) else {
+            return Ok(false);
+        };
+        let mut payouts = lock(&self.payout);
+        if payouts
+            .iter()
+            .any(|other| other.booking == payout.booking && other.kind == payout.kind)
+        {
+            return Ok(false);
+        }
+        payouts.push(payout);
+        Ok(true)
+    }
+
+    async fn record_tip(&self, tip: &Payout) -> Result<(), AppError> {
+        let mut payouts = lock(&self.payout);
+        if payouts
+            .iter()
+            .any(|other| other.booking == tip.booking && other.kind == PayoutKind::Tip)
+        {
+            return Err(tip_exists());
+        }
+        payouts.push(tip.clone());
+        Ok(())
+    }
+
+    async fn get_booking_invoice(&self, booking_id: &ObjectId) -> Result<Invoice, AppError> {
+        lock(&self.invoice)
+            .iter()
+            .find(|invoice| invoice.booking == *booking_id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+    }
+
+    async fn get_invoice_by_payment_reference(&self, reference: &str) -> Result<Invoice, AppError> {
+        lock(&self.invoice)
+            .iter()
+            .find(|invoice| invoice.payment_reference.as_deref() == Some(reference))
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+    }
+
+    async fn get_owner_invoices(
+        &self,
+        owner_id: &ObjectId,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Invoice>>, AppError> {
+        let invoices: Vec<WithId<Invoice>> = lock(&self.invoice)
+            .iter()
+            .rev()
+            .filter(|invoice| invoice.owner == *owner_id)
+            .cloned()
+            .map(WithId)
+            .collect();
+
+        Ok(Page {
+            total: invoices.len() as u64,
+            items: invoices
+                .into_iter()
+                .skip(((page - 1) * limit) as usize)
+                .take(limit as usize)
+                .collect(),
+            page,
+            limit,
+        })
+    }
+
+    async fn update_payment(
+        &self,
+        invoice_id: &ObjectId,
+        next: PaymentStatus,
+        reference: Option<String>,
+    ) -> Result<Invoice, AppError> {
+        let mut invoices = lock(&self.invoice);
+        let invoice = invoices
+            .iter_mut()
+            .find(|invoice| invoice._id == *invoice_id)
+            .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))?;
+        if !next.allowed_from().contains(&invoice.payment_status) {
+            return Err(illegal_payment_transition(invoice.payment_status, next));
+        }
+
+        let now = DateTime::now();
+        invoice.payment_status = next;
+        invoice.updated_at = now;
+        if reference.is_some() {
+            invoice.payment_reference = reference;
+        }
+        if next == PaymentStatus::Paid {
+            invoice.paid_at = Some(now);
+        }
+        Ok(invoice.clone())
+    }
+
+    async fn record_refund(
+        &self,
+        invoice_id: &ObjectId,
+        refund: &InvoiceRefund,
+    ) -> Result<Invoice, AppError> {
+        let mut invoices = lock(&self.invoice);
+        let invoice = invoices
+            .iter_mut()
+            .find(|invoice| invoice._id == *invoice_id)
+            .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))?;
+        if invoice.payment_status != PaymentStatus::Paid || invoice.refund.is_some() {
+            return Err(refund_refused(invoice));
+        }
+
+        if refund.amount_cents >= invoice.total_cents {
+            invoice.payment_status = PaymentStatus::Refunded;
+        }
+        invoice.refund = Some(refund.clone());
+        invoice.updated_at = DateTime::now();
+        Ok(invoice.clone())
+    }
+
+    async fn create_coupon(&self, coupon: &Coupon) -> Result<(), AppError> {
+        let mut coupons = lock(&self.coupon);
+        if coupons.contains_key(&coupon.code) {
+            return Err(coupon_exists(&coupon.code));
+        }
+        coupons.insert(coupon.code.clone(), coupon.clone());
+        Ok(())
+    }
+
+    async fn get_usable_coupon(&self, code: &str) -> Result<Coupon, AppError> {
+        usable_coupon(&mut lock(&self.coupon), code).map(|coupon| coupon.clone())
+    }
+
+    async fn create_series(&self, series: &BookingSeries) -> Result<(), AppError> {
+        lock(&self.series).insert(series._id, series.clone());
+        Ok(())
+    }
+
+    async fn delete_series(&self, series_id: &ObjectId) -> Result<(), AppError> {
+        lock(&self.series).remove(series_id);
+        Ok(())
+    }
+
+    async fn get_series(&self, series_id: &ObjectId) -> Result<BookingSeries, AppError> {
+        lock(&self.series)
+            .get(series_id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Series not found".to_string()))
+    }
+
+    async fn get_series_due(&self, horizon: DateTime) -> Result<Vec<BookingSeries>, AppError> {
+        Ok(lock(&self.series)
+            .values()
+            .filter(|series| {
+                series.cancelled_at.is_none()
+                    && series.materialized_until < horizon
+                    && series.materialized_until < series.until
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn claim_series_window(
+        &self,
+        series_id: &ObjectId,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<bool, AppError> {
+        let mut all_series = lock(&self.series);
+        match all_series.get_mut(series_id) {
+            Some(series) if series.cancelled_at.is_none() && series.materialized_until == from => {
+                series.materialized_until = to;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn skip_occurrence(
+        &self,
+        series_id: &ObjectId,
+        start: DateTime,
+    ) -> Result<Option<BookingSeries>, AppError> {
+        let mut all_series = lock(&self.series);
+        let series = all_series
+            .get_mut(series_id)
+            .ok_or_else(|| AppError::NotFound("Series not found".to_string()))?;
+        if series.cancelled_at.is_some() {
+            return Err(series_cancelled());
+        }
+        if series.materialized_until >= start {
+            return Ok(None);
+        }
+        if !series.skipped.contains(&start) {
+            series.skipped.push(start);
+        }
+        Ok(Some(series.clone()))
+    }
+
+    async fn cancel_series(&self, series_id: &ObjectId) -> Result<BookingSeries, AppError> {
+        let mut all_series = lock(&self.series);
+        let series = all_series
+            .get_mut(series_id)
+            .ok_or_else(|| AppError::NotFound("Series not found".to_string()))?;
+        if series.cancelled_at.is_some() {
+            return Err(series_cancelled());
+        }
+        series.cancelled_at = Some(DateTime::now());
+        Ok(series.clone())
+    }
+
+    async fn get_series_bookings(&self, series_id: &ObjectId) -> Result<Vec<Booking>, AppError> {
+        let now = DateTime::now();
+        let mut bookings: Vec<Booking> = deserialize_all(matching(
+            &self.booking,
+            &doc! {"series": series_id, "deleted_at": null},
+        ))?;
+        bookings.retain(|booking| {
+            booking.start_time > now
+                && BookingStatus::Cancelled
+                    .allowed_from()
+                    .contains(&booking.status)
+        });
+        bookings.sort_by_key(|booking| booking.start_time);
+        Ok(bookings)
+    }
+
+    async fn is_slot_full(&self, start: DateTime, end: DateTime) -> Result<bool, AppError> {
+        match self.max_concurrent_bookings {
+            Some(max) => Ok(overlapping(&lock(&self.booking), start, end, |_| true)?.len() >= max),
+            None => Ok(false),
+        }
+    }
+
+    async fn create_waitlist_entry(&self, entry: &WaitlistEntry) -> Result<(), AppError> {
+        let mut waitlist = lock(&self.waitlist);
+        if waitlist
+            .iter()
+            .any(|other| other.owner == entry.owner && waiting_overlapping(other, entry))
+        {
+            return Err(already_waitlisted());
+        }
+        waitlist.push(entry.clone());
+        Ok(())
+    }
+
+    async fn get_owner_waitlist(
+        &self,
+        owner_id: &ObjectId,
+    ) -> Result<Vec<WaitlistEntry>, AppError> {
+        let now = DateTime::now();
+        let mut entries: Vec<WaitlistEntry> = lock(&self.waitlist)
+            .iter()
+            .filter(|entry| entry.owner == *owner_id && entry.start_time > now)
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.start_time);
+        Ok(entries)
+    }
+
+    async fn waitlist_position(&self, entry: &WaitlistEntry) -> Result<u64, AppError> {
+        let ahead = lock(&self.waitlist)
+            .iter()
+            .filter(|other| other._id < entry._id && waiting_overlapping(other, entry))
+            .count();
+        Ok(ahead as u64 + 1)
+    }
+
+    async fn get_waiting_entries(&self) -> Result<Vec<WaitlistEntry>, AppError> {
+        let now = DateTime::now();
+        Ok(lock(&self.waitlist)
+            .iter()
+            .filter(|entry| entry.status == WaitlistStatus::Waiting && entry.start_time > now)
+            .cloned()
+            .collect())
+    }
+
+    async fn claim_waitlist_entry(
+        &self,
+        entry_id: &ObjectId,
+        booking_id: &ObjectId,
+    ) -> Result<bool, AppError> {
+        let mut waitlist = lock(&self.waitlist);
+        match waitlist.iter_mut().find(|entry| entry._id == *entry_id) {
+            Some(entry) if entry.status == WaitlistStatus::Waiting => {
+                entry.status = WaitlistStatus::Promoted;
+                entry.booking = Some(*booking_id);
+                entry.promoted_at = Some(DateTime::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn release_waitlist_entry(&self, entry_id: &ObjectId) -> Result<(), AppError> {
+        let mut waitlist = lock(&self.waitlist);
+        if let Some(entry) = waitlist
+            .iter_mut()
+            .find(|entry| entry._id == *entry_id && entry.status == WaitlistStatus::Promoted)
+        {
+            entry.status = WaitlistStatus::Waiting;
+            entry.booking = None;
+            entry.promoted_at = None;
+        }
+        Ok(())
+    }
+
+    async fn expire_waitlist(&self) -> Result<u64, AppError> {
+        let now = DateTime::now();
+        let mut expired = 0;
+        for entry in lock(&self.waitlist).iter_mut() {
+            if entry.status == WaitlistStatus::Waiting && entry.start_time <= now {
+                entry.status = WaitlistStatus::Expired;
+                expired += 1;
+            }
+        }
+        Ok(expired)
+    }
+
+    async fn claim_next_reminder(
+        &self,
+        from: DateTime,
+        until: DateTime,
+    ) -> Result<Option<Booking>, AppError> {
+        let mut bookings = lock(&self.booking);
+        let mut next: Option<Booking> = None;
+        for document in bookings.values().filter(|document| !is_deleted(document)) {
+            let booking: Booking = from_document(document.clone())?;
+            if booking.reminder_sent_at.is_none()
+                && RESCHEDULABLE.contains(&booking.status)
+                && booking.start_time >= from
+                && booking.start_time <= until
+                && next
+                    .as_ref()
+                    .is_none_or(|next| booking.start_time < next.start_time)
+            {
+                next = Some(booking);
+            }
+        }
+        let Some(mut booking) = next else {
+            return Ok(None);
+        };
+        let now = DateTime::now();
+        if let Some(document) = bookings.get_mut(&booking._id) {
+            document.insert("reminder_sent_at", now);
+        }
+        booking.reminder_sent_at = Some(now);
+        Ok(Some(booking))
+    }
+}
+
+#[async_trait]
+impl AuditRepository for InMemoryDatabase {
+    async fn record_audit(
+        &self,
+        actor: AuditActor,
+        action: AuditAction,
+        entity: EntityRef,
+        before: Option<Document>,
+        after: Option<Document>,
+    ) -> Result<(), AppError> {
+        lock(&self.audit_log).push(AuditEntry {
+            _id: ObjectId::new(),
+            at: DateTime::now(),
+            actor,
+            action,
+            entity,
+            before,
+            after,
+        });
+        Ok(())
+    }
+
+    async fn get_audit_log(
+        &self,
+        filter: &AuditFilter,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<AuditEntry>, AppError> {
+        let entries: Vec<AuditEntry> = lock(&self.audit_log)
+            .iter()
+            .rev()
+            .filter(|entry| filter.entity_id.is_none_or(|id| entry.entity.id == id))
+            .filter(|entry| filter.from.is_none_or(|from| entry.at >= from))
+            .filter(|entry| filter.to.is_none_or(|to| entry.at < to))
+            .cloned()
+            .collect();
+
+        Ok(Page {
+            total: entries.len() as u64,
+            items: entries
+                .into_iter()
+                .skip(((page - 1) * limit) as usize)
+                .take(limit as usize)
+                .collect(),
+            page,
+            limit,
+        })
+    }
+}
+
+#[async_trait]
+impl SearchRepository for InMemoryDatabase {
+    /// Whole words compared case insensitively, with the weights of the MongoDB
+    /// text indexes; there is no stemming, so scores differ from MongoDB's.
+    async fn search(
+        &self,
+        text: &str,
+        limit: u64,
+        include_deleted: bool,
+    ) -> Result<Vec<SearchHit>, AppError> {
+        let terms = words(text);
+        let mut hits = Vec::new();
+        for (score, document) in text_matches(
+            &self.owner,
+            &[("name", 3.0), ("email", 1.0)],
+            &terms,
+            include_deleted,
+        ) {
+            hits.push(SearchHit::Owner {
+                score,
+                owner: WithId(from_document(document)?),
+            });
+        }
+        for (score, document) in text_matches(
+            &self.dog,
+            &[("name", 3.0), ("breed", 1.0)],
+            &terms,
+            include_deleted,
+        ) {
+            hits.push(SearchHit::Dog {
+                score,
+                dog: WithId(from_document(document)?),
+            });
+        }
+        Ok(rank(hits, limit))
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for InMemoryDatabase {
+    async fn claim_idempotency_key(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, AppError> {
+        let mut records = lock(&self.idempotency);
+        if let Some(existing) = records.get(&record._id)
+            && !existing.is_stale(DateTime::now())
+        {
+            return Ok(Some(existing.clone()));
+        }
+        records.insert(record._id.clone(), record);
+        Ok(None)
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        id: &str,
+        response: StoredResponse,
+    ) -> Result<(), AppError> {
+        if let Some(record) = lock(&self.idempotency).get_mut(id) {
+            record.response = Some(response);
+        }
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, id: &str) -> Result<(), AppError> {
+        lock(&self.idempotency).remove(id);
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap()
+}
+
+/// Id of the owner registered with `email`.
+fn email_owner(owners: &HashMap<ObjectId, Document>, email: &str) -> Option<ObjectId> {
+    owners
+        .iter()
+        .find(|(_, owner)| owner.get_str("email") == Ok(email))
+        .map(|(id, _)| *id)
+}
+
+fn insert<T: Serialize>(collection: &Collection, id: ObjectId, value: &T) -> Result<(), AppError> {
+    let document = to_document(value)?;
+    lock(collection).insert(id, document);
+    Ok(())
+}
+
+/// Price of `booking` under the default `PricingRules`. Walkers are not kept
+/// in memory, so every walker is standard.
+fn price(booking: &Booking, dogs: usize) -> Quote {
+    pricing::quote(
+        &PricingRules::default(),
+        booking.start_time,
+        booking.duration_in_minutes,
+        dogs as u64,
+        WalkerTier::Standard,
+        booking.points_redeemed,
+        booking.coupon.as_ref().map(|coupon| coupon.discount),
+    )
+}
+
+/// The coupon of `code` if it can be redeemed now, a `coupon_unavailable` 409 otherwise.
+fn usable_coupon<'a>(
+    coupons: &'a mut HashMap<String, Coupon>,
+    code: &str,
+) -> Result<&'a mut Coupon, AppError> {
+    let code = coupon_key(code);
+    let coupon = coupons
+        .get_mut(&code)
+        .ok_or_else(|| coupon_unavailable(&code, "doesn't exist"))?;
+    match coupon.unavailable_reason(DateTime::now()) {
+        Some(reason) => Err(coupon_unavailable(&code, reason)),
+        None => Ok(coupon),
+    }
+}
+
+fn find<T: DeserializeOwned>(
+    collection: &Collection,
+    id: &ObjectId,
+) -> Result<Option<T>, AppError> {
+    match live(&lock(collection), id) {
+        Some(document) => Ok(Some(from_document(document.clone())?)),
+        None => Ok(None),
+    }
+}
+
+/// Apply a `$set` document and return the updated value, `None` if `id` doesn't exist
+/// or is soft deleted.
+fn update<T: DeserializeOwned>(
+    collection: &Collection,
+    id: &ObjectId,
+    changes: Document,
+) -> Result<Option<T>, AppError> {
+    match live_mut(&mut lock(collection), id) {
+        Some(document) => {
+            apply(document, changes);
+            Ok(Some(from_document(document.clone())?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Change the `vaccinations` array of a live dog under the collection lock.
+fn edit_vaccinations(
+    dogs: &Collection,
+    dog_id: &ObjectId,
+    edit: impl FnOnce(&mut Vec<Vaccination>) -> Result<(), AppError>,
+) -> Result<Dog, AppError> {
+    let mut dogs = lock(dogs);
+    let document = live_mut(&mut dogs, dog_id)
+        .ok_or_else(|| AppError::NotFound("Dog not found".to_string()))?;
+    let mut dog: Dog = from_document(document.clone())?;
+    edit(&mut dog.vaccinations)?;
+    apply(document, doc! {"vaccinations": to_bson(&dog.vaccinations)?});
+    Ok(from_document(document.clone())?)
+}
+
+fn vaccination_not_found() -> AppError {
+    AppError::NotFound("Vaccination not found".to_string())
+}
+
+/// `update` on an already locked booking map, for changes checked under the same lock.
+fn set(
+    bookings: &mut HashMap<ObjectId, Document>,
+    booking_id: &ObjectId,
+    changes: Document,
+) -> Result<Booking, AppError> {
+    let document = live_mut(bookings, booking_id)
+        .ok_or_else(|| AppError::NotFound("Booking not found".to_string()))?;
+    apply(document, changes);
+    Ok(from_document(document.clone())?)
+}
+
+/// `$set` the changes and `$inc` the `version` of documents that have one.
+fn apply(document: &mut Document, changes: Document) {
+    document.extend(changes);
+    bump_version(document);
+}
+
+/// `$unset` the `deleted_at` of a soft deleted document, an update like any other.
+fn undelete(document: &mut Document) {
+    document.remove("deleted_at");
+    bump_version(document);
+}
+
+fn bump_version(document: &mut Document) {
+    if let Ok(version) = document.get_i64("version") {
+        document.insert("version", version + 1);
+    }
+}
+
+fn is_deleted(document: &Document) -> bool {
+    !matches!(document.get("deleted_at"), None | Some(Bson::Null))
+}
+
+/// Document `id` unless it doesn't exist or is soft deleted.
+fn live<'a>(collection: &'a HashMap<ObjectId, Document>, id: &ObjectId) -> Option<&'a Document> {
+    collection.get(id).filter(|document| !is_deleted(document))
+}
+
+fn live_mut<'a>(
+    collection: &'a mut HashMap<ObjectId, Document>,
+    id: &ObjectId,
+) -> Option<&'a mut Document> {
+    collection
+        .get_mut(id)
+        .filter(|document| !is_deleted(document))
+}
+
+/// Documents equal to `filter` on each of its fields, in `_id` (creation) order.
+fn matching(collection: &Collection, filter: &Document) -> Vec<Document> {
+    let collection = lock(collection);
+    let mut ids: Vec<&ObjectId> = collection.keys().collect();
+    ids.sort();
+    ids.into_iter()
+        .map(|id| &collection[id])
+        .filter(|document| matches(document, filter))
+        .cloned()
+        .collect()
+}
+
+/// Plain equality on every field of `filter`, a missing field equals `null` like in MongoDB.
+fn matches(document: &Document, filter: &Document) -> bool {
+    filter
+        .iter()
+        .all(|(key, value)| document.get(key).unwrap_or(&Bson::Null) == value)
+}
+
+/// `$project` of the dotted paths to include, a path through an array
+/// applies to each of its documents like in MongoDB.
+fn project(document: &Document, projection: &Document) -> Document {
+    let mut projected = Document::new();
+    for (path, _) in projection {
+        let segments: Vec<&str> = path.split('.').collect();
+        copy_path(document, &mut projected, &segments);
+    }
+    projected
+}
+
+fn copy_path(from: &Document, to: &mut Document, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    let Some(value) = from.get(*first) else {
+        return;
+    };
+    if rest.is_empty() {
+        to.insert(*first, value.clone());
+        return;
+    }
+    match value {
+        Bson::Document(inner) => {
+            let mut target = match to.remove(*first) {
+                Some(Bson::Document(target)) => target,
+                _ => Document::new(),
+            };
+            copy_path(inner, &mut target, rest);
+            to.insert(*first, target);
+        }
+        Bson::Array(items) => {
+            let mut targets = match to.remove(*first) {
+                Some(Bson::Array(targets)) => targets,
+                _ => Vec::new(),
+            };
+            let mut projected = Vec::new();
+            for (index, item) in items.iter().enumerate() {
+                if let Bson::Document(inner) = item {
+                    let mut target = match targets.get_mut(index).map(std::mem::take) {
+                        Some(Bson::Document(target)) => target,
+                        _ => Document::new(),
+                    };
+                    copy_path(inner, &mut target, rest);
+                    projected.push(Bson::Document(target));
+                }
+            }
+            to.insert(*first, projected);
+        }
+        _ => {}
+    }
+}
+
+/// Lowercase words of `text`, split on anything but letters and digits like a text index.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Documents holding one of `terms` in a string field of `weights`, scored by
+/// the weight of each field matched.
+fn text_matches(
+    collection: &Collection,
+    weights: &[(&str, f64)],
+    terms: &[String],
+    include_deleted: bool,
+) -> Vec<(f64, Document)> {
+    matching(collection, &visible(include_deleted))
+        .into_iter()
+        .filter_map(|document| {
+            let score: f64 = weights
+                .iter()
+                .filter_map(|(field, weight)| {
+                    let found = words(document.get_str(field).ok()?)
+                        .iter()
+                        .filter(|word| terms.contains(word))
+                        .count();
+                    Some(found as f64 * weight)
+                })
+                .sum();
+            (score > 0.0).then_some((score, document))
+        })
+        .collect()
+}
+
+fn deserialize_all<T: DeserializeOwned>(documents: Vec<Document>) -> Result<Vec<T>, AppError> {
+    documents
+        .into_iter()
+        .map(|document| from_document(document).map_err(AppError::from))
+        .collect()
+}
+
+/// Whether `other` is waiting for a slot overlapping the one of `entry`.
+fn waiting_overlapping(other: &WaitlistEntry, entry: &WaitlistEntry) -> bool {
+    other.status == WaitlistStatus::Waiting
+        && other.start_time < entry.end_time()
+        && other.end_time() > entry.start_time
+}
+
+/// Active bookings overlapping `[start, end)` and accepted by `keep`.
+fn overlapping(
+    bookings: &HashMap<ObjectId, Document>,
+    start: DateTime,
+    end: DateTime,
+    keep: impl Fn(&Booking) -> bool,
+) -> Result<Vec<Booking>, AppError> {
+    let mut found = Vec::new();
+    for document in bookings.values().filter(|document| !is_deleted(document)) {
+        let booking: Booking = from_document(document.clone())?;
+        if BookingStatus::active().contains(&booking.status)
+            && booking.start_time < end
+            && booking_end(&booking) > start
+            && keep(&booking)
+        {
+            found.push(booking);
+        }
+    }
+    found.sort_by_key(|booking| booking._id);
+    Ok(found)
+}
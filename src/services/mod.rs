@@ -1 +1,24 @@
+pub mod auth;
+pub mod booking_updates;
+pub mod cache;
+pub mod change_streams;
+pub mod circuit_breaker;
 pub mod db;
+pub mod jobs;
+pub mod mailer;
+pub mod matching;
+pub mod memory;
+pub mod notifications;
+pub mod owner_events;
+pub mod payments;
+pub mod pricing;
+pub mod push;
+pub mod rate_limit;
+pub mod repository;
+pub mod retry;
+pub mod schema;
+pub mod series;
+pub mod sms;
+pub mod tokens;
+pub mod waitlist;
+pub mod webhooks;
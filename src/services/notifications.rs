@@ -0,0 +1,316 @@
+use actix_web::web::Data;
+use mongodb::bson::DateTime;
+use tracing::{error, info};
+
+use crate::{
+    config::BookingEmailEvents,
+    errors::AppError,
+    models::{
+        booking_model::Booking,
+        owner_model::{EventPreferences, Owner},
+    },
+    services::{
+        mailer::Mailer,
+        push::{PushError, PushProviders},
+        repository::{BookingRepository, OwnerRepository},
+        sms::SmsProvider,
+    },
+};
+
+/// Change of a booking the owner is told about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookingEvent {
+    Created,
+    Confirmed,
+    Cancelled,
+    Rescheduled,
+    /// Not confirmed in time. Told like a cancellation, as allowed by the
+    /// `cancelled` toggles.
+    Expired,
+    /// The walk starts in about `sms.reminder_hours`.
+    Reminder,
+    /// The walker left to pick the dogs up.
+    WalkerOnTheWay,
+    Completed,
+    /// A walk the owner waited for on the waitlist was booked once a walker freed up.
+    WaitlistBooked,
+}
+
+impl BookingEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BookingEvent::Created => "created",
+            BookingEvent::Confirmed => "confirmed",
+            BookingEvent::Cancelled => "cancelled",
+            BookingEvent::Rescheduled => "rescheduled",
+            BookingEvent::Expired => "expired",
+            BookingEvent::Reminder => "reminder",
+            BookingEvent::WalkerOnTheWay => "walker_on_the_way",
+            BookingEvent::Completed => "completed",
+            BookingEvent::WaitlistBooked => "waitlist_booked",
+        }
+    }
+
+    /// Email template of the event, its first line is the `Subject:`;
+    /// `None` for the events only pushed.
+    fn template(self) -> Option<&'static str> {
+        match self {
+            BookingEvent::Created => {
+                Some(include_str!("../../templates/email/booking_created.txt"))
+            }
+            BookingEvent::Confirmed => {
+                Some(include_str!("../../templates/email/booking_confirmed.txt"))
+            }
+            BookingEvent::Cancelled => {
+                Some(include_str!("../../templates/email/booking_cancelled.txt"))
+            }
+            BookingEvent::Rescheduled => Some(include_str!(
+                "../../templates/email/booking_rescheduled.txt"
+            )),
+            BookingEvent::Expired => {
+                Some(include_str!("../../templates/email/booking_expired.txt"))
+            }
+            BookingEvent::Reminder => {
+                Some(include_str!("../../templates/email/booking_reminder.txt"))
+            }
+            BookingEvent::WaitlistBooked => {
+                Some(include_str!("../../templates/email/waitlist_booked.txt"))
+            }
+            BookingEvent::WalkerOnTheWay | BookingEvent::Completed => None,
+        }
+    }
+
+    /// Text message template of the event, `None` for the events told by email only.
+    fn sms_template(self) -> Option<&'static str> {
+        match self {
+            BookingEvent::Cancelled => {
+                Some(include_str!("../../templates/sms/booking_cancelled.txt"))
+            }
+            BookingEvent::Reminder => {
+                Some(include_str!("../../templates/sms/booking_reminder.txt"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Push notification template of the event, its first line is the `Title:`;
+    /// `None` for the events not pushed.
+    fn push_template(self) -> Option<&'static str> {
+        match self {
+            BookingEvent::Reminder => {
+                Some(include_str!("../../templates/push/booking_reminder.txt"))
+            }
+            BookingEvent::WalkerOnTheWay => {
+                Some(include_str!("../../templates/push/walker_on_the_way.txt"))
+            }
+            BookingEvent::Completed => {
+                Some(include_str!("../../templates/push/booking_completed.txt"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the owner wants the event on the channel of `preferences`.
+    fn wanted(self, preferences: &EventPreferences) -> bool {
+        match self {
+            BookingEvent::Created => preferences.created,
+            BookingEvent::Confirmed => preferences.confirmed,
+            BookingEvent::Cancelled | BookingEvent::Expired => preferences.cancelled,
+            BookingEvent::Rescheduled => preferences.rescheduled,
+            BookingEvent::Reminder => preferences.reminder,
+            BookingEvent::WalkerOnTheWay => preferences.walker_on_the_way,
+            BookingEvent::Completed => preferences.completed,
+            BookingEvent::WaitlistBooked => preferences.waitlist_booked,
+        }
+    }
+}
+
+/// Tells the owners about their bookings by email, by SMS unless they opted out,
+/// and by push notifications to their registered devices, each as allowed by
+/// their `notification_preferences`.
+pub struct Notifier {
+    owners: Data<dyn OwnerRepository>,
+    mailer: Data<dyn Mailer>,
+    sms: Data<dyn SmsProvider>,
+    push: PushProviders,
+    events: BookingEmailEvents,
+}
+
+impl Notifier {
+    pub fn new(
+        owners: Data<dyn OwnerRepository>,
+        mailer: Data<dyn Mailer>,
+        sms: Data<dyn SmsProvider>,
+        push: PushProviders,
+        events: BookingEmailEvents,
+    ) -> Self {
+        Notifier {
+            owners,
+            mailer,
+            sms,
+            push,
+            events,
+        }
+    }
+
+    fn enabled(&self, event: BookingEvent) -> bool {
+        match event {
+            BookingEvent::Created | BookingEvent::WaitlistBooked => self.events.created,
+            BookingEvent::Confirmed => self.events.confirmed,
+            BookingEvent::Cancelled | BookingEvent::Expired => self.events.cancelled,
+            BookingEvent::Rescheduled => self.events.rescheduled,
+            BookingEvent::Reminder => self.events.reminder,
+            BookingEvent::WalkerOnTheWay | BookingEvent::Completed => false,
+        }
+    }
+
+    /// Email the owner of `booking` about `event`, unless the event is turned off
+    /// in `email.events` or the owner's address isn't verified, and text or push it the events that have an SMS or a
+    /// push notification; a channel the owner turned the event off on is skipped.
+    /// The change is already stored, so a failure is only logged.
+    pub async fn booking_event(&self, event: BookingEvent, booking: &Booking) {
+        let Some(owner) = self.owner(booking).await else {
+            return;
+        };
+        let preferences = &owner.notification_preferences;
+
+        if let Some(template) = event.template()
+            && owner.email_verified
+            && self.enabled(event)
+            && event.wanted(&preferences.email)
+        {
+            let (subject, body) = split_header(render(template, &owner.name, booking), "Subject:");
+            if let Err(err) = self.mailer.send(&owner.email, &subject, &body) {
+                error!(error = %err, booking_id = %booking._id, event = event.as_str(), "Failed to send the booking email");
+            }
+        }
+        if let Some(template) = event.sms_template()
+            && !owner.sms_opt_out
+            && event.wanted(&preferences.sms)
+        {
+            self.text(&owner, booking, template).await;
+        }
+        if let Some(template) = event.push_template()
+            && event.wanted(&preferences.push)
+        {
+            self.push(&owner, booking, template).await;
+        }
+    }
+
+    async fn owner(&self, booking: &Booking) -> Option<Owner> {
+        match self.owners.get_owner_by_id(&booking.owner).await {
+            Ok(owner) => Some(owner),
+            Err(err) => {
+                error!(error = %err, booking_id = %booking._id, "Failed to load the owner to notify");
+                None
+            }
+        }
+    }
+
+    async fn text(&self, owner: &Owner, booking: &Booking, template: &str) {
+        let body = render(template, &owner.name, booking);
+        if let Err(err) = self.sms.send(&owner.phone, body.trim()).await {
+            error!(error = %err, booking_id = %booking._id, "Failed to send the SMS");
+        }
+    }
+
+    /// Push to every device of the owner, forgetting the tokens the push
+    /// services no longer know.
+    async fn push(&self, owner: &Owner, booking: &Booking, template: &str) {
+        let devices = match self.owners.get_owner_devices(&booking.owner).await {
+            Ok(devices) => devices,
+            Err(err) => {
+                error!(error = %err, booking_id = %booking._id, "Failed to load the devices to notify");
+                return;
+            }
+        };
+        let (title, body) = split_header(render(template, &owner.name, booking), "Title:");
+
+        let mut invalid = Vec::new();
+        for device in devices {
+            match self
+                .push
+                .provider(device.platform)
+                .push(&device.token, &title, &body)
+                .await
+            {
+                Ok(()) => {}
+                Err(PushError::InvalidToken) => invalid.push(device.token),
+                Err(PushError::Failed(err)) => {
+                    error!(error = %err, booking_id = %booking._id, device_id = %device._id, "Failed to push the notification")
+                }
+            }
+        }
+        if invalid.is_empty() {
+            return;
+        }
+        match self.owners.delete_devices(&invalid).await {
+            Ok(deleted) => info!(deleted, "Devices with an invalid token forgotten"),
+            Err(err) => error!(error = %err, "Failed to forget the devices with an invalid token"),
+        }
+    }
+}
+
+/// Remind the owners whose walk starts `reminder_hours` from now, give or take
+/// `window_minutes`, returns how many walks were reminded. Each booking is claimed
+/// before it is reminded, so it is reminded once across restarts and replicas.
+pub async fn send_reminders(
+    bookings: &dyn BookingRepository,
+    notifier: &Notifier,
+    reminder_hours: u32,
+    window_minutes: u32,
+) -> Result<u64, AppError> {
+    let due = DateTime::now().timestamp_millis() + i64::from(reminder_hours) * 3_600_000;
+    let window = i64::from(window_minutes) * 60_000;
+    let (from, until) = (
+        DateTime::from_millis(due - window),
+        DateTime::from_millis(due + window),
+    );
+    let mut reminded = 0;
+    while let Some(booking) = bookings.claim_next_reminder(from, until).await? {
+        notifier
+            .booking_event(BookingEvent::Reminder, &booking)
+            .await;
+        info!(booking_id = %booking._id, "Walk reminded");
+        reminded += 1;
+    }
+    Ok(reminded)
+}
+
+/// `template` with its `{{name}}` placeholders filled for `booking`.
+fn render(template: &str, owner: &str, booking: &Booking) -> String {
+    let start_time = booking
+        .start_time
+        .try_to_rfc3339_string()
+        .unwrap_or_else(|_| booking.start_time.to_string());
+    let price = match (booking.price_cents, &booking.currency) {
+        (Some(cents), Some(currency)) => {
+            format!("{}.{:02} {}", cents / 100, cents % 100, currency)
+        }
+        _ => "to be quoted".to_string(),
+    };
+    template
+        .replace("{{owner}}", owner)
+        .replace("{{start_time}}", &start_time)
+        .replace("{{duration}}", &booking.duration_in_minutes.to_string())
+        .replace("{{booking_id}}", &booking._id.to_hex())
+        .replace("{{price}}", &price)
+        .replace(
+            "{{reason}}",
+            booking
+                .cancellation_reason
+                .as_deref()
+                .unwrap_or("not given"),
+        )
+}
+
+/// The `Subject:` (email) or `Title:` (push) first line of a template apart from its body.
+fn split_header(text: String, header: &str) -> (String, String) {
+    match text.split_once('\n') {
+        Some((first, body)) if first.starts_with(header) => (
+            first.trim_start_matches(header).trim().to_string(),
+            body.trim().to_string(),
+        ),
+        _ => (String::new(), text.trim().to_string()),
+    }
+}
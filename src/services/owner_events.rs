@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use mongodb::bson::{DateTime, oid::ObjectId};
+use tokio::sync::broadcast;
+
+use crate::models::{
+    booking_model::BookingStatus, owner_event_model::OwnerEventData, track_model::TrackPing,
+};
+
+/// Events a subscriber can fall behind by before it skips the oldest ones.
+const CAPACITY: usize = 256;
+/// Latest events kept, across owners, for the clients resuming with `Last-Event-ID`.
+const REPLAYABLE: usize = 1024;
+
+/// One event of an owner, numbered in publication order.
+#[derive(Debug)]
+pub struct OwnerEvent {
+    pub id: u64,
+    pub owner: ObjectId,
+    pub data: OwnerEventData,
+}
+
+impl OwnerEvent {
+    /// The event in the `text/event-stream` format.
+    pub fn to_sse(&self) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id,
+            self.data.name(),
+            serde_json::to_string(&self.data).unwrap_or_default()
+        )
+    }
+}
+
+/// Booking status changes, read from the booking change stream with MongoDB, and
+/// walker positions of the owners, streamed by `GET /owner/{id}/events`. The latest
+/// events are kept in memory so a client reconnecting with `Last-Event-ID` gets
+/// the ones it missed; older ones are lost, and the ids differ between replicas.
+pub struct OwnerEvents {
+    sender: broadcast::Sender<Arc<OwnerEvent>>,
+    /// Oldest first. Also held while sending, so the channel gets the events in `id` order.
+    recent: Mutex<VecDeque<Arc<OwnerEvent>>>,
+    /// Id of the first event, the boot time in milliseconds: ids keep growing
+    /// across restarts, so an id from before one replays nothing.
+    first_id: u64,
+}
+
+impl Default for OwnerEvents {
+    fn default() -> Self {
+        OwnerEvents {
+            sender: broadcast::channel(CAPACITY).0,
+            recent: Mutex::new(VecDeque::with_capacity(REPLAYABLE)),
+            first_id: DateTime::now().timestamp_millis() as u64,
+        }
+    }
+}
+
+impl OwnerEvents {
+    pub fn booking_status(&self, owner: ObjectId, booking_id: &ObjectId, status: BookingStatus) {
+        self.publish(
+            owner,
+            OwnerEventData::BookingStatus {
+                booking_id: booking_id.to_hex(),
+                status,
+                at: DateTime::now().try_to_rfc3339_string().unwrap_or_default(),
+            },
+        );
+    }
+
+    pub fn walker_locations(&self, owner: ObjectId, pings: &[TrackPing]) {
+        for ping in pings {
+            self.publish(
+                owner,
+                OwnerEventData::WalkerLocation {
+                    booking_id: ping.booking.to_hex(),
+                    location: ping.location,
+                    at: ping.at.try_to_rfc3339_string().unwrap_or_default(),
+                },
+            );
+        }
+    }
+
+    fn publish(&self, owner: ObjectId, data: OwnerEventData) {
+        let mut recent = self.recent.lock().unwrap();
+        let event = Arc::new(OwnerEvent {
+            id: recent.back().map_or(self.first_id, |last| last.id + 1),
+            owner,
+            data,
+        });
+        if recent.len() == REPLAYABLE {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        // Only fails when nobody is subscribed.
+        let _ = self.sender.send(event);
+    }
+
+    /// The kept events of `owner` after `last_event_id`, then every event
+    /// published from now on, each exactly once.
+    pub fn subscribe(
+        &self,
+        owner: &ObjectId,
+        last_event_id: Option<u64>,
+    ) -> (Vec<Arc<OwnerEvent>>, broadcast::Receiver<Arc<OwnerEvent>>) {
+        let recent = self.recent.lock().unwrap();
+        let missed = match last_event_id {
+            Some(last_event_id) => recent
+                .iter()
+                .filter(|event| event.id > last_event_id && event.owner == *owner)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        (missed, self.sender.subscribe())
+    }
+}
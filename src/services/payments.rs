@@ -0,0 +1,390 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::PaymentsConfig,
+    models::{booking_model::Booking, payment_model::BookingPayment},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a webhook call reports about a payment intent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentEvent {
+    Succeeded { intent_id: String },
+    Failed { intent_id: String },
+}
+
+/// Card payments of the bookings, kept behind a trait so the provider can be
+/// swapped (Stripe, or a mock in development).
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    /// Authorize the price of a confirmed booking, charged later by `capture`.
+    async fn create_payment_intent(
+        &self,
+        booking: &Booking,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<BookingPayment, String>;
+
+    /// Charge `amount_cents` of an authorized payment, at most its authorized amount.
+    async fn capture(&self, payment: &BookingPayment, amount_cents: i64) -> Result<(), String>;
+
+    /// Charge a tip of `amount_cents` right away, on the card of the booking's
+    /// `payment`. Returns the id of the new payment intent.
+    async fn charge_tip(
+        &self,
+        booking: &Booking,
+        payment: &BookingPayment,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<String, String>;
+
+    /// Give back `amount_cents` of a captured payment.
+    async fn refund(&self, payment: &BookingPayment, amount_cents: i64) -> Result<(), String>;
+
+    /// The payment event of a webhook call once its signature is checked,
+    /// `Ok(None)` for the event types the API doesn't act on.
+    fn webhook_event(
+        &self,
+        payload: &[u8],
+        signature: Option<&str>,
+    ) -> Result<Option<PaymentEvent>, String>;
+}
+
+/// Stripe when `payments.stripe_secret_key` and `payments.stripe_webhook_secret` are set
+/// (`Config::validate` wants both or neither), the mock provider otherwise.
+pub fn from_config(config: &PaymentsConfig) -> Arc<dyn PaymentProvider> {
+    match (&config.stripe_secret_key, &config.stripe_webhook_secret) {
+        (Some(secret_key), Some(webhook_secret)) => Arc::new(StripeProvider::new(
+            secret_key.clone(),
+            webhook_secret.clone(),
+        )),
+        _ => {
+            warn!("STRIPE_SECRET_KEY and STRIPE_WEBHOOK_SECRET are not set, payments are mocked");
+            Arc::new(MockPaymentProvider)
+        }
+    }
+}
+
+const STRIPE_API: &str = "https://api.stripe.com/v1";
+
+/// Webhook calls signed longer ago than this are refused, against replays.
+const WEBHOOK_TOLERANCE_SECS: i64 = 300;
+
+/// Payment intents with manual capture: the card is authorized when the booking is
+/// confirmed and charged when the walk completes. Stripe reports the outcome to
+/// `POST /webhooks/stripe`.
+pub struct StripeProvider {
+    client: reqwest::Client,
+    secret_key: String,
+    webhook_secret: Vec<u8>,
+}
+
+/// What the API reads of the objects Stripe answers with, payment intents and refunds.
+#[derive(Deserialize)]
+struct StripeIntent {
+    id: String,
+    client_secret: Option<String>,
+    payment_method: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StripeErrorBody {
+    error: StripeError,
+}
+
+#[derive(Deserialize)]
+struct StripeError {
+    message: Option<String>,
+}
+
+impl StripeProvider {
+    pub fn new(secret_key: String, webhook_secret: String) -> Self {
+        StripeProvider {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build the Stripe HTTP client"),
+            secret_key,
+            webhook_secret: webhook_secret.into_bytes(),
+        }
+    }
+
+    /// Form-encoded `POST` to the Stripe API. The idempotency key makes a retried
+    /// call return the first result instead of charging twice.
+    async fn post(
+        &self,
+        path: &str,
+        idempotency_key: &str,
+        form: &[(&str, String)],
+    ) -> Result<StripeIntent, String> {
+        self.send(
+            self.client
+                .post(format!("{}{}", STRIPE_API, path))
+                .header("Idempotency-Key", idempotency_key)
+                .form(form),
+        )
+        .await
+    }
+
+    async fn get(&self, path: &str) -> Result<StripeIntent, String> {
+        self.send(self.client.get(format!("{}{}", STRIPE_API, path)))
+            .await
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<StripeIntent, String> {
+        let response = request
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = response
+                .json::<StripeErrorBody>()
+                .await
+                .ok()
+                .and_then(|body| body.error.message)
+                .unwrap_or_default();
+            return Err(format!("Stripe answered {}: {}", status, message));
+        }
+        response.json().await.map_err(|err| err.to_string())
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for StripeProvider {
+    async fn create_payment_intent(
+        &self,
+        booking: &Booking,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<BookingPayment, String> {
+        let intent = self
+            .post(
+                "/payment_intents",
+                &format!("booking-{}", booking._id.to_hex()),
+                &[
+                    ("amount", amount_cents.to_string()),
+                    ("currency", currency.to_lowercase()),
+                    ("capture_method", "manual".to_string()),
+                    ("metadata[booking_id]", booking._id.to_hex()),
+                    ("metadata[owner_id]", booking.owner.to_hex()),
+                ],
+            )
+            .await?;
+
+        Ok(BookingPayment {
+            provider: "stripe".to_string(),
+            intent_id: intent.id,
+            amount_cents,
+            currency: currency.to_string(),
+            client_secret: intent.client_secret,
+        })
+    }
+
+    async fn capture(&self, payment: &BookingPayment, amount_cents: i64) -> Result<(), String> {
+        self.post(
+            &format!("/payment_intents/{}/capture", payment.intent_id),
+            &format!("{}-capture", payment.intent_id),
+            &[(
+                "amount_to_capture",
+                amount_cents.min(payment.amount_cents).to_string(),
+            )],
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Off-session payment intent confirmed at once with the payment method of
+    /// the booking's intent. One tip per booking, the idempotency key makes a retry return it.
+    async fn charge_tip(
+        &self,
+        booking: &Booking,
+        payment: &BookingPayment,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<String, String> {
+        let payment_method = self
+            .get(&format!("/payment_intents/{}", payment.intent_id))
+            .await?
+            .payment_method
+            .ok_or("The booking's payment has no payment method")?;
+        let intent = self
+            .post(
+                "/payment_intents",
+                &format!("tip-{}", booking._id.to_hex()),
+                &[
+                    ("amount", amount_cents.to_string()),
+                    ("currency", currency.to_lowercase()),
+                    ("payment_method", payment_method),
+                    ("confirm", "true".to_string()),
+                    ("off_session", "true".to_string()),
+                    ("metadata[booking_id]", booking._id.to_hex()),
+                    ("metadata[kind]", "tip".to_string()),
+                ],
+            )
+            .await?;
+        Ok(intent.id)
+    }
+
+    /// One refund per payment, the idempotency key makes a retry return it.
+    async fn refund(&self, payment: &BookingPayment, amount_cents: i64) -> Result<(), String> {
+        self.post(
+            "/refunds",
+            &format!("{}-refund", payment.intent_id),
+            &[
+                ("payment_intent", payment.intent_id.clone()),
+                ("amount", amount_cents.to_string()),
+            ],
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Check the `Stripe-Signature` header, `t=<timestamp>,v1=<hex HMAC-SHA256>`
+    /// of `<timestamp>.<payload>` under the webhook secret.
+    fn webhook_event(
+        &self,
+        payload: &[u8],
+        signature: Option<&str>,
+    ) -> Result<Option<PaymentEvent>, String> {
+        let signature = signature.ok_or("Missing Stripe-Signature header")?;
+        let mut timestamp = None;
+        let mut candidates = Vec::new();
+        for part in signature.split(',') {
+            match part.trim().split_once('=') {
+                Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+                Some(("v1", value)) => candidates.extend(decode_hex(value)),
+                _ => {}
+            }
+        }
+
+        let timestamp = timestamp.ok_or("Malformed Stripe-Signature header")?;
+        if (chrono::Utc::now().timestamp() - timestamp).abs() > WEBHOOK_TOLERANCE_SECS {
+            return Err("Webhook timestamp is too old".to_string());
+        }
+
+        let verified = candidates.iter().any(|candidate| {
+            let mut mac = HmacSha256::new_from_slice(&self.webhook_secret)
+                .expect("HMAC accepts keys of any length");
+            mac.update(timestamp.to_string().as_bytes());
+            mac.update(b".");
+            mac.update(payload);
+            mac.verify_slice(candidate).is_ok()
+        });
+        if !verified {
+            return Err("Invalid webhook signature".to_string());
+        }
+
+        parse_event(payload)
+    }
+}
+
+/// Development provider: nothing is charged, intents get a `mock_pi_` id and
+/// webhook calls are accepted unsigned, so Stripe events can be replayed by hand.
+pub struct MockPaymentProvider;
+
+#[async_trait]
+impl PaymentProvider for MockPaymentProvider {
+    async fn create_payment_intent(
+        &self,
+        booking: &Booking,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<BookingPayment, String> {
+        let intent_id = format!("mock_pi_{}", Uuid::new_v4().simple());
+        info!(booking_id = %booking._id, intent_id, amount_cents, currency, "Payment authorized by the mock provider");
+        Ok(BookingPayment {
+            provider: "mock".to_string(),
+            intent_id,
+            amount_cents,
+            currency: currency.to_string(),
+            client_secret: None,
+        })
+    }
+
+    async fn capture(&self, payment: &BookingPayment, amount_cents: i64) -> Result<(), String> {
+        let amount_cents = amount_cents.min(payment.amount_cents);
+        info!(
+            intent_id = payment.intent_id,
+            amount_cents, "Payment captured by the mock provider"
+        );
+        Ok(())
+    }
+
+    async fn charge_tip(
+        &self,
+        booking: &Booking,
+        _payment: &BookingPayment,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<String, String> {
+        let intent_id = format!("mock_pi_{}", Uuid::new_v4().simple());
+        info!(booking_id = %booking._id, intent_id, amount_cents, currency, "Tip charged by the mock provider");
+        Ok(intent_id)
+    }
+
+    async fn refund(&self, payment: &BookingPayment, amount_cents: i64) -> Result<(), String> {
+        info!(
+            intent_id = payment.intent_id,
+            amount_cents, "Payment refunded by the mock provider"
+        );
+        Ok(())
+    }
+
+    fn webhook_event(
+        &self,
+        payload: &[u8],
+        _signature: Option<&str>,
+    ) -> Result<Option<PaymentEvent>, String> {
+        parse_event(payload)
+    }
+}
+
+#[derive(Deserialize)]
+struct WebhookEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    data: WebhookData,
+}
+
+#[derive(Deserialize)]
+struct WebhookData {
+    object: WebhookObject,
+}
+
+#[derive(Deserialize)]
+struct WebhookObject {
+    id: String,
+}
+
+/// The payment event of a Stripe event body.
+fn parse_event(payload: &[u8]) -> Result<Option<PaymentEvent>, String> {
+    let event: WebhookEvent =
+        serde_json::from_slice(payload).map_err(|err| format!("Malformed event: {}", err))?;
+    let intent_id = event.data.object.id;
+    Ok(match event.kind.as_str() {
+        "payment_intent.succeeded" => Some(PaymentEvent::Succeeded { intent_id }),
+        "payment_intent.payment_failed" => Some(PaymentEvent::Failed { intent_id }),
+        _ => None,
+    })
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
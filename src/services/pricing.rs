@@ -0,0 +1,201 @@
+use chrono::{Datelike, Timelike, Weekday};
+use mongodb::bson::DateTime;
+
+use crate::models::{
+    booking_model::Booking,
+    coupon_model::Discount,
+    group_walk_model::GroupWalk,
+    invoice_model::{Invoice, InvoiceRefund, RefundPolicy},
+    pricing_model::{PriceLine, PriceLineKind, PricingRules, Quote},
+    walker_model::WalkerTier,
+};
+
+/// Price of a walk of `duration_in_minutes` starting at `start_time` for `dogs` dogs.
+///
+/// The base price is the duration at the hourly rate, each dog after the first adds
+/// `extra_dog_percent` of it. The weekend, evening and walker tier surcharges are
+/// percentages of that subtotal, they add up rather than compound. The free walk
+/// paid with `points_redeemed` comes off the resulting price, then the coupon
+/// `discount` off what is left.
+pub fn quote(
+    rules: &PricingRules,
+    start_time: DateTime,
+    duration_in_minutes: u8,
+    dogs: u64,
+    tier: WalkerTier,
+    points_redeemed: i64,
+    discount: Option<Discount>,
+) -> Quote {
+    let base = (rules.base_cents_per_hour * duration_in_minutes as i64 + 30).div_euclid(60);
+    let extra_dogs = percent_of(base, rules.extra_dog_percent) * dogs.saturating_sub(1) as i64;
+    let subtotal = base + extra_dogs;
+
+    let local = chrono::DateTime::from_timestamp_millis(
+        start_time.timestamp_millis() + rules.utc_offset_minutes as i64 * 60_000,
+    )
+    .unwrap_or_default();
+    let weekend_percent = match local.weekday() {
+        Weekday::Sat | Weekday::Sun => rules.weekend_percent,
+        _ => 0,
+    };
+    let evening_percent =
+        if local.hour() >= rules.evening_from_hour || local.hour() < rules.evening_until_hour {
+            rules.evening_percent
+        } else {
+            0
+        };
+    let tier_percent = match tier {
+        WalkerTier::Standard => 0,
+        WalkerTier::Senior => rules.senior_walker_percent,
+        WalkerTier::Expert => rules.expert_walker_percent,
+    };
+
+    let mut lines: Vec<PriceLine> = [
+        (PriceLineKind::Base, base),
+        (PriceLineKind::ExtraDogs, extra_dogs),
+        (
+            PriceLineKind::Weekend,
+            percent_of(subtotal, weekend_percent),
+        ),
+        (
+            PriceLineKind::Evening,
+            percent_of(subtotal, evening_percent),
+        ),
+        (
+            PriceLineKind::WalkerTier,
+            percent_of(subtotal, tier_percent),
+        ),
+    ]
+    .into_iter()
+    .filter(|(kind, amount_cents)| *kind == PriceLineKind::Base || *amount_cents != 0)
+    .map(|(kind, amount_cents)| PriceLine { kind, amount_cents })
+    .collect();
+
+    let mut price: i64 = lines.iter().map(|line| line.amount_cents).sum();
+    let free_walks = match rules.points_per_free_walk {
+        cost if cost > 0 => points_redeemed / cost,
+        _ => 0,
+    };
+    let points_off = (rules.base_cents_per_hour * rules.free_walk_minutes * free_walks + 30)
+        .div_euclid(60)
+        .clamp(0, price);
+    price -= points_off;
+    let coupon_off = match discount {
+        Some(Discount::Percent { percent }) => percent_of(price, percent),
+        Some(Discount::Fixed { amount_cents }) => amount_cents,
+        None => 0,
+    }
+    .clamp(0, price);
+    price -= coupon_off;
+
+    for (kind, off) in [
+        (PriceLineKind::Points, points_off),
+        (PriceLineKind::Coupon, coupon_off),
+    ] {
+        if off != 0 {
+            lines.push(PriceLine {
+                kind,
+                amount_cents: -off,
+            });
+        }
+    }
+
+    Quote {
+        price_cents: price,
+        currency: rules.currency.clone(),
+        lines,
+    }
+}
+
+/// Shares of the participants of a group walk, in their order. The walk is priced
+/// as one walk of all their dogs with the walker's `tier`, then split by number of
+/// dogs; the cents left by the rounding go to the first owners who joined.
+pub fn group_shares(rules: &PricingRules, walk: &GroupWalk, tier: WalkerTier) -> Vec<i64> {
+    let dogs: usize = walk.participants.iter().map(|p| p.dogs.len()).sum();
+    if dogs == 0 {
+        return Vec::new();
+    }
+    let total = quote(
+        rules,
+        walk.start_time,
+        walk.duration_in_minutes,
+        dogs as u64,
+        tier,
+        0,
+        None,
+    )
+    .price_cents;
+    let mut shares: Vec<i64> = walk
+        .participants
+        .iter()
+        .map(|p| total * p.dogs.len() as i64 / dogs as i64)
+        .collect();
+    let left = total - shares.iter().sum::<i64>();
+    for share in shares.iter_mut().take(left as usize) {
+        *share += 1;
+    }
+    shares
+}
+
+/// `percent` % of `amount`, rounded to the nearest cent.
+fn percent_of(amount: i64, percent: i64) -> i64 {
+    (amount * percent + 50).div_euclid(100)
+}
+
+/// Earnings of the walker of an invoice: their share of the lines it charges,
+/// the coupon and the loyalty points are taken on the business's part.
+pub fn walker_earnings(rules: &PricingRules, invoice: &Invoice) -> i64 {
+    let charged: i64 = invoice
+        .lines
+        .iter()
+        .map(|line| line.amount_cents)
+        .filter(|amount| *amount > 0)
+        .sum();
+    percent_of(charged, rules.walker_share_percent)
+}
+
+/// Fee owed for cancelling at `at` a walk starting at `start_time` and priced
+/// `price_cents`: nothing with enough notice, `late_cancel_fee_percent` of the
+/// price within it. `None` once the walk started, it can no longer be cancelled.
+pub fn cancellation_fee(
+    rules: &PricingRules,
+    start_time: DateTime,
+    price_cents: i64,
+    at: DateTime,
+) -> Option<i64> {
+    let notice_millis = start_time.timestamp_millis() - at.timestamp_millis();
+    if notice_millis <= 0 {
+        None
+    } else if notice_millis >= rules.free_cancel_notice_hours * 60 * 60 * 1000 {
+        Some(0)
+    } else {
+        Some(percent_of(price_cents, rules.late_cancel_fee_percent).clamp(0, price_cents))
+    }
+}
+
+/// Refund of a cancelled `booking` out of the `paid_cents` charged for it: all
+/// of it but the cancellation fee. Bookings cancelled before the fee was recorded
+/// owe the fee of the current policy at their cancellation.
+pub fn refund(rules: &PricingRules, booking: &Booking, paid_cents: i64) -> InvoiceRefund {
+    let cancelled_at = booking.cancelled_at.unwrap_or_else(DateTime::now);
+    let fee_cents = booking.cancellation_fee_cents.unwrap_or_else(|| {
+        cancellation_fee(rules, booking.start_time, paid_cents, cancelled_at).unwrap_or(paid_cents)
+    });
+    let notice_minutes =
+        ((booking.start_time.timestamp_millis() - cancelled_at.timestamp_millis()) / 60_000).max(0);
+    let amount_cents = (paid_cents - fee_cents).clamp(0, paid_cents);
+    let policy = if amount_cents == paid_cents {
+        RefundPolicy::Full
+    } else if amount_cents > 0 {
+        RefundPolicy::Partial
+    } else {
+        RefundPolicy::None
+    };
+
+    InvoiceRefund {
+        policy,
+        notice_minutes,
+        amount_cents,
+        refunded_at: DateTime::now(),
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::{config::PushConfig, models::device_model::DevicePlatform};
+
+/// Scope of the OAuth token FCM accepts.
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+/// APNs refuses provider tokens older than an hour and throttles new ones
+/// issued more often than every 20 minutes.
+const APNS_TOKEN_TTL: Duration = Duration::from_secs(50 * 60);
+
+#[derive(Debug)]
+pub enum PushError {
+    /// The service no longer knows the token (app uninstalled, token rotated):
+    /// the device is forgotten.
+    InvalidToken,
+    Failed(String),
+}
+
+/// Outgoing push notifications of one platform, kept behind a trait so the
+/// service can be swapped (or just the logs in development).
+#[async_trait]
+pub trait PushProvider: Send + Sync {
+    async fn push(&self, token: &str, title: &str, body: &str) -> Result<(), PushError>;
+}
+
+/// Provider of each platform, the platforms without credentials in `push` log instead.
+pub struct PushProviders {
+    fcm: Arc<dyn PushProvider>,
+    apns: Arc<dyn PushProvider>,
+}
+
+impl PushProviders {
+    pub fn from_config(config: &PushConfig) -> Result<Self, String> {
+        let fcm: Arc<dyn PushProvider> = match FcmPush::new(config)? {
+            Some(fcm) => Arc::new(fcm),
+            None => Arc::new(LogPush(DevicePlatform::Fcm)),
+        };
+        let apns: Arc<dyn PushProvider> = match ApnsPush::new(config)? {
+            Some(apns) => Arc::new(apns),
+            None => Arc::new(LogPush(DevicePlatform::Apns)),
+        };
+        Ok(PushProviders { fcm, apns })
+    }
+
+    pub fn provider(&self, platform: DevicePlatform) -> &dyn PushProvider {
+        match platform {
+            DevicePlatform::Fcm => self.fcm.as_ref(),
+            DevicePlatform::Apns => self.apns.as_ref(),
+        }
+    }
+}
+
+/// Development provider: writes the notification to the logs instead of pushing it.
+pub struct LogPush(DevicePlatform);
+
+#[async_trait]
+impl PushProvider for LogPush {
+    async fn push(&self, token: &str, title: &str, body: &str) -> Result<(), PushError> {
+        info!(
+            platform = self.0.as_str(),
+            token, title, body, "Push notification not sent, logged instead"
+        );
+        Ok(())
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+/// A token reused until it expires.
+struct CachedToken {
+    value: String,
+    expires: Instant,
+}
+
+fn cached(cache: &Mutex<Option<CachedToken>>) -> Option<String> {
+    cache
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|token| token.expires > Instant::now())
+        .map(|token| token.value.clone())
+}
+
+/// Firebase Cloud Messaging HTTP v1 API, authenticated as a service account.
+pub struct FcmPush {
+    client: reqwest::Client,
+    send_url: String,
+    client_email: String,
+    token_uri: String,
+    key: EncodingKey,
+    access_token: Mutex<Option<CachedToken>>,
+}
+
+/// The fields of a service account JSON key that are used.
+#[derive(Deserialize)]
+struct ServiceAccount {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct ServiceAccountClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct FcmErrorBody {
+    error: FcmError,
+}
+
+#[derive(Deserialize)]
+struct FcmError {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    details: Vec<FcmErrorDetail>,
+}
+
+#[derive(Deserialize)]
+struct FcmErrorDetail {
+    #[serde(rename = "errorCode")]
+    error_code: Option<String>,
+}
+
+impl FcmPush {
+    /// `None` when FCM is not configured.
+    pub fn new(config: &PushConfig) -> Result<Option<Self>, String> {
+        let (Some(project_id), Some(credentials_file)) =
+            (&config.fcm_project_id, &config.fcm_credentials_file)
+        else {
+            return Ok(None);
+        };
+        let account: ServiceAccount = fs::read_to_string(credentials_file)
+            .map_err(|err| format!("{}: {}", credentials_file, err))
+            .and_then(|json| {
+                serde_json::from_str(&json).map_err(|err| format!("{}: {}", credentials_file, err))
+            })?;
+        let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+            .map_err(|err| format!("{}: invalid private_key: {}", credentials_file, err))?;
+
+        Ok(Some(FcmPush {
+            client: http_client()?,
+            send_url: format!(
+                "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+                project_id
+            ),
+            client_email: account.client_email,
+            token_uri: account.token_uri,
+            key,
+            access_token: Mutex::new(None),
+        }))
+    }
+
+    /// OAuth token of the service account, exchanged for a signed assertion
+    /// and reused until a minute before it expires.
+    async fn access_token(&self) -> Result<String, String> {
+        if let Some(token) = cached(&self.access_token) {
+            return Ok(token);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = ServiceAccountClaims {
+            iss: &self.client_email,
+            scope: FCM_SCOPE,
+            aud: &self.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let assertion = encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .map_err(|err| err.to_string())?;
+        let response = self
+            .client
+            .post(&self.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "The OAuth token endpoint answered {}",
+                response.status()
+            ));
+        }
+        let token: AccessToken = response.json().await.map_err(|err| err.to_string())?;
+
+        *self.access_token.lock().unwrap() = Some(CachedToken {
+            value: token.access_token.clone(),
+            expires: Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(60)),
+        });
+        Ok(token.access_token)
+    }
+}
+
+#[async_trait]
+impl PushProvider for FcmPush {
+    async fn push(&self, token: &str, title: &str, body: &str) -> Result<(), PushError> {
+        let access_token = self.access_token().await.map_err(PushError::Failed)?;
+        let response = self
+            .client
+            .post(&self.send_url)
+            .bearer_auth(access_token)
+            .json(&json!({
+                "message": {
+                    "token": token,
+                    "notification": {"title": title, "body": body},
+                }
+            }))
+            .send()
+            .await
+            .map_err(|err| PushError::Failed(err.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let error = response
+            .json::<FcmErrorBody>()
+            .await
+            .ok()
+            .map(|body| body.error);
+        // The message is always well formed, so an invalid argument is the token.
+        let invalid = error.as_ref().is_some_and(|error| {
+            error.status == "INVALID_ARGUMENT"
+                || error
+                    .details
+                    .iter()
+                    .any(|detail| detail.error_code.as_deref() == Some("UNREGISTERED"))
+        });
+        if invalid || status == reqwest::StatusCode::NOT_FOUND {
+            return Err(PushError::InvalidToken);
+        }
+        Err(PushError::Failed(format!(
+            "FCM answered {}: {}",
+            status,
+            error.map(|error| error.message).unwrap_or_default()
+        )))
+    }
+}
+
+/// Apple Push Notification service, authenticated with a provider token
+/// signed by the `.p8` key of the team.
+pub struct ApnsPush {
+    client: reqwest::Client,
+    base_url: &'static str,
+    topic: String,
+    key_id: String,
+    team_id: String,
+    key: EncodingKey,
+    provider_token: Mutex<Option<CachedToken>>,
+}
+
+#[derive(Serialize)]
+struct ProviderClaims<'a> {
+    iss: &'a str,
+    iat: i64,
+}
+
+#[derive(Deserialize)]
+struct ApnsError {
+    #[serde(default)]
+    reason: String,
+}
+
+impl ApnsPush {
+    /// `None` when APNs is not configured.
+    pub fn new(config: &PushConfig) -> Result<Option<Self>, String> {
+        let (Some(key_file), Some(key_id), Some(team_id), Some(topic)) = (
+            &config.apns_key_file,
+            &config.apns_key_id,
+            &config.apns_team_id,
+            &config.apns_topic,
+        ) else {
+            return Ok(None);
+        };
+        let pem = fs::read(key_file).map_err(|err| format!("{}: {}", key_file, err))?;
+        let key = EncodingKey::from_ec_pem(&pem).map_err(|err| format!("{}: {}", key_file, err))?;
+
+        Ok(Some(ApnsPush {
+            client: http_client()?,
+            base_url: if config.apns_sandbox {
+                "https://api.sandbox.push.apple.com"
+            } else {
+                "https://api.push.apple.com"
+            },
+            topic: topic.clone(),
+            key_id: key_id.clone(),
+            team_id: team_id.clone(),
+            key,
+            provider_token: Mutex::new(None),
+        }))
+    }
+
+    fn provider_token(&self) -> Result<String, String> {
+        if let Some(token) = cached(&self.provider_token) {
+            return Ok(token);
+        }
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(self.key_id.clone());
+        let claims = ProviderClaims {
+            iss: &self.team_id,
+            iat: chrono::Utc::now().timestamp(),
+        };
+        let token = encode(&header, &claims, &self.key).map_err(|err| err.to_string())?;
+        *self.provider_token.lock().unwrap() = Some(CachedToken {
+            value: token.clone(),
+            expires: Instant::now() + APNS_TOKEN_TTL,
+        });
+        Ok(token)
+    }
+}
+
+#[async_trait]
+impl PushProvider for ApnsPush {
+    async fn push(&self, token: &str, title: &str, body: &str) -> Result<(), PushError> {
+        let provider_token = self.provider_token().map_err(PushError::Failed)?;
+        let response = self
+            .client
+            .post(format!("{}/3/device/{}", self.base_url, token))
+            .bearer_auth(provider_token)
+            .header("apns-topic", &self.topic)
+            .header("apns-push-type", "alert")
+            .json(&json!({"aps": {"alert": {"title": title, "body": body}, "sound": "default"}}))
+            .send()
+            .await
+            .map_err(|err| PushError::Failed(err.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let reason = response
+            .json::<ApnsError>()
+            .await
+            .map(|error| error.reason)
+            .unwrap_or_default();
+        match (status, reason.as_str()) {
+            (reqwest::StatusCode::GONE, _)
+            | (_, "BadDeviceToken" | "DeviceTokenNotForTopic" | "Unregistered") => {
+                Err(PushError::InvalidToken)
+            }
+            _ => Err(PushError::Failed(format!(
+                "APNs answered {}: {}",
+                status, reason
+            ))),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use redis::{Script, aio::ConnectionManager};
+use tracing::warn;
+
+use crate::config::{RateLimitBackend, RateLimitConfig};
+
+/// Number of tracked clients above which idle buckets are swept.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Prefix of the bucket keys in Redis.
+const REDIS_KEY_PREFIX: &str = "rate_limit:";
+
+/// Same token bucket as the in-process one, evaluated atomically by Redis.
+/// The clock is Redis' own so replicas with skewed clocks agree.
+/// Returns the wait in microseconds, 0 when a token was taken.
+const REDIS_TOKEN_BUCKET: &str = r#"
+local per_second = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'refilled_at')
+local tokens = tonumber(bucket[1]) or burst
+local refilled_at = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - refilled_at) * per_second)
+
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    wait = math.ceil((1 - tokens) / per_second * 1000000)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'refilled_at', tostring(now))
+redis.call('EXPIRE', KEYS[1], math.ceil(burst / per_second) + 1)
+return wait
+"#;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+enum Store {
+    /// Buckets of this process only, enough for a single instance.
+    Memory(Mutex<HashMap<String, Bucket>>),
+    /// Buckets shared by every replica.
+    Redis {
+        connection: ConnectionManager,
+        script: Script,
+    },
+}
+
+/// Token bucket per client, see `routes::middleware::rate_limit` for how clients are keyed.
+/// Each client may send `burst` requests at once, then `per_second` requests
+/// per second; a refused request learns how long to wait before the next token.
+pub struct RateLimiter {
+    store: Store,
+    per_second: f64,
+    burst: f64,
+}
+
+impl RateLimiter {
+    /// Build the limiter on the configured backend, connecting to Redis when needed.
+    pub async fn new(config: &RateLimitConfig) -> Result<Self, redis::RedisError> {
+        let store = match config.backend {
+            RateLimitBackend::Memory => Store::Memory(Mutex::new(HashMap::new())),
+            RateLimitBackend::Redis => {
+                let client = redis::Client::open(config.redis_url.as_str())?;
+                Store::Redis {
+                    connection: ConnectionManager::new(client).await?,
+                    script: Script::new(REDIS_TOKEN_BUCKET),
+                }
+            }
+        };
+
+        Ok(RateLimiter {
+            store,
+            per_second: config.per_second,
+            burst: f64::from(config.burst),
+        })
+    }
+
+    /// `per_second = 0` turns the limiter off.
+    pub fn is_enabled(&self) -> bool {
+        self.per_second > 0.0
+    }
+
+    /// Round trip to Redis for the readiness probe, `None` with the memory backend.
+    pub async fn ping(&self) -> Option<Result<(), redis::RedisError>> {
+        match &self.store {
+            Store::Memory(_) => None,
+            Store::Redis { connection, .. } => Some(
+                redis::cmd("PING")
+                    .query_async::<String>(&mut connection.clone())
+                    .await
+                    .map(|_| ()),
+            ),
+        }
+    }
+
+    /// Take a token for this client, or return the delay until one is available.
+    /// When Redis is unreachable the request is let through rather than failing the API.
+    pub async fn check(&self, client: &str) -> Result<(), Duration> {
+        match &self.store {
+            Store::Memory(buckets) => self.check_memory(buckets, client),
+            Store::Redis { connection, script } => {
+                let wait: Result<u64, _> = script
+                    .key(format!("{}{}", REDIS_KEY_PREFIX, client))
+                    .arg(self.per_second)
+                    .arg(self.burst)
+                    .invoke_async(&mut connection.clone())
+                    .await;
+                match wait {
+                    Ok(0) => Ok(()),
+                    Ok(micros) => Err(Duration::from_micros(micros)),
+                    Err(err) => {
+                        warn!(error = %err, "Rate limiter unavailable, request let through");
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_memory(
+        &self,
+        buckets: &Mutex<HashMap<String, Bucket>>,
+        client: &str,
+    ) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = buckets.lock().unwrap();
+        if buckets.len() > SWEEP_THRESHOLD {
+            self.sweep(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+
+    /// Forget clients whose bucket is full again, they are indistinguishable from new ones.
+    fn sweep(&self, buckets: &mut HashMap<String, Bucket>, now: Instant) {
+        let refill_time = self.burst / self.per_second;
+        buckets
+            .retain(|_, bucket| now.duration_since(bucket.refilled_at).as_secs_f64() < refill_time);
+    }
+}
@@ -0,0 +1,528 @@
+use async_trait::async_trait;
+use mongodb::bson::{DateTime, Document, oid::ObjectId};
+
+use crate::{
+    errors::AppError,
+    models::{
+        audit_model::{AuditAction, AuditActor, AuditEntry, AuditFilter, EntityRef},
+        booking_model::{
+            ArchiveFilter, Booking, BookingList, BookingQuery, BookingStatus, BookingUpdateRequest,
+            BulkCancelResult, FullBooking, WalkReport,
+        },
+        coupon_model::Coupon,
+        device_model::Device,
+        dog_model::{Dog, DogProfile, DogUpdateRequest},
+        idempotency_model::{IdempotencyRecord, StoredResponse},
+        incident_model::{Incident, IncidentSeverity, IncidentStatus},
+        invoice_model::{Invoice, InvoiceRefund, PaymentStatus},
+        owner_model::{
+            NotificationPreferences, Owner, OwnerDeletion, OwnerPoints, OwnerSort,
+            OwnerUpdateRequest, OwnerWithDogs,
+        },
+        page_model::Page,
+        payment_model::BookingPayment,
+        payout_model::Payout,
+        pricing_model::{PricingRules, Quote},
+        result_model::UpdatedCount,
+        review_model::Review,
+        search_model::SearchHit,
+        serde_helpers::WithId,
+        series_model::BookingSeries,
+        track_model::TrackPing,
+        vaccination_model::Vaccination,
+        waitlist_model::WaitlistEntry,
+    },
+    services::{booking_updates::BookingUpdates, owner_events::OwnerEvents},
+};
+
+/// Storage of owners, registered as `Data<dyn OwnerRepository>`.
+/// `Database` is the MongoDB implementation.
+///
+/// Deletes are soft: they set `deleted_at`, and reads skip such documents
+/// unless `include_deleted` is passed.
+#[async_trait]
+pub trait OwnerRepository: Send + Sync {
+    async fn get_owner_by_id(&self, owner_id: &ObjectId) -> Result<Owner, AppError>;
+
+    async fn owner_exists(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<bool, AppError>;
+
+    /// One owner with its dogs.
+    async fn get_owner_full(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<OwnerWithDogs, AppError>;
+
+    async fn get_owners(
+        &self,
+        page: u64,
+        limit: u64,
+        sort: OwnerSort,
+        include_deleted: bool,
+    ) -> Result<Page<WithId<Owner>>, AppError>;
+
+    /// Insert an owner, returns its id and the email verification token to send.
+    async fn create_owner(&self, owner: Owner) -> Result<(ObjectId, String), AppError>;
+
+    /// Insert an owner together with its dogs, all or nothing, returns like `create_owner`.
+    async fn create_owner_with_dogs(
+        &self,
+        owner: Owner,
+        dogs: Vec<Dog>,
+    ) -> Result<(ObjectId, String), AppError>;
+
+    /// Mark the owner behind a verification token hash as verified, the token is spent.
+    async fn verify_owner_email(&self, token_hash: &str) -> Result<Owner, AppError>;
+
+    /// Apply `update` if the owner is still at `expected_version`, bumping it.
+    async fn update_owner(
+        &self,
+        owner_id: &ObjectId,
+        update: &OwnerUpdateRequest,
+        expected_version: i64,
+    ) -> Result<Owner, AppError>;
+
+    /// Replace the whole `notification_preferences` of an owner, returns the updated owner.
+    async fn set_notification_preferences(
+        &self,
+        owner_id: &ObjectId,
+        preferences: &NotificationPreferences,
+    ) -> Result<Owner, AppError>;
+
+    /// Soft delete an owner with its dogs, and cancel its upcoming bookings.
+    async fn delete_owner_cascade(&self, owner_id: &ObjectId) -> Result<OwnerDeletion, AppError>;
+
+    /// Undo a soft delete, along with the dogs deleted together with the owner.
+    async fn restore_owner(&self, owner_id: &ObjectId) -> Result<Owner, AppError>;
+
+    /// Loyalty points of an owner, with the current rules to earn and spend them.
+    async fn get_owner_points(&self, owner_id: &ObjectId) -> Result<OwnerPoints, AppError>;
+
+    /// Store a push token for its owner, or refresh the entry already holding the token.
+    async fn register_device(&self, device: Device) -> Result<Device, AppError>;
+
+    /// Devices notifications to an owner are pushed to.
+    async fn get_owner_devices(&self, owner_id: &ObjectId) -> Result<Vec<Device>, AppError>;
+
+    /// Forget the tokens a push service reported invalid, returns how many were stored.
+    async fn delete_devices(&self, tokens: &[String]) -> Result<u64, AppError>;
+}
+
+/// Storage of dogs, registered as `Data<dyn DogRepository>`.
+#[async_trait]
+pub trait DogRepository: Send + Sync {
+    async fn create_dog(&self, dog: Dog) -> Result<ObjectId, AppError>;
+
+    async fn get_dog(&self, dog_id: &ObjectId) -> Result<Dog, AppError>;
+
+    async fn update_dog(
+        &self,
+        dog_id: &ObjectId,
+        update: &DogUpdateRequest,
+    ) -> Result<Dog, AppError>;
+
+    async fn delete_dog(&self, dog_id: &ObjectId) -> Result<(), AppError>;
+
+    /// Undo a soft delete, refused while the owner itself is deleted.
+    async fn restore_dog(&self, dog_id: &ObjectId) -> Result<Dog, AppError>;
+
+    async fn get_dogs_by_owner(
+        &self,
+        owner_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<Vec<WithId<Dog>>, AppError>;
+
+    /// Catalog names matching `prefix` (see `matches_prefix`), alphabetically.
+    async fn search_breeds(
+        &self,
+        prefix: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<String>, AppError>;
+
+    /// Catalog spelling of `name`, compared case insensitively, `None` when unknown.
+    async fn find_breed(&self, name: &str) -> Result<Option<String>, AppError>;
+
+    /// Replace the whole `profile` of a dog, returns the updated dog.
+    async fn set_dog_profile(
+        &self,
+        dog_id: &ObjectId,
+        profile: &DogProfile,
+    ) -> Result<Dog, AppError>;
+
+    /// Append a vaccination to a dog, returns the updated dog.
+    async fn add_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination: Vaccination,
+    ) -> Result<Dog, AppError>;
+
+    /// Replace the vaccination of the same `_id`.
+    async fn replace_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination: Vaccination,
+    ) -> Result<Dog, AppError>;
+
+    async fn delete_vaccination(
+        &self,
+        dog_id: &ObjectId,
+        vaccination_id: &ObjectId,
+    ) -> Result<Dog, AppError>;
+}
+
+/// Storage of bookings, registered as `Data<dyn BookingRepository>`.
+/// Overlap, capacity and status rules are enforced by the implementation.
+#[async_trait]
+pub trait BookingRepository: Send + Sync {
+    /// Insert a booking unless it overlaps another one of the owner or the slot is full.
+    /// A `coupon_code` is redeemed in the same step: a use is taken when the booking
+    /// is stored and given back when it isn't, a `coupon_unavailable` 409 refuses it.
+    /// `redeem_points` spends the owner's points on a free walk the same way,
+    /// `not_enough_points` 409 when the balance is short.
+    async fn create_booking(
+        &self,
+        booking: Booking,
+        coupon_code: Option<&str>,
+        redeem_points: bool,
+    ) -> Result<ObjectId, AppError>;
+
+    async fn get_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError>;
+
+    /// Price of `booking` under the pricing rules, for the live dogs of its owner
+    /// and the tier of its walker (standard until one is assigned), less its coupon.
+    async fn quote_booking(&self, booking: &Booking) -> Result<Quote, AppError>;
+
+    /// The pricing rules in force, also holding the cancellation and tipping policies.
+    async fn get_pricing_rules(&self) -> Result<PricingRules, AppError>;
+
+    /// One booking with its owner and dogs.
+    async fn get_full_booking(
+        &self,
+        booking_id: &ObjectId,
+        include_deleted: bool,
+    ) -> Result<WithId<FullBooking>, AppError>;
+
+    /// One page of the bookings matching `query`, with their owner and dogs.
+    async fn get_bookings(
+        &self,
+        query: &BookingQuery,
+        include_deleted: bool,
+    ) -> Result<BookingList, AppError>;
+
+    /// Reschedule a booking if it is still at `expected_version`, bumping it.
+    async fn update_booking(
+        &self,
+        booking_id: &ObjectId,
+        update: &BookingUpdateRequest,
+        expected_version: i64,
+    ) -> Result<Booking, AppError>;
+
+    async fn delete_booking(&self, booking_id: &ObjectId) -> Result<(), AppError>;
+
+    /// Undo a soft delete. The owner must not be deleted, and an active booking
+    /// must still fit next to the ones booked in the meantime.
+    async fn restore_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError>;
+
+    async fn assign_walker(
+        &self,
+        booking_id: &ObjectId,
+        walker_id: &ObjectId,
+    ) -> Result<Booking, AppError>;
+
+    /// Assign the walker with the best `matching::Candidate::score` that can take
+    /// the booking, and record the score. `None` when no walker can.
+    async fn match_walker(&self, booking_id: &ObjectId) -> Result<Option<Booking>, AppError>;
+
+    /// Active bookings still to walk that have no walker, soonest first.
+    async fn get_unassigned_bookings(
+        &self,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Booking>>, AppError>;
+
+    /// Record the payment authorized for a booking.
+    async fn set_booking_payment(
+        &self,
+        booking_id: &ObjectId,
+        payment: &BookingPayment,
+    ) -> Result<Booking, AppError>;
+
+    /// Store the walk report and mark the booking completed.
+    async fn save_walk_report(
+        &self,
+        booking_id: &ObjectId,
+        report: WalkReport,
+        overwrite: bool,
+    ) -> Result<UpdatedCount, AppError>;
+
+    /// Move a booking to `next` if the transition is legal from its current status,
+    /// `extra_filter` and `extra_set` are merged into the filter and the `$set` document.
+    async fn transition_booking(
+        &self,
+        booking_id: &ObjectId,
+        next: BookingStatus,
+        extra_filter: Document,
+        extra_set: Document,
+    ) -> Result<Booking, AppError>;
+
+    async fn cancel_booking(&self, booking_id: &ObjectId) -> Result<Booking, AppError>;
+
+    /// Cancel a booking only if it belongs to `owner_id` (signed cancel link).
+    async fn cancel_booking_for_owner(
+        &self,
+        booking_id: &ObjectId,
+        owner_id: &ObjectId,
+    ) -> Result<Booking, AppError>;
+
+    /// Cancel every pending or confirmed booking starting in `[from, to)`.
+    async fn cancel_bookings_in_range(
+        &self,
+        from: DateTime,
+        to: DateTime,
+        reason: &str,
+    ) -> Result<BulkCancelResult, AppError>;
+
+    /// Move to expired the pending bookings past their `expires_at` at `now`, or
+    /// whose walk started, which frees their slot.
+    async fn expire_pending_bookings(&self, now: DateTime) -> Result<BulkCancelResult, AppError>;
+
+    /// Move the completed, cancelled, no-show and expired bookings whose walk started before
+    /// `before` out of the bookings, into the archive; their walk tracks stay, for
+    /// the disputes. Returns how many were archived.
+    async fn archive_bookings(&self, before: DateTime) -> Result<u64, AppError>;
+
+    /// One page of the archived bookings matching `filter`, latest walk first.
+    async fn get_archived_bookings(
+        &self,
+        filter: &ArchiveFilter,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Booking>>, AppError>;
+
+    /// Creations, assignments, cancellations and completions of bookings, published
+    /// from the booking change stream with MongoDB (see `change_streams`), by the
+    /// implementation once stored otherwise.
+    fn booking_updates(&self) -> &BookingUpdates;
+
+    /// Booking status changes of the owners, published like `booking_updates`, next
+    /// to the walker positions published by `POST /booking/{id}/track`.
+    fn owner_events(&self) -> &OwnerEvents;
+
+    /// Store positions of the walker, the caller checks the walk is in progress.
+    async fn add_track_pings(&self, pings: Vec<TrackPing>) -> Result<(), AppError>;
+
+    /// Every position stored for a booking, oldest first.
+    async fn get_track(&self, booking_id: &ObjectId) -> Result<Vec<TrackPing>, AppError>;
+
+    /// Store the review of a booking, a `review_exists` 409 when it already has one.
+    async fn create_review(&self, review: &Review) -> Result<(), AppError>;
+
+    async fn create_incident(&self, incident: &Incident) -> Result<(), AppError>;
+
+    async fn get_incident(&self, incident_id: &ObjectId) -> Result<Incident, AppError>;
+
+    /// Incidents in one of `statuses`, newest first.
+    async fn get_incidents(
+        &self,
+        statuses: &[IncidentStatus],
+        severity: Option<IncidentSeverity>,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Incident>>, AppError>;
+
+    /// Move an incident to `next` if its current status allows it (see `IncidentStatus::allowed_from`).
+    async fn triage_incident(
+        &self,
+        incident_id: &ObjectId,
+        next: IncidentStatus,
+        resolution: Option<String>,
+    ) -> Result<Incident, AppError>;
+
+    /// Credit the owner of a completed booking with its loyalty points, together
+    /// with `points_earned` on the booking so a booking only pays out once.
+    /// Returns the points credited, 0 when the booking was already credited.
+    async fn award_points(&self, booking_id: &ObjectId) -> Result<i64, AppError>;
+
+    /// Store the invoice of a booking, false without storing it when the booking already has one.
+    async fn issue_invoice(&self, invoice: &Invoice) -> Result<bool, AppError>;
+
+    async fn get_invoice(&self, invoice_id: &ObjectId) -> Result<Invoice, AppError>;
+
+    /// Credit the walker of an invoice in the `payouts` ledger, once per booking.
+    /// `false` when it was already credited or nobody walked.
+    async fn record_payout(&self, invoice: &Invoice) -> Result<bool, AppError>;
+
+    /// Credit a tip in the `payouts` ledger, a `tip_exists` 409 when the walk already has one.
+    async fn record_tip(&self, tip: &Payout) -> Result<(), AppError>;
+
+    async fn get_booking_invoice(&self, booking_id: &ObjectId) -> Result<Invoice, AppError>;
+
+    /// The invoice paid by the payment intent `reference`.
+    async fn get_invoice_by_payment_reference(&self, reference: &str) -> Result<Invoice, AppError>;
+
+    /// Invoices of an owner, newest first.
+    async fn get_owner_invoices(
+        &self,
+        owner_id: &ObjectId,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<WithId<Invoice>>, AppError>;
+
+    /// Move the payment of an invoice to `next` if its current status allows it
+    /// (see `PaymentStatus::allowed_from`), keeping `reference` when given.
+    async fn update_payment(
+        &self,
+        invoice_id: &ObjectId,
+        next: PaymentStatus,
+        reference: Option<String>,
+    ) -> Result<Invoice, AppError>;
+
+    /// Record the refund of a paid invoice, refunded in full it becomes `refunded`.
+    /// A 409 when the invoice isn't paid or already has a refund.
+    async fn record_refund(
+        &self,
+        invoice_id: &ObjectId,
+        refund: &InvoiceRefund,
+    ) -> Result<Invoice, AppError>;
+
+    /// Store a new coupon, `coupon_exists` 409 when its code is taken.
+    async fn create_coupon(&self, coupon: &Coupon) -> Result<(), AppError>;
+
+    /// The coupon of `code` if it can be redeemed now, without redeeming it.
+    async fn get_usable_coupon(&self, code: &str) -> Result<Coupon, AppError>;
+
+    /// Store a new recurring series, its first walk is booked by the caller right after.
+    async fn create_series(&self, series: &BookingSeries) -> Result<(), AppError>;
+
+    /// Remove a series whose first walk could not be booked.
+    async fn delete_series(&self, series_id: &ObjectId) -> Result<(), AppError>;
+
+    async fn get_series(&self, series_id: &ObjectId) -> Result<BookingSeries, AppError>;
+
+    /// Running series whose walks are not booked up to `horizon` yet.
+    async fn get_series_due(&self, horizon: DateTime) -> Result<Vec<BookingSeries>, AppError>;
+
+    /// Move `materialized_until` of a running series from `from` to `to`, false when
+    /// another run moved it first. The caller that moved it books the walks in between.
+    async fn claim_series_window(
+        &self,
+        series_id: &ObjectId,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<bool, AppError>;
+
+    /// Leave out the walk starting at `start` from a running series. `None` when
+    /// the walk was booked in the meantime and must be cancelled as a booking instead.
+    async fn skip_occurrence(
+        &self,
+        series_id: &ObjectId,
+        start: DateTime,
+    ) -> Result<Option<BookingSeries>, AppError>;
+
+    /// Stop a series, a `series_cancelled` 409 when it already is.
+    async fn cancel_series(&self, series_id: &ObjectId) -> Result<BookingSeries, AppError>;
+
+    /// Live upcoming bookings of a series that can still be cancelled, soonest first.
+    async fn get_series_bookings(&self, series_id: &ObjectId) -> Result<Vec<Booking>, AppError>;
+
+    /// Whether `bookings.max_concurrent_bookings` active bookings overlap `[start, end)`,
+    /// never when no limit is set.
+    async fn is_slot_full(&self, start: DateTime, end: DateTime) -> Result<bool, AppError>;
+
+    /// Queue an owner for a full slot, an `already_waitlisted` 409 when the owner
+    /// already waits for an overlapping slot.
+    async fn create_waitlist_entry(&self, entry: &WaitlistEntry) -> Result<(), AppError>;
+
+    /// Entries of an owner for walks still to come, soonest first.
+    async fn get_owner_waitlist(&self, owner_id: &ObjectId)
+    -> Result<Vec<WaitlistEntry>, AppError>;
+
+    /// 1 plus the number of older waiting entries for an overlapping slot.
+    async fn waitlist_position(&self, entry: &WaitlistEntry) -> Result<u64, AppError>;
+
+    /// Waiting entries for walks still to come, oldest first.
+    async fn get_waiting_entries(&self) -> Result<Vec<WaitlistEntry>, AppError>;
+
+    /// Mark a waiting entry promoted to `booking_id`, false when another run did first.
+    /// The caller that promoted it creates the booking.
+    async fn claim_waitlist_entry(
+        &self,
+        entry_id: &ObjectId,
+        booking_id: &ObjectId,
+    ) -> Result<bool, AppError>;
+
+    /// Put a promoted entry back in the queue, its booking couldn't be created.
+    async fn release_waitlist_entry(&self, entry_id: &ObjectId) -> Result<(), AppError>;
+
+    /// Expire the waiting entries whose walk started, returns how many.
+    async fn expire_waitlist(&self) -> Result<u64, AppError>;
+
+    /// The soonest active booking starting in `[from, until]` whose owner wasn't
+    /// reminded yet, with its `reminder_sent_at` set in the same step so no other
+    /// run claims it. `None` once there is none left.
+    async fn claim_next_reminder(
+        &self,
+        from: DateTime,
+        until: DateTime,
+    ) -> Result<Option<Booking>, AppError>;
+}
+
+/// Trail of the changes made through the API, registered as `Data<dyn AuditRepository>`.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Store who did `action` on `entity`, with the document before and after the change.
+    async fn record_audit(
+        &self,
+        actor: AuditActor,
+        action: AuditAction,
+        entity: EntityRef,
+        before: Option<Document>,
+        after: Option<Document>,
+    ) -> Result<(), AppError>;
+
+    /// Entries matching `filter`, newest first.
+    async fn get_audit_log(
+        &self,
+        filter: &AuditFilter,
+        page: u64,
+        limit: u64,
+    ) -> Result<Page<AuditEntry>, AppError>;
+}
+
+/// Text search over owners and dogs, registered as `Data<dyn SearchRepository>`.
+#[async_trait]
+pub trait SearchRepository: Send + Sync {
+    /// The `limit` best matches of `text` among owners (name, email) and dogs
+    /// (name, breed), best first.
+    async fn search(
+        &self,
+        text: &str,
+        limit: u64,
+        include_deleted: bool,
+    ) -> Result<Vec<SearchHit>, AppError>;
+}
+
+/// Requests sent with an `Idempotency-Key`, registered as `Data<dyn IdempotencyRepository>`.
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Store `record` unless its key is already claimed, in which case the existing
+    /// record is returned. Stale records (see `IdempotencyRecord::is_stale`) are replaced.
+    async fn claim_idempotency_key(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, AppError>;
+
+    /// Attach the response to replay to a claimed key.
+    async fn complete_idempotency_key(
+        &self,
+        id: &str,
+        response: StoredResponse,
+    ) -> Result<(), AppError>;
+
+    /// Forget a claimed key, so the failed request can be retried with it.
+    async fn release_idempotency_key(&self, id: &str) -> Result<(), AppError>;
+}
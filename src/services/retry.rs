@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use mongodb::error::{
+    ErrorKind, RETRYABLE_ERROR, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR,
+};
+use rand::Rng;
+use tracing::warn;
+
+use crate::{config::MongoConfig, errors::AppError};
+
+/// Delay before the second try, doubled for each one after.
+const BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Server codes of a node stepping down, shutting down, unreachable or too slow to
+/// answer within `mongo.max_time_ms`, the operation can succeed a moment later.
+const TRANSIENT_CODES: [i32; 12] = [
+    6,     // HostUnreachable
+    7,     // HostNotFound
+    50,    // MaxTimeMSExpired
+    89,    // NetworkTimeout
+    91,    // ShutdownInProgress
+    189,   // PrimarySteppedDown
+    262,   // ExceededTimeLimit
+    9001,  // SocketException
+    10107, // NotWritablePrimary
+    11600, // InterruptedAtShutdown
+    11602, // InterruptedDueToReplStateChange
+    13435, // NotPrimaryNoSecondaryOk
+];
+
+/// How the `Database` operations that can run twice are tried again when MongoDB
+/// is briefly out of reach (a failover, a network blip), on top of the single
+/// retry of the driver: `mongo.retry_attempts` tries with a jittered exponential
+/// backoff capped at `mongo.retry_max_delay_ms`.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    attempts: u32,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy of the reads, `mongo.retry_*`.
+    pub fn new(config: &MongoConfig) -> Self {
+        RetryPolicy {
+            attempts: config.retry_attempts.max(1),
+            max_delay: Duration::from_millis(config.retry_max_delay_ms),
+        }
+    }
+
+    /// Policy of the start, `mongo.connect_*`: how long the server waits for
+    /// MongoDB before giving up, or starting degraded with `mongo.start_degraded`.
+    pub fn startup(config: &MongoConfig) -> Self {
+        RetryPolicy {
+            attempts: config.connect_attempts.max(1),
+            max_delay: Duration::from_secs(config.connect_max_delay_secs),
+        }
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    /// `Retry-After` of the 503 answered once MongoDB stayed out of reach: the
+    /// backoff ceiling, rounded up to seconds.
+    pub fn retry_after_secs(&self) -> u64 {
+        self.max_delay.as_millis().div_ceil(1000).max(1) as u64
+    }
+
+    /// Run `operation` until it succeeds or fails on an error that isn't transient.
+    /// Once the tries are used up the last error becomes `DatabaseUnavailable`,
+    /// answered with 503 and a `Retry-After` of the backoff ceiling.
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, AppError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let err = match operation().await {
+                Err(AppError::Database(err)) if is_transient(&err) => err,
+                result => return result,
+            };
+            if attempt >= self.attempts {
+                return Err(AppError::DatabaseUnavailable {
+                    error: Some(err),
+                    retry_after_secs: self.retry_after_secs(),
+                });
+            }
+            let delay = self.delay(attempt);
+            warn!(error = %err, attempt, attempts = self.attempts, delay_ms = delay.as_millis() as u64, "Database out of reach, trying again");
+            actix_web::rt::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Full jitter: anywhere up to the doubled delay, so the replicas that failed
+    /// together don't all come back at once.
+    fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = BASE_DELAY
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay);
+        Duration::from_millis(rand::rng().random_range(0..=ceiling.as_millis() as u64))
+    }
+}
+
+/// Whether `err` comes from MongoDB being out of reach rather than from the
+/// operation itself, so the same operation can succeed a moment later.
+pub fn is_transient(err: &mongodb::error::Error) -> bool {
+    if err.contains_label(RETRYABLE_WRITE_ERROR)
+        || err.contains_label(TRANSIENT_TRANSACTION_ERROR)
+        || err.contains_label(RETRYABLE_ERROR)
+    {
+        return true;
+    }
+    match err.kind.as_ref() {
+        ErrorKind::Io(_)
+        | ErrorKind::ConnectionPoolCleared { .. }
+        | ErrorKind::ServerSelection { .. } => true,
+        ErrorKind::Command(command_error) => TRANSIENT_CODES.contains(&command_error.code),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(&MongoConfig {
+            retry_attempts: 3,
+            retry_max_delay_ms: 100,
+            ..MongoConfig::default()
+        })
+    }
+
+    fn outage() -> AppError {
+        mongodb::error::Error::from(std::io::Error::other("down")).into()
+    }
+
+    #[actix_web::test]
+    async fn errors_of_the_call_are_not_retried() {
+        let calls = Cell::new(0);
+        let result = policy()
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                Err::<(), _>(AppError::NotFound("Booking".to_string()))
+            })
+            .await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[actix_web::test]
+    async fn transient_errors_are_retried_until_success() {
+        let calls = Cell::new(0);
+        let result = policy()
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err(outage())
+                } else {
+                    Ok(calls.get())
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[actix_web::test]
+    async fn stops_at_the_last_attempt() {
+        let calls = Cell::new(0);
+        let result = policy()
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                Err::<(), _>(outage())
+            })
+            .await;
+        assert_eq!(calls.get(), 3);
+        assert!(matches!(
+            result,
+            Err(AppError::DatabaseUnavailable {
+                error: Some(_),
+                retry_after_secs: 1,
+            })
+        ));
+    }
+
+    #[test]
+    fn backoff_stays_under_the_ceiling() {
+        let policy = policy();
+        for attempt in 1..40 {
+            assert!(policy.delay(attempt) <= policy.max_delay());
+        }
+        let startup = RetryPolicy::startup(&MongoConfig::default());
+        assert!(startup.delay(u32::MAX) <= startup.max_delay());
+    }
+}
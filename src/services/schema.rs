@@ -0,0 +1,169 @@
+use mongodb::bson::{Document, doc};
+
+use crate::models::booking_model::{BookingStatus, status_list};
+
+/// `$jsonSchema` validators of the collections the API deserializes, applied by
+/// `Database::apply_validators` (`--init-schema`). They mirror the Rust models:
+/// fields of `Option` type may be missing or `null`, integers accept `int` and `long`.
+pub fn validators() -> [(&'static str, Document); 3] {
+    [
+        ("owner", owner_schema()),
+        ("dog", dog_schema()),
+        ("booking", booking_schema()),
+    ]
+}
+
+/// `Owner`
+fn owner_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["_id", "name", "email", "phone", "address"],
+        "properties": {
+            "_id": { "bsonType": "objectId" },
+            "name": { "bsonType": "string" },
+            "email": { "bsonType": "string" },
+            "phone": { "bsonType": "string" },
+            "address": { "bsonType": "string" },
+            "location": { "bsonType": ["object", "null"] },
+            "email_verified": { "bsonType": "bool" },
+            "version": { "bsonType": ["int", "long"], "minimum": 0 },
+            "deleted_at": { "bsonType": ["date", "null"] },
+            "points_balance": { "bsonType": ["int", "long"], "minimum": 0 },
+            "sms_opt_out": { "bsonType": "bool" },
+            "notification_preferences": { "bsonType": "object" }
+        }
+    }
+}
+
+/// `Dog`
+fn dog_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["_id", "owner"],
+        "properties": {
+            "_id": { "bsonType": "objectId" },
+            "owner": { "bsonType": "objectId" },
+            "name": { "bsonType": ["string", "null"] },
+            "age": u8_schema(true),
+            "birthdate": { "bsonType": ["date", "null"] },
+            "breed": { "bsonType": ["string", "null"] },
+            "photo_id": { "bsonType": ["objectId", "null"] },
+            "vaccinations": {
+                "bsonType": "array",
+                "items": {
+                    "bsonType": "object",
+                    "required": ["_id", "vaccine", "administered_on"],
+                    "properties": {
+                        "_id": { "bsonType": "objectId" },
+                        "vaccine": { "bsonType": "string" },
+                        "administered_on": { "bsonType": "date" },
+                        "expires_on": { "bsonType": ["date", "null"] },
+                        "document": { "bsonType": ["string", "null"] }
+                    }
+                }
+            },
+            "profile": {
+                "bsonType": "object",
+                "properties": {
+                    "weight_kg": { "bsonType": ["double", "null"] },
+                    "allergies": { "bsonType": "array", "items": { "bsonType": "string" } },
+                    "medications": { "bsonType": "array", "items": { "bsonType": "string" } },
+                    "temperament": { "bsonType": "array", "items": { "bsonType": "string" } }
+                }
+            },
+            "deleted_at": { "bsonType": ["date", "null"] }
+        }
+    }
+}
+
+/// `Booking`, with its embedded `WalkReport`.
+fn booking_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["_id", "owner", "start_time", "duration_in_minutes", "status"],
+        "properties": {
+            "_id": { "bsonType": "objectId" },
+            "owner": { "bsonType": "objectId" },
+            "start_time": { "bsonType": "date" },
+            "duration_in_minutes": u8_schema(false),
+            "status": { "enum": status_list(&[
+                BookingStatus::Pending,
+                BookingStatus::Confirmed,
+                BookingStatus::InProgress,
+                BookingStatus::Completed,
+                BookingStatus::Cancelled,
+                BookingStatus::NoShow,
+                BookingStatus::Expired,
+            ]) },
+            "cancelled_at": { "bsonType": ["date", "null"] },
+            "cancellation_reason": { "bsonType": ["string", "null"] },
+            "cancellation_fee_cents": { "bsonType": ["int", "long", "null"], "minimum": 0 },
+            "walker": { "bsonType": ["objectId", "null"] },
+            "match_score": { "bsonType": ["double", "null"], "minimum": 0, "maximum": 1 },
+            "reminder_sent_at": { "bsonType": ["date", "null"] },
+            "expires_at": { "bsonType": ["date", "null"] },
+            "version": { "bsonType": ["int", "long"], "minimum": 0 },
+            "deleted_at": { "bsonType": ["date", "null"] },
+            "price_cents": { "bsonType": ["int", "long", "null"], "minimum": 0 },
+            "currency": { "bsonType": ["string", "null"] },
+            "payment": {
+                "bsonType": ["object", "null"],
+                "required": ["provider", "intent_id", "amount_cents", "currency"],
+                "properties": {
+                    "provider": { "bsonType": "string" },
+                    "intent_id": { "bsonType": "string" },
+                    "amount_cents": { "bsonType": ["int", "long"], "minimum": 0 },
+                    "currency": { "bsonType": "string" },
+                    "client_secret": { "bsonType": ["string", "null"] }
+                }
+            },
+            "points_redeemed": { "bsonType": ["int", "long"], "minimum": 0 },
+            "points_earned": { "bsonType": ["int", "long", "null"], "minimum": 0 },
+            "series": { "bsonType": ["objectId", "null"] },
+            "coupon": {
+                "bsonType": ["object", "null"],
+                "required": ["code", "discount"],
+                "properties": {
+                    "code": { "bsonType": "string" },
+                    "discount": {
+                        "bsonType": "object",
+                        "required": ["kind"],
+                        "properties": {
+                            "kind": { "enum": ["percent", "fixed"] }
+                        }
+                    }
+                }
+            },
+            "report": {
+                "bsonType": ["object", "null"],
+                "required": ["notes", "distance_meters"],
+                "properties": {
+                    "notes": { "bsonType": "string" },
+                    "distance_meters": {
+                        "bsonType": ["int", "long"],
+                        "minimum": 0,
+                        "maximum": u32::MAX as i64
+                    },
+                    "incidents": { "bsonType": ["string", "null"] },
+                    "pee": { "bsonType": "bool" },
+                    "poo": { "bsonType": "bool" },
+                    "water": { "bsonType": "bool" },
+                    "photos": { "bsonType": "array", "items": { "bsonType": "string" } }
+                }
+            }
+        }
+    }
+}
+
+/// Integer fitting the `u8` of the model.
+fn u8_schema(nullable: bool) -> Document {
+    let mut types = vec!["int", "long"];
+    if nullable {
+        types.push("null");
+    }
+    doc! {
+        "bsonType": types,
+        "minimum": 0,
+        "maximum": u8::MAX as i32
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use actix_web::web::Data;
+use mongodb::bson::{DateTime, oid::ObjectId};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::RecurrenceConfig,
+    errors::AppError,
+    models::{
+        owner_model::OwnerWithDogs, series_model::BookingSeries, vaccination_model::RabiesPolicy,
+    },
+    routes::booking_routes::ensure_vaccinated,
+    services::{
+        matching::match_new_booking,
+        repository::{BookingRepository, OwnerRepository},
+    },
+};
+
+const WEEK_MILLIS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// End of the booking window of the series: walks starting up to it are booked.
+pub fn horizon(config: &RecurrenceConfig) -> DateTime {
+    DateTime::from_millis(
+        DateTime::now().timestamp_millis() + i64::from(config.horizon_weeks) * WEEK_MILLIS,
+    )
+}
+
+/// Book the walks of `series` up to `horizon`, each given a walker by the matching,
+/// returns the ids of the new bookings.
+/// A walk that can't be booked (owner deleted, a dog's vaccination expired, a clash
+/// with another booking) is left out with a warning, the others are still booked.
+pub async fn materialize(
+    owners: &dyn OwnerRepository,
+    bookings: &dyn BookingRepository,
+    series: &BookingSeries,
+    horizon: DateTime,
+    rabies_policy: RabiesPolicy,
+) -> Result<Vec<ObjectId>, AppError> {
+    let to = horizon.min(series.until);
+    if to <= series.materialized_until {
+        return Ok(Vec::new());
+    }
+    let dogs = match owners.get_owner_full(&series.owner, false).await {
+        Ok(OwnerWithDogs { dogs, .. }) => Some(dogs),
+        Err(AppError::NotFound(_)) => None,
+        Err(err) => return Err(err),
+    };
+    if !bookings
+        .claim_series_window(&series._id, series.materialized_until, to)
+        .await?
+    {
+        return Ok(Vec::new());
+    }
+    let Some(dogs) = dogs else {
+        warn!(series_id = %series._id, "The owner of the series is deleted, its walks are not booked");
+        return Ok(Vec::new());
+    };
+
+    let mut booked = Vec::new();
+    for start in series.occurrences(series.materialized_until, to) {
+        let booking = series.occurrence(start);
+        let result = match ensure_vaccinated(rabies_policy, &dogs, start) {
+            Ok(()) => bookings.create_booking(booking, None, false).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(booking_id) => {
+                match_new_booking(bookings, &booking_id).await;
+                booked.push(booking_id)
+            }
+            Err(err) => {
+                warn!(error = %err, series_id = %series._id, start = %start, "Walk of the series not booked")
+            }
+        }
+    }
+    Ok(booked)
+}
+
+/// Every `interval_secs`, book the walks of the running series up to the horizon.
+pub fn spawn_materializer(
+    owners: Data<dyn OwnerRepository>,
+    bookings: Data<dyn BookingRepository>,
+    config: RecurrenceConfig,
+    rabies_policy: RabiesPolicy,
+) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(config.interval_secs));
+        loop {
+            interval.tick().await;
+            let horizon = horizon(&config);
+            let due = match bookings.get_series_due(horizon).await {
+                Ok(due) => due,
+                Err(err) => {
+                    error!(error = %err, "Failed to list the series to book");
+                    continue;
+                }
+            };
+            for series in due {
+                match materialize(
+                    owners.get_ref(),
+                    bookings.get_ref(),
+                    &series,
+                    horizon,
+                    rabies_policy,
+                )
+                .await
+                {
+                    Ok(booked) if !booked.is_empty() => {
+                        info!(series_id = %series._id, booked = booked.len(), "Walks of the series booked")
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        error!(error = %err, series_id = %series._id, "Failed to book the walks of the series")
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,96 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::config::{SmsConfig, SmsProviderKind};
+
+/// Outgoing text messages, kept behind a trait so the provider can be swapped
+/// (Twilio or a compatible API, or just the logs in development).
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    async fn send(&self, to: &str, body: &str) -> Result<(), String>;
+}
+
+/// Provider of `sms.provider`.
+pub fn from_config(config: &SmsConfig) -> Result<Arc<dyn SmsProvider>, String> {
+    Ok(match config.provider {
+        SmsProviderKind::Log => Arc::new(LogSms),
+        SmsProviderKind::Twilio => Arc::new(TwilioSms::new(config)?),
+    })
+}
+
+/// Development provider: writes the message to the logs instead of sending it.
+pub struct LogSms;
+
+#[async_trait]
+impl SmsProvider for LogSms {
+    async fn send(&self, to: &str, body: &str) -> Result<(), String> {
+        info!(to, body, "SMS not sent, logged instead");
+        Ok(())
+    }
+}
+
+/// The Messages API of Twilio, or of a provider speaking the same protocol at `api_url`.
+pub struct TwilioSms {
+    client: reqwest::Client,
+    messages_url: String,
+    account_sid: String,
+    auth_token: String,
+    from: String,
+}
+
+#[derive(Deserialize)]
+struct TwilioError {
+    message: Option<String>,
+}
+
+impl TwilioSms {
+    pub fn new(config: &SmsConfig) -> Result<Self, String> {
+        let (Some(account_sid), Some(auth_token)) = (&config.account_sid, &config.auth_token)
+        else {
+            return Err("sms.account_sid and sms.auth_token are required by twilio".to_string());
+        };
+        Ok(TwilioSms {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .map_err(|err| err.to_string())?,
+            messages_url: format!(
+                "{}/2010-04-01/Accounts/{}/Messages.json",
+                config.api_url.trim_end_matches('/'),
+                account_sid
+            ),
+            account_sid: account_sid.clone(),
+            auth_token: auth_token.clone(),
+            from: config.from.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl SmsProvider for TwilioSms {
+    async fn send(&self, to: &str, body: &str) -> Result<(), String> {
+        let response = self
+            .client
+            .post(&self.messages_url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&[("To", to), ("From", self.from.as_str()), ("Body", body)])
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = response
+                .json::<TwilioError>()
+                .await
+                .ok()
+                .and_then(|body| body.message)
+                .unwrap_or_default();
+            return Err(format!("The SMS provider answered {}: {}", status, message));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use mongodb::bson::oid::ObjectId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::{
+    config::AuthConfig,
+    models::serde_helpers::{deserialize_object_id, serialize_object_id_as_hex},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Payload of a signed "cancel this walk" link.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelClaims {
+    #[serde(
+        serialize_with = "serialize_object_id_as_hex",
+        deserialize_with = "deserialize_object_id"
+    )]
+    pub booking_id: ObjectId,
+    #[serde(
+        serialize_with = "serialize_object_id_as_hex",
+        deserialize_with = "deserialize_object_id"
+    )]
+    pub owner_id: ObjectId,
+    /// Expiry as a unix timestamp in seconds.
+    pub exp: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TokenError {
+    /// Malformed token or signature mismatch.
+    Invalid,
+    /// Correctly signed but past its `exp`.
+    Expired,
+}
+
+/// Signs and verifies owner self-service tokens.
+/// A token is `base64url(claims json).base64url(HMAC-SHA256(claims json))`.
+pub struct TokenSigner {
+    secret: Vec<u8>,
+    ttl: Duration,
+}
+
+impl TokenSigner {
+    pub fn new(secret: &[u8], ttl: Duration) -> Self {
+        TokenSigner {
+            secret: secret.to_vec(),
+            ttl,
+        }
+    }
+
+    /// HMAC secret and link validity of `config`. Without a secret, which `Config::load`
+    /// only allows with `--in-memory`, a random one is generated, so links stop working
+    /// after a restart.
+    pub fn from_config(config: &AuthConfig) -> Self {
+        let secret = match &config.token_secret {
+            Some(secret) => secret.clone().into_bytes(),
+            None => {
+                warn!("TOKEN_SECRET is not set, using a random secret for this process");
+                let mut secret = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut secret);
+                secret
+            }
+        };
+
+        TokenSigner::new(&secret, Duration::from_secs(config.cancel_link_ttl_secs))
+    }
+
+    /// Issue a token for this booking and owner, valid for the configured TTL.
+    pub fn issue(&self, booking_id: ObjectId, owner_id: ObjectId) -> (String, CancelClaims) {
+        let claims = CancelClaims {
+            booking_id,
+            owner_id,
+            exp: chrono::Utc::now().timestamp() + self.ttl.as_secs() as i64,
+        };
+
+        (self.sign(&claims), claims)
+    }
+
+    pub fn sign(&self, claims: &CancelClaims) -> String {
+        let payload = serde_json::to_vec(claims).expect("Failed to serialize token claims");
+        let signature = self.mac(&payload).finalize().into_bytes();
+
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// Check the signature (in constant time) before looking at the claims,
+    /// then reject the token if it is expired.
+    pub fn verify(&self, token: &str) -> Result<CancelClaims, TokenError> {
+        let (payload, signature) = token.split_once('.').ok_or(TokenError::Invalid)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| TokenError::Invalid)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| TokenError::Invalid)?;
+
+        self.mac(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| TokenError::Invalid)?;
+
+        let claims: CancelClaims =
+            serde_json::from_slice(&payload).map_err(|_| TokenError::Invalid)?;
+        if claims.exp <= chrono::Utc::now().timestamp() {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(claims)
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(payload);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(secret: &[u8]) -> TokenSigner {
+        TokenSigner::new(secret, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn issued_token_verifies_to_its_claims() {
+        let signer = signer(b"secret");
+        let (booking_id, owner_id) = (ObjectId::new(), ObjectId::new());
+        let (token, issued) = signer.issue(booking_id, owner_id);
+
+        let claims = signer.verify(&token).unwrap();
+        assert_eq!(claims.booking_id, booking_id);
+        assert_eq!(claims.owner_id, owner_id);
+        assert_eq!(claims.exp, issued.exp);
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let signer = signer(b"secret");
+        let token = signer.sign(&CancelClaims {
+            booking_id: ObjectId::new(),
+            owner_id: ObjectId::new(),
+            exp: chrono::Utc::now().timestamp() - 1,
+        });
+
+        assert_eq!(signer.verify(&token).unwrap_err(), TokenError::Expired);
+    }
+
+    #[test]
+    fn token_signed_with_another_secret_is_rejected() {
+        let (token, _) = signer(b"other").issue(ObjectId::new(), ObjectId::new());
+
+        assert_eq!(
+            signer(b"secret").verify(&token).unwrap_err(),
+            TokenError::Invalid
+        );
+    }
+
+    #[test]
+    fn tampered_or_malformed_token_is_rejected() {
+        let signer = signer(b"secret");
+        let (token, _) = signer.issue(ObjectId::new(), ObjectId::new());
+        let (_, signature) = token.split_once('.').unwrap();
+        let (forged, _) = signer.issue(ObjectId::new(), ObjectId::new());
+        let (payload, _) = forged.split_once('.').unwrap();
+
+        for token in [
+            format!("{}.{}", payload, signature),
+            "not a token".to_string(),
+        ] {
+            assert_eq!(signer.verify(&token).unwrap_err(), TokenError::Invalid);
+        }
+    }
+}
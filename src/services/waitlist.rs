@@ -0,0 +1,101 @@
+use mongodb::bson::oid::ObjectId;
+use tracing::{debug, error, info};
+
+use crate::{
+    errors::AppError,
+    models::{
+        owner_model::OwnerWithDogs, vaccination_model::RabiesPolicy, waitlist_model::WaitlistEntry,
+    },
+    routes::booking_routes::ensure_vaccinated,
+    services::{
+        matching::match_new_booking,
+        notifications::{BookingEvent, Notifier},
+        repository::{BookingRepository, OwnerRepository},
+    },
+};
+
+/// Book the walk of a waiting entry if its slot has room again, then notify the
+/// owner. Returns the id of the booking, `None` when the entry stays in the queue.
+/// A walk that still can't be booked (slot full, a clash with another booking of
+/// the owner, a dog's vaccination) leaves the entry waiting.
+pub async fn promote(
+    owners: &dyn OwnerRepository,
+    bookings: &dyn BookingRepository,
+    notifier: &Notifier,
+    rabies_policy: RabiesPolicy,
+    entry: &WaitlistEntry,
+) -> Result<Option<ObjectId>, AppError> {
+    if bookings
+        .is_slot_full(entry.start_time, entry.end_time())
+        .await?
+    {
+        return Ok(None);
+    }
+    let OwnerWithDogs { dogs, .. } = match owners.get_owner_full(&entry.owner, false).await {
+        Ok(owner) => owner,
+        // Expires with its walk.
+        Err(AppError::NotFound(_)) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if let Err(err) = ensure_vaccinated(rabies_policy, &dogs, entry.start_time) {
+        debug!(error = %err, entry_id = %entry._id, "Waitlisted walk not booked");
+        return Ok(None);
+    }
+
+    let booking = entry.booking();
+    let booking_id = booking._id;
+    if !bookings
+        .claim_waitlist_entry(&entry._id, &booking_id)
+        .await?
+    {
+        return Ok(None);
+    }
+    if let Err(err) = bookings.create_booking(booking, None, false).await {
+        bookings.release_waitlist_entry(&entry._id).await?;
+        return match err {
+            AppError::Conflict { .. } => {
+                debug!(error = %err, entry_id = %entry._id, "Waitlisted walk not booked");
+                Ok(None)
+            }
+            err => Err(err),
+        };
+    }
+    let booked = match match_new_booking(bookings, &booking_id).await {
+        Some(matched) => Some(matched),
+        None => bookings.get_booking(&booking_id).await.ok(),
+    };
+    if let Some(booking) = &booked {
+        notifier
+            .booking_event(BookingEvent::WaitlistBooked, booking)
+            .await;
+    }
+    Ok(Some(booking_id))
+}
+
+/// Expire the entries whose walk started and book the others, oldest first,
+/// wherever a walker freed up. Returns how many walks were booked.
+pub async fn promote_waitlist(
+    owners: &dyn OwnerRepository,
+    bookings: &dyn BookingRepository,
+    notifier: &Notifier,
+    rabies_policy: RabiesPolicy,
+) -> Result<u64, AppError> {
+    let expired = bookings.expire_waitlist().await?;
+    if expired > 0 {
+        info!(expired, "Waitlist entries expired");
+    }
+    let mut booked = 0;
+    for entry in bookings.get_waiting_entries().await? {
+        match promote(owners, bookings, notifier, rabies_policy, &entry).await {
+            Ok(Some(booking_id)) => {
+                info!(entry_id = %entry._id, booking_id = %booking_id, "Waitlisted walk booked");
+                booked += 1;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                error!(error = %err, entry_id = %entry._id, "Failed to promote the waitlist entry")
+            }
+        }
+    }
+    Ok(booked)
+}
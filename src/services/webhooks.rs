@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use actix_web::web::Data;
+use hmac::{Hmac, Mac};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde_json::json;
+use sha2::Sha256;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::WebhookConfig,
+    errors::AppError,
+    models::{
+        booking_model::Booking,
+        serde_helpers::WithId,
+        webhook_model::{DeliveryStatus, Webhook, WebhookDelivery, WebhookEvent},
+    },
+    services::db::Database,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Delay before the first retry, each next one is 4 times longer.
+const FIRST_RETRY_SECS: i64 = 30;
+/// Longest delay between two attempts.
+const MAX_RETRY_SECS: i64 = 6 * 60 * 60;
+/// Deliveries sent per tick at most, the others wait for the next tick.
+const BATCH: usize = 100;
+
+/// Queue `event` of `booking` for every webhook subscribed to it, once per
+/// `change_id` of the booking change stream. The change is already stored, so a
+/// failure is only logged.
+pub async fn queue(db: &Database, event: WebhookEvent, booking: &Booking, change_id: &str) {
+    let queued = async {
+        let deliveries = db
+            .get_webhooks_for(event)
+            .await?
+            .iter()
+            .map(|webhook| delivery(webhook, event, booking, change_id))
+            .collect::<Result<Vec<_>, _>>()?;
+        db.queue_webhook_deliveries(&deliveries).await
+    }
+    .await;
+    if let Err(err) = queued {
+        error!(error = %err, booking_id = %booking._id, event = event.as_str(), "Failed to queue the webhooks");
+    }
+}
+
+/// The pending delivery of `event` to `webhook`, its payload carries the booking
+/// as `GET /booking/{id}` returns it.
+fn delivery(
+    webhook: &Webhook,
+    event: WebhookEvent,
+    booking: &Booking,
+    change_id: &str,
+) -> Result<WebhookDelivery, AppError> {
+    let _id = ObjectId::new();
+    let now = DateTime::now();
+    let payload = json!({
+        "id": _id.to_hex(),
+        "event": event,
+        "created_at": now.try_to_rfc3339_string().unwrap_or_default(),
+        "data": WithId(booking),
+    });
+    Ok(WebhookDelivery {
+        _id,
+        webhook: webhook._id,
+        event,
+        booking: booking._id,
+        change_id: Some(change_id.to_string()),
+        payload: serde_json::to_string(&payload)
+            .map_err(|err| AppError::Internal(err.to_string()))?,
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        next_attempt_at: Some(now),
+        last_status_code: None,
+        last_error: None,
+        created_at: now,
+        delivered_at: None,
+    })
+}
+
+/// Every `interval_secs`, send the deliveries that are due. A failed attempt
+/// (no answer within `timeout_secs`, or not a 2xx) is retried later, until
+/// `max_attempts` is reached and the delivery is marked failed.
+pub fn spawn_deliveries(db: Data<Database>, config: WebhookConfig) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()
+        .expect("Failed to build the webhook HTTP client");
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(config.interval_secs));
+        loop {
+            interval.tick().await;
+            for _ in 0..BATCH {
+                // Long enough for the attempt to record its outcome first.
+                let lease_until = DateTime::from_millis(
+                    DateTime::now().timestamp_millis() + 2 * config.timeout_secs as i64 * 1000,
+                );
+                match db.claim_webhook_delivery(lease_until).await {
+                    Ok(Some(delivery)) => {
+                        attempt(&db, &client, delivery, config.max_attempts).await
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        error!(error = %err, "Failed to claim a webhook delivery");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Send a claimed delivery and record the outcome.
+async fn attempt(
+    db: &Database,
+    client: &reqwest::Client,
+    mut delivery: WebhookDelivery,
+    max_attempts: u32,
+) {
+    let outcome = match db.get_webhook(&delivery.webhook).await {
+        Ok(webhook) => send(client, &webhook, &delivery).await,
+        Err(err) => Err((None, err.to_string())),
+    };
+
+    delivery.attempts += 1;
+    match outcome {
+        Ok(status_code) => {
+            delivery.status = DeliveryStatus::Delivered;
+            delivery.delivered_at = Some(DateTime::now());
+            delivery.next_attempt_at = None;
+            delivery.last_status_code = Some(status_code);
+            delivery.last_error = None;
+            info!(delivery_id = %delivery._id, event = delivery.event.as_str(), "Webhook delivered");
+        }
+        Err((status_code, err)) => {
+            delivery.last_status_code = status_code;
+            delivery.last_error = Some(err);
+            if delivery.attempts >= max_attempts {
+                delivery.status = DeliveryStatus::Failed;
+                delivery.next_attempt_at = None;
+                warn!(delivery_id = %delivery._id, webhook_id = %delivery.webhook, attempts = delivery.attempts, "Webhook delivery abandoned");
+            } else {
+                delivery.next_attempt_at = Some(DateTime::from_millis(
+                    DateTime::now().timestamp_millis() + retry_delay_secs(delivery.attempts) * 1000,
+                ));
+            }
+        }
+    }
+    if let Err(err) = db.record_webhook_attempt(&delivery).await {
+        error!(error = %err, delivery_id = %delivery._id, "Failed to record the webhook attempt");
+    }
+}
+
+/// POST the payload, returns the status code of a 2xx answer, else the status
+/// code if any with the error.
+async fn send(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+) -> Result<u16, (Option<u16>, String)> {
+    let timestamp = chrono::Utc::now().timestamp();
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", delivery._id.to_hex())
+        .header("X-Webhook-Event", delivery.event.as_str())
+        .header(
+            "X-Webhook-Signature",
+            signature(&webhook.secret, timestamp, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((
+            Some(status.as_u16()),
+            format!("The webhook answered {}", status),
+        ))
+    }
+}
+
+/// `t=<timestamp>,v1=<hex HMAC-SHA256>` of `<timestamp>.<payload>` under the
+/// secret of the webhook, the scheme of the Stripe webhooks this API receives.
+pub fn signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("t={},v1={}", timestamp, digest)
+}
+
+/// Delay after the `attempts`-th failed attempt: 30 s, 2 min, 8 min... up to 6 h.
+fn retry_delay_secs(attempts: u32) -> i64 {
+    4_i64
+        .saturating_pow(attempts.saturating_sub(1))
+        .saturating_mul(FIRST_RETRY_SECS)
+        .min(MAX_RETRY_SECS)
+}
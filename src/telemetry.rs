@@ -0,0 +1,90 @@
+use actix_web::{dev::ServiceRequest, http::header::HeaderMap};
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    trace::{TraceContextExt, TracerProvider},
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use tracing::{Span, level_filters::LevelFilter};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    Layer, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+use crate::config::{Config, LogFormat};
+
+/// Install the global tracing subscriber: log lines in the configured format and,
+/// when `telemetry.otlp_endpoint` is set, every span exported over OTLP/HTTP.
+/// The returned provider must be shut down before exit to flush the last spans.
+pub fn init(config: &Config) -> Result<Option<SdkTracerProvider>, String> {
+    let logs = match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(config.log_filter())
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(config.log_filter())
+            .boxed(),
+    };
+
+    let provider = match &config.telemetry.otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()
+                .map_err(|err| format!("{}: {}", endpoint, err))?;
+            let resource = Resource::builder()
+                .with_service_name(config.telemetry.service_name.clone())
+                .build();
+            Some(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(resource)
+                    .build(),
+            )
+        }
+        None => None,
+    };
+    let traces = provider.as_ref().map(|provider| {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        // Traces keep the `debug` spans of `Database` whatever the log level.
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+            .with_filter(LevelFilter::DEBUG)
+            .boxed()
+    });
+
+    tracing_subscriber::registry()
+        .with(logs)
+        .with(traces)
+        .init();
+
+    Ok(provider)
+}
+
+/// Continue the trace of the caller (our gateway) when the request carries a
+/// `traceparent` header, so this API shows up as a child of the gateway span.
+pub fn set_remote_parent(request: &ServiceRequest, span: &Span) {
+    let context = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    if context.span().span_context().is_remote() {
+        let _ = span.set_parent(context);
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use rustls::{ServerConfig, crypto::ring};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+
+use crate::config::ServerConfig as Settings;
+
+/// rustls configuration for `bind_rustls_0_23`, `None` when no certificate is configured.
+/// Reading the PEM files happens once at startup, a bad file stops the server.
+pub fn load(settings: &Settings) -> Result<Option<ServerConfig>, String> {
+    let (Some(cert_file), Some(key_file)) = (&settings.tls_cert_file, &settings.tls_key_file)
+    else {
+        return Ok(None);
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| format!("{}: {}", cert_file, err))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificate found", cert_file));
+    }
+    let key =
+        PrivateKeyDer::from_pem_file(key_file).map_err(|err| format!("{}: {}", key_file, err))?;
+
+    ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map(Some)
+        .map_err(|err| format!("{}: {}", key_file, err))
+}
@@ -0,0 +1,25 @@
+//! Admin endpoints take an admin bearer token, the admin key only creates accounts.
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use api_server_mongodb_actix_web::{config::BookingsConfig, routes::extractors::ADMIN_KEY_HEADER};
+
+use common::{TestApp, bearer, send, verified_owner};
+
+#[actix_web::test]
+async fn admin_routes_need_an_admin_token() {
+    let test_app = TestApp::in_memory(BookingsConfig::default());
+    let app = test::init_service(test_app.app()).await;
+    let (_, owner_token) = verified_owner(&test_app, &app, "jane@example.com").await;
+    let audit = || test::TestRequest::get().uri("/api/v1/admin/audit");
+
+    let (status, body) = send(&app, audit()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    let (status, body) = send(&app, audit().insert_header((ADMIN_KEY_HEADER, "any key"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    let (status, body) = send(&app, audit().insert_header(bearer(&owner_token))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+
+    let (status, body) = send(&app, audit().insert_header(bearer(&test_app.admin_token()))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
@@ -0,0 +1,81 @@
+//! `GET /admin/export` then `POST /admin/import` into an emptied database. MongoDB
+//! only: both stream the collections through `Database`, `--in-memory` has neither.
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use api_server_mongodb_actix_web::config::BookingsConfig;
+use mongodb::bson::{Document, doc};
+use serde_json::json;
+
+use common::{TestApp, bearer, book, create_dog, send, start_time, verified_owner};
+
+#[actix_web::test]
+#[ignore = "starts a MongoDB container, needs Docker"]
+async fn export_wipe_and_import_restores_the_bookings() {
+    let test_app = TestApp::mongo(BookingsConfig::default()).await;
+    let app = test::init_service(test_app.app()).await;
+    for (email, hour) in [("ann@example.com", 9), ("bob@example.com", 14)] {
+        let (owner_id, token) = verified_owner(&test_app, &app, email).await;
+        create_dog(&app, &token, owner_id, "Rex").await;
+        create_dog(&app, &token, owner_id, "Ziggy").await;
+        let (status, body) = book(&app, &token, owner_id, &start_time(2, hour), 45).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+    let admin = test_app.admin_token();
+    let list_bookings = || {
+        test::TestRequest::get()
+            .uri("/api/v1/bookings")
+            .insert_header(bearer(&admin))
+    };
+    let (status, before) = send(&app, list_bookings()).await;
+    assert_eq!(status, StatusCode::OK, "{}", before);
+    assert_eq!(
+        before["bookings"].as_array().unwrap().len(),
+        2,
+        "{}",
+        before
+    );
+
+    let (status, backup) = send(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/v1/admin/export")
+            .insert_header(bearer(&admin)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", backup);
+
+    let database = &test_app.mongo.as_ref().unwrap().database;
+    for collection in ["owner", "dog", "booking"] {
+        database
+            .collection::<Document>(collection)
+            .delete_many(doc! {})
+            .await
+            .unwrap();
+    }
+    let (status, body) = send(&app, list_bookings()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["bookings"].as_array().unwrap().len(), 0, "{}", body);
+
+    let (status, report) = send(
+        &app,
+        test::TestRequest::post()
+            .uri("/api/v1/admin/import")
+            .insert_header(bearer(&admin))
+            .set_json(&backup),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!(
+        report,
+        json!({
+            "owners": {"inserted": 2, "skipped": 0},
+            "dogs": {"inserted": 4, "skipped": 0},
+            "bookings": {"inserted": 2, "skipped": 0},
+        })
+    );
+
+    let (status, after) = send(&app, list_bookings()).await;
+    assert_eq!(status, StatusCode::OK, "{}", after);
+    assert_eq!(after, before);
+}
@@ -0,0 +1,61 @@
+//! `bookings.max_concurrent_bookings`: walks overlapping a time slot, whoever booked them.
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use api_server_mongodb_actix_web::config::BookingsConfig;
+use chrono::DateTime;
+
+use common::{TestApp, book, create_dog, start_time, verified_owner};
+
+async fn full_time_slot_is_refused(test_app: TestApp) {
+    let app = test::init_service(test_app.app()).await;
+    let mut owners = Vec::new();
+    for email in ["ann@example.com", "bob@example.com", "cal@example.com"] {
+        let (owner_id, token) = verified_owner(&test_app, &app, email).await;
+        create_dog(&app, &token, owner_id, "Rex").await;
+        owners.push((owner_id, token));
+    }
+    let at = |hour: u32, minutes: i64| {
+        let start = DateTime::parse_from_rfc3339(&start_time(2, hour)).unwrap();
+        (start + chrono::Duration::minutes(minutes)).to_rfc3339()
+    };
+
+    // Two walks fill the 09:00 - 10:00 slot, the second one ends first.
+    let (status, body) = book(&app, &owners[0].1, owners[0].0, &at(9, 0), 60).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = book(&app, &owners[1].1, owners[1].0, &at(9, 30), 15).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = book(&app, &owners[2].1, owners[2].0, &at(9, 15), 30).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(body["code"], "capacity_reached", "{}", body);
+    let next_available_at =
+        DateTime::parse_from_rfc3339(body["details"]["next_available_at"].as_str().unwrap())
+            .unwrap();
+    assert_eq!(
+        next_available_at,
+        DateTime::parse_from_rfc3339(&at(9, 45)).unwrap()
+    );
+
+    // Outside of the full slot there is room again.
+    let (status, body) = book(&app, &owners[2].1, owners[2].0, &at(11, 0), 30).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+fn two_walkers() -> BookingsConfig {
+    BookingsConfig {
+        max_concurrent_bookings: Some(2),
+        ..BookingsConfig::default()
+    }
+}
+
+#[actix_web::test]
+#[ignore = "starts a MongoDB container, needs Docker"]
+async fn full_time_slot_is_refused_on_mongo() {
+    full_time_slot_is_refused(TestApp::mongo(two_walkers()).await).await;
+}
+
+#[actix_web::test]
+async fn full_time_slot_is_refused_in_memory() {
+    full_time_slot_is_refused(TestApp::in_memory(two_walkers())).await;
+}
@@ -0,0 +1,82 @@
+//! Owner → dog → booking → listing → cancellation, through the HTTP routes.
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use api_server_mongodb_actix_web::config::BookingsConfig;
+
+use common::{TestApp, bearer, book, create_dog, send, start_time, verified_owner};
+
+async fn book_list_and_cancel(test_app: TestApp) {
+    let app = test::init_service(test_app.app()).await;
+    let (owner_id, token) = verified_owner(&test_app, &app, "jane@example.com").await;
+    let dog_id = create_dog(&app, &token, owner_id, "Rex").await;
+
+    let (status, body) = book(&app, &token, owner_id, &start_time(2, 9), 30).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let booking_id = common::inserted_id(&body);
+
+    // The listing joins the owner and its dogs.
+    let (status, body) = send(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/v1/bookings")
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["skipped"], 0);
+    let bookings = body["bookings"].as_array().unwrap();
+    assert_eq!(bookings.len(), 1, "{}", body);
+    let booking = &bookings[0];
+    assert_eq!(booking["id"], booking_id.to_hex());
+    assert_eq!(booking["status"], "pending");
+    assert_eq!(booking["duration_in_minutes"], 30);
+    assert_eq!(booking["owner"]["id"], owner_id.to_hex());
+    assert_eq!(booking["owner"]["name"], "Jane Doe");
+    let dogs = booking["dogs"].as_array().unwrap();
+    assert_eq!(dogs.len(), 1);
+    assert_eq!(dogs[0]["id"], dog_id.to_hex());
+    assert_eq!(dogs[0]["name"], "Rex");
+
+    let (status, body) = send(
+        &app,
+        test::TestRequest::put()
+            .uri(&format!("/api/v1/booking/{}/cancel", booking_id))
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Cancelled bookings leave the default listing, and show up when asked for.
+    let (status, body) = send(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/v1/bookings")
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["bookings"].as_array().unwrap().len(), 0, "{}", body);
+    let (status, body) = send(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/v1/bookings?status=cancelled")
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let bookings = body["bookings"].as_array().unwrap();
+    assert_eq!(bookings.len(), 1, "{}", body);
+    assert_eq!(bookings[0]["status"], "cancelled");
+}
+
+#[actix_web::test]
+#[ignore = "starts a MongoDB container, needs Docker"]
+async fn book_list_and_cancel_on_mongo() {
+    book_list_and_cancel(TestApp::mongo(BookingsConfig::default()).await).await;
+}
+
+#[actix_web::test]
+async fn book_list_and_cancel_in_memory() {
+    book_list_and_cancel(TestApp::in_memory(BookingsConfig::default())).await;
+}
@@ -0,0 +1,53 @@
+//! `GET /bookings` over documents the models can't read.
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use api_server_mongodb_actix_web::config::BookingsConfig;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use common::{TestApp, bearer, book, create_dog, send, start_time, verified_owner};
+
+async fn malformed_booking_is_skipped_and_counted(test_app: TestApp) {
+    let app = test::init_service(test_app.app()).await;
+    let (owner_id, token) = verified_owner(&test_app, &app, "jane@example.com").await;
+    create_dog(&app, &token, owner_id, "Rex").await;
+    let (status, body) = book(&app, &token, owner_id, &start_time(2, 9), 30).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let booking_id = common::inserted_id(&body);
+
+    // An old record without `duration_in_minutes`, written around the models.
+    let start = DateTime::parse_rfc3339_str(start_time(3, 9)).unwrap();
+    test_app
+        .insert_raw_booking(doc! {
+            "_id": ObjectId::new(),
+            "owner": owner_id,
+            "start_time": start,
+            "status": "pending",
+            "deleted_at": null,
+        })
+        .await;
+
+    let (status, body) = send(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/v1/bookings")
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["skipped"], 1, "{}", body);
+    let bookings = body["bookings"].as_array().unwrap();
+    assert_eq!(bookings.len(), 1, "{}", body);
+    assert_eq!(bookings[0]["id"], booking_id.to_hex());
+}
+
+#[actix_web::test]
+#[ignore = "starts a MongoDB container, needs Docker"]
+async fn malformed_booking_is_skipped_and_counted_on_mongo() {
+    malformed_booking_is_skipped_and_counted(TestApp::mongo(BookingsConfig::default()).await).await;
+}
+
+#[actix_web::test]
+async fn malformed_booking_is_skipped_and_counted_in_memory() {
+    malformed_booking_is_skipped_and_counted(TestApp::in_memory(BookingsConfig::default())).await;
+}
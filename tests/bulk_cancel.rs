@@ -0,0 +1,103 @@
+//! `POST /bookings/cancel`: every booking of a time range, by an admin.
+mod common;
+
+use actix_web::{http::StatusCode, test};
+use api_server_mongodb_actix_web::config::BookingsConfig;
+use serde_json::json;
+
+use common::{TestApp, bearer, book, create_dog, send, start_time, verified_owner};
+
+async fn bookings_in_range_are_cancelled_once(test_app: TestApp) {
+    let app = test::init_service(test_app.app()).await;
+    let (owner_id, token) = verified_owner(&test_app, &app, "jane@example.com").await;
+    create_dog(&app, &token, owner_id, "Rex").await;
+    let mut booking_ids = Vec::new();
+    for (days, hour) in [(2, 9), (2, 14), (5, 9)] {
+        let (status, body) = book(&app, &token, owner_id, &start_time(days, hour), 30).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        booking_ids.push(common::inserted_id(&body));
+    }
+    let (status, body) = send(
+        &app,
+        test::TestRequest::put()
+            .uri(&format!("/api/v1/booking/{}/cancel", booking_ids[1]))
+            .insert_header(bearer(&token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let admin = test_app.admin_token();
+    let (status, body) = send(
+        &app,
+        test::TestRequest::post()
+            .uri("/api/v1/bookings/cancel")
+            .insert_header(bearer(&admin))
+            .set_json(json!({
+                "from": start_time(2, 0),
+                "to": start_time(3, 0),
+                "reason": "Storm warning",
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    // The booking the owner already cancelled is neither counted nor reported.
+    assert_eq!(body["modified_count"], 1, "{}", body);
+    assert_eq!(
+        body["booking_ids"],
+        json!([{"$oid": booking_ids[0].to_hex()}]),
+        "{}",
+        body
+    );
+
+    let booking = |id| {
+        test::TestRequest::get()
+            .uri(&format!("/api/v1/booking/{}", id))
+            .insert_header(bearer(&admin))
+    };
+    let (status, body) = send(&app, booking(booking_ids[0])).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "cancelled", "{}", body);
+    assert_eq!(body["cancellation_reason"], "Storm warning", "{}", body);
+    let (status, body) = send(&app, booking(booking_ids[1])).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "cancelled", "{}", body);
+    assert_eq!(body["cancellation_reason"], json!(null), "{}", body);
+    let (status, body) = send(&app, booking(booking_ids[2])).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "pending", "{}", body);
+}
+
+async fn empty_or_inverted_range_is_refused(test_app: TestApp) {
+    let app = test::init_service(test_app.app()).await;
+    let admin = test_app.admin_token();
+    for (from, to) in [
+        (start_time(2, 9), start_time(2, 9)),
+        (start_time(3, 0), start_time(2, 0)),
+    ] {
+        let (status, body) = send(
+            &app,
+            test::TestRequest::post()
+                .uri("/api/v1/bookings/cancel")
+                .insert_header(bearer(&admin))
+                .set_json(json!({"from": from, "to": to, "reason": "Storm warning"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+}
+
+#[actix_web::test]
+#[ignore = "starts a MongoDB container, needs Docker"]
+async fn bookings_in_range_are_cancelled_once_on_mongo() {
+    bookings_in_range_are_cancelled_once(TestApp::mongo(BookingsConfig::default()).await).await;
+}
+
+#[actix_web::test]
+async fn bookings_in_range_are_cancelled_once_in_memory() {
+    bookings_in_range_are_cancelled_once(TestApp::in_memory(BookingsConfig::default())).await;
+}
+
+#[actix_web::test]
+async fn empty_or_inverted_range_is_refused_in_memory() {
+    empty_or_inverted_range_is_refused(TestApp::in_memory(BookingsConfig::default())).await;
+}
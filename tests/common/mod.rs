@@ -0,0 +1,385 @@
+//! App of the integration tests, over MongoDB in a throwaway container or over
+//! the in-memory repositories. The MongoDB tests need Docker and are ignored by
+//! default, run them with `cargo test -- --ignored` (the CI does).
+#![allow(dead_code)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::{
+    App,
+    body::MessageBody,
+    dev::{Service, ServiceFactory, ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    test,
+    web::{self, Data, JsonConfig},
+};
+use api_server_mongodb_actix_web::{
+    config::{BookingsConfig, Config, MongoConfig},
+    errors::AppError,
+    models::{auth_model::Role, vaccination_model::RabiesPolicy},
+    routes::{self, PublicUrl},
+    services::{
+        auth::Authenticator,
+        db::Database,
+        mailer::Mailer,
+        memory::InMemoryDatabase,
+        notifications::Notifier,
+        payments::{MockPaymentProvider, PaymentProvider},
+        push::PushProviders,
+        repository::{
+            AuditRepository, BookingRepository, DogRepository, IdempotencyRepository,
+            OwnerRepository, SearchRepository,
+        },
+        sms,
+        tokens::TokenSigner,
+    },
+};
+use mongodb::bson::{Document, oid::ObjectId};
+use serde_json::{Value, json};
+use testcontainers_modules::{
+    mongo::Mongo,
+    testcontainers::{ContainerAsync, runners::AsyncRunner},
+};
+
+const JWT_SECRET: &[u8] = b"integration-tests-jwt-secret-123";
+
+/// Keeps the emails instead of sending them, so a test can follow the links.
+#[derive(Default)]
+pub struct Outbox(Mutex<Vec<(String, String)>>);
+
+impl Outbox {
+    /// Last path segment of the last link emailed to `to` under `prefix`.
+    pub fn token(&self, to: &str, prefix: &str) -> Option<String> {
+        let emails = self.0.lock().unwrap();
+        emails
+            .iter()
+            .rev()
+            .find(|(address, _)| address == to)
+            .and_then(|(_, body)| {
+                let start = body.find(prefix)? + prefix.len();
+                Some(body[start..].split_whitespace().next()?.to_string())
+            })
+    }
+}
+
+impl Mailer for Outbox {
+    fn send(&self, to: &str, _subject: &str, body: &str) -> Result<(), String> {
+        self.0
+            .lock()
+            .unwrap()
+            .push((to.to_string(), body.to_string()));
+        Ok(())
+    }
+}
+
+/// MongoDB container of a test, removed when dropped.
+pub struct MongoContainer {
+    _container: ContainerAsync<Mongo>,
+    /// Untyped handle on the database of the app, to seed or wipe documents.
+    pub database: mongodb::Database,
+}
+
+pub struct TestApp {
+    pub db: Option<Data<Database>>,
+    pub owners: Data<dyn OwnerRepository>,
+    pub dogs: Data<dyn DogRepository>,
+    pub bookings: Data<dyn BookingRepository>,
+    pub audit: Data<dyn AuditRepository>,
+    pub idempotency: Data<dyn IdempotencyRepository>,
+    pub search: Data<dyn SearchRepository>,
+    pub outbox: Arc<Outbox>,
+    pub auth: Data<Authenticator>,
+    notifier: Data<Notifier>,
+    mailer: Data<dyn Mailer>,
+    signer: Data<TokenSigner>,
+    payments: Data<dyn PaymentProvider>,
+    rabies_policy: Data<RabiesPolicy>,
+    public_url: Data<PublicUrl>,
+    pub mongo: Option<MongoContainer>,
+    pub memory: Option<Arc<InMemoryDatabase>>,
+}
+
+impl TestApp {
+    /// App over the in-memory repositories, like `--in-memory`.
+    pub fn in_memory(bookings: BookingsConfig) -> Self {
+        let memory = Arc::new(InMemoryDatabase::new(&bookings));
+        let mut test_app = TestApp::new(
+            None,
+            Data::from(memory.clone() as Arc<dyn OwnerRepository>),
+            Data::from(memory.clone() as Arc<dyn DogRepository>),
+            Data::from(memory.clone() as Arc<dyn BookingRepository>),
+            Data::from(memory.clone() as Arc<dyn AuditRepository>),
+            Data::from(memory.clone() as Arc<dyn IdempotencyRepository>),
+            Data::from(memory.clone() as Arc<dyn SearchRepository>),
+            bookings.rabies_vaccination_policy,
+            None,
+        );
+        test_app.memory = Some(memory);
+        test_app
+    }
+
+    /// App over a fresh MongoDB replica set (transactions need one), prepared like
+    /// at startup.
+    pub async fn mongo(bookings: BookingsConfig) -> Self {
+        let container = Mongo::repl_set()
+            .start()
+            .await
+            .expect("Failed to start the MongoDB container");
+        let host = container.get_host().await.unwrap();
+        let port = container.get_host_port_ipv4(27017).await.unwrap();
+        let config = MongoConfig {
+            uri: format!("mongodb://{}:{}/?directConnection=true", host, port),
+            database: "dog_walking_test".to_string(),
+            ..MongoConfig::default()
+        };
+        let db = Arc::new(
+            Database::connect(&config, &bookings)
+                .await
+                .expect("Failed to connect to MongoDB"),
+        );
+        db.prepare(false).await.expect("Failed to prepare MongoDB");
+        let database = mongodb::Client::with_uri_str(&config.uri)
+            .await
+            .unwrap()
+            .database(&config.database);
+
+        TestApp::new(
+            Some(Data::from(db.clone())),
+            Data::from(db.clone() as Arc<dyn OwnerRepository>),
+            Data::from(db.clone() as Arc<dyn DogRepository>),
+            Data::from(db.clone() as Arc<dyn BookingRepository>),
+            Data::from(db.clone() as Arc<dyn AuditRepository>),
+            Data::from(db.clone() as Arc<dyn IdempotencyRepository>),
+            Data::from(db as Arc<dyn SearchRepository>),
+            bookings.rabies_vaccination_policy,
+            Some(MongoContainer {
+                _container: container,
+                database,
+            }),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        db: Option<Data<Database>>,
+        owners: Data<dyn OwnerRepository>,
+        dogs: Data<dyn DogRepository>,
+        bookings: Data<dyn BookingRepository>,
+        audit: Data<dyn AuditRepository>,
+        idempotency: Data<dyn IdempotencyRepository>,
+        search: Data<dyn SearchRepository>,
+        rabies_policy: RabiesPolicy,
+        mongo: Option<MongoContainer>,
+    ) -> Self {
+        let config = Config::default();
+        let outbox = Arc::new(Outbox::default());
+        let mailer: Data<dyn Mailer> = Data::from(outbox.clone() as Arc<dyn Mailer>);
+        let notifier = Data::new(Notifier::new(
+            owners.clone(),
+            mailer.clone(),
+            Data::from(sms::from_config(&config.sms).unwrap()),
+            PushProviders::from_config(&config.push).unwrap(),
+            config.email.events.clone(),
+        ));
+        TestApp {
+            db,
+            owners,
+            dogs,
+            bookings,
+            audit,
+            idempotency,
+            search,
+            outbox,
+            auth: Data::new(Authenticator::new(JWT_SECRET, Duration::from_secs(3600))),
+            notifier,
+            mailer,
+            signer: Data::new(TokenSigner::new(JWT_SECRET, Duration::from_secs(3600))),
+            payments: Data::from(Arc::new(MockPaymentProvider) as Arc<dyn PaymentProvider>),
+            rabies_policy: Data::new(rabies_policy),
+            public_url: Data::new(PublicUrl::new(&config.server.public_base_url)),
+            mongo,
+            memory: None,
+        }
+    }
+
+    /// The `/api/v1` routes with the app data of `main`, without the middlewares.
+    pub fn app(
+        &self,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse,
+            Error = actix_web::Error,
+            InitError = (),
+        > + use<>,
+    > {
+        App::new()
+            .configure(|cfg| {
+                if let Some(db) = &self.db {
+                    cfg.app_data(db.clone());
+                }
+            })
+            .app_data(self.owners.clone())
+            .app_data(self.dogs.clone())
+            .app_data(self.bookings.clone())
+            .app_data(self.audit.clone())
+            .app_data(self.idempotency.clone())
+            .app_data(self.search.clone())
+            .app_data(self.signer.clone())
+            .app_data(self.auth.clone())
+            .app_data(self.mailer.clone())
+            .app_data(self.notifier.clone())
+            .app_data(self.payments.clone())
+            .app_data(self.rabies_policy.clone())
+            .app_data(self.public_url.clone())
+            .app_data(
+                JsonConfig::default()
+                    .error_handler(|err, _| AppError::Validation(err.to_string()).into()),
+            )
+            .service(web::scope(routes::API_V1).configure(routes::v1))
+    }
+
+    /// Store a booking document as is, around the models and their validation.
+    pub async fn insert_raw_booking(&self, document: Document) {
+        match (&self.mongo, &self.memory) {
+            (Some(mongo), _) => {
+                mongo
+                    .database
+                    .collection::<Document>("booking")
+                    .insert_one(document)
+                    .await
+                    .unwrap();
+            }
+            (None, Some(memory)) => {
+                memory.insert_raw_booking(document);
+            }
+            (None, None) => unreachable!("A test app has a storage"),
+        }
+    }
+
+    pub fn token(&self, user_id: ObjectId, role: Role) -> String {
+        self.auth.issue(user_id, role).access_token
+    }
+
+    pub fn admin_token(&self) -> String {
+        self.token(ObjectId::new(), Role::Admin)
+    }
+}
+
+/// Send `request` and read the answer as JSON (`null` when the body is empty).
+pub async fn send<S, B>(app: &S, request: test::TestRequest) -> (StatusCode, Value)
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, body)
+}
+
+/// ObjectId of a `{"id": ..., "insertedId": {"$oid": ...}}` answer, both must agree.
+pub fn inserted_id(body: &Value) -> ObjectId {
+    let id = body["id"].as_str().expect("No id");
+    assert_eq!(body["insertedId"]["$oid"], id, "{}", body);
+    ObjectId::parse_str(id).unwrap()
+}
+
+/// `Authorization` header of `token`.
+pub fn bearer(token: &str) -> (&'static str, String) {
+    ("Authorization", format!("Bearer {}", token))
+}
+
+/// Create an owner with a verified email, and its token.
+pub async fn verified_owner<S, B>(test_app: &TestApp, app: &S, email: &str) -> (ObjectId, String)
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let (status, body) = send(
+        app,
+        test::TestRequest::post()
+            .uri("/api/v1/owner")
+            .set_json(json!({
+                "name": "Jane Doe",
+                "email": email,
+                "phone": "+33612345678",
+                "address": "1 rue de la Paix, Paris",
+            })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let owner_id = inserted_id(&body);
+
+    let token = test_app
+        .outbox
+        .token(email, "/owner/verify/")
+        .expect("No verification email");
+    let (status, body) = send(
+        app,
+        test::TestRequest::get().uri(&format!("/api/v1/owner/verify/{}", token)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    (owner_id, test_app.token(owner_id, Role::Owner))
+}
+
+/// Create a dog of `owner_id`.
+pub async fn create_dog<S, B>(app: &S, token: &str, owner_id: ObjectId, name: &str) -> ObjectId
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let (status, body) = send(
+        app,
+        test::TestRequest::post()
+            .uri("/api/v1/dog")
+            .insert_header(bearer(token))
+            .set_json(json!({"owner": owner_id.to_hex(), "name": name, "breed": "Other"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    inserted_id(&body)
+}
+
+/// Book a walk of `duration_in_minutes` for `owner_id`, starting at `start_time` (RFC 3339).
+pub async fn book<S, B>(
+    app: &S,
+    token: &str,
+    owner_id: ObjectId,
+    start_time: &str,
+    duration_in_minutes: u8,
+) -> (StatusCode, Value)
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    send(
+        app,
+        test::TestRequest::post()
+            .uri("/api/v1/booking")
+            .insert_header(bearer(token))
+            .set_json(json!({
+                "owner": owner_id.to_hex(),
+                "start_time": start_time,
+                "duration_in_minutes": duration_in_minutes,
+            })),
+    )
+    .await
+}
+
+/// RFC 3339 start of a walk `days` from now, at `hour` UTC.
+pub fn start_time(days: i64, hour: u32) -> String {
+    (chrono::Utc::now() + chrono::Duration::days(days))
+        .date_naive()
+        .and_hms_opt(hour, 0, 0)
+        .unwrap()
+        .and_utc()
+        .to_rfc3339()
+}